@@ -0,0 +1,82 @@
+// Copyright 2016 Brian Smith.
+//
+// Permission to use, copy, modify, and/or distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHORS DISCLAIM ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHORS BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+//! Benchmarks of name matching against a wildcard certificate.
+//!
+//! `EndEntityCert::from` finds the subjectAltName extension once, so checking
+//! many names against the same `EndEntityCert` only walks the
+//! subjectAltName entries, not the whole certificate. Compare
+//! `many_names_one_parse` with `many_names_parse_each_time` to see the
+//! difference.
+//!
+//! Run the benchmarks with `cargo bench --features=bench`.
+
+#[macro_use]
+extern crate criterion;
+extern crate webpki;
+
+use criterion::Criterion;
+
+const WILDCARD_EE: &'static [u8] =
+    include_bytes!("../tests/data/ecdsa-wildcard-ee.der");
+
+// Subdomains of example.com that match the `*.example.com` subjectAltName
+// entry, which is the last one.
+const NAMES: [&'static [u8]; 8] = [
+    b"a.example.com",
+    b"www.example.com",
+    b"mail.example.com",
+    b"api.example.com",
+    b"cdn.example.com",
+    b"static.example.com",
+    b"login.example.com",
+    b"payments.example.com",
+];
+
+fn bench_many_names_one_parse(c: &mut Criterion) {
+    c.bench_function("many_names_one_parse", |b| {
+        let cert = webpki::EndEntityCert::from(WILDCARD_EE).unwrap();
+        b.iter(|| {
+            for name in NAMES.iter() {
+                cert.verify_is_valid_for_dns_name(name).unwrap();
+            }
+        });
+    });
+}
+
+fn bench_many_names_parse_each_time(c: &mut Criterion) {
+    c.bench_function("many_names_parse_each_time", |b| {
+        b.iter(|| {
+            for name in NAMES.iter() {
+                let cert = webpki::EndEntityCert::from(WILDCARD_EE).unwrap();
+                cert.verify_is_valid_for_dns_name(name).unwrap();
+            }
+        });
+    });
+}
+
+fn bench_at_least_one_name(c: &mut Criterion) {
+    c.bench_function("at_least_one_name", |b| {
+        let cert = webpki::EndEntityCert::from(WILDCARD_EE).unwrap();
+        let names = NAMES.iter().cloned();
+        b.iter(|| {
+            cert.verify_is_valid_for_at_least_one_dns_name(names.clone())
+                .unwrap()
+        });
+    });
+}
+
+criterion_group!(benches, bench_many_names_one_parse,
+                 bench_many_names_parse_each_time, bench_at_least_one_name);
+criterion_main!(benches);
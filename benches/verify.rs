@@ -0,0 +1,111 @@
+// Copyright 2016 Brian Smith.
+//
+// Permission to use, copy, modify, and/or distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHORS DISCLAIM ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHORS BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+//! End-to-end benchmarks of `EndEntityCert::verify_is_valid_tls_server_cert`.
+//!
+//! Run the benchmarks with `cargo bench --features=bench`. The certificates
+//! are generated by `tests/data/generate.sh`.
+
+#[macro_use]
+extern crate criterion;
+extern crate webpki;
+
+use criterion::{Bencher, Criterion};
+
+const ECDSA_ROOT: &'static [u8] =
+    include_bytes!("../tests/data/ecdsa-root.der");
+const RSA_ROOT: &'static [u8] = include_bytes!("../tests/data/rsa-root.der");
+
+static ALL_SIGALGS: &'static [&'static webpki::SignatureAlgorithm] = &[
+    &webpki::ECDSA_P256_SHA256,
+    &webpki::ECDSA_P256_SHA384,
+    &webpki::ECDSA_P384_SHA256,
+    &webpki::ECDSA_P384_SHA384,
+    &webpki::RSA_PKCS1_2048_8192_SHA1,
+    &webpki::RSA_PKCS1_2048_8192_SHA256,
+    &webpki::RSA_PKCS1_2048_8192_SHA384,
+    &webpki::RSA_PKCS1_2048_8192_SHA512,
+    &webpki::RSA_PKCS1_3072_8192_SHA384,
+];
+
+// A time at which all of the certificates are valid.
+fn time() -> webpki::Time {
+    webpki::Time::from_ymdhms_utc(2027, 1, 1, 0, 0, 0).unwrap()
+}
+
+fn bench_verify(b: &mut Bencher, roots: &[&[u8]], ee: &[u8],
+                intermediates: &[&[u8]]) {
+    let anchors = roots.iter().map(|root| {
+        webpki::trust_anchor_util::cert_der_as_trust_anchor(root).unwrap()
+    }).collect::<Vec<_>>();
+    let time = time();
+
+    // Check once outside of the benchmark loop so that a broken benchmark
+    // fails instead of measuring how fast an error is returned.
+    let verify = || {
+        let cert = webpki::EndEntityCert::from(ee).unwrap();
+        cert.verify_is_valid_tls_server_cert(ALL_SIGALGS, &anchors,
+                                             intermediates, time)
+    };
+    let _ = verify().unwrap();
+
+    b.iter(verify);
+}
+
+fn bench_ecdsa_chain(c: &mut Criterion) {
+    c.bench_function("ecdsa_chain", |b| {
+        bench_verify(b, &[ECDSA_ROOT],
+                     include_bytes!("../tests/data/ecdsa-ee.der"),
+                     &[include_bytes!("../tests/data/ecdsa-intermediate.der")])
+    });
+}
+
+fn bench_rsa_chain(c: &mut Criterion) {
+    c.bench_function("rsa_chain", |b| {
+        bench_verify(b, &[RSA_ROOT],
+                     include_bytes!("../tests/data/rsa-ee.der"),
+                     &[include_bytes!("../tests/data/rsa-intermediate.der")])
+    });
+}
+
+// The first intermediate is issued by a root that isn't trusted, so path
+// building has to back out of it and try the cross-signed one.
+fn bench_cross_signed_chain(c: &mut Criterion) {
+    c.bench_function("cross_signed_chain", |b| {
+        bench_verify(
+            b, &[RSA_ROOT, ECDSA_ROOT],
+            include_bytes!("../tests/data/cross-ee.der"),
+            &[include_bytes!(
+                  "../tests/data/cross-intermediate-by-old-root.der"),
+              include_bytes!(
+                  "../tests/data/cross-intermediate-by-ecdsa-root.der")])
+    });
+}
+
+// Five intermediates, given in the reverse of the order they are needed.
+fn bench_deep_chain(c: &mut Criterion) {
+    c.bench_function("deep_chain", |b| {
+        bench_verify(b, &[ECDSA_ROOT],
+                     include_bytes!("../tests/data/deep-ee.der"),
+                     &[include_bytes!("../tests/data/deep-intermediate-1.der"),
+                       include_bytes!("../tests/data/deep-intermediate-2.der"),
+                       include_bytes!("../tests/data/deep-intermediate-3.der"),
+                       include_bytes!("../tests/data/deep-intermediate-4.der"),
+                       include_bytes!("../tests/data/deep-intermediate-5.der")])
+    });
+}
+
+criterion_group!(benches, bench_ecdsa_chain, bench_rsa_chain,
+                 bench_cross_signed_chain, bench_deep_chain);
+criterion_main!(benches);
@@ -31,8 +31,17 @@ if [[ "$MODE_X" == "RELWITHDEBINFO" ]]; then mode=--release; fi
 
 CC=$CC_X CXX=$CXX_X cargo build -j2 ${mode-} --verbose
 
+# Verify that each signature algorithm family can be compiled out, with and
+# without `std`.
+for features in "" ecdsa rsa std,ecdsa std,rsa; do
+  CC=$CC_X CXX=$CXX_X cargo build -j2 ${mode-} --verbose \
+    --no-default-features --features="$features"
+done
+
 CC=$CC_X CXX=$CXX_X cargo test -j2 ${mode-} --verbose
 
+CC=$CC_X CXX=$CXX_X cargo test -j2 ${mode-} --verbose --features=certdata
+
 CC=$CC_X CXX=$CXX_X cargo doc --verbose
 
 CC=$CC_X CXX=$CXX_X cargo clean --verbose
@@ -0,0 +1,79 @@
+// Copyright 2016 Brian Smith.
+//
+// Permission to use, copy, modify, and/or distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHORS DISCLAIM ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHORS BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+// Validation of ACME tls-alpn-01 challenge certificates; see
+// https://tools.ietf.org/html/rfc8737#section-3.
+//
+// The challenge certificate is self-signed by the ACME client and the
+// validator learns nothing from its signature or its validity period, so,
+// like the validation procedure of RFC 8737, we check only the
+// id-pe-acmeIdentifier extension and the subjectAltName extension.
+
+use cert::Cert;
+use name::{self, ServerName, SubjectAltName};
+use untrusted;
+use Error;
+
+// RFC 8737 Section 3: "The subjectAltName extension MUST contain a single
+// dNSName entry where the value is the domain name being validated. The
+// acmeIdentifier extension MUST be critical and the extnValue MUST contain
+// the ASN.1 DER encoding of the Authorization structure, which contains the
+// SHA-256 digest of the key authorization for the challenge." RFC 8738
+// Section 6 allows a single iPAddress entry instead, for IP address
+// identifiers.
+pub fn verify_tls_alpn_01_cert(cert: &Cert, identifier: &ServerName,
+                               key_authorization_digest: &[u8])
+                               -> Result<(), Error> {
+    match cert.acme_identifier {
+        Some(authorization) if cert.acme_identifier_is_critical &&
+                               authorization == key_authorization_digest => (),
+        _ => { return Err(Error::InvalidAcmeIdentifier); }
+    }
+
+    let mut names = try!(name::subject_alt_names(cert.subject_alt_name));
+    let presented = match (names.next(), names.next()) {
+        (Some(presented), None) => presented,
+        _ => { return Err(Error::CertNotValidForName); }
+    };
+
+    let matches = match *identifier {
+        ServerName::DnsName(reference) => {
+            if !name::is_valid_reference_dns_id(
+                    untrusted::Input::from(reference)) {
+                return Err(Error::InvalidReferenceName);
+            }
+            match presented {
+                SubjectAltName::DnsName(presented) =>
+                    name::ascii_eq_ignore_case(presented.as_slice(),
+                                               reference),
+                _ => false,
+            }
+        },
+        ServerName::IpAddress(reference) => {
+            if reference.len() != 4 && reference.len() != 16 {
+                return Err(Error::InvalidReferenceName);
+            }
+            match presented {
+                SubjectAltName::IpAddress(presented) =>
+                    presented.as_slice() == reference,
+                _ => false,
+            }
+        },
+        _ => { return Err(Error::InvalidReferenceName); },
+    };
+    if !matches {
+        return Err(Error::CertNotValidForName);
+    }
+    Ok(())
+}
@@ -0,0 +1,203 @@
+// Copyright 2016 Brian Smith.
+//
+// Permission to use, copy, modify, and/or distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHORS DISCLAIM ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHORS BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+use Error;
+
+#[cfg(feature = "std")]
+use std::vec::Vec;
+
+/// Where a list of values that webpki builds is kept, e.g. the trust anchors
+/// of `trust_anchors_for_usage_in` or the entries of a `ReportRecorder`.
+///
+/// With the `std` feature, `Vec` is an `AllocScope` that never runs out of
+/// room, and it is what the functions that return their results use. A
+/// `BumpArena` keeps the values in memory provided by the caller instead, so
+/// that a server that reuses that memory doesn't allocate at all.
+pub trait AllocScope<T> {
+    /// Appends `value`. Fails with `Error::InputTooLong` if there is no room
+    /// for it.
+    fn push(&mut self, value: T) -> Result<(), Error>;
+
+    /// The values, in the order they were pushed.
+    fn as_slice(&self) -> &[T];
+
+    /// The values, in the order they were pushed, for modifying them.
+    fn as_mut_slice(&mut self) -> &mut [T];
+
+    /// Removes all the values, keeping the memory for reuse.
+    fn clear(&mut self);
+}
+
+#[cfg(feature = "std")]
+impl<T> AllocScope<T> for Vec<T> {
+    fn push(&mut self, value: T) -> Result<(), Error> {
+        Vec::push(self, value);
+        Ok(())
+    }
+
+    fn as_slice(&self) -> &[T] { self }
+
+    fn as_mut_slice(&mut self) -> &mut [T] { self }
+
+    fn clear(&mut self) { Vec::clear(self) }
+}
+
+/// An `AllocScope` that keeps the values in a fixed-size buffer provided by
+/// the caller, which doesn't require the `std` feature.
+///
+/// Each value that is pushed replaces the one in the next slot of the
+/// buffer, so the buffer has to be initialized, e.g. with placeholder
+/// values, and the values that were replaced are dropped.
+pub struct BumpArena<'s, T: 's> {
+    storage: &'s mut [T],
+    len: usize,
+}
+
+impl<'s, T> BumpArena<'s, T> {
+    /// Keeps the values in `storage`, starting at its beginning.
+    pub fn new(storage: &'s mut [T]) -> Self {
+        BumpArena { storage: storage, len: 0 }
+    }
+
+    /// The number of values there is room for.
+    pub fn capacity(&self) -> usize { self.storage.len() }
+
+    /// The values, for the lifetime of the buffer.
+    pub fn into_slice(self) -> &'s [T] { &self.storage[..self.len] }
+}
+
+impl<'s, T> AllocScope<T> for BumpArena<'s, T> {
+    fn push(&mut self, value: T) -> Result<(), Error> {
+        if self.len == self.storage.len() {
+            return Err(Error::InputTooLong);
+        }
+        self.storage[self.len] = value;
+        self.len += 1;
+        Ok(())
+    }
+
+    fn as_slice(&self) -> &[T] { &self.storage[..self.len] }
+
+    fn as_mut_slice(&mut self) -> &mut [T] { &mut self.storage[..self.len] }
+
+    fn clear(&mut self) { self.len = 0; }
+}
+
+// An `AllocScope` for lists that are usually short, which keeps the values
+// in `arena` and, with the `std` feature, moves them to the heap when
+// `arena` is full, so that the common case doesn't allocate and the rare
+// long list isn't rejected.
+pub struct ArenaOrHeap<'s, T: 's + Clone> {
+    arena: BumpArena<'s, T>,
+
+    #[cfg(feature = "std")]
+    heap: Option<Vec<T>>,
+}
+
+impl<'s, T: Clone> ArenaOrHeap<'s, T> {
+    pub fn new(storage: &'s mut [T]) -> Self {
+        ArenaOrHeap {
+            arena: BumpArena::new(storage),
+            #[cfg(feature = "std")]
+            heap: None,
+        }
+    }
+}
+
+impl<'s, T: Clone> AllocScope<T> for ArenaOrHeap<'s, T> {
+    #[cfg(feature = "std")]
+    fn push(&mut self, value: T) -> Result<(), Error> {
+        if self.heap.is_none() && self.arena.len == self.arena.capacity() {
+            self.heap = Some(self.arena.as_slice().to_vec());
+        }
+        match self.heap {
+            Some(ref mut heap) => AllocScope::push(heap, value),
+            None => self.arena.push(value),
+        }
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn push(&mut self, value: T) -> Result<(), Error> {
+        self.arena.push(value)
+    }
+
+    fn as_slice(&self) -> &[T] {
+        #[cfg(feature = "std")]
+        {
+            if let Some(ref heap) = self.heap {
+                return heap;
+            }
+        }
+        self.arena.as_slice()
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [T] {
+        #[cfg(feature = "std")]
+        {
+            if let Some(ref mut heap) = self.heap {
+                return heap;
+            }
+        }
+        self.arena.as_mut_slice()
+    }
+
+    // The memory of the heap, if any, is freed too, so that the next list
+    // starts in `arena` again.
+    fn clear(&mut self) {
+        #[cfg(feature = "std")]
+        {
+            self.heap = None;
+        }
+        self.arena.clear()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AllocScope, BumpArena};
+    use Error;
+
+    #[test]
+    fn test_bump_arena() {
+        let mut storage = [0u8; 2];
+        let mut arena = BumpArena::new(&mut storage);
+        assert_eq!(2, arena.capacity());
+        assert_eq!(Ok(()), arena.push(1));
+        assert_eq!(Ok(()), arena.push(2));
+        assert_eq!(Err(Error::InputTooLong), arena.push(3));
+        assert_eq!(&[1, 2], arena.as_slice());
+
+        arena.clear();
+        assert_eq!(Ok(()), arena.push(3));
+        assert_eq!(&[3], arena.into_slice());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_arena_or_heap() {
+        use super::ArenaOrHeap;
+
+        let mut storage = [0u8; 2];
+        let mut scope = ArenaOrHeap::new(&mut storage);
+        for value in 1..4 {
+            assert_eq!(Ok(()), scope.push(value));
+        }
+        assert_eq!(&[1, 2, 3], scope.as_slice());
+
+        scope.clear();
+        assert_eq!(Ok(()), scope.push(4));
+        assert_eq!(&[4], scope.as_slice());
+        assert!(scope.heap.is_none());
+    }
+}
@@ -0,0 +1,304 @@
+// Copyright 2016 Brian Smith.
+//
+// Permission to use, copy, modify, and/or distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHORS DISCLAIM ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHORS BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+//! Captures of the inputs to a TLS server certificate verification, for
+//! replaying the verification offline.
+//!
+//! An application records a `HandshakeCapture` of each verification that it
+//! may need to explain later, e.g. to an incident-response team
+//! reconstructing why a connection was or wasn't trusted. Replaying the
+//! capture verifies the certificates again, at the recorded times and with
+//! the recorded trust anchors, and produces the same `ValidationReport` as
+//! the original verification did.
+//!
+//! The policy and the supported signature algorithms are part of the
+//! application rather than of the handshake, so they aren't captured; the
+//! replay must use the same ones as the original verification.
+//!
+//! A capture is encoded as the DER encoding of:
+//!
+//! ```ASN.1
+//! HandshakeCapture ::= SEQUENCE {
+//!     version             INTEGER { v1(0) },
+//!     validityTime        GeneralizedTime,
+//!     currentTime         GeneralizedTime,
+//!     endEntityCert       OCTET STRING,
+//!     intermediateCerts   SEQUENCE OF OCTET STRING,
+//!     trustAnchors        SEQUENCE OF CapturedTrustAnchor }
+//!
+//! CapturedTrustAnchor ::= SEQUENCE {
+//!     subject             OCTET STRING,
+//!     spki                OCTET STRING,
+//!     nameConstraints     OCTET STRING OPTIONAL }
+//! ```
+//!
+//! The certificates are captured as `OCTET STRING`s, rather than as
+//! `Certificate`s, so that a capture of a malformed certificate can be
+//! encoded and replayed too. The elements may be longer than
+//! `MAX_DER_ELEMENT_LEN`.
+
+use {der, der_writer, EndEntityCert, Error, Policy, ReportRecorder,
+     SignatureAlgorithm, TrustAnchor, ValidationReport, VerificationOptions,
+     VerificationTimes};
+use std::vec::Vec;
+use untrusted;
+
+/// The inputs to a TLS server certificate verification, as taken by
+/// `EndEntityCert::verify_is_valid_tls_server_cert_with_options`. See the
+/// module documentation.
+#[derive(Debug)]
+pub struct HandshakeCapture<'a> {
+    /// The DER-encoded end-entity certificate.
+    pub end_entity_cert: &'a [u8],
+
+    /// The DER-encoded intermediate certificates, in the order in which the
+    /// peer sent them.
+    pub intermediate_certs: Vec<&'a [u8]>,
+
+    /// The trust anchors that the application trusted at the time.
+    pub trust_anchors: Vec<TrustAnchor<'a>>,
+
+    /// The times at which the certificates were verified.
+    pub times: VerificationTimes,
+}
+
+impl<'a> HandshakeCapture<'a> {
+    /// Parses an encoded capture. The result borrows the certificates and
+    /// trust anchors from `capture`.
+    pub fn from(capture: &'a [u8]) -> Result<HandshakeCapture<'a>, Error> {
+        untrusted::Input::from(capture).read_all(Error::BadDER, |input| {
+            let value = try!(der::expect_tag_and_get_long_value(
+                input, der::Tag::Sequence));
+            value.read_all(Error::BadDER, parse_capture)
+        })
+    }
+
+    /// The DER encoding of the capture, which `HandshakeCapture::from`
+    /// parses.
+    ///
+    /// Fails with `Error::DERElementTooLong` only if the capture is too long
+    /// to encode, i.e. longer than 2^32 - 1 bytes.
+    pub fn encode(&self) -> Result<Vec<u8>, Error> {
+        use der_writer::write_long_tlv;
+
+        const OCTET_STRING: u8 = der::Tag::OctetString as u8;
+        const SEQUENCE: u8 = der::Tag::Sequence as u8;
+
+        let mut value = Vec::new();
+        try!(der_writer::write_unsigned_integer(&mut value, 0));
+        try!(der_writer::write_generalized_time(&mut value,
+                                                self.times.validity_time));
+        try!(der_writer::write_generalized_time(&mut value,
+                                                self.times.current_time));
+        try!(write_long_tlv(&mut value, OCTET_STRING, self.end_entity_cert));
+
+        let mut intermediate_certs = Vec::new();
+        for cert_der in &self.intermediate_certs {
+            try!(write_long_tlv(&mut intermediate_certs, OCTET_STRING,
+                                cert_der));
+        }
+        try!(write_long_tlv(&mut value, SEQUENCE, &intermediate_certs));
+
+        let mut trust_anchors = Vec::new();
+        for trust_anchor in &self.trust_anchors {
+            let mut fields = Vec::new();
+            try!(write_long_tlv(&mut fields, OCTET_STRING,
+                                trust_anchor.subject));
+            try!(write_long_tlv(&mut fields, OCTET_STRING, trust_anchor.spki));
+            if let Some(name_constraints) = trust_anchor.name_constraints {
+                try!(write_long_tlv(&mut fields, OCTET_STRING,
+                                    name_constraints));
+            }
+            try!(write_long_tlv(&mut trust_anchors, SEQUENCE, &fields));
+        }
+        try!(write_long_tlv(&mut value, SEQUENCE, &trust_anchors));
+
+        let mut out = Vec::new();
+        try!(write_long_tlv(&mut out, SEQUENCE, &value));
+        Ok(out)
+    }
+
+    /// Verifies the captured certificates again, using `policy` and
+    /// `supported_sig_algs`, which should be the ones that the original
+    /// verification used.
+    ///
+    /// The result is deterministic: the same capture, policy, and signature
+    /// algorithms always produce the same report, which is the report that
+    /// the original verification produced. If the end-entity certificate
+    /// can't be parsed, the report has no entries and the parsing error as
+    /// its result.
+    pub fn replay(&self, policy: &Policy,
+                  supported_sig_algs: &[&SignatureAlgorithm])
+                  -> ValidationReport {
+        let cert = match EndEntityCert::from_with_policy(self.end_entity_cert,
+                                                         policy) {
+            Ok(cert) => cert,
+            Err(err) => {
+                return ValidationReport {
+                    entries: Vec::new(),
+                    result: Err(err),
+                };
+            }
+        };
+        let recorder = ReportRecorder::new();
+        let result = cert.verify_is_valid_tls_server_cert_with_options(
+            policy, supported_sig_algs, &self.trust_anchors,
+            &self.intermediate_certs, self.times,
+            &VerificationOptions::new().observer(&recorder));
+        recorder.into_report(result)
+    }
+}
+
+fn parse_capture<'a>(input: &mut untrusted::Reader<'a>)
+                     -> Result<HandshakeCapture<'a>, Error> {
+    if try!(der::small_nonnegative_integer(input)) != 0 {
+        return Err(Error::BadDER);
+    }
+    let validity_time = try!(der::time_choice(input));
+    let current_time = try!(der::time_choice(input));
+    let end_entity_cert = try!(octet_string(input));
+
+    let mut intermediate_certs = Vec::new();
+    let value = try!(der::expect_tag_and_get_long_value(input,
+                                                        der::Tag::Sequence));
+    try!(value.read_all(Error::BadDER, |value| {
+        while !value.at_end() {
+            intermediate_certs.push(try!(octet_string(value)));
+        }
+        Ok(())
+    }));
+
+    let mut trust_anchors = Vec::new();
+    let value = try!(der::expect_tag_and_get_long_value(input,
+                                                        der::Tag::Sequence));
+    try!(value.read_all(Error::BadDER, |value| {
+        while !value.at_end() {
+            let fields = try!(der::expect_tag_and_get_long_value(
+                value, der::Tag::Sequence));
+            trust_anchors.push(try!(fields.read_all(Error::BadDER, |fields| {
+                let subject = try!(octet_string(fields));
+                let spki = try!(octet_string(fields));
+                let name_constraints = if fields.at_end() {
+                    None
+                } else {
+                    Some(try!(octet_string(fields)))
+                };
+                Ok(TrustAnchor {
+                    subject: subject,
+                    spki: spki,
+                    name_constraints: name_constraints,
+                })
+            })));
+        }
+        Ok(())
+    }));
+
+    Ok(HandshakeCapture {
+        end_entity_cert: end_entity_cert,
+        intermediate_certs: intermediate_certs,
+        trust_anchors: trust_anchors,
+        times: VerificationTimes {
+            validity_time: validity_time,
+            current_time: current_time,
+        },
+    })
+}
+
+fn octet_string<'a>(input: &mut untrusted::Reader<'a>)
+                    -> Result<&'a [u8], Error> {
+    der::expect_tag_and_get_long_value(input, der::Tag::OctetString)
+        .map(|value| value.as_slice_less_safe())
+}
+
+#[cfg(all(test, feature = "trust_anchor_util", feature = "ecdsa"))]
+mod tests {
+    use super::HandshakeCapture;
+    use std::vec::Vec;
+    use {EndEntityCert, Error, Policy, ReportRecorder, Time, TrustAnchor,
+         VerificationOptions, VerificationTimes, ECDSA_P256_SHA256};
+    use trust_anchor_util::cert_der_as_trust_anchor;
+
+    #[test]
+    fn test_encode_and_replay() {
+        let root: &[u8] = include_bytes!("../tests/data/ecdsa-root.der");
+        let intermediate: &[u8] =
+            include_bytes!("../tests/data/ecdsa-intermediate.der");
+        let ee: &[u8] = include_bytes!("../tests/data/ecdsa-ee.der");
+        let root_anchor = cert_der_as_trust_anchor(root).unwrap();
+        let constrained_anchor = TrustAnchor {
+            // Permits only www.example.org.
+            name_constraints: Some(&[
+                0xa0, 0x13, 0x30, 0x11, 0x82, 0x0f,
+                b'w', b'w', b'w', b'.', b'e', b'x', b'a', b'm', b'p', b'l',
+                b'e', b'.', b'o', b'r', b'g',
+            ]),
+            ..root_anchor
+        };
+        let times = VerificationTimes {
+            validity_time: Time::from_ymdhms_utc(2027, 1, 1, 0, 0, 0)
+                .unwrap(),
+            current_time: Time::from_ymdhms_utc(2027, 1, 2, 3, 4, 5)
+                .unwrap(),
+        };
+        let policy = Policy::default();
+        let sig_algs = &[&ECDSA_P256_SHA256];
+
+        for trust_anchors in &[&[root_anchor][..], &[constrained_anchor][..]] {
+            let capture = HandshakeCapture {
+                end_entity_cert: ee,
+                intermediate_certs: [intermediate, root].to_vec(),
+                trust_anchors: trust_anchors.iter().map(|trust_anchor| {
+                    TrustAnchor { ..*trust_anchor }
+                }).collect(),
+                times: times,
+            };
+            let encoded = capture.encode().unwrap();
+            let parsed = HandshakeCapture::from(&encoded).unwrap();
+            assert_eq!(format!("{:?}", capture), format!("{:?}", parsed));
+
+            let recorder = ReportRecorder::new();
+            let result = EndEntityCert::from(ee).unwrap()
+                .verify_is_valid_tls_server_cert_with_options(
+                    &policy, sig_algs, trust_anchors, &[intermediate, root],
+                    times, &VerificationOptions::new().observer(&recorder));
+            assert_eq!(recorder.into_report(result),
+                       parsed.replay(&policy, sig_algs));
+        }
+    }
+
+    #[test]
+    fn test_replay_malformed_cert() {
+        let capture = HandshakeCapture {
+            end_entity_cert: &[0x30, 0x00],
+            intermediate_certs: Vec::new(),
+            trust_anchors: Vec::new(),
+            times:
+                VerificationTimes::at(Time::from_seconds_since_unix_epoch(0)),
+        };
+        let encoded = capture.encode().unwrap();
+        let report = HandshakeCapture::from(&encoded).unwrap()
+            .replay(&Policy::default(), &[&ECDSA_P256_SHA256]);
+        assert!(report.entries.is_empty());
+        assert_eq!(Err(Error::BadDER), report.result);
+
+        // Truncated, and an unknown version.
+        assert_eq!(Some(Error::BadDER),
+                   HandshakeCapture::from(&encoded[..(encoded.len() - 1)])
+                       .err());
+        let mut unknown_version = encoded.clone();
+        unknown_version[4] = 1;
+        assert_eq!(Some(Error::BadDER),
+                   HandshakeCapture::from(&unknown_version).err());
+    }
+}
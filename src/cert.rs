@@ -12,48 +12,124 @@
 // ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
 // OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
 
-use {Error, der, signed_data};
+use {Error, der, Policy, signed_data};
 use untrusted;
 
+#[derive(Clone)]
 pub enum EndEntityOrCA<'a> {
     EndEntity,
     CA(&'a Cert<'a>),
+
+    // A CA certificate that is itself the subject of the verification, e.g.
+    // a sub-CA certificate being checked before it is installed. It is
+    // verified as a CA but has no child certificate.
+    TargetCA,
 }
 
+#[derive(Clone)]
 pub struct Cert<'a> {
     pub ee_or_ca: EndEntityOrCA<'a>,
 
+    // The whole encoded certificate.
+    pub der: untrusted::Input<'a>,
+
     pub signed_data: signed_data::SignedData<'a>,
+    pub serial: untrusted::Input<'a>,
+    pub tbs_signature: untrusted::Input<'a>,
     pub issuer: untrusted::Input<'a>,
     pub validity: untrusted::Input<'a>,
     pub subject: untrusted::Input<'a>,
     pub spki: untrusted::Input<'a>,
 
+    // The contents of the `extensions` SEQUENCE, i.e. the encoded
+    // `Extension`s.
+    pub extensions: untrusted::Input<'a>,
+
     pub basic_constraints: Option<untrusted::Input<'a>>,
     pub eku: Option<untrusted::Input<'a>>,
+    pub eku_is_critical: bool,
     pub name_constraints: Option<untrusted::Input<'a>>,
     pub subject_alt_name: Option<untrusted::Input<'a>>,
+    pub subject_alt_name_is_critical: bool,
+    pub subject_directory_attributes_is_critical: bool,
+    pub subject_key_id: Option<untrusted::Input<'a>>,
+
+    // Whether any extension appears more than once. Duplicates of the
+    // extensions that we process are rejected by `parse_cert`; duplicates of
+    // the others are rejected unless `Policy::allow_duplicate_extensions`.
+    pub has_duplicate_extension: bool,
+    pub authority_key_id: Option<AuthorityKeyId<'a>>,
+
+    // Whether the certificate has the id-pkix-ocsp-nocheck extension.
+    pub ocsp_no_check: bool,
+
+    // The value of the `Authorization` OCTET STRING of the
+    // id-pe-acmeIdentifier extension of an ACME tls-alpn-01 challenge
+    // certificate, and whether the extension is critical; see
+    // `acme::verify_tls_alpn_01_cert`.
+    pub acme_identifier: Option<untrusted::Input<'a>>,
+    pub acme_identifier_is_critical: bool,
+
+    // The policy extensions; see `certificate_policies`.
+    // `inhibit_any_policy` is the value of the INTEGER; the others are the
+    // values of the SEQUENCEs.
+    pub certificate_policies: Option<untrusted::Input<'a>>,
+    pub policy_mappings: Option<untrusted::Input<'a>>,
+    pub policy_constraints: Option<untrusted::Input<'a>>,
+    pub inhibit_any_policy: Option<untrusted::Input<'a>>,
 }
 
+// `policy` limits the structure of the DER encoding; see
+// `Policy::max_der_nesting_depth` and `Policy::max_der_element_len`.
 pub fn parse_cert<'a>(cert_der: untrusted::Input<'a>,
-                      ee_or_ca: EndEntityOrCA<'a>) -> Result<Cert<'a>, Error> {
-    let (tbs, signed_data) = try!(cert_der.read_all(Error::BadDER, |cert_der| {
-        der::nested(cert_der, der::Tag::Sequence, Error::BadDER,
-                    signed_data::parse_signed_data)
+                      ee_or_ca: EndEntityOrCA<'a>, policy: &Policy)
+                      -> Result<Cert<'a>, Error> {
+    parse_cert_with(cert_der, ee_or_ca, policy, &mut |_, err| Err(err))
+}
+
+// The depth of the fields of each extension, which are the most deeply
+// nested elements that `parse_cert` parses: `Certificate`, `tbsCertificate`,
+// `[3]`, `Extensions`, `Extension`, then the fields.
+const EXTENSION_FIELD_DEPTH: usize = 6;
+
+// Like `parse_cert`, but each critical extension that isn't understood, and
+// each understood extension whose value can't be parsed, is passed to
+// `extension_error`, with its OID and the error it would cause
+// (`Error::UnsupportedCriticalExtension` or `Error::BadDER`), instead of
+// being rejected; the certificate is rejected only if that returns an error,
+// and otherwise the extension is ignored. This lets `lint::lint_cert_der`
+// find all of them.
+pub fn parse_cert_with<'a>(
+        cert_der: untrusted::Input<'a>, ee_or_ca: EndEntityOrCA<'a>,
+        policy: &Policy,
+        extension_error:
+            &mut FnMut(untrusted::Input<'a>, Error) -> Result<(), Error>)
+        -> Result<Cert<'a>, Error> {
+    // The limits are checked as the certificate is parsed. Every element is
+    // nested in the outer one, so its length is the only one that needs to
+    // be checked, and the depth of the elements that are parsed is fixed.
+    // The elements that are only read, and not parsed, are checked
+    // individually.
+    try!(der::check_depth(policy, EXTENSION_FIELD_DEPTH));
+    let value = try!(cert_der.read_all(Error::BadDER, |cert_der| {
+        der::expect_tag_and_get_long_value(cert_der, der::Tag::Sequence)
     }));
+    try!(der::check_element_len(policy, value.len()));
+
+    let (tbs, signed_data) =
+        try!(value.read_all(Error::BadDER, signed_data::parse_signed_data));
+    try!(der::check_nesting(policy, 3, signed_data.algorithm));
 
     tbs.read_all(Error::BadDER, |tbs| {
         try!(version3(tbs));
-        try!(certificate_serial_number(tbs));
+        let serial = try!(certificate_serial_number(tbs));
 
-        let signature =
+        // Like mozilla::pkix, we compare `signature` to
+        // `signed_data.algorithm` in
+        // `verify_cert::check_issuer_independent_properties`, where the
+        // `Policy` that controls how strict the comparison is is available.
+        let tbs_signature =
             try!(der::expect_tag_and_get_value(tbs, der::Tag::Sequence));
-        // TODO: In mozilla::pkix, the comparison is done based on the
-        // normalized value (ignoring whether or not there is an optional NULL
-        // parameter for RSA-based algorithms), so this may be too strict.
-        if signature != signed_data.algorithm {
-            return Err(Error::SignatureAlgorithmMismatch);
-        }
 
         let issuer =
             try!(der::expect_tag_and_get_value(tbs, der::Tag::Sequence));
@@ -63,56 +139,124 @@ pub fn parse_cert<'a>(cert_der: untrusted::Input<'a>,
             try!(der::expect_tag_and_get_value(tbs, der::Tag::Sequence));
         let spki =
             try!(der::expect_tag_and_get_value(tbs, der::Tag::Sequence));
+        for field in &[tbs_signature, issuer, validity, subject, spki] {
+            try!(der::check_nesting(policy, 4, *field));
+        }
 
         // In theory there could be fields [1] issuerUniqueID and [2]
         // subjectUniqueID, but in practice there never are, and to keep the
         // code small and simple we don't accept any certificates that do
         // contain them.
 
+        // mozilla::pkix allows the extensions to be omitted. However, since
+        // the subjectAltName extension is mandatory, the extensions are
+        // mandatory too, and we enforce that. Also, mozilla::pkix includes
+        // special logic for handling critical Netscape Cert Type extensions.
+        // That has been intentionally omitted.
+        let extensions =
+            try!(der::nested(tbs, der::Tag::ContextSpecificConstructed3,
+                             Error::BadDER, |tagged| {
+                der::expect_tag_and_get_value(tagged, der::Tag::Sequence)
+            }));
+
         let mut cert: Cert<'a> = Cert {
             ee_or_ca: ee_or_ca,
 
+            der: cert_der,
+
             signed_data: signed_data,
+            serial: serial,
+            tbs_signature: tbs_signature,
             issuer: issuer,
             validity: validity,
             subject: subject,
             spki: spki,
 
+            extensions: extensions,
+
             basic_constraints: None,
             eku: None,
+            eku_is_critical: false,
             name_constraints: None,
             subject_alt_name: None,
+            subject_alt_name_is_critical: false,
+            subject_directory_attributes_is_critical: false,
+            subject_key_id: None,
+            has_duplicate_extension: false,
+            authority_key_id: None,
+            ocsp_no_check: false,
+            acme_identifier: None,
+            acme_identifier_is_critical: false,
+            certificate_policies: None,
+            policy_mappings: None,
+            policy_constraints: None,
+            inhibit_any_policy: None,
         };
 
-        // mozilla::pkix allows the extensions to be omitted. However, since
-        // the subjectAltName extension is mandatory, the extensions are
-        // mandatory too, and we enforce that. Also, mozilla::pkix includes
-        // special logic for handling critical Netscape Cert Type extensions.
-        // That has been intentionally omitted.
+        try!(extensions.read_all_mut(Error::BadDER, |extensions| {
+            loop {
+                let (id, critical, value) = try!(read_extension(extensions));
+                match remember_extension(&mut cert, id, critical, value) {
+                    Ok(Understood::No) if critical => {
+                        try!(extension_error(
+                            id, Error::UnsupportedCriticalExtension));
+                    },
+                    Ok(..) => (),
 
-        try!(der::nested_mut(tbs, der::Tag::ContextSpecificConstructed3,
-                             Error::BadDER, |tagged| {
-            der::nested_of_mut(tagged, der::Tag::Sequence, der::Tag::Sequence,
-                               Error::BadDER, |extension| {
-                let extn_id = try!(der::expect_tag_and_get_value(extension,
-                                                                 der::Tag::OID));
-                let critical = try!(der::optional_boolean(extension));
-                let extn_value: untrusted::Input<'a> =
-                    try!(der::expect_tag_and_get_value(extension,
-                                                       der::Tag::OctetString));
-                match try!(remember_extension(&mut cert, extn_id, extn_value)) {
-                    Understood::No if critical => {
-                        Err(Error::UnsupportedCriticalExtension)
+                    // Only the parsing of the value results in `BadDER`.
+                    Err(Error::BadDER) => {
+                        try!(extension_error(id, Error::BadDER));
                     },
-                    _ => Ok(())
+                    Err(err) => { return Err(err); }
                 }
-            })
+                if extensions.at_end() {
+                    break;
+                }
+            }
+            Ok(())
         }));
 
+        // https://tools.ietf.org/html/rfc5280#section-4.2: "A certificate
+        // MUST NOT include more than one instance of a particular extension."
+        let ids = || extensions_of(extensions).map(|extension| extension.id);
+        cert.has_duplicate_extension =
+            ids().enumerate()
+                 .any(|(i, id)| ids().skip(i + 1).any(|other| other == id));
+
         Ok(cert)
     })
 }
 
+// Parses only as much of the certificate as is needed to find its
+// subjectPublicKeyInfo. In particular, the extensions aren't parsed and the
+// signature algorithms aren't compared, so a certificate that `parse_cert`
+// would reject may be accepted here.
+pub fn parse_spki<'a>(cert_der: untrusted::Input<'a>)
+                      -> Result<untrusted::Input<'a>, Error> {
+    let (tbs, _) = try!(cert_der.read_all(Error::BadDER, |cert_der| {
+        der::nested(cert_der, der::Tag::Sequence, Error::BadDER,
+                    signed_data::parse_signed_data)
+    }));
+
+    tbs.read_all(Error::BadDER, |tbs| {
+        try!(version3(tbs));
+        let _ = try!(certificate_serial_number(tbs));
+
+        // signature, issuer, validity, subject.
+        for _ in 0..4 {
+            let _ = try!(der::expect_tag_and_get_value(tbs,
+                                                       der::Tag::Sequence));
+        }
+
+        let spki = try!(der::expect_tag_and_get_value(tbs, der::Tag::Sequence));
+
+        // Skip any issuerUniqueID, subjectUniqueID, and extensions.
+        let _ = tbs.skip_to_end();
+
+        Ok(spki)
+    })
+}
+
 // mozilla::pkix supports v1, v2, v3, and v4, including both the implicit
 // (correct) and explicit (incorrect) encoding of v1. We allow only v3.
 fn version3(input: &mut untrusted::Reader) -> Result<(), Error> {
@@ -126,8 +270,10 @@ fn version3(input: &mut untrusted::Reader) -> Result<(), Error> {
     })
 }
 
-fn certificate_serial_number<'a>(input: &mut untrusted::Reader<'a>)
-                                 -> Result<(), Error> {
+// The result is the value of the serial number with any leading zero byte
+// removed; i.e. its big-endian unsigned magnitude.
+pub fn certificate_serial_number<'a>(input: &mut untrusted::Reader<'a>)
+                                     -> Result<untrusted::Input<'a>, Error> {
     // https://tools.ietf.org/html/rfc5280#section-4.1.2.2:
     // * Conforming CAs MUST NOT use serialNumber values longer than 20 octets."
     // * "The serial number MUST be a positive integer [...]"
@@ -136,17 +282,184 @@ fn certificate_serial_number<'a>(input: &mut untrusted::Reader<'a>)
     if value.len() > 20 {
         return Err(Error::BadDER);
     }
-    Ok(())
+    Ok(value)
+}
+
+// The fields of an authorityKeyIdentifier extension, which identifies the
+// issuer's key either by its key identifier or by the issuer and serial number
+// of the issuer's certificate; see `verify_cert::authority_key_id_matches`.
+#[derive(Clone, Copy)]
+pub struct AuthorityKeyId<'a> {
+    pub key_id: Option<untrusted::Input<'a>>,
+
+    // The contents of the `GeneralNames` `SEQUENCE`.
+    pub cert_issuer: Option<untrusted::Input<'a>>,
+
+    // Like `Cert::serial`, with any leading zero byte removed.
+    pub cert_serial: Option<untrusted::Input<'a>>,
+}
+
+// AuthorityKeyIdentifier ::= SEQUENCE {
+//     keyIdentifier             [0] KeyIdentifier           OPTIONAL,
+//     authorityCertIssuer       [1] GeneralNames            OPTIONAL,
+//     authorityCertSerialNumber [2] CertificateSerialNumber OPTIONAL  }
+//
+// The tags are implicit.
+fn authority_key_id<'a>(input: &mut untrusted::Reader<'a>)
+                        -> Result<AuthorityKeyId<'a>, Error> {
+    const KEY_ID_TAG: u8 = 0x80;
+    const CERT_ISSUER_TAG: u8 = 0x80 | der::CONSTRUCTED | 1;
+    const CERT_SERIAL_TAG: u8 = 0x80 | 2;
+
+    fn optional<'a>(input: &mut untrusted::Reader<'a>, tag: u8)
+                    -> Result<Option<untrusted::Input<'a>>, Error> {
+        if !input.peek(tag) {
+            return Ok(None);
+        }
+        let (_, value) = try!(der::read_tag_and_get_value(input));
+        Ok(Some(value))
+    }
+
+    let key_id = try!(optional(input, KEY_ID_TAG));
+    let cert_issuer = try!(optional(input, CERT_ISSUER_TAG));
+    let cert_serial = match try!(optional(input, CERT_SERIAL_TAG)) {
+        Some(serial) => {
+            let bytes = serial.as_slice_less_safe();
+            if bytes.is_empty() {
+                return Err(Error::BadDER);
+            }
+            if bytes.len() > 1 && bytes[0] == 0 {
+                Some(untrusted::Input::from(&bytes[1..]))
+            } else {
+                Some(serial)
+            }
+        },
+        None => None,
+    };
+    Ok(AuthorityKeyId {
+        key_id: key_id,
+        cert_issuer: cert_issuer,
+        cert_serial: cert_serial,
+    })
+}
+
+/// An extension of a certificate.
+#[derive(Clone, Copy, Debug)]
+pub struct Extension<'a> {
+    /// The encoded value of the extension's OID (`extnID`), without the tag
+    /// and length.
+    pub id: der::Der<'a>,
+
+    /// Whether the extension is marked critical.
+    pub critical: bool,
+
+    /// The contents of the extension's `extnValue` OCTET STRING, i.e. the
+    /// DER-encoded extension value.
+    pub value: der::Der<'a>,
+}
+
+/// An iterator over the extensions of a certificate, in the order they are
+/// encoded in the certificate. Both the extensions that webpki understands
+/// and the ones it ignores are included.
+pub struct Extensions<'a> {
+    reader: untrusted::Reader<'a>,
+}
+
+pub fn extensions<'a>(cert: &Cert<'a>) -> Extensions<'a> {
+    extensions_of(cert.extensions)
+}
+
+fn extensions_of(extensions: untrusted::Input) -> Extensions {
+    Extensions { reader: untrusted::Reader::new(extensions) }
+}
+
+impl<'a> Iterator for Extensions<'a> {
+    type Item = Extension<'a>;
+
+    fn next(&mut self) -> Option<Extension<'a>> {
+        if self.reader.at_end() {
+            return None;
+        }
+        // The extensions were already parsed successfully by `parse_cert`.
+        read_extension(&mut self.reader).ok().map(|(id, critical, value)| {
+            Extension {
+                id: der::from_input(id),
+                critical: critical,
+                value: der::from_input(value),
+            }
+        })
+    }
+}
+
+// Extension  ::=  SEQUENCE  {
+//      extnID      OBJECT IDENTIFIER,
+//      critical    BOOLEAN DEFAULT FALSE,
+//      extnValue   OCTET STRING }
+pub fn read_extension<'a>(input: &mut untrusted::Reader<'a>)
+                          -> Result<(untrusted::Input<'a>, bool,
+                                     untrusted::Input<'a>), Error> {
+    der::nested(input, der::Tag::Sequence, Error::BadDER, |extension| {
+        let id = try!(der::expect_tag_and_get_value(extension, der::Tag::OID));
+        let critical = try!(der::optional_boolean(extension));
+        let value = try!(der::expect_tag_and_get_value(extension,
+                                                       der::Tag::OctetString));
+        Ok((id, critical, value))
+    })
+}
+
+// Checks the contents of a non-empty `Extensions` `SEQUENCE`, none of which
+// may be critical, as in CRLs and OCSP responses, where webpki doesn't
+// understand any extension that could be critical.
+pub fn check_no_critical_extensions(extensions: &mut untrusted::Reader)
+                                    -> Result<(), Error> {
+    loop {
+        let (_, critical, _) = try!(read_extension(extensions));
+        if critical {
+            return Err(Error::UnsupportedCriticalExtension);
+        }
+        if extensions.at_end() {
+            return Ok(());
+        }
+    }
 }
 
 enum Understood { Yes, No }
 
 fn remember_extension<'a>(cert: &mut Cert<'a>, extn_id: untrusted::Input,
-                          value: untrusted::Input<'a>)
+                          critical: bool, value: untrusted::Input<'a>)
                           -> Result<Understood, Error> {
-    // We don't do anything with certificate policies so we can safely ignore
-    // all policy-related stuff. We assume that the policy-related extensions
-    // are not marked critical.
+    // id-pkix-ocsp-nocheck 1.3.6.1.5.5.7.48.1.5. RFC 6960 Section 4.2.2.2.1
+    // says that a delegated OCSP responder certificate with this extension
+    // doesn't need to be checked for revocation. Its value is NULL.
+    static ID_PKIX_OCSP_NOCHECK: [u8; 9] = oid![1, 3, 6, 1, 5, 5, 7, 48, 1, 5];
+
+    if extn_id == &ID_PKIX_OCSP_NOCHECK[..] {
+        if cert.ocsp_no_check {
+            // The certificate contains more than one instance of this
+            // extension.
+            return Err(Error::DuplicateExtension);
+        }
+        try!(value.read_all(Error::BadDER, |value| der::null(value)));
+        cert.ocsp_no_check = true;
+        return Ok(Understood::Yes);
+    }
+
+    // id-pe-acmeIdentifier 1.3.6.1.5.5.7.1.31. RFC 8737 Section 3 requires it
+    // to be critical in tls-alpn-01 challenge certificates, so it must be
+    // understood for them to be parsed at all. Its value is an OCTET STRING.
+    static ID_PE_ACME_IDENTIFIER: [u8; 8] = oid![1, 3, 6, 1, 5, 5, 7, 1, 31];
+
+    if extn_id == &ID_PE_ACME_IDENTIFIER[..] {
+        if cert.acme_identifier.is_some() {
+            return Err(Error::DuplicateExtension);
+        }
+        cert.acme_identifier =
+            Some(try!(value.read_all(Error::BadDER, |value| {
+                der::expect_tag_and_get_value(value, der::Tag::OctetString)
+            })));
+        cert.acme_identifier_is_critical = critical;
+        return Ok(Understood::Yes);
+    }
 
     // id-ce 2.5.29
     static ID_CE: [u8; 2] = oid![2, 5, 29];
@@ -157,6 +470,15 @@ fn remember_extension<'a>(cert: &mut Cert<'a>, extn_id: untrusted::Input,
     }
 
     let out = match *extn_id.as_slice_less_safe().last().unwrap() {
+        // id-ce-subjectDirectoryAttributes 2.5.29.9. We ignore its value. TPM
+        // endorsement key certificates carry the TPM's specification and
+        // security assertions in it, and some vendors mark it critical; see
+        // `Policy::allow_critical_subject_directory_attributes`.
+        9 => {
+            cert.subject_directory_attributes_is_critical = critical;
+            return Ok(Understood::Yes);
+        },
+
         // id-ce-keyUsage 2.5.29.15. We ignore the KeyUsage extension. For CA
         // certificates, BasicConstraints.cA makes KeyUsage redundant. Firefox
         // and other common browsers do not check KeyUsage for end-entities,
@@ -164,8 +486,27 @@ fn remember_extension<'a>(cert: &mut Cert<'a>, extn_id: untrusted::Input,
         // the keyEncipherment bit could not be used for RSA key exchange.
         15 => { return Ok(Understood::Yes); },
 
-        // id-ce-subjectAltName 2.5.29.17
-        17 => &mut cert.subject_alt_name,
+        // id-ce-subjectKeyIdentifier 2.5.29.14. Unlike the others, its value
+        // is an OCTET STRING, not a SEQUENCE.
+        14 => {
+            if cert.subject_key_id.is_some() {
+                return Err(Error::DuplicateExtension);
+            }
+            cert.subject_key_id =
+                Some(try!(value.read_all(Error::BadDER, |value| {
+                    der::expect_tag_and_get_value(value,
+                                                  der::Tag::OctetString)
+                })));
+            return Ok(Understood::Yes);
+        },
+
+        // id-ce-subjectAltName 2.5.29.17. Its criticality matters when the
+        // subject is empty; see
+        // `verify_cert::check_subject_alt_name_conformance`.
+        17 => {
+            cert.subject_alt_name_is_critical = critical;
+            &mut cert.subject_alt_name
+        },
 
         // id-ce-basicConstraints 2.5.29.19
         19 => &mut cert.basic_constraints,
@@ -173,8 +514,48 @@ fn remember_extension<'a>(cert: &mut Cert<'a>, extn_id: untrusted::Input,
         // id-ce-nameConstraints 2.5.29.30
         30 => &mut cert.name_constraints,
 
-        // id-ce-extKeyUsage 2.5.29.37
-        37 => &mut cert.eku,
+        // id-ce-certificatePolicies 2.5.29.32
+        32 => &mut cert.certificate_policies,
+
+        // id-ce-policyMappings 2.5.29.33
+        33 => &mut cert.policy_mappings,
+
+        // id-ce-policyConstraints 2.5.29.36
+        36 => &mut cert.policy_constraints,
+
+        // id-ce-authorityKeyIdentifier 2.5.29.35
+        35 => {
+            if cert.authority_key_id.is_some() {
+                return Err(Error::DuplicateExtension);
+            }
+            cert.authority_key_id =
+                Some(try!(value.read_all(Error::BadDER, |value| {
+                    der::nested(value, der::Tag::Sequence, Error::BadDER,
+                                authority_key_id)
+                })));
+            return Ok(Understood::Yes);
+        },
+
+        // id-ce-extKeyUsage 2.5.29.37. Its criticality matters for
+        // time-stamping certificates; see
+        // `verify_cert::check_time_stamping_eku`.
+        37 => {
+            cert.eku_is_critical = critical;
+            &mut cert.eku
+        },
+
+        // id-ce-inhibitAnyPolicy 2.5.29.54. Unlike the others, its value is
+        // an INTEGER, not a SEQUENCE.
+        54 => {
+            if cert.inhibit_any_policy.is_some() {
+                return Err(Error::DuplicateExtension);
+            }
+            cert.inhibit_any_policy =
+                Some(try!(value.read_all(Error::BadDER, |value| {
+                    der::expect_tag_and_get_value(value, der::Tag::Integer)
+                })));
+            return Ok(Understood::Yes);
+        },
 
         _ => { return Ok(Understood::No); }
     };
@@ -183,7 +564,7 @@ fn remember_extension<'a>(cert: &mut Cert<'a>, extn_id: untrusted::Input,
         Some(..) => {
             // The certificate contains more than one instance of this
             // extension.
-            return Err(Error::ExtensionValueInvalid);
+            return Err(Error::DuplicateExtension);
         }
         None => {
             // All the extensions that we care about are wrapped in a SEQUENCE.
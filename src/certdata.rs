@@ -0,0 +1,480 @@
+// Copyright 2016 Brian Smith.
+//
+// Permission to use, copy, modify, and/or distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHORS DISCLAIM ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHORS BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+// Parsing of NSS's `certdata.txt`, the source of Mozilla's root store.
+//
+// The file is a sequence of PKCS#11 objects. Each object starts with its
+// `CKA_CLASS` attribute and is followed by its other attributes, one per
+// line, as `<name> <type> <value>`. `MULTILINE_OCTAL` values are given on
+// the following lines, as `\ooo` escapes, up to a line containing `END`.
+// Whether a certificate is trusted is recorded in a separate `CKO_NSS_TRUST`
+// object that refers to the certificate by its issuer and serial number.
+
+use {Error, TrustAnchor, der, time};
+use std::string::String;
+use std::vec::Vec;
+use trust_anchor_util::cert_der_as_trust_anchor;
+use untrusted;
+
+/// The trusted roots and the explicitly distrusted certificates of a
+/// `certdata.txt` file, as parsed by `parse_certdata`.
+#[derive(Clone, Debug)]
+pub struct Certdata {
+    /// The certificates that are trusted for issuing TLS server
+    /// certificates (`CKT_NSS_TRUSTED_DELEGATOR` for
+    /// `CKA_TRUST_SERVER_AUTH`), in the order they appear in the file.
+    pub roots: Vec<CertdataRoot>,
+
+    /// The certificates that must not be trusted for TLS server
+    /// authentication (`CKT_NSS_NOT_TRUSTED` for `CKA_TRUST_SERVER_AUTH`),
+    /// in the order they appear in the file.
+    pub distrusted: Vec<CertdataDistrust>,
+}
+
+/// A root certificate that `certdata.txt` trusts for issuing TLS server
+/// certificates.
+#[derive(Clone, Debug)]
+pub struct CertdataRoot {
+    /// The certificate's `CKA_LABEL`.
+    pub label: String,
+
+    /// The DER-encoded certificate (`CKA_VALUE`).
+    pub cert_der: Vec<u8>,
+
+    /// The certificate's `CKA_NSS_SERVER_DISTRUST_AFTER`, if it has one.
+    /// TLS server certificates that the root issued after this time, as
+    /// determined by their `notBefore`, must not be trusted.
+    pub server_distrust_after: Option<time::Time>,
+}
+
+impl CertdataRoot {
+    /// Interprets the certificate as a `TrustAnchor`; see
+    /// `cert_der_as_trust_anchor`.
+    pub fn trust_anchor<'a>(&'a self) -> Result<TrustAnchor<'a>, Error> {
+        cert_der_as_trust_anchor(&self.cert_der)
+    }
+}
+
+/// A certificate that `certdata.txt` explicitly distrusts for TLS server
+/// authentication, identified by its issuer and serial number.
+#[derive(Clone, Debug)]
+pub struct CertdataDistrust {
+    /// The trust object's `CKA_LABEL`.
+    pub label: String,
+
+    /// The DER-encoded issuer `Name` (`CKA_ISSUER`), including the tag and
+    /// length.
+    pub issuer: Vec<u8>,
+
+    /// The DER-encoded serial number `INTEGER` (`CKA_SERIAL_NUMBER`),
+    /// including the tag and length.
+    pub serial_number: Vec<u8>,
+}
+
+/// An error parsing a `certdata.txt` file.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CertdataError {
+    /// The file is not syntactically valid. The value is the (one-based)
+    /// number of the line at which the problem was found.
+    Syntax(usize),
+
+    /// An object is missing an attribute that is required for its class,
+    /// has an invalid value for one, or refers to a certificate that isn't
+    /// in the file. The value is the (one-based) number of the line of the
+    /// object's `CKA_CLASS`.
+    InvalidObject(usize),
+}
+
+/// Parses the contents of an NSS `certdata.txt` file.
+///
+/// Only the trust for TLS server authentication is considered. Certificates
+/// that have no trust object, or whose trust object neither trusts nor
+/// distrusts them for TLS server authentication, are ignored. The contents
+/// of the certificates are not validated; see `CertdataRoot::trust_anchor`.
+pub fn parse_certdata(input: &str) -> Result<Certdata, CertdataError> {
+    let objects = try!(parse_objects(input));
+
+    let mut certdata = Certdata {
+        roots: Vec::new(),
+        distrusted: Vec::new(),
+    };
+
+    for trust in &objects {
+        if try!(trust.token("CKA_CLASS")) != "CKO_NSS_TRUST" {
+            continue;
+        }
+
+        let issuer = try!(trust.octal("CKA_ISSUER"));
+        let serial_number = try!(trust.octal("CKA_SERIAL_NUMBER"));
+
+        match try!(trust.token("CKA_TRUST_SERVER_AUTH")) {
+            "CKT_NSS_TRUSTED_DELEGATOR" => {
+                let cert = try!(find_cert(&objects, issuer, serial_number)
+                                    .ok_or(trust.invalid()));
+                certdata.roots.push(CertdataRoot {
+                    label: String::from(try!(cert.utf8("CKA_LABEL"))),
+                    cert_der: try!(cert.octal("CKA_VALUE")).to_vec(),
+                    server_distrust_after:
+                        try!(cert.server_distrust_after()),
+                });
+            },
+            "CKT_NSS_NOT_TRUSTED" => {
+                certdata.distrusted.push(CertdataDistrust {
+                    label: String::from(try!(trust.utf8("CKA_LABEL"))),
+                    issuer: issuer.to_vec(),
+                    serial_number: serial_number.to_vec(),
+                });
+            },
+            "CKT_NSS_MUST_VERIFY_TRUST" | "CKT_NSS_TRUST_UNKNOWN" => (),
+            _ => { return Err(trust.invalid()); }
+        }
+    }
+
+    Ok(certdata)
+}
+
+fn find_cert<'a, 'b>(objects: &'b [Object<'a>], issuer: &[u8],
+                     serial_number: &[u8]) -> Option<&'b Object<'a>> {
+    objects.iter().find(|object| {
+        object.token("CKA_CLASS") == Ok("CKO_CERTIFICATE") &&
+            object.octal("CKA_ISSUER") == Ok(issuer) &&
+            object.octal("CKA_SERIAL_NUMBER") == Ok(serial_number)
+    })
+}
+
+struct Object<'a> {
+    // The line number of the object's `CKA_CLASS`.
+    line_number: usize,
+
+    attributes: Vec<(&'a str, Value<'a>)>,
+}
+
+enum Value<'a> {
+    // `CK_OBJECT_CLASS`, `CK_BBOOL`, `CK_TRUST`, etc.
+    Token(&'a str),
+    Utf8(&'a str),
+    Octal(Vec<u8>),
+}
+
+impl<'a> Object<'a> {
+    fn invalid(&self) -> CertdataError {
+        CertdataError::InvalidObject(self.line_number)
+    }
+
+    fn get(&self, name: &str) -> Result<&Value<'a>, CertdataError> {
+        self.attributes.iter()
+            .find(|&&(attribute_name, _)| attribute_name == name)
+            .map(|&(_, ref value)| value)
+            .ok_or(self.invalid())
+    }
+
+    fn token(&self, name: &str) -> Result<&'a str, CertdataError> {
+        match try!(self.get(name)) {
+            &Value::Token(token) => Ok(token),
+            _ => Err(self.invalid())
+        }
+    }
+
+    fn utf8(&self, name: &str) -> Result<&'a str, CertdataError> {
+        match try!(self.get(name)) {
+            &Value::Utf8(value) => Ok(value),
+            _ => Err(self.invalid())
+        }
+    }
+
+    fn octal(&self, name: &str) -> Result<&[u8], CertdataError> {
+        match try!(self.get(name)) {
+            &Value::Octal(ref value) => Ok(value),
+            _ => Err(self.invalid())
+        }
+    }
+
+    // `CKA_NSS_SERVER_DISTRUST_AFTER` is either `CK_FALSE` or the value of a
+    // `UTCTime`.
+    fn server_distrust_after(&self)
+                             -> Result<Option<time::Time>, CertdataError> {
+        match self.get("CKA_NSS_SERVER_DISTRUST_AFTER") {
+            Err(..) | Ok(&Value::Token("CK_FALSE")) => Ok(None),
+            Ok(&Value::Octal(ref value)) => {
+                untrusted::Input::from(value)
+                    .read_all(Error::BadDERTime,
+                              |value| der::time_value(value, true))
+                    .map(Some)
+                    .map_err(|_| self.invalid())
+            },
+            Ok(..) => Err(self.invalid())
+        }
+    }
+}
+
+fn parse_objects<'a>(input: &'a str)
+                     -> Result<Vec<Object<'a>>, CertdataError> {
+    let mut lines = input.lines().enumerate()
+                         .map(|(i, line)| (i + 1, line.trim()));
+
+    // Skip the header, e.g. the `CVS_ID` line.
+    loop {
+        match lines.next() {
+            Some((_, "BEGINDATA")) => { break; },
+            Some(..) => (),
+            None => {
+                return Err(CertdataError::Syntax(input.lines().count() + 1));
+            }
+        }
+    }
+
+    let mut objects = Vec::new();
+
+    while let Some((line_number, line)) = lines.next() {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let mut fields = line.splitn(3, ' ');
+        let name = fields.next().unwrap();
+        let value = match (fields.next(), fields.next()) {
+            (Some("MULTILINE_OCTAL"), None) => {
+                let mut value = Vec::new();
+                loop {
+                    match lines.next() {
+                        Some((_, "END")) => { break; },
+                        Some((line_number, line)) => {
+                            try!(parse_octal_line(line, &mut value)
+                                    .map_err(|_| CertdataError::Syntax(
+                                                    line_number)));
+                        },
+                        None => {
+                            return Err(CertdataError::Syntax(line_number));
+                        }
+                    }
+                }
+                Value::Octal(value)
+            },
+            (Some("UTF8"), Some(value))
+                if value.len() >= 2 && value.starts_with('"') &&
+                   value.ends_with('"') =>
+                Value::Utf8(&value[1..(value.len() - 1)]),
+            (Some(_), Some(token)) if !token.contains(' ') =>
+                Value::Token(token),
+            _ => { return Err(CertdataError::Syntax(line_number)); }
+        };
+
+        if name == "CKA_CLASS" {
+            objects.push(Object {
+                line_number: line_number,
+                attributes: Vec::new(),
+            });
+        }
+
+        match objects.last_mut() {
+            Some(object) => object.attributes.push((name, value)),
+            None => { return Err(CertdataError::Syntax(line_number)); }
+        }
+    }
+
+    Ok(objects)
+}
+
+// Parses a line like `\060\202\005\272`.
+fn parse_octal_line(line: &str, out: &mut Vec<u8>) -> Result<(), ()> {
+    if !line.starts_with('\\') {
+        return Err(());
+    }
+    for escape in line[1..].split('\\') {
+        if escape.len() != 3 {
+            return Err(());
+        }
+        let mut value: u16 = 0;
+        for digit in escape.bytes() {
+            match digit {
+                b'0'...b'7' => { value = (value * 8) + (digit - b'0') as u16; },
+                _ => { return Err(()); }
+            }
+        }
+        if value > 0xff {
+            return Err(());
+        }
+        out.push(value as u8);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CertdataError, parse_certdata};
+    use time;
+
+    const CERTDATA: &'static str = r#"
+CVS_ID "@(#) $RCSfile: certdata.txt $"
+
+BEGINDATA
+CKA_CLASS CK_OBJECT_CLASS CKO_NSS_BUILTIN_ROOT_LIST
+CKA_TOKEN CK_BBOOL CK_TRUE
+CKA_LABEL UTF8 "Mozilla Builtin Roots"
+
+#
+# Certificate "Trusted Root"
+#
+CKA_CLASS CK_OBJECT_CLASS CKO_CERTIFICATE
+CKA_LABEL UTF8 "Trusted Root"
+CKA_ISSUER MULTILINE_OCTAL
+\060\001\001
+END
+CKA_SERIAL_NUMBER MULTILINE_OCTAL
+\002\001\001
+END
+CKA_VALUE MULTILINE_OCTAL
+\060\003
+\001\002\003
+END
+CKA_NSS_SERVER_DISTRUST_AFTER CK_BBOOL CK_FALSE
+
+# Trust for "Trusted Root"
+CKA_CLASS CK_OBJECT_CLASS CKO_NSS_TRUST
+CKA_LABEL UTF8 "Trusted Root"
+CKA_ISSUER MULTILINE_OCTAL
+\060\001\001
+END
+CKA_SERIAL_NUMBER MULTILINE_OCTAL
+\002\001\001
+END
+CKA_TRUST_SERVER_AUTH CK_TRUST CKT_NSS_TRUSTED_DELEGATOR
+
+#
+# Certificate "Sunsetting Root"
+#
+CKA_CLASS CK_OBJECT_CLASS CKO_CERTIFICATE
+CKA_LABEL UTF8 "Sunsetting Root"
+CKA_ISSUER MULTILINE_OCTAL
+\060\001\002
+END
+CKA_SERIAL_NUMBER MULTILINE_OCTAL
+\002\001\002
+END
+CKA_VALUE MULTILINE_OCTAL
+\060\000
+END
+CKA_NSS_SERVER_DISTRUST_AFTER MULTILINE_OCTAL
+\061\071\061\061\063\060\062\063\065\071\065\071\132
+END
+
+# Trust for "Sunsetting Root"
+CKA_CLASS CK_OBJECT_CLASS CKO_NSS_TRUST
+CKA_LABEL UTF8 "Sunsetting Root"
+CKA_ISSUER MULTILINE_OCTAL
+\060\001\002
+END
+CKA_SERIAL_NUMBER MULTILINE_OCTAL
+\002\001\002
+END
+CKA_TRUST_SERVER_AUTH CK_TRUST CKT_NSS_TRUSTED_DELEGATOR
+
+#
+# Certificate "Email Root"
+#
+CKA_CLASS CK_OBJECT_CLASS CKO_CERTIFICATE
+CKA_LABEL UTF8 "Email Root"
+CKA_ISSUER MULTILINE_OCTAL
+\060\001\003
+END
+CKA_SERIAL_NUMBER MULTILINE_OCTAL
+\002\001\003
+END
+CKA_VALUE MULTILINE_OCTAL
+\060\000
+END
+
+# Trust for "Email Root"
+CKA_CLASS CK_OBJECT_CLASS CKO_NSS_TRUST
+CKA_LABEL UTF8 "Email Root"
+CKA_ISSUER MULTILINE_OCTAL
+\060\001\003
+END
+CKA_SERIAL_NUMBER MULTILINE_OCTAL
+\002\001\003
+END
+CKA_TRUST_SERVER_AUTH CK_TRUST CKT_NSS_MUST_VERIFY_TRUST
+
+# Distrust "Bad Intermediate"
+CKA_CLASS CK_OBJECT_CLASS CKO_NSS_TRUST
+CKA_LABEL UTF8 "Bad Intermediate"
+CKA_ISSUER MULTILINE_OCTAL
+\060\001\004
+END
+CKA_SERIAL_NUMBER MULTILINE_OCTAL
+\002\002\000\200
+END
+CKA_TRUST_SERVER_AUTH CK_TRUST CKT_NSS_NOT_TRUSTED
+"#;
+
+    #[test]
+    fn parse_certdata_test() {
+        let certdata = parse_certdata(CERTDATA).unwrap();
+
+        assert_eq!(2, certdata.roots.len());
+
+        let root = &certdata.roots[0];
+        assert_eq!("Trusted Root", root.label);
+        assert_eq!(&[0x30, 0x03, 0x01, 0x02, 0x03], &root.cert_der[..]);
+        assert_eq!(None, root.server_distrust_after);
+
+        let root = &certdata.roots[1];
+        assert_eq!("Sunsetting Root", root.label);
+        assert_eq!(Some(time::Time::from_ymdhms_utc(2019, 11, 30, 23, 59, 59)
+                            .unwrap()),
+                   root.server_distrust_after);
+
+        assert_eq!(1, certdata.distrusted.len());
+        let distrusted = &certdata.distrusted[0];
+        assert_eq!("Bad Intermediate", distrusted.label);
+        assert_eq!(&[0x30, 0x01, 0x04], &distrusted.issuer[..]);
+        assert_eq!(&[0x02, 0x02, 0x00, 0x80], &distrusted.serial_number[..]);
+    }
+
+    #[test]
+    fn parse_certdata_errors_test() {
+        // No BEGINDATA.
+        assert_eq!(CertdataError::Syntax(2),
+                   parse_certdata("CKA_CLASS CK_OBJECT_CLASS CKO_CERTIFICATE")
+                       .unwrap_err());
+
+        // An attribute outside of any object.
+        assert_eq!(CertdataError::Syntax(2),
+                   parse_certdata("BEGINDATA\nCKA_TOKEN CK_BBOOL CK_TRUE")
+                       .unwrap_err());
+
+        // A bad octal escape.
+        assert_eq!(CertdataError::Syntax(4),
+                   parse_certdata("BEGINDATA\n\
+                                   CKA_CLASS CK_OBJECT_CLASS CKO_CERTIFICATE\n\
+                                   CKA_VALUE MULTILINE_OCTAL\n\
+                                   \\060\\400\n\
+                                   END").unwrap_err());
+
+        // An unterminated MULTILINE_OCTAL value.
+        assert_eq!(CertdataError::Syntax(3),
+                   parse_certdata("BEGINDATA\n\
+                                   CKA_CLASS CK_OBJECT_CLASS CKO_CERTIFICATE\n\
+                                   CKA_VALUE MULTILINE_OCTAL\n\
+                                   \\060\\000").unwrap_err());
+
+        // A trust object for a certificate that isn't in the file.
+        let without_trusted_root =
+            CERTDATA.replace("CKO_CERTIFICATE\nCKA_LABEL UTF8 \"Trusted Root\"",
+                             "CKO_NETSCAPE_SMIME\n\
+                              CKA_LABEL UTF8 \"Trusted Root\"");
+        assert_eq!(CertdataError::InvalidObject(27),
+                   parse_certdata(&without_trusted_root).unwrap_err());
+    }
+}
@@ -0,0 +1,554 @@
+// Copyright 2016 Brian Smith.
+//
+// Permission to use, copy, modify, and/or distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHORS DISCLAIM ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHORS BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+// Certificate policy processing; see
+// https://tools.ietf.org/html/rfc5280#section-6.1.
+//
+// Policy qualifiers are ignored and the user-initial-policy-set is always
+// anyPolicy, so the only outcome of the processing is whether the path has a
+// valid policy wherever the explicit_policy counter requires one. Instead of
+// the policy tree of RFC 5280 we use the equivalent policy graph of RFC 9618,
+// which doesn't grow exponentially with the policy mappings, and we only keep
+// the current level of it, since that is all that is needed to decide whether
+// the tree would be NULL.
+//
+// A level is kept in buffers on the stack, so that verification doesn't
+// allocate for the levels of real PKIs, including bridge PKIs. With the `std`
+// feature, a level that needs more than `INLINE_POLICY_NODES` nodes, or more
+// than `INLINE_EXPECTED_POLICIES` expected policies in total, is moved to the
+// heap; without it, such a level is rejected with
+// `Error::PolicyValidationFailed`.
+
+use alloc_scope::{AllocScope, ArenaOrHeap};
+use cert::{Cert, EndEntityOrCA};
+use core::{cmp, mem};
+use {der, Error, Policy, untrusted};
+use ring::der::CONTEXT_SPECIFIC;
+
+// Checks the certificate policies of the path that starts with `cert_chain`,
+// the certificate issued by the trust anchor, and continues through the
+// `EndEntityOrCA::CA` links to the certificate being verified.
+pub fn check_certificate_policies(policy: &Policy, cert_chain: &Cert)
+                                  -> Result<(), Error> {
+    let n = path_len(cert_chain);
+
+    // 6.1.2 (d), (e), and (f).
+    let initial = |inhibit| if inhibit { 0 } else { n + 1 };
+    let mut explicit_policy = initial(policy.require_explicit_policy);
+    let mut inhibit_any_policy = initial(policy.inhibit_any_policy);
+    let mut policy_mapping = initial(policy.inhibit_policy_mapping);
+
+    // 6.1.2 (a): The root of the graph is an anyPolicy node. An empty level
+    // is the NULL tree. Each step builds the next level in `next`, which then
+    // becomes the current one.
+    let mut storage = LevelStorage::new();
+    let mut next_storage = LevelStorage::new();
+    let mut level = Level::new(&mut storage);
+    let mut next = Level::new(&mut next_storage);
+    try!(level.add_node(ANY_POLICY, &[ANY_POLICY]));
+
+    let mut cert = cert_chain;
+    let mut i = 1;
+    loop {
+        let self_issued = cert.subject == cert.issuer;
+
+        // 6.1.3 (d) and (e).
+        match cert.certificate_policies {
+            Some(certificate_policies) if !level.is_empty() => {
+                let any_policy_allowed =
+                    inhibit_any_policy > 0 || (i < n && self_issued);
+                try!(next_level(&level, certificate_policies,
+                                any_policy_allowed, &mut next));
+                mem::swap(&mut level, &mut next);
+            },
+            _ => level.clear(),
+        }
+
+        // 6.1.3 (f).
+        if explicit_policy == 0 && level.is_empty() {
+            return Err(Error::PolicyValidationFailed);
+        }
+
+        let child_cert = match cert.ee_or_ca {
+            EndEntityOrCA::CA(child_cert) => child_cert,
+            EndEntityOrCA::EndEntity |
+            EndEntityOrCA::TargetCA => { break; }
+        };
+
+        // 6.1.4 (a) and (b). The mappings must be valid even when the level
+        // is already empty.
+        if let Some(policy_mappings) = cert.policy_mappings {
+            try!(apply_policy_mappings(&level, policy_mappings,
+                                       policy_mapping > 0, &mut next));
+            mem::swap(&mut level, &mut next);
+        }
+
+        // 6.1.4 (h).
+        if !self_issued {
+            explicit_policy = explicit_policy.saturating_sub(1);
+            policy_mapping = policy_mapping.saturating_sub(1);
+            inhibit_any_policy = inhibit_any_policy.saturating_sub(1);
+        }
+
+        // 6.1.4 (i).
+        let (require_explicit_policy, inhibit_policy_mapping) =
+            try!(policy_constraints(cert));
+        if let Some(skip_certs) = require_explicit_policy {
+            explicit_policy = cmp::min(explicit_policy, skip_certs);
+        }
+        if let Some(skip_certs) = inhibit_policy_mapping {
+            policy_mapping = cmp::min(policy_mapping, skip_certs);
+        }
+
+        // 6.1.4 (j).
+        if let Some(skip_certs) = cert.inhibit_any_policy {
+            inhibit_any_policy = cmp::min(inhibit_any_policy,
+                                          try!(skip_certs_value(skip_certs)));
+        }
+
+        cert = child_cert;
+        i += 1;
+    }
+
+    // 6.1.5 (a) and (b).
+    explicit_policy = explicit_policy.saturating_sub(1);
+    if let (Some(0), _) = try!(policy_constraints(cert)) {
+        explicit_policy = 0;
+    }
+
+    // 6.1.5 (g) has nothing to do, since the user-initial-policy-set is
+    // anyPolicy.
+    if explicit_policy == 0 && level.is_empty() {
+        return Err(Error::PolicyValidationFailed);
+    }
+
+    Ok(())
+}
+
+// anyPolicy 2.5.29.32.0
+const ANY_POLICY: &'static [u8] = &oid![2, 5, 29, 32, 0];
+
+const INLINE_POLICY_NODES: usize = 64;
+const INLINE_EXPECTED_POLICIES: usize = 128;
+
+// A node of the current level of the policy graph. The parents of the node
+// don't matter, since every node at a level has at least one parent. Its
+// expected_policy_set is `expected_policies[expected_start..expected_end]`
+// of its `Level`.
+#[derive(Clone, Copy)]
+struct Node<'a> {
+    valid_policy: &'a [u8],
+    expected_start: usize,
+    expected_end: usize,
+}
+
+// The buffers on the stack for a `Level`.
+struct LevelStorage<'a> {
+    nodes: [Node<'a>; INLINE_POLICY_NODES],
+    expected_policies: [&'a [u8]; INLINE_EXPECTED_POLICIES],
+}
+
+impl<'a> LevelStorage<'a> {
+    fn new() -> LevelStorage<'a> {
+        let empty = Node {
+            valid_policy: &[],
+            expected_start: 0,
+            expected_end: 0,
+        };
+        LevelStorage {
+            nodes: [empty; INLINE_POLICY_NODES],
+            expected_policies: [&[]; INLINE_EXPECTED_POLICIES],
+        }
+    }
+}
+
+// A level of the policy graph. Nodes are only ever added at the end, and the
+// expected policies are only ever added to the last node.
+struct Level<'a: 's, 's> {
+    nodes: ArenaOrHeap<'s, Node<'a>>,
+    expected_policies: ArenaOrHeap<'s, &'a [u8]>,
+}
+
+impl<'a, 's> Level<'a, 's> {
+    fn new(storage: &'s mut LevelStorage<'a>) -> Level<'a, 's> {
+        Level {
+            nodes: ArenaOrHeap::new(&mut storage.nodes),
+            expected_policies: ArenaOrHeap::new(&mut storage.expected_policies),
+        }
+    }
+
+    fn clear(&mut self) {
+        self.nodes.clear();
+        self.expected_policies.clear();
+    }
+
+    fn is_empty(&self) -> bool { self.nodes().is_empty() }
+
+    fn nodes(&self) -> &[Node<'a>] { self.nodes.as_slice() }
+
+    fn expected_policy_set(&self, node: &Node) -> &[&'a [u8]] {
+        &self.expected_policies.as_slice()[node.expected_start..
+                                           node.expected_end]
+    }
+
+    fn has_node(&self, valid_policy: &[u8]) -> bool {
+        self.nodes().iter().any(|node| node.valid_policy == valid_policy)
+    }
+
+    fn add_node(&mut self, valid_policy: &'a [u8],
+                expected_policy_set: &[&'a [u8]]) -> Result<(), Error> {
+        let expected_len = self.expected_policies.as_slice().len();
+        try!(self.nodes.push(Node {
+            valid_policy: valid_policy,
+            expected_start: expected_len,
+            expected_end: expected_len,
+        }).map_err(|_| Error::PolicyValidationFailed));
+        for &expected_policy in expected_policy_set {
+            try!(self.add_expected_policy(expected_policy));
+        }
+        Ok(())
+    }
+
+    // Adds `expected_policy` to the expected_policy_set of the last node.
+    fn add_expected_policy(&mut self, expected_policy: &'a [u8])
+                           -> Result<(), Error> {
+        try!(self.expected_policies.push(expected_policy)
+                                   .map_err(|_| Error::PolicyValidationFailed));
+        let expected_len = self.expected_policies.as_slice().len();
+        let last = self.nodes.as_mut_slice().len() - 1;
+        self.nodes.as_mut_slice()[last].expected_end = expected_len;
+        Ok(())
+    }
+}
+
+fn path_len(cert_chain: &Cert) -> usize {
+    let mut len = 1;
+    let mut cert = cert_chain;
+    while let EndEntityOrCA::CA(child_cert) = cert.ee_or_ca {
+        len += 1;
+        cert = child_cert;
+    }
+    len
+}
+
+// 6.1.3 (d) (1) and (2). The next level replaces the contents of `level`.
+fn next_level<'a, 'p, 'l>(parents: &Level<'a, 'p>,
+                          certificate_policies: untrusted::Input<'a>,
+                          any_policy_allowed: bool, level: &mut Level<'a, 'l>)
+                          -> Result<(), Error> {
+    let parent_is_any_policy = parents.has_node(ANY_POLICY);
+
+    level.clear();
+    let mut cert_has_any_policy = false;
+    try!(certificate_policies.read_all(Error::BadDER, |input| {
+        loop {
+            let policy_id = try!(der::nested(input, der::Tag::Sequence,
+                                             Error::BadDER, |info| {
+                let policy_id =
+                    try!(der::expect_tag_and_get_value(info, der::Tag::OID));
+                // The policyQualifiers are ignored.
+                let _ = info.skip_to_end();
+                Ok(policy_id.as_slice_less_safe())
+            }));
+
+            if policy_id == ANY_POLICY {
+                cert_has_any_policy = true;
+            } else {
+                let is_expected = parent_is_any_policy ||
+                    parents.nodes().iter().any(|parent| {
+                        parents.expected_policy_set(parent)
+                               .contains(&policy_id)
+                    });
+                if is_expected && !level.has_node(policy_id) {
+                    try!(level.add_node(policy_id, &[policy_id]));
+                }
+            }
+
+            if input.at_end() {
+                break;
+            }
+        }
+        Ok(())
+    }));
+
+    if cert_has_any_policy && any_policy_allowed {
+        for parent in parents.nodes() {
+            for &expected_policy in parents.expected_policy_set(parent) {
+                if !level.has_node(expected_policy) {
+                    try!(level.add_node(expected_policy, &[expected_policy]));
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// 6.1.4 (a) and (b). The mapped level replaces the contents of `level`.
+fn apply_policy_mappings<'a, 'n, 'l>(nodes: &Level<'a, 'n>,
+                                     policy_mappings: untrusted::Input<'a>,
+                                     mapping_allowed: bool,
+                                     level: &mut Level<'a, 'l>)
+                                     -> Result<(), Error> {
+    // 6.1.4 (a). This also checks that the extension is well-formed, so the
+    // passes over it below can't fail because of it.
+    try!(for_each_policy_mapping(policy_mappings, |issuer, subject| {
+        if issuer == ANY_POLICY || subject == ANY_POLICY {
+            return Err(Error::ExtensionValueInvalid);
+        }
+        Ok(())
+    }));
+
+    let is_mapped = |valid_policy: &[u8]| {
+        let mut found = false;
+        let _ = for_each_policy_mapping(policy_mappings, |issuer, _| {
+            found = found || issuer == valid_policy;
+            Ok(())
+        });
+        found
+    };
+
+    // Each issuerDomainPolicy is handled once, with all of its
+    // subjectDomainPolicy values.
+    let add_mapped_node = |level: &mut Level<'a, 'l>,
+                           issuer_domain_policy: &'a [u8]| {
+        try!(level.add_node(issuer_domain_policy, &[]));
+        for_each_policy_mapping(policy_mappings, |issuer, subject| {
+            if issuer == issuer_domain_policy {
+                try!(level.add_expected_policy(subject));
+            }
+            Ok(())
+        })
+    };
+
+    level.clear();
+    for node in nodes.nodes() {
+        if !is_mapped(node.valid_policy) {
+            try!(level.add_node(node.valid_policy,
+                                nodes.expected_policy_set(node)));
+        } else if mapping_allowed {
+            // 6.1.4 (b) (1).
+            try!(add_mapped_node(level, node.valid_policy));
+        }
+        // Otherwise the node is deleted; 6.1.4 (b) (2).
+    }
+
+    // 6.1.4 (b) (1): Mapped policies without a node of their own get one if
+    // there is an anyPolicy node.
+    if mapping_allowed && nodes.has_node(ANY_POLICY) {
+        try!(for_each_policy_mapping(policy_mappings, |issuer, _| {
+            if !level.has_node(issuer) {
+                try!(add_mapped_node(level, issuer));
+            }
+            Ok(())
+        }));
+    }
+
+    Ok(())
+}
+
+// Calls `f` with the issuerDomainPolicy and subjectDomainPolicy of each
+// mapping of a policyMappings extension, in order.
+fn for_each_policy_mapping<'a, F>(policy_mappings: untrusted::Input<'a>,
+                                  mut f: F) -> Result<(), Error>
+                                  where F: FnMut(&'a [u8], &'a [u8])
+                                                 -> Result<(), Error> {
+    policy_mappings.read_all(Error::BadDER, |input| {
+        loop {
+            try!(der::nested(input, der::Tag::Sequence, Error::BadDER,
+                             |mapping| {
+                let issuer_domain_policy =
+                    try!(der::expect_tag_and_get_value(mapping, der::Tag::OID));
+                let subject_domain_policy =
+                    try!(der::expect_tag_and_get_value(mapping, der::Tag::OID));
+                f(issuer_domain_policy.as_slice_less_safe(),
+                  subject_domain_policy.as_slice_less_safe())
+            }));
+            if input.at_end() {
+                break;
+            }
+        }
+        Ok(())
+    })
+}
+
+// Returns the requireExplicitPolicy and inhibitPolicyMapping values of the
+// certificate's policyConstraints extension, if any.
+//
+// PolicyConstraints ::= SEQUENCE {
+//      requireExplicitPolicy           [0] SkipCerts OPTIONAL,
+//      inhibitPolicyMapping            [1] SkipCerts OPTIONAL }
+fn policy_constraints(cert: &Cert)
+                      -> Result<(Option<usize>, Option<usize>), Error> {
+    const REQUIRE_EXPLICIT_POLICY_TAG: u8 = CONTEXT_SPECIFIC;
+    const INHIBIT_POLICY_MAPPING_TAG: u8 = CONTEXT_SPECIFIC | 1;
+
+    fn optional_skip_certs(input: &mut untrusted::Reader, tag: u8)
+                           -> Result<Option<usize>, Error> {
+        if !input.peek(tag) {
+            return Ok(None);
+        }
+        let (_, value) = try!(der::read_tag_and_get_value(input));
+        skip_certs_value(value).map(Some)
+    }
+
+    let value = match cert.policy_constraints {
+        Some(value) => value,
+        None => { return Ok((None, None)); }
+    };
+    value.read_all(Error::BadDER, |input| {
+        let require_explicit_policy =
+            try!(optional_skip_certs(input, REQUIRE_EXPLICIT_POLICY_TAG));
+        let inhibit_policy_mapping =
+            try!(optional_skip_certs(input, INHIBIT_POLICY_MAPPING_TAG));
+        // RFC 5280: "Conforming CAs MUST NOT issue certificates where policy
+        // constraints is an empty sequence."
+        if require_explicit_policy.is_none() &&
+           inhibit_policy_mapping.is_none() {
+            return Err(Error::BadDER);
+        }
+        Ok((require_explicit_policy, inhibit_policy_mapping))
+    })
+}
+
+// Parses the value of a `SkipCerts ::= INTEGER (0..MAX)`, without its tag and
+// length. Values too large for `usize` are saturated, since they are
+// effectively infinite anyway.
+fn skip_certs_value(value: untrusted::Input) -> Result<usize, Error> {
+    let bytes = value.as_slice_less_safe();
+    if bytes.is_empty() ||
+       bytes[0] & 0x80 != 0 || // Negative.
+       (bytes.len() > 1 && bytes[0] == 0 && bytes[1] & 0x80 == 0) {
+        // Not minimally encoded.
+        return Err(Error::BadDER);
+    }
+    Ok(bytes.iter().fold(0usize, |acc, &b| {
+        acc.saturating_mul(0x100).saturating_add(b as usize)
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::vec::Vec;
+
+    #[test]
+    fn test_skip_certs_value() {
+        let valid: [(&[u8], usize); 5] = [
+            (&[0x00], 0),
+            (&[0x7f], 127),
+            (&[0x00, 0x80], 128),
+            (&[0x01, 0x00], 256),
+            (&[0x7f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff],
+             usize::MAX),
+        ];
+        for &(value, expected) in valid.iter() {
+            assert_eq!(Ok(expected),
+                       skip_certs_value(untrusted::Input::from(value)));
+        }
+
+        let invalid: [&[u8]; 3] = [&[], &[0x80], &[0x00, 0x7f]];
+        for value in invalid.iter() {
+            assert_eq!(Err(Error::BadDER),
+                       skip_certs_value(untrusted::Input::from(value)));
+        }
+    }
+
+    const A: &'static [u8] = &[0x2a, 0x03];
+    const B: &'static [u8] = &[0x2a, 0x04];
+    const C: &'static [u8] = &[0x2a, 0x05];
+    const D: &'static [u8] = &[0x2a, 0x06];
+
+    fn valid_policies<'a, 's>(level: &Level<'a, 's>) -> Vec<&'a [u8]> {
+        level.nodes().iter().map(|node| node.valid_policy).collect()
+    }
+
+    #[test]
+    fn test_policy_mappings() {
+        let mut root_storage = LevelStorage::new();
+        let mut root = Level::new(&mut root_storage);
+        root.add_node(ANY_POLICY, &[ANY_POLICY]).unwrap();
+
+        // PolicyInformation SEQUENCEs for A and B.
+        let certificate_policies = [
+            0x30, 0x04, 0x06, 0x02, 0x2a, 0x03,
+            0x30, 0x04, 0x06, 0x02, 0x2a, 0x04,
+        ];
+        let mut storage = LevelStorage::new();
+        let mut level = Level::new(&mut storage);
+        next_level(&root, untrusted::Input::from(&certificate_policies),
+                   false, &mut level).unwrap();
+        assert_eq!(valid_policies(&level), [A, B]);
+
+        // A maps to C and D.
+        let policy_mappings = [
+            0x30, 0x08, 0x06, 0x02, 0x2a, 0x03, 0x06, 0x02, 0x2a, 0x05,
+            0x30, 0x08, 0x06, 0x02, 0x2a, 0x03, 0x06, 0x02, 0x2a, 0x06,
+        ];
+        let policy_mappings = untrusted::Input::from(&policy_mappings);
+        let mut mapped_storage = LevelStorage::new();
+        let mut mapped = Level::new(&mut mapped_storage);
+        apply_policy_mappings(&level, policy_mappings, true, &mut mapped)
+            .unwrap();
+        assert_eq!(valid_policies(&mapped), [A, B]);
+        assert_eq!(mapped.expected_policy_set(&mapped.nodes()[0]), [C, D]);
+        assert_eq!(mapped.expected_policy_set(&mapped.nodes()[1]), [B]);
+
+        // With mapping inhibited, the node for A is deleted.
+        apply_policy_mappings(&level, policy_mappings, false, &mut mapped)
+            .unwrap();
+        assert_eq!(valid_policies(&mapped), [B]);
+
+        // Mapping from anyPolicy is invalid, even for an empty level.
+        let any_policy_mapping = [
+            0x30, 0x09, 0x06, 0x04, 0x55, 0x1d, 0x20, 0x00, 0x06, 0x01, 0x2a,
+        ];
+        level.clear();
+        assert_eq!(
+            Err(Error::ExtensionValueInvalid),
+            apply_policy_mappings(&level,
+                                  untrusted::Input::from(&any_policy_mapping),
+                                  false, &mut mapped));
+    }
+
+    // With the `std` feature, a level that doesn't fit in the buffers on the
+    // stack is moved to the heap, rather than being rejected.
+    #[test]
+    fn test_many_policies() {
+        let mut root_storage = LevelStorage::new();
+        let mut root = Level::new(&mut root_storage);
+        root.add_node(ANY_POLICY, &[ANY_POLICY]).unwrap();
+
+        let mut certificate_policies = Vec::new();
+        for i in 0..(INLINE_EXPECTED_POLICIES + 1) {
+            certificate_policies.extend_from_slice(
+                &[0x30, 0x05, 0x06, 0x03, 0x2a, 0x03, i as u8]);
+        }
+        let mut storage = LevelStorage::new();
+        let mut level = Level::new(&mut storage);
+        let result =
+            next_level(&root, untrusted::Input::from(&certificate_policies),
+                       false, &mut level);
+        if !cfg!(feature = "std") {
+            assert_eq!(Err(Error::PolicyValidationFailed), result);
+            return;
+        }
+        assert_eq!(Ok(()), result);
+        assert_eq!(INLINE_EXPECTED_POLICIES + 1, level.nodes().len());
+        for (i, node) in level.nodes().iter().enumerate() {
+            assert_eq!(&[0x2a, 0x03, i as u8], node.valid_policy);
+            assert_eq!([node.valid_policy], level.expected_policy_set(node));
+        }
+    }
+}
@@ -0,0 +1,63 @@
+// Copyright 2016 Brian Smith.
+//
+// Permission to use, copy, modify, and/or distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHORS DISCLAIM ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHORS BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+//! Constant-time comparisons, for implementing public key pinning and
+//! similar checks without using `==`.
+//!
+//! These are thin wrappers around *ring*'s `constant_time` module. The time
+//! they take depends on the lengths of their inputs but not on their
+//! contents.
+
+use ring;
+
+/// Returns `true` if `a` and `b` are equal, in time that doesn't depend on
+/// the contents of `a` or `b`.
+pub fn slices_are_equal(a: &[u8], b: &[u8]) -> bool {
+    ring::constant_time::verify_slices_are_equal(a, b).is_ok()
+}
+
+/// Returns `true` if `value` is equal to at least one of `candidates`, in
+/// time that doesn't depend on the contents of `value` or `candidates`, or
+/// on which of the candidates matched. In particular, every candidate is
+/// compared even after a match has been found.
+pub fn slice_is_one_of<T: AsRef<[u8]>>(value: &[u8], candidates: &[T])
+                                       -> bool {
+    candidates.iter().fold(false, |found, candidate| {
+        slices_are_equal(value, candidate.as_ref()) | found
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{slice_is_one_of, slices_are_equal};
+
+    #[test]
+    fn test_slices_are_equal() {
+        assert!(slices_are_equal(b"", b""));
+        assert!(slices_are_equal(b"abc", b"abc"));
+        assert!(!slices_are_equal(b"abc", b"abd"));
+        assert!(!slices_are_equal(b"abc", b"ab"));
+        assert!(!slices_are_equal(b"", b"a"));
+    }
+
+    #[test]
+    fn test_slice_is_one_of() {
+        let candidates: [&[u8]; 3] = [b"abc", b"def", b"abc"];
+        assert!(slice_is_one_of(b"abc", &candidates));
+        assert!(slice_is_one_of(b"def", &candidates));
+        assert!(!slice_is_one_of(b"ghi", &candidates));
+        assert!(!slice_is_one_of(b"abc", &[[0u8; 3]; 0]));
+        assert!(slice_is_one_of(&[1u8; 32], &[[0u8; 32], [1u8; 32]]));
+    }
+}
@@ -0,0 +1,580 @@
+// Copyright 2016 Brian Smith.
+//
+// Permission to use, copy, modify, and/or distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHORS DISCLAIM ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHORS BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+//! Certificate revocation lists (CRLs).
+//!
+//! See [RFC 5280 Section 5](https://tools.ietf.org/html/rfc5280#section-5).
+//!
+//! The CRLs of some PKIs, e.g. those of some national PKIs, list millions of
+//! certificates and are hundreds of megabytes long. A `CertRevocationList`
+//! never copies or collects the revoked certificates: they are checked in a
+//! single pass when the CRL is parsed, and afterwards they are read directly
+//! from the DER-encoded CRL whenever they are looked up. The memory used is
+//! the same regardless of the length of the CRL.
+
+use core::cmp::Ordering;
+use {cert, der, Der, EndEntityCert, Error, Policy, SignatureAlgorithm, Time};
+use cert::EndEntityOrCA;
+use revocation::{CertStatus, RevocationInfo};
+use signed_data;
+use untrusted;
+
+// The number of revoked certificates whose positions are remembered for
+// `CertRevocationList::find`.
+const INDEX_LEN: usize = 128;
+
+/// A parsed certificate revocation list.
+///
+/// ```ASN.1
+/// CertificateList  ::=  SEQUENCE  {
+///      tbsCertList          TBSCertList,
+///      signatureAlgorithm   AlgorithmIdentifier,
+///      signatureValue       BIT STRING  }
+///
+/// TBSCertList  ::=  SEQUENCE  {
+///      version                 Version OPTIONAL,
+///      signature               AlgorithmIdentifier,
+///      issuer                  Name,
+///      thisUpdate              Time,
+///      nextUpdate              Time OPTIONAL,
+///      revokedCertificates     SEQUENCE OF SEQUENCE  {
+///           userCertificate         CertificateSerialNumber,
+///           revocationDate          Time,
+///           crlEntryExtensions      Extensions OPTIONAL
+///      }  OPTIONAL,
+///      crlExtensions           [0]  EXPLICIT Extensions OPTIONAL  }
+/// ```
+///
+/// Only complete CRLs for all the certificates of their issuer are
+/// supported. CRLs with any critical extension, which includes all delta
+/// CRLs (deltaCRLIndicator) and partitioned CRLs (issuingDistributionPoint),
+/// and CRLs with a critical entry extension, which includes all indirect
+/// CRLs (certificateIssuer), are rejected with
+/// `Error::UnsupportedCriticalExtension`. Non-critical extensions, like the
+/// reasonCode of entries, are ignored.
+pub struct CertRevocationList<'a> {
+    signed_data: signed_data::SignedData<'a>,
+    tbs_signature: untrusted::Input<'a>,
+    issuer: untrusted::Input<'a>,
+    this_update: Time,
+    next_update: Option<Time>,
+
+    // The contents of `revokedCertificates`, which is empty if it is absent.
+    revoked_certs: &'a [u8],
+    revoked_cert_count: usize,
+
+    // Whether the serial numbers of `revoked_certs` are in increasing order.
+    sorted: bool,
+
+    // If `sorted`, the offsets in `revoked_certs` of the revoked certificates
+    // that start at or after each `INDEX_LEN`th of its length, in increasing
+    // order; `index[0]` is always 0 unless `revoked_certs` is empty.
+    index: [usize; INDEX_LEN],
+    index_len: usize,
+}
+
+impl<'a> CertRevocationList<'a> {
+    /// Parses the ASN.1 DER-encoded CRL `crl_der`.
+    ///
+    /// Every revoked certificate is parsed and checked, and whether they are
+    /// sorted by serial number is determined, so this takes time
+    /// proportional to the length of the CRL. The signature isn't verified;
+    /// see `verify_signed_by`.
+    pub fn from(crl_der: &'a [u8]) -> Result<CertRevocationList<'a>, Error> {
+        let (tbs, signed_data) =
+            try!(untrusted::Input::from(crl_der).read_all(Error::BadDER,
+                                                          |crl_der| {
+                let crl = try!(der::expect_tag_and_get_long_value(
+                                   crl_der, der::Tag::Sequence));
+                crl.read_all(Error::BadDER,
+                             signed_data::parse_long_signed_data)
+            }));
+
+        tbs.read_all(Error::BadDER, |tbs| {
+            if tbs.peek(der::Tag::Integer as u8) {
+                // v2; v1 is encoded by omitting the version.
+                if try!(der::small_nonnegative_integer(tbs)) != 1 {
+                    return Err(Error::BadDER);
+                }
+            }
+            let tbs_signature =
+                try!(der::expect_tag_and_get_value(tbs, der::Tag::Sequence));
+            let issuer =
+                try!(der::expect_tag_and_get_value(tbs, der::Tag::Sequence));
+            let this_update = try!(der::time_choice(tbs));
+            let next_update =
+                if tbs.peek(der::Tag::UTCTime as u8) ||
+                   tbs.peek(der::Tag::GeneralizedTime as u8) {
+                    Some(try!(der::time_choice(tbs)))
+                } else {
+                    None
+                };
+            let revoked_certs = if tbs.peek(der::Tag::Sequence as u8) {
+                try!(der::expect_tag_and_get_long_value(tbs,
+                                                        der::Tag::Sequence))
+            } else {
+                untrusted::Input::from(&[])
+            };
+            if !tbs.at_end() {
+                try!(der::nested(tbs, der::Tag::ContextSpecificConstructed0,
+                                 Error::BadDER, |tagged| {
+                    der::nested(tagged, der::Tag::Sequence, Error::BadDER,
+                                cert::check_no_critical_extensions)
+                }));
+            }
+
+            let mut crl = CertRevocationList {
+                signed_data: signed_data,
+                tbs_signature: tbs_signature,
+                issuer: issuer,
+                this_update: this_update,
+                next_update: next_update,
+                revoked_certs: revoked_certs.as_slice_less_safe(),
+                revoked_cert_count: 0,
+                sorted: true,
+                index: [0; INDEX_LEN],
+                index_len: 0,
+            };
+            try!(crl.check_revoked_certs());
+            Ok(crl)
+        })
+    }
+
+    // Checks every revoked certificate, counting them and building `index`.
+    fn check_revoked_certs(&mut self) -> Result<(), Error> {
+        let len = self.revoked_certs.len();
+        let spacing = (len + INDEX_LEN - 1) / INDEX_LEN;
+        let mut reader =
+            untrusted::Reader::new(untrusted::Input::from(self.revoked_certs));
+        let mut offset = 0;
+        let mut previous_serial_number: Option<&[u8]> = None;
+        while !reader.at_end() {
+            let mark1 = reader.mark();
+            let entry = try!(read_entry(&mut reader));
+            let mark2 = reader.mark();
+            let entry_len =
+                reader.get_input_between_marks(mark1, mark2).unwrap().len();
+            let serial_number = entry.serial_number.as_slice();
+            if let Some(previous) = previous_serial_number {
+                if compare_serial_numbers(previous, serial_number) ==
+                        Ordering::Greater {
+                    self.sorted = false;
+                }
+            }
+            previous_serial_number = Some(serial_number);
+            if self.index_len < INDEX_LEN &&
+               offset >= self.index_len * spacing {
+                self.index[self.index_len] = offset;
+                self.index_len += 1;
+            }
+            self.revoked_cert_count += 1;
+            offset += entry_len;
+        }
+        Ok(())
+    }
+
+    /// The CRL's `issuer` field, in the same form as `TrustAnchor::subject`:
+    /// the value of the `Name` `SEQUENCE` without its tag and length.
+    pub fn issuer(&self) -> Der<'a> { der::from_input(self.issuer) }
+
+    /// The CRL's `thisUpdate`.
+    pub fn this_update(&self) -> Time { self.this_update }
+
+    /// The CRL's `nextUpdate`, if any.
+    pub fn next_update(&self) -> Option<Time> { self.next_update }
+
+    /// The number of revoked certificates listed in the CRL.
+    pub fn revoked_cert_count(&self) -> usize { self.revoked_cert_count }
+
+    /// Whether the revoked certificates are listed in increasing order of
+    /// serial number, in which case `find` doesn't need to read all of them.
+    pub fn is_sorted(&self) -> bool { self.sorted }
+
+    /// The revoked certificates, in the order they are listed in the CRL.
+    pub fn revoked_certs(&self) -> CrlEntries<'a> {
+        self.revoked_certs_from(0)
+    }
+
+    fn revoked_certs_from(&self, offset: usize) -> CrlEntries<'a> {
+        CrlEntries {
+            reader: untrusted::Reader::new(
+                untrusted::Input::from(&self.revoked_certs[offset..])),
+        }
+    }
+
+    /// Finds the revoked certificate with the serial number `serial_number`,
+    /// in the same form as `EndEntityCert::serial_number`.
+    ///
+    /// If the CRL `is_sorted`, a binary search over a fixed number of the
+    /// revoked certificates narrows the search down to at most about a
+    /// 128th of the CRL. Otherwise, every revoked certificate may have to be
+    /// read.
+    pub fn find(&self, serial_number: &[u8]) -> Option<CrlEntry<'a>> {
+        if !self.sorted {
+            return self.revoked_certs().find(|entry| {
+                entry.serial_number.as_slice() == serial_number
+            });
+        }
+        if self.index_len == 0 ||
+           compare_serial_numbers(self.serial_number_at(0),
+                                  serial_number) == Ordering::Greater {
+            return None;
+        }
+
+        // Find the last indexed entry whose serial number isn't greater.
+        let mut low = 0;
+        let mut high = self.index_len;
+        while high - low > 1 {
+            let middle = low + ((high - low) / 2);
+            match compare_serial_numbers(self.serial_number_at(middle),
+                                         serial_number) {
+                Ordering::Greater => { high = middle; },
+                _ => { low = middle; },
+            }
+        }
+
+        for entry in self.revoked_certs_from(self.index[low]) {
+            match compare_serial_numbers(entry.serial_number.as_slice(),
+                                         serial_number) {
+                Ordering::Less => (),
+                Ordering::Equal => { return Some(entry); },
+                Ordering::Greater => { break; },
+            }
+        }
+        None
+    }
+
+    // The serial number of the revoked certificate at `self.index[i]`.
+    fn serial_number_at(&self, i: usize) -> &'a [u8] {
+        // The entries were already checked by `from`.
+        self.revoked_certs_from(self.index[i]).next().unwrap()
+            .serial_number.as_slice()
+    }
+
+    /// Verifies the CRL's signature, given the ASN.1 DER-encoded X.509
+    /// certificate of its issuer, `issuer_cert_der`.
+    ///
+    /// The issuer's certificate is not validated in any way other than
+    /// checking that its subject is the CRL's issuer; if it isn't,
+    /// `Error::UnknownIssuer` is returned. It must be the certificate of a
+    /// CA that the caller has already verified, e.g. the issuer in the
+    /// verified path of the certificate whose status is being checked.
+    ///
+    /// `policy` is the policy the signature is checked against, which should
+    /// be the one the path was verified with.
+    pub fn verify_signed_by(&self, policy: &Policy,
+                            supported_sig_algs: &[&SignatureAlgorithm],
+                            issuer_cert_der: &[u8]) -> Result<(), Error> {
+        let issuer = try!(cert::parse_cert(
+                              untrusted::Input::from(issuer_cert_der),
+                              EndEntityOrCA::TargetCA, policy));
+        if issuer.subject != self.issuer {
+            return Err(Error::UnknownIssuer);
+        }
+        if !signed_data::algorithm_identifiers_are_equal(
+                self.signed_data.algorithm, self.tbs_signature,
+                policy.allow_signature_algorithm_null_parameter_mismatch) {
+            return Err(Error::SignatureAlgorithmMismatch);
+        }
+        signed_data::verify_signed_data(policy, supported_sig_algs,
+                                        issuer.spki, &self.signed_data)
+    }
+
+    /// What the CRL says about the status of `cert`, for
+    /// `revocation::RevocationChecker`.
+    ///
+    /// The status is `CertStatus::Unknown` if the CRL wasn't issued by
+    /// `cert`'s issuer. The CRL's signature must already have been verified
+    /// with `verify_signed_by`.
+    pub fn revocation_info(&self, cert: &EndEntityCert) -> RevocationInfo {
+        let status = if cert.inner.issuer != self.issuer {
+            CertStatus::Unknown
+        } else if self.find(cert.inner.serial.as_slice_less_safe())
+                      .is_some() {
+            CertStatus::Revoked
+        } else {
+            CertStatus::Good
+        };
+        RevocationInfo {
+            status: status,
+            this_update: self.this_update,
+            next_update: self.next_update,
+        }
+    }
+}
+
+/// A revoked certificate listed in a CRL.
+#[derive(Clone, Copy, Debug)]
+pub struct CrlEntry<'a> {
+    /// The certificate's serial number, in the same form as
+    /// `EndEntityCert::serial_number`.
+    pub serial_number: Der<'a>,
+
+    /// When the certificate was revoked.
+    pub revocation_date: Time,
+}
+
+/// An iterator over the revoked certificates of a CRL. See
+/// `CertRevocationList::revoked_certs`.
+pub struct CrlEntries<'a> {
+    reader: untrusted::Reader<'a>,
+}
+
+impl<'a> Iterator for CrlEntries<'a> {
+    type Item = CrlEntry<'a>;
+
+    fn next(&mut self) -> Option<CrlEntry<'a>> {
+        if self.reader.at_end() {
+            return None;
+        }
+        // The entries were already checked by `CertRevocationList::from`.
+        read_entry(&mut self.reader).ok()
+    }
+}
+
+fn read_entry<'a>(input: &mut untrusted::Reader<'a>)
+                  -> Result<CrlEntry<'a>, Error> {
+    der::nested(input, der::Tag::Sequence, Error::BadDER, |entry| {
+        let serial_number = try!(cert::certificate_serial_number(entry));
+        let revocation_date = try!(der::time_choice(entry));
+        if !entry.at_end() {
+            try!(der::nested(entry, der::Tag::Sequence, Error::BadDER,
+                             cert::check_no_critical_extensions));
+        }
+        Ok(CrlEntry {
+            serial_number: der::from_input(serial_number),
+            revocation_date: revocation_date,
+        })
+    })
+}
+
+// Compares serial numbers without leading zero bytes numerically.
+fn compare_serial_numbers(a: &[u8], b: &[u8]) -> Ordering {
+    match a.len().cmp(&b.len()) {
+        Ordering::Equal => a.cmp(b),
+        ordering => ordering,
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::{CertRevocationList, INDEX_LEN};
+    use {der_writer, Error, Time};
+    use std::vec::Vec;
+
+    fn tlv(tag: u8, value: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        der_writer::write_long_tlv(&mut out, tag, value).unwrap();
+        out
+    }
+
+    // A `SEQUENCE` with the concatenation of `values`.
+    fn sequence(values: &[&[u8]]) -> Vec<u8> {
+        tlv(0x30, &values.concat())
+    }
+
+    // ecdsa-with-SHA256.
+    const ALGORITHM: &'static [u8] =
+        &[0x30, 0x0a, 0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03,
+          0x02];
+
+    // UTCTime 1970-01-01 00:00:00 plus `hours`.
+    fn time(hours: u8) -> Vec<u8> {
+        tlv(0x17, format!("700101{:02}0000Z", hours).as_bytes())
+    }
+
+    fn serial_number(n: u32) -> Vec<u8> {
+        let bytes = [(n >> 24) as u8, (n >> 16) as u8, (n >> 8) as u8,
+                     n as u8];
+        let first = bytes.iter().position(|b| *b != 0).unwrap();
+        let mut value = Vec::new();
+        if bytes[first] & 0x80 != 0 {
+            value.push(0);
+        }
+        value.extend_from_slice(&bytes[first..]);
+        tlv(0x02, &value)
+    }
+
+    fn entry(n: u32, extensions: Option<&[u8]>) -> Vec<u8> {
+        let mut value = serial_number(n);
+        value.extend_from_slice(&time(1));
+        if let Some(extensions) = extensions {
+            value.extend_from_slice(&sequence(&[extensions]));
+        }
+        tlv(0x30, &value)
+    }
+
+    // An (unsigned) CRL issued by the name with the contents `issuer`.
+    fn build_crl(issuer: &[u8], entries: &[Vec<u8>],
+                 extensions: Option<&[u8]>) -> Vec<u8> {
+        let mut tbs = [&[0x02, 0x01, 0x01][..], ALGORITHM, &tlv(0x30, issuer),
+                       &time(0), &time(2)].concat();
+        if !entries.is_empty() {
+            tbs.extend_from_slice(&tlv(0x30, &entries.concat()));
+        }
+        if let Some(extensions) = extensions {
+            tbs.extend_from_slice(&tlv(0xa0, &sequence(&[extensions])));
+        }
+        sequence(&[&tlv(0x30, &tbs), ALGORITHM,
+                   &[0x03, 0x03, 0x00, 0x30, 0x00]])
+    }
+
+    fn serial_number_value(n: u32) -> Vec<u8> {
+        serial_number(n)[2..].iter().cloned().skip_while(|b| *b == 0)
+                             .collect()
+    }
+
+    #[test]
+    fn test_find() {
+        const ISSUER: &'static [u8] = &[0x31, 0x00];
+
+        // Sorted, with more than `INDEX_LEN` entries and long enough that
+        // the lengths of the outer `SEQUENCE`s take three bytes, and
+        // unsorted.
+        let count = 5000;
+        let sorted = (1..(count + 1)).map(|n| entry(n * 2, None))
+                                     .collect::<Vec<_>>();
+        let mut unsorted = sorted.clone();
+        unsorted.swap(0, (count - 1) as usize);
+
+        for &(ref entries, is_sorted) in
+                [(sorted, true), (unsorted, false)].iter() {
+            let der = build_crl(ISSUER, entries, None);
+            assert!(der.len() > 0x10000);
+            let crl = CertRevocationList::from(&der).unwrap();
+            assert_eq!(is_sorted, crl.is_sorted());
+            assert_eq!(count as usize, crl.revoked_cert_count());
+            assert_eq!(count as usize, crl.revoked_certs().count());
+            assert_eq!(ISSUER, crl.issuer().as_slice());
+            assert_eq!(Time::from_seconds_since_unix_epoch(0),
+                       crl.this_update());
+            assert_eq!(Some(Time::from_seconds_since_unix_epoch(2 * 3600)),
+                       crl.next_update());
+            if is_sorted {
+                assert_eq!(INDEX_LEN, crl.index_len);
+            }
+
+            // Looking up every serial number in the unsorted CRL would read
+            // it thousands of times, so only some are looked up.
+            let step = if is_sorted { 1 } else { 101 };
+            for n in (1..((2 * count) + 2)).step_by(step) {
+                let found = crl.find(&serial_number_value(n));
+                assert_eq!(n % 2 == 0 && n <= 2 * count,
+                           found.is_some());
+                if let Some(found) = found {
+                    assert_eq!(&serial_number_value(n)[..],
+                               found.serial_number.as_slice());
+                    assert_eq!(Time::from_seconds_since_unix_epoch(3600),
+                               found.revocation_date);
+                }
+            }
+        }
+
+        // Without any revoked certificates.
+        let der = build_crl(ISSUER, &[], None);
+        let crl = CertRevocationList::from(&der).unwrap();
+        assert_eq!(0, crl.revoked_cert_count());
+        assert!(crl.find(&[1]).is_none());
+    }
+
+    #[test]
+    fn test_extensions() {
+        const ISSUER: &'static [u8] = &[0x31, 0x00];
+
+        // reasonCode keyCompromise.
+        const REASON_CODE: &'static [u8] =
+            &[0x30, 0x0a, 0x06, 0x03, 0x55, 0x1d, 0x15, 0x04, 0x03, 0x0a,
+              0x01, 0x01];
+        // certificateIssuer, critical, with an empty `GeneralNames`.
+        const CERTIFICATE_ISSUER: &'static [u8] =
+            &[0x30, 0x0c, 0x06, 0x03, 0x55, 0x1d, 0x1d, 0x01, 0x01, 0xff,
+              0x04, 0x02, 0x30, 0x00];
+        // cRLNumber 1.
+        const CRL_NUMBER: &'static [u8] =
+            &[0x30, 0x08, 0x06, 0x03, 0x55, 0x1d, 0x14, 0x04, 0x01, 0x01];
+        // deltaCRLIndicator 1, critical.
+        const DELTA_CRL_INDICATOR: &'static [u8] =
+            &[0x30, 0x0d, 0x06, 0x03, 0x55, 0x1d, 0x1b, 0x01, 0x01, 0xff,
+              0x04, 0x03, 0x02, 0x01, 0x01];
+
+        let der = build_crl(ISSUER, &[entry(1, Some(REASON_CODE))],
+                            Some(CRL_NUMBER));
+        assert!(CertRevocationList::from(&der).unwrap().find(&[1]).is_some());
+
+        let der =
+            build_crl(ISSUER, &[entry(1, Some(CERTIFICATE_ISSUER))], None);
+        assert_eq!(Some(Error::UnsupportedCriticalExtension),
+                   CertRevocationList::from(&der).err());
+
+        let der =
+            build_crl(ISSUER, &[entry(1, None)], Some(DELTA_CRL_INDICATOR));
+        assert_eq!(Some(Error::UnsupportedCriticalExtension),
+                   CertRevocationList::from(&der).err());
+
+        // Empty `crlEntryExtensions`.
+        let empty_extensions =
+            [&serial_number(1)[..], &time(1), &[0x30, 0x00]].concat();
+        let der = build_crl(ISSUER, &[tlv(0x30, &empty_extensions)], None);
+        assert_eq!(Some(Error::BadDER), CertRevocationList::from(&der).err());
+    }
+
+    #[cfg(feature = "ecdsa")]
+    #[test]
+    fn test_revocation_info() {
+        use {EndEntityCert, Policy, SignatureRequirements};
+        use revocation::CertStatus;
+
+        let ee_der: &[u8] = include_bytes!("../tests/data/ecdsa-ee.der");
+        let intermediate_der: &[u8] =
+            include_bytes!("../tests/data/ecdsa-intermediate.der");
+        let root_der: &[u8] = include_bytes!("../tests/data/ecdsa-root.der");
+        let ee = EndEntityCert::from(ee_der).unwrap();
+        let issuer = ee.inner.issuer.as_slice_less_safe();
+        let mut serial = Vec::new();
+        if ee.serial_number().as_slice()[0] & 0x80 != 0 {
+            serial.push(0);
+        }
+        serial.extend_from_slice(ee.serial_number().as_slice());
+        let serial_entry = tlv(0x30, &[&tlv(0x02, &serial)[..], &time(1)]
+                                          .concat());
+
+        let der = build_crl(issuer, &[entry(1, None), serial_entry], None);
+        let crl = CertRevocationList::from(&der).unwrap();
+        assert_eq!(CertStatus::Revoked, crl.revocation_info(&ee).status);
+        let policy = Policy::default();
+        let algs = &[&::ECDSA_P256_SHA256];
+        assert_eq!(Err(Error::InvalidSignatureForPublicKey),
+                   crl.verify_signed_by(&policy, algs, intermediate_der));
+        assert_eq!(Err(Error::UnknownIssuer),
+                   crl.verify_signed_by(&policy, algs, root_der));
+        // The signature is checked against the given policy.
+        let p384_only = Policy {
+            signature_requirements: Some(SignatureRequirements {
+                min_ecdsa_curve_bits: 384,
+                ..SignatureRequirements::default()
+            }),
+            ..Policy::default()
+        };
+        assert_eq!(Err(Error::PublicKeyTooSmall),
+                   crl.verify_signed_by(&p384_only, algs, intermediate_der));
+
+        let der = build_crl(issuer, &[entry(1, None)], None);
+        let crl = CertRevocationList::from(&der).unwrap();
+        assert_eq!(CertStatus::Good, crl.revocation_info(&ee).status);
+
+        let der = build_crl(&[0x31, 0x00], &[], None);
+        let crl = CertRevocationList::from(&der).unwrap();
+        assert_eq!(CertStatus::Unknown, crl.revocation_info(&ee).status);
+    }
+}
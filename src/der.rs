@@ -12,7 +12,10 @@
 // ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
 // OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
 
+use core::cmp;
+use core::fmt;
 use ring;
+use ring::digest;
 pub use ring::der::{
     CONSTRUCTED,
 
@@ -20,10 +23,184 @@ pub use ring::der::{
 
     nested,
 };
-use Error;
+use {Error, Policy};
 use time;
 use untrusted;
 
+/// The value of a DER-encoded ASN.1 element, e.g. a field of a certificate,
+/// without its tag and length. The value is borrowed from the input it was
+/// parsed from; it is never copied.
+///
+/// webpki returns values of this type, instead of values of the input type of
+/// the parser it uses internally, so that its API doesn't depend on the
+/// version of the parser.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Der<'a>(&'a [u8]);
+
+impl<'a> Der<'a> {
+    /// The encoded value.
+    pub fn as_slice(&self) -> &'a [u8] { self.0 }
+
+    /// The length of the encoded value, in bytes.
+    pub fn len(&self) -> usize { self.0.len() }
+
+    /// Whether the encoded value is empty.
+    pub fn is_empty(&self) -> bool { self.0.is_empty() }
+}
+
+impl<'a> From<&'a [u8]> for Der<'a> {
+    fn from(value: &'a [u8]) -> Der<'a> { Der(value) }
+}
+
+impl<'a> AsRef<[u8]> for Der<'a> {
+    fn as_ref(&self) -> &[u8] { self.0 }
+}
+
+// Used when converting the results of the parser into values of the public
+// API.
+pub fn from_input(input: untrusted::Input) -> Der {
+    Der(input.as_slice_less_safe())
+}
+
+/// The maximum length, in bytes, of the value of a DER element that the
+/// underlying DER parser accepts, so a certificate can't be longer than this
+/// plus its outer tag and length. `Policy::max_der_element_len` can only
+/// lower this limit.
+pub const MAX_DER_ELEMENT_LEN: usize = 0xffff;
+
+// Checks `len`, the length of the value of the outer element of a
+// certificate, against `Policy::max_der_element_len`. The value of every
+// element nested in it is shorter, so this is the only length check that is
+// needed.
+pub fn check_element_len(policy: &Policy, len: usize) -> Result<(), Error> {
+    if len > cmp::min(policy.max_der_element_len, MAX_DER_ELEMENT_LEN) {
+        return Err(Error::DERElementTooLong);
+    }
+    Ok(())
+}
+
+// Checks that an element at depth `depth`, counting the outer element of a
+// certificate as depth 1, is within `Policy::max_der_nesting_depth`.
+pub fn check_depth(policy: &Policy, depth: usize) -> Result<(), Error> {
+    if depth > policy.max_der_nesting_depth {
+        return Err(Error::DERNestingTooDeep);
+    }
+    Ok(())
+}
+
+// Like `check_depth`, for every element of `input`, which is a sequence of
+// DER elements at depth `depth` that the certificate parser doesn't descend
+// into, e.g. the contents of the issuer's `Name`, and everything nested in
+// them. The contents of primitive elements, including `OCTET STRING`s that
+// contain DER-encoded values like extensions, aren't checked; those values
+// are parsed according to their fixed structure.
+pub fn check_nesting(policy: &Policy, depth: usize, input: untrusted::Input)
+                     -> Result<(), Error> {
+    input.read_all(Error::BadDER, |input| {
+        check_nesting_of(policy, depth, input)
+    })
+}
+
+fn check_nesting_of(policy: &Policy, depth: usize,
+                    input: &mut untrusted::Reader) -> Result<(), Error> {
+    while !input.at_end() {
+        try!(check_depth(policy, depth));
+        let tag = try!(input.read_byte().map_err(|_| Error::BadDER));
+        if (tag & 0x1f) == 0x1f {
+            return Err(Error::BadDER); // High tag numbers aren't supported.
+        }
+        let len = try!(read_len(input));
+        let value = try!(input.skip_and_get_input(len)
+                              .map_err(|_| Error::BadDER));
+        if (tag & CONSTRUCTED) != 0 {
+            try!(value.read_all(Error::BadDER, |value| {
+                check_nesting_of(policy, depth + 1, value)
+            }));
+        }
+    }
+    Ok(())
+}
+
+// Reads a definite length of up to four bytes. Whether the encoding of the
+// length is minimal is checked when the element is actually parsed.
+fn read_len(input: &mut untrusted::Reader) -> Result<usize, Error> {
+    let first = try!(input.read_byte().map_err(|_| Error::BadDER));
+    let num_bytes = match first {
+        0x00...0x7f => { return Ok(first as usize); },
+        0x81...0x84 => (first & 0x7f) as usize,
+        _ => { return Err(Error::BadDER); }
+    };
+    let mut len = 0usize;
+    for _ in 0..num_bytes {
+        let b = try!(input.read_byte().map_err(|_| Error::BadDER));
+        len = (len << 8) | (b as usize);
+    }
+    Ok(len)
+}
+
+// Returns the total length, including the tag and length, of the element with
+// tag `tag` whose encoding starts with `prefix`, or `None` if `prefix` ends
+// before the length does. The length must be minimally encoded, and values
+// longer than `MAX_DER_ELEMENT_LEN` are rejected with `Error::InputTooLong`,
+// so at most four bytes of `prefix` are looked at.
+pub fn element_len(prefix: &[u8], tag: Tag) -> Result<Option<usize>, Error> {
+    if prefix.len() < 2 {
+        return match prefix.first() {
+            Some(&actual_tag) if actual_tag != (tag as u8) =>
+                Err(Error::BadDER),
+            _ => Ok(None),
+        };
+    }
+    if prefix[0] != (tag as u8) {
+        return Err(Error::BadDER);
+    }
+    let (header_len, min_len) = match prefix[1] {
+        0x00...0x7f => { return Ok(Some(2 + prefix[1] as usize)); },
+        0x81 => (3, 0x80),
+        0x82 => (4, 0x100),
+        0x83 | 0x84 => { return Err(Error::InputTooLong); },
+        _ => { return Err(Error::BadDER); }
+    };
+    if prefix.len() < header_len {
+        return Ok(None);
+    }
+    let len = prefix[2..header_len].iter()
+                                   .fold(0, |acc, &b| (acc << 8) | b as usize);
+    if len < min_len {
+        return Err(Error::BadDER); // Not minimally encoded.
+    }
+    Ok(Some(header_len + len))
+}
+
+// Like `expect_tag_and_get_value`, but the value may be longer than
+// `MAX_DER_ELEMENT_LEN`, up to 2^32 - 1 bytes. Only the outer elements of a
+// CRL, whose list of revoked certificates can be hundreds of megabytes long,
+// are read this way; their contents are still parsed with the limits.
+pub fn expect_tag_and_get_long_value<'a>(input: &mut untrusted::Reader<'a>,
+                                         tag: Tag)
+                                         -> Result<untrusted::Input<'a>,
+                                                   Error> {
+    let actual_tag = try!(input.read_byte().map_err(|_| Error::BadDER));
+    if actual_tag != (tag as u8) {
+        return Err(Error::BadDER);
+    }
+    let mark1 = input.mark();
+    let len = try!(read_len(input));
+    let mark2 = input.mark();
+    let encoded_len_len =
+        input.get_input_between_marks(mark1, mark2).unwrap().len();
+    let minimal_len_len =
+        if len < 0x80 { 1 }
+        else if len < 0x100 { 2 }
+        else if len < 0x10000 { 3 }
+        else if len < 0x1000000 { 4 }
+        else { 5 };
+    if encoded_len_len != minimal_len_len {
+        return Err(Error::BadDER);
+    }
+    input.skip_and_get_input(len).map_err(|_| Error::BadDER)
+}
+
 #[inline(always)]
 pub fn expect_tag_and_get_value<'a>(input: &mut untrusted::Reader<'a>,
                                     tag: Tag) ->
@@ -37,36 +214,6 @@ pub fn read_tag_and_get_value<'a>(input: &mut untrusted::Reader<'a>)
     ring::der::read_tag_and_get_value(input).map_err(|_| Error::BadDER)
 }
 
-// TODO: investigate taking decoder as a reference to reduce generated code
-// size.
-#[inline(always)]
-pub fn nested_mut<'a, F, R, E: Copy>(input: &mut untrusted::Reader<'a>,
-                                     tag: Tag, error: E, decoder: F)
-                                     -> Result<R, E>
-                                     where F : FnMut(&mut untrusted::Reader<'a>)
-                                                     -> Result<R, E> {
-    let inner = try!(expect_tag_and_get_value(input, tag).map_err(|_| error));
-    inner.read_all_mut(error, decoder).map_err(|_| error)
-}
-
-// TODO: investigate taking decoder as a reference to reduce generated code
-// size.
-pub fn nested_of_mut<'a, F, E: Copy>(input: &mut untrusted::Reader<'a>,
-                                     outer_tag: Tag, inner_tag: Tag, error: E,
-                                     mut decoder: F) -> Result<(), E>
-                                     where F : FnMut(&mut untrusted::Reader<'a>)
-                                                     -> Result<(), E> {
-    nested_mut(input, outer_tag, error, |outer| {
-        loop {
-            try!(nested_mut(outer, inner_tag, error, |inner| decoder(inner)));
-            if outer.at_end() {
-                break;
-            }
-        }
-        Ok(())
-    })
-}
-
 pub fn bit_string_with_no_unused_bits<'a>(input: &mut untrusted::Reader<'a>)
                                           -> Result<untrusted::Input<'a>,
                                                     Error> {
@@ -95,13 +242,13 @@ pub fn optional_boolean(input: &mut untrusted::Reader) -> Result<bool, Error> {
     })
 }
 
-pub fn positive_integer<'a>(input: &'a mut untrusted::Reader)
+pub fn positive_integer<'a>(input: &mut untrusted::Reader<'a>)
                             -> Result<untrusted::Input<'a>, Error> {
     ring::der::positive_integer(input).map_err(|_| Error::BadDER)
 }
 
-pub fn small_nonnegative_integer<'a>(input: &'a mut untrusted::Reader)
-                                     -> Result<u8, Error> {
+pub fn small_nonnegative_integer(input: &mut untrusted::Reader)
+                                 -> Result<u8, Error> {
     ring::der::small_nonnegative_integer(input).map_err(|_| Error::BadDER)
 }
 
@@ -116,6 +263,14 @@ pub fn time_choice<'a>(input: &mut untrusted::Reader<'a>)
     let expected_tag = if is_utc_time { Tag::UTCTime }
                        else { Tag::GeneralizedTime };
 
+    nested(input, expected_tag, Error::BadDER,
+           |value| time_value(value, is_utc_time))
+}
+
+// Parses the value (without the tag and length) of a `UTCTime`, if
+// `is_utc_time`, or of a `GeneralizedTime` otherwise.
+pub fn time_value(value: &mut untrusted::Reader, is_utc_time: bool)
+                  -> Result<time::Time, Error> {
     fn read_digit(inner: &mut untrusted::Reader) -> Result<u64, Error> {
         let b = try!(inner.read_byte().map_err(|_| Error::BadDERTime));
         if b < b'0' || b > b'9' {
@@ -135,36 +290,106 @@ pub fn time_choice<'a>(input: &mut untrusted::Reader<'a>)
         Ok(value)
     }
 
-    nested(input, expected_tag, Error::BadDER, |value| {
-        let (year_hi, year_lo) =
-            if is_utc_time {
-                let lo = try!(read_two_digits(value, 0, 99));
-                let hi = if lo >= 50 { 19 } else { 20 };
-                (hi, lo)
-            } else {
-                let hi = try!(read_two_digits(value, 0, 99));
-                let lo = try!(read_two_digits(value, 0, 99));
-                (hi, lo)
-            };
-
-        let year = (year_hi * 100) + year_lo;
-        let month = try!(read_two_digits(value, 1, 12));
-        let days_in_month = time::days_in_month(year, month);
-        let day_of_month = try!(read_two_digits(value, 1, days_in_month));
-        let hours = try!(read_two_digits(value, 0, 23));
-        let minutes = try!(read_two_digits(value, 0, 59));
-        let seconds = try!(read_two_digits(value, 0, 59));
-
-        let time_zone = try!(value.read_byte().map_err(|_| Error::BadDERTime));
-        if time_zone != b'Z' {
-            return Err(Error::BadDERTime);
-        }
+    let (year_hi, year_lo) =
+        if is_utc_time {
+            let lo = try!(read_two_digits(value, 0, 99));
+            let hi = if lo >= 50 { 19 } else { 20 };
+            (hi, lo)
+        } else {
+            let hi = try!(read_two_digits(value, 0, 99));
+            let lo = try!(read_two_digits(value, 0, 99));
+            (hi, lo)
+        };
+
+    let year = (year_hi * 100) + year_lo;
+    let month = try!(read_two_digits(value, 1, 12));
+    let days_in_month = time::days_in_month(year, month);
+    let day_of_month = try!(read_two_digits(value, 1, days_in_month));
+    let hours = try!(read_two_digits(value, 0, 23));
+    let minutes = try!(read_two_digits(value, 0, 59));
+    let seconds = try!(read_two_digits(value, 0, 59));
+
+    let time_zone = try!(value.read_byte().map_err(|_| Error::BadDERTime));
+    if time_zone != b'Z' {
+        return Err(Error::BadDERTime);
+    }
 
-        time::time_from_ymdhms_utc(year, month, day_of_month, hours, minutes,
-                                   seconds)
+    time::Time::from_ymdhms_utc(year, month, day_of_month, hours, minutes,
+                                seconds)
+}
+
+// Computes the digest of the DER encoding of the `SEQUENCE` with the contents
+// `value`; i.e. of `value` with the tag and length that were stripped off when
+// it was parsed.
+pub fn digest_of_sequence(algorithm: &'static digest::Algorithm,
+                          value: untrusted::Input) -> digest::Digest {
+    let value = value.as_slice_less_safe();
+    let len = value.len();
+    // `value` was parsed by *ring*'s DER parser, which doesn't accept values
+    // longer than 0xffff bytes.
+    debug_assert!(len <= 0xffff);
+    let sequence = Tag::Sequence as u8;
+    let mut ctx = digest::Context::new(algorithm);
+    if len < 0x80 {
+        ctx.update(&[sequence, len as u8]);
+    } else if len < 0x100 {
+        ctx.update(&[sequence, 0x81, len as u8]);
+    } else {
+        ctx.update(&[sequence, 0x82, (len >> 8) as u8, len as u8]);
+    }
+    ctx.update(value);
+    ctx.finish()
+}
+
+// Checks that `oid` is a valid OID value whose arcs are small enough for
+// `write_oid`.
+pub fn check_oid(oid: untrusted::Input) -> Result<(), Error> {
+    oid.read_all(Error::BadDER, |oid| {
+        if oid.at_end() {
+            return Err(Error::BadDER);
+        }
+        while !oid.at_end() {
+            let _ = try!(read_arc(oid));
+        }
+        Ok(())
     })
 }
 
+// Reads one base-128 subidentifier of an OID.
+fn read_arc(oid: &mut untrusted::Reader) -> Result<u128, Error> {
+    if oid.peek(0x80) { // A non-minimal encoding.
+        return Err(Error::BadDER);
+    }
+    let mut value: u128 = 0;
+    loop {
+        let b = try!(oid.read_byte().map_err(|_| Error::BadDER));
+        if value.leading_zeros() < 7 {
+            return Err(Error::BadDER);
+        }
+        value = (value << 7) | u128::from(b & 0x7f);
+        if b & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+}
+
+// Writes an OID value that was checked by `check_oid` in dotted-decimal form.
+pub fn write_oid(f: &mut fmt::Formatter, oid: &[u8]) -> fmt::Result {
+    let mut oid = untrusted::Reader::new(untrusted::Input::from(oid));
+    let first = try!(read_arc(&mut oid).map_err(|_| fmt::Error));
+    let (first, second) = match first {
+        0...39 => (0, first),
+        40...79 => (1, first - 40),
+        _ => (2, first - 80),
+    };
+    try!(write!(f, "{}.{}", first, second));
+    while !oid.at_end() {
+        try!(write!(f, ".{}", try!(read_arc(&mut oid)
+                                       .map_err(|_| fmt::Error))));
+    }
+    Ok(())
+}
+
 macro_rules! oid {
     ( $first:expr, $second:expr, $( $tail:expr ),* ) =>
     (
@@ -200,3 +425,116 @@ macro_rules! oid_1_3_132 {
              $( $tail ),* ]
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{check_element_len, check_nesting, element_len,
+                expect_tag_and_get_long_value, MAX_DER_ELEMENT_LEN, Tag};
+    use {Error, Policy};
+    use untrusted;
+
+    #[test]
+    fn test_expect_tag_and_get_long_value() {
+        fn value_len(input: &[u8]) -> Result<usize, Error> {
+            untrusted::Input::from(input).read_all(Error::BadDER, |input| {
+                expect_tag_and_get_long_value(input, Tag::Sequence)
+                    .map(|value| value.len())
+            })
+        }
+
+        let mut long = [0u8; 5 + 0x10000];
+        long[..5].copy_from_slice(&[0x30, 0x83, 0x01, 0x00, 0x00]);
+        assert_eq!(Ok(0x10000), value_len(&long));
+        assert_eq!(Ok(1), value_len(&[0x30, 0x01, 0x00]));
+        let mut medium = [0u8; 3 + 0x80];
+        medium[..3].copy_from_slice(&[0x30, 0x81, 0x80]);
+        assert_eq!(Ok(0x80), value_len(&medium));
+
+        // Non-minimal lengths.
+        assert_eq!(Err(Error::BadDER), value_len(&[0x30, 0x81, 0x01, 0x00]));
+        assert_eq!(Err(Error::BadDER),
+                   value_len(&[0x30, 0x82, 0x00, 0x01, 0x00]));
+        // Indefinite length, wrong tag, and truncated value.
+        assert_eq!(Err(Error::BadDER), value_len(&[0x30, 0x80, 0x00, 0x00]));
+        assert_eq!(Err(Error::BadDER), value_len(&[0x31, 0x01, 0x00]));
+        assert_eq!(Err(Error::BadDER), value_len(&long[..(5 + 0xffff)]));
+    }
+
+    #[test]
+    fn test_element_len() {
+        let len = |prefix: &[u8]| element_len(prefix, Tag::Sequence);
+
+        assert_eq!(Ok(None), len(&[]));
+        assert_eq!(Ok(None), len(&[0x30]));
+        assert_eq!(Ok(Some(2)), len(&[0x30, 0x00]));
+        assert_eq!(Ok(Some(2 + 0x7f)), len(&[0x30, 0x7f]));
+        assert_eq!(Ok(None), len(&[0x30, 0x81]));
+        assert_eq!(Ok(Some(3 + 0x80)), len(&[0x30, 0x81, 0x80, 0x00]));
+        assert_eq!(Ok(None), len(&[0x30, 0x82, 0x01]));
+        assert_eq!(Ok(Some(4 + 0xffff)), len(&[0x30, 0x82, 0xff, 0xff]));
+
+        assert_eq!(Err(Error::BadDER), len(&[0x31]));
+        assert_eq!(Err(Error::BadDER), len(&[0x30, 0x80]));
+        assert_eq!(Err(Error::BadDER), len(&[0x30, 0x81, 0x7f]));
+        assert_eq!(Err(Error::BadDER), len(&[0x30, 0x82, 0x00, 0xff]));
+        assert_eq!(Err(Error::InputTooLong), len(&[0x30, 0x83]));
+    }
+
+    #[test]
+    fn test_check_element_len() {
+        let policy = Policy {
+            max_der_element_len: 0x100,
+            ..Policy::default()
+        };
+        assert_eq!(Ok(()), check_element_len(&policy, 0x100));
+        assert_eq!(Err(Error::DERElementTooLong),
+                   check_element_len(&policy, 0x101));
+
+        // The parser's own limit can't be raised.
+        let policy = Policy {
+            max_der_element_len: 2 * MAX_DER_ELEMENT_LEN,
+            ..Policy::default()
+        };
+        assert_eq!(Ok(()), check_element_len(&policy, MAX_DER_ELEMENT_LEN));
+        assert_eq!(Err(Error::DERElementTooLong),
+                   check_element_len(&policy, MAX_DER_ELEMENT_LEN + 1));
+    }
+
+    #[test]
+    fn test_check_nesting() {
+        const MAX_DEPTH: usize = 4;
+        let policy = Policy {
+            max_der_nesting_depth: MAX_DEPTH,
+            ..Policy::default()
+        };
+        let check = |input: &[u8]| {
+            check_nesting(&policy, 1, untrusted::Input::from(input))
+        };
+
+        assert_eq!(Ok(()), check(&[0x30, 0x03, 0x02, 0x01, 0x00]));
+        assert_eq!(Ok(()), check(&[0x30, 0x81, 0x00]));
+        assert_eq!(Err(Error::BadDER), check(&[0x30, 0x80, 0x00, 0x00]));
+        assert_eq!(Err(Error::BadDER), check(&[0x30, 0x03, 0x02, 0x01]));
+
+        // `depth` nested `SEQUENCE`s, the innermost one empty, followed by
+        // padding.
+        const LEN: usize = 2 * (MAX_DEPTH + 1);
+        fn nested_sequences(depth: usize) -> [u8; LEN] {
+            let mut result = [0u8; LEN];
+            for i in 0..depth {
+                result[2 * i] = 0x30;
+                result[(2 * i) + 1] = (2 * (depth - i - 1)) as u8;
+            }
+            result
+        }
+        let ok = nested_sequences(MAX_DEPTH);
+        let ok = &ok[..(2 * MAX_DEPTH)];
+        assert_eq!(Ok(()), check(ok));
+        let too_deep = nested_sequences(MAX_DEPTH + 1);
+        assert_eq!(Err(Error::DERNestingTooDeep), check(&too_deep));
+
+        // The same elements are one level deeper inside another element.
+        assert_eq!(Err(Error::DERNestingTooDeep),
+                   check_nesting(&policy, 2, untrusted::Input::from(ok)));
+    }
+}
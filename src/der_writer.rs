@@ -0,0 +1,482 @@
+// Copyright 2016 Brian Smith.
+//
+// Permission to use, copy, modify, and/or distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHORS DISCLAIM ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHORS BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+//! A minimal ASN.1 DER encoder.
+//!
+//! This is what webpki itself uses to encode OCSP requests
+//! (`ocsp::build_request`), trust anchors
+//! (`trust_anchor_util::trust_anchor_to_der`), name constraints
+//! (`name_constraints::encode`), and handshake captures, and to build the
+//! certificates and CRLs in its tests. It is public so that applications
+//! that need to encode similar small structures, e.g. in a build script,
+//! don't need another ASN.1 library.
+//!
+//! Each function appends one DER element to `out`, which is either a
+//! `Vec<u8>`, with the `std` feature, or a `SliceOutput` that writes into a
+//! fixed-size buffer. The contents of a constructed element are written by
+//! the closure passed to `write_nested` or `write_sequence`, using the same
+//! functions:
+//!
+//! ```
+//! use webpki::der_writer;
+//!
+//! let mut buf = [0u8; 16];
+//! let mut out = der_writer::SliceOutput::new(&mut buf);
+//! der_writer::write_sequence(&mut out, |out| {
+//!     try!(der_writer::write_unsigned_integer(out, 2));
+//!     der_writer::write_boolean(out, true)
+//! }).unwrap();
+//! assert_eq!(&[0x30, 0x06, 0x02, 0x01, 0x02, 0x01, 0x01, 0xff][..],
+//!            out.as_slice());
+//! ```
+//!
+//! Only the definite-length encodings that webpki's parser accepts are
+//! written. Values longer than `MAX_DER_ELEMENT_LEN` can only be written
+//! with `write_long_tlv`; the other functions return
+//! `Error::DERElementTooLong` instead. Writing past the end of a
+//! `SliceOutput` fails with `Error::InputTooLong`. After an error, `out` may
+//! contain a partially-written element.
+
+use der::{self, MAX_DER_ELEMENT_LEN};
+use time::Time;
+use Error;
+
+#[cfg(feature = "std")]
+use std::vec::Vec;
+
+/// Where the DER encoding is written.
+pub trait Output {
+    /// The number of bytes that have been written.
+    fn written_len(&self) -> usize;
+
+    /// Appends `bytes`.
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), Error>;
+
+    /// Inserts `bytes` at `offset`, which is at most `written_len()`, moving
+    /// the bytes after it.
+    fn insert_bytes(&mut self, offset: usize, bytes: &[u8])
+                    -> Result<(), Error>;
+}
+
+#[cfg(feature = "std")]
+impl Output for Vec<u8> {
+    fn written_len(&self) -> usize { self.len() }
+
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        self.extend_from_slice(bytes);
+        Ok(())
+    }
+
+    fn insert_bytes(&mut self, offset: usize, bytes: &[u8])
+                    -> Result<(), Error> {
+        let tail = self.split_off(offset);
+        self.extend_from_slice(bytes);
+        self.extend_from_slice(&tail);
+        Ok(())
+    }
+}
+
+/// An `Output` that writes to a fixed-size buffer, which doesn't require the
+/// `std` feature.
+pub struct SliceOutput<'b> {
+    out: &'b mut [u8],
+    len: usize,
+}
+
+impl<'b> SliceOutput<'b> {
+    /// Writes to the start of `out`.
+    pub fn new(out: &'b mut [u8]) -> Self {
+        SliceOutput { out: out, len: 0 }
+    }
+
+    /// The bytes that have been written.
+    pub fn as_slice(&self) -> &[u8] { &self.out[..self.len] }
+
+    /// The bytes that have been written, for the lifetime of the buffer.
+    pub fn into_slice(self) -> &'b [u8] { &self.out[..self.len] }
+}
+
+impl<'b> Output for SliceOutput<'b> {
+    fn written_len(&self) -> usize { self.len }
+
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        let len = self.len;
+        self.insert_bytes(len, bytes)
+    }
+
+    fn insert_bytes(&mut self, offset: usize, bytes: &[u8])
+                    -> Result<(), Error> {
+        let new_len = self.len + bytes.len();
+        if new_len > self.out.len() {
+            return Err(Error::InputTooLong);
+        }
+        for i in (offset..self.len).rev() {
+            self.out[i + bytes.len()] = self.out[i];
+        }
+        self.out[offset..(offset + bytes.len())].copy_from_slice(bytes);
+        self.len = new_len;
+        Ok(())
+    }
+}
+
+// The encoding of the length `len` of a value, in `buf`.
+fn encode_len(len: usize, buf: &mut [u8; 3]) -> Result<&[u8], Error> {
+    if len < 0x80 {
+        buf[0] = len as u8;
+        Ok(&buf[..1])
+    } else if len < 0x100 {
+        buf[0] = 0x81;
+        buf[1] = len as u8;
+        Ok(&buf[..2])
+    } else if len <= MAX_DER_ELEMENT_LEN {
+        buf[0] = 0x82;
+        buf[1] = (len >> 8) as u8;
+        buf[2] = len as u8;
+        Ok(&buf[..])
+    } else {
+        Err(Error::DERElementTooLong)
+    }
+}
+
+// Appends the tag and length of an element whose value has length `len`.
+fn write_tag_and_len(out: &mut Output, tag: u8, len: usize)
+                     -> Result<(), Error> {
+    let mut buf = [0u8; 3];
+    let encoded_len = try!(encode_len(len, &mut buf));
+    try!(out.write_bytes(&[tag]));
+    out.write_bytes(encoded_len)
+}
+
+/// Appends the DER encoding of the value `value` with tag `tag` to `out`.
+pub fn write_tlv(out: &mut Output, tag: u8, value: &[u8])
+                 -> Result<(), Error> {
+    try!(write_tag_and_len(out, tag, value.len()));
+    out.write_bytes(value)
+}
+
+/// Like `write_tlv`, but the value may be longer than `MAX_DER_ELEMENT_LEN`,
+/// up to 2^32 - 1 bytes, for the outer elements of CRLs and handshake
+/// captures, which are parsed without that limit.
+pub fn write_long_tlv(out: &mut Output, tag: u8, value: &[u8])
+                      -> Result<(), Error> {
+    let len = value.len() as u64;
+    if len > 0xffff_ffff {
+        return Err(Error::DERElementTooLong);
+    }
+    try!(out.write_bytes(&[tag]));
+    if len < 0x80 {
+        try!(out.write_bytes(&[len as u8]));
+    } else {
+        let len_bytes =
+            [(len >> 24) as u8, (len >> 16) as u8, (len >> 8) as u8, len as u8];
+        let leading_zeros = len_bytes.iter().take_while(|&&b| b == 0).count();
+        let len_len = (len_bytes.len() - leading_zeros) as u8;
+        try!(out.write_bytes(&[0x80 | len_len]));
+        try!(out.write_bytes(&len_bytes[leading_zeros..]));
+    }
+    out.write_bytes(value)
+}
+
+/// Appends an element with the tag `tag` whose value is whatever `f` writes
+/// to the output it is given, e.g. a context-specific constructed element.
+pub fn write_nested<F>(out: &mut Output, tag: u8, f: F) -> Result<(), Error>
+                       where F: FnOnce(&mut Output) -> Result<(), Error> {
+    try!(out.write_bytes(&[tag]));
+    let value_start = out.written_len();
+    try!(f(out));
+    let mut buf = [0u8; 3];
+    let encoded_len =
+        try!(encode_len(out.written_len() - value_start, &mut buf));
+    out.insert_bytes(value_start, encoded_len)
+}
+
+/// Appends a `SEQUENCE` whose value is whatever `f` writes to the output it
+/// is given.
+pub fn write_sequence<F>(out: &mut Output, f: F) -> Result<(), Error>
+                         where F: FnOnce(&mut Output) -> Result<(), Error> {
+    write_nested(out, der::Tag::Sequence as u8, f)
+}
+
+/// Appends a `BOOLEAN`.
+pub fn write_boolean(out: &mut Output, value: bool) -> Result<(), Error> {
+    write_tlv(out, der::Tag::Boolean as u8, &[if value { 0xff } else { 0 }])
+}
+
+/// Appends a `NULL`.
+pub fn write_null(out: &mut Output) -> Result<(), Error> {
+    write_tlv(out, der::Tag::Null as u8, &[])
+}
+
+/// Appends an `INTEGER` whose value is the unsigned big-endian integer
+/// `value`, e.g. a serial number. Leading zeros are removed, and a zero byte
+/// is added if needed to keep the value positive, so `value` may be in
+/// either form; an empty `value` is zero.
+pub fn write_positive_integer(out: &mut Output, value: &[u8])
+                              -> Result<(), Error> {
+    let first_nonzero = value.iter()
+                             .position(|b| *b != 0)
+                             .unwrap_or(value.len());
+    let value = &value[first_nonzero..];
+    if value.is_empty() || (value[0] & 0x80) != 0 {
+        try!(write_tag_and_len(out, der::Tag::Integer as u8,
+                               value.len() + 1));
+        try!(out.write_bytes(&[0]));
+    } else {
+        try!(write_tag_and_len(out, der::Tag::Integer as u8, value.len()));
+    }
+    out.write_bytes(value)
+}
+
+/// Appends an `INTEGER` with the value `value`, e.g. a version number.
+pub fn write_unsigned_integer(out: &mut Output, value: u64)
+                              -> Result<(), Error> {
+    let bytes = [(value >> 56) as u8, (value >> 48) as u8, (value >> 40) as u8,
+                 (value >> 32) as u8, (value >> 24) as u8, (value >> 16) as u8,
+                 (value >> 8) as u8, value as u8];
+    write_positive_integer(out, &bytes)
+}
+
+/// Appends an `OBJECT IDENTIFIER` whose value, without the tag and length, is
+/// `value`; this is the same form as the OIDs that webpki returns, e.g.
+/// `Extension::id`.
+pub fn write_oid(out: &mut Output, value: &[u8]) -> Result<(), Error> {
+    write_tlv(out, der::Tag::OID as u8, value)
+}
+
+/// Appends a `BIT STRING` with the bits of `value` and no unused bits, e.g. a
+/// public key or a signature.
+pub fn write_bit_string(out: &mut Output, value: &[u8]) -> Result<(), Error> {
+    try!(write_tag_and_len(out, der::Tag::BitString as u8, value.len() + 1));
+    try!(out.write_bytes(&[0]));
+    out.write_bytes(value)
+}
+
+/// Appends an `OCTET STRING`.
+pub fn write_octet_string(out: &mut Output, value: &[u8])
+                          -> Result<(), Error> {
+    write_tlv(out, der::Tag::OctetString as u8, value)
+}
+
+/// Appends `time` as a `UTCTime` if its year is before 2050, or as a
+/// `GeneralizedTime` otherwise, as RFC 5280 requires for the validity
+/// periods of certificates; see
+/// https://tools.ietf.org/html/rfc5280#section-4.1.2.5.
+pub fn write_time(out: &mut Output, time: Time) -> Result<(), Error> {
+    let year = time.to_date_time().year;
+    if year >= 2050 {
+        return write_generalized_time(out, time);
+    }
+    let mut value = [0u8; 13];
+    write_digits(&mut value[..2], year % 100);
+    write_time_after_year(&mut value[2..], time);
+    write_tlv(out, der::Tag::UTCTime as u8, &value)
+}
+
+/// Appends `time` as a `GeneralizedTime` of the form `YYYYMMDDHHMMSSZ`.
+pub fn write_generalized_time(out: &mut Output, time: Time)
+                              -> Result<(), Error> {
+    let mut value = [0u8; 15];
+    write_digits(&mut value[..4], time.to_date_time().year);
+    write_time_after_year(&mut value[4..], time);
+    write_tlv(out, der::Tag::GeneralizedTime as u8, &value)
+}
+
+// Writes the `MMDDHHMMSSZ` that follows the year in both time types to
+// `value`, which is 11 bytes long.
+fn write_time_after_year(value: &mut [u8], time: Time) {
+    let date_time = time.to_date_time();
+    write_digits(&mut value[0..2], date_time.month);
+    write_digits(&mut value[2..4], date_time.day_of_month);
+    write_digits(&mut value[4..6], date_time.hours);
+    write_digits(&mut value[6..8], date_time.minutes);
+    write_digits(&mut value[8..10], date_time.seconds);
+    value[10] = b'Z';
+}
+
+// Writes the decimal digits of `n` to `value`, with leading zeros, keeping
+// only as many of the least significant digits as fit.
+fn write_digits(value: &mut [u8], mut n: u64) {
+    for digit in value.iter_mut().rev() {
+        *digit = b'0' + (n % 10) as u8;
+        n /= 10;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Output, SliceOutput, write_bit_string, write_boolean,
+                write_generalized_time, write_long_tlv, write_nested,
+                write_null, write_octet_string, write_oid,
+                write_positive_integer, write_sequence, write_time, write_tlv,
+                write_unsigned_integer};
+    use der;
+    use std::vec::Vec;
+    use time::Time;
+    use untrusted;
+    use Error;
+
+    // Writes to a `SliceOutput` with a buffer of `buf_len` bytes and returns
+    // what was written.
+    fn write_to_slice<F>(buf_len: usize, f: F) -> Result<Vec<u8>, Error>
+                         where F: FnOnce(&mut Output) -> Result<(), Error> {
+        let mut buf = std::vec![0; buf_len];
+        let mut out = SliceOutput::new(&mut buf);
+        try!(f(&mut out));
+        Ok(out.as_slice().to_vec())
+    }
+
+    #[test]
+    fn test_write_tlv() {
+        let out = write_to_slice(0x100, |out| {
+            write_tlv(out, 0x04, &[0x5a; 0x80])
+        }).unwrap();
+        assert_eq!(&[0x04, 0x81, 0x80, 0x5a][..], &out[..4]);
+        assert_eq!(3 + 0x80, out.len());
+
+        let out = write_to_slice(0x200, |out| {
+            write_tlv(out, 0x04, &[0x5a; 0x100])
+        }).unwrap();
+        assert_eq!(&[0x04, 0x82, 0x01, 0x00, 0x5a][..], &out[..5]);
+
+        let out = write_to_slice(0x10010, |out| {
+            write_long_tlv(out, 0x30, &[0x5a; 0x10000])
+        }).unwrap();
+        assert_eq!(&[0x30, 0x83, 0x01, 0x00, 0x00, 0x5a][..], &out[..6]);
+    }
+
+    #[test]
+    fn test_write_tlv_too_long() {
+        assert_eq!(Err(Error::DERElementTooLong),
+                   write_to_slice(0x10010, |out| {
+                       write_tlv(out, 0x04, &[0; 0x10000])
+                   }));
+        assert_eq!(Err(Error::DERElementTooLong),
+                   write_to_slice(0x10010, |out| {
+                       write_sequence(out, |out| {
+                           write_octet_string(out, &[0; 0xfffc])
+                       })
+                   }));
+    }
+
+    #[test]
+    fn test_slice_output() {
+        // The length of a nested element is inserted before its value once
+        // the value has been written.
+        let out = write_to_slice(0x100, |out| {
+            write_nested(out, 0xa0, |out| {
+                try!(write_null(out));
+                write_octet_string(out, &[0x5a; 0x80])
+            })
+        }).unwrap();
+        assert_eq!(&[0xa0, 0x81, 0x85, 0x05, 0x00, 0x04, 0x81, 0x80, 0x5a][..],
+                   &out[..9]);
+        assert_eq!(3 + 0x85, out.len());
+
+        assert_eq!(&[0x05, 0x00][..],
+                   &write_to_slice(2, |out| write_null(out)).unwrap()[..]);
+        assert_eq!(Err(Error::InputTooLong),
+                   write_to_slice(1, |out| write_null(out)));
+        assert_eq!(Err(Error::InputTooLong),
+                   write_to_slice(3, |out| {
+                       write_sequence(out, |out| write_null(out))
+                   }));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_vec_output() {
+        let mut out = Vec::new();
+        write_nested(&mut out, 0xa0, |out| {
+            write_octet_string(out, &[0x5a; 0x80])
+        }).unwrap();
+        assert_eq!(&[0xa0, 0x81, 0x83, 0x04, 0x81, 0x80, 0x5a][..], &out[..7]);
+        assert_eq!(3 + 0x83, out.len());
+    }
+
+    #[test]
+    fn test_write_positive_integer() {
+        let values: [(&[u8], &[u8]); 4] = [
+            (&[], &[0x02, 0x01, 0x00]),
+            (&[0x00, 0x00], &[0x02, 0x01, 0x00]),
+            (&[0x00, 0x7f, 0xff], &[0x02, 0x02, 0x7f, 0xff]),
+            (&[0x80, 0x01], &[0x02, 0x03, 0x00, 0x80, 0x01]),
+        ];
+        for &(value, expected) in &values {
+            let out = write_to_slice(8, |out| {
+                write_positive_integer(out, value)
+            }).unwrap();
+            assert_eq!(expected, &out[..]);
+        }
+    }
+
+    // Each value is parsed back with the parser that webpki uses for it.
+    #[test]
+    fn test_round_trip() {
+        let out = write_to_slice(0x40, |out| {
+            write_sequence(out, |out| {
+                try!(write_boolean(out, true));
+                try!(write_null(out));
+                try!(write_positive_integer(out, &[0x00, 0x00, 0x80, 0x01]));
+                try!(write_unsigned_integer(out, 0));
+                try!(write_unsigned_integer(out, 0x1234));
+                try!(write_oid(out, &[0x55, 0x1d, 0x13]));
+                try!(write_bit_string(out, &[0xaa, 0xbb]));
+                write_octet_string(out, &[])
+            })
+        }).unwrap();
+
+        let result = untrusted::Input::from(&out).read_all(Error::BadDER, |r| {
+            der::nested(r, der::Tag::Sequence, Error::BadDER, |r| {
+                assert_eq!(Ok(true), der::optional_boolean(r));
+                assert_eq!(Ok(()), der::null(r));
+                assert!(try!(der::positive_integer(r)) == &[0x80, 0x01][..]);
+                assert_eq!(Ok(0), der::small_nonnegative_integer(r));
+                assert!(try!(der::positive_integer(r)) == &[0x12, 0x34][..]);
+                assert!(try!(der::expect_tag_and_get_value(r, der::Tag::OID))
+                        == &[0x55, 0x1d, 0x13][..]);
+                assert!(try!(der::bit_string_with_no_unused_bits(r)) ==
+                        &[0xaa, 0xbb][..]);
+                assert!(try!(der::expect_tag_and_get_value(
+                             r, der::Tag::OctetString)) == &[][..]);
+                Ok(())
+            })
+        });
+        assert_eq!(Ok(()), result);
+    }
+
+    #[test]
+    fn test_write_time() {
+        let times = [
+            (Time::from_ymdhms_utc(1970, 1, 1, 0, 0, 0).unwrap(),
+             &b"\x17\x0d700101000000Z"[..]),
+            (Time::from_ymdhms_utc(2049, 12, 31, 23, 59, 59).unwrap(),
+             &b"\x17\x0d491231235959Z"[..]),
+            (Time::from_ymdhms_utc(2050, 1, 1, 0, 0, 0).unwrap(),
+             &b"\x18\x0f20500101000000Z"[..]),
+        ];
+        for &(time, expected) in &times {
+            let out = write_to_slice(17, |out| write_time(out, time)).unwrap();
+            assert_eq!(expected, &out[..]);
+            assert_eq!(Ok(time), untrusted::Input::from(&out).read_all(
+                           Error::BadDER, der::time_choice));
+        }
+
+        let out = write_to_slice(17, |out| {
+            write_generalized_time(out, times[0].0)
+        }).unwrap();
+        assert_eq!(&b"\x18\x0f19700101000000Z"[..], &out[..]);
+        assert_eq!(Ok(times[0].0), untrusted::Input::from(&out).read_all(
+                       Error::BadDER, der::time_choice));
+    }
+}
@@ -0,0 +1,582 @@
+// Copyright 2016 Brian Smith.
+//
+// Permission to use, copy, modify, and/or distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHORS DISCLAIM ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHORS BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+//! Parsing, comparing, and formatting X.501 distinguished names.
+//!
+//! ```ASN.1
+//! Name ::= SEQUENCE OF RelativeDistinguishedName
+//!
+//! RelativeDistinguishedName ::= SET SIZE (1..MAX) OF AttributeTypeAndValue
+//!
+//! AttributeTypeAndValue ::= SEQUENCE {
+//!      type     AttributeType,
+//!      value    AttributeValue }
+//! ```
+//!
+//! Like everywhere else in webpki, a name is represented by the value of the
+//! `Name` `SEQUENCE`, without its tag and length; this is the form used by
+//! `TrustAnchor::subject` and `EndEntityCert::issuer`. The same parser is
+//! used for the certificate accessors, for directoryName name constraints,
+//! and for formatting names as RFC 4514 strings with `Display`, which doesn't
+//! require `std`.
+
+use core::fmt::{self, Write};
+use core::iter::Peekable;
+use der::{self, CONSTRUCTED};
+use {Der, Error};
+use untrusted;
+
+/// A distinguished name, e.g. the subject or issuer of a certificate.
+#[derive(Clone, Copy, Debug)]
+pub struct DistinguishedName<'a> {
+    rdns: untrusted::Input<'a>,
+}
+
+impl<'a> DistinguishedName<'a> {
+    /// Parses the value of a DER-encoded `Name`, without the outer `SEQUENCE`
+    /// tag and length, e.g. the value of `EndEntityCert::issuer` or
+    /// `TrustAnchor::subject`.
+    ///
+    /// Every attribute is parsed and every string value is decoded, so
+    /// iterating over the RDNs and attributes of the result can't fail.
+    pub fn from(value: &'a [u8]) -> Result<DistinguishedName<'a>, Error> {
+        let rdns = untrusted::Input::from(value);
+        try!(rdns.read_all(Error::BadDER, |rdns| {
+            while !rdns.at_end() {
+                let rdn = try!(relative_distinguished_name(rdns));
+                try!(rdn.read_all(Error::BadDER, |rdn| {
+                    while !rdn.at_end() {
+                        let (type_id, tag, value, _) =
+                            try!(attribute_type_and_value(rdn));
+                        try!(der::check_oid(type_id));
+                        let _ = try!(directory_string(tag, value));
+                    }
+                    Ok(())
+                }));
+            }
+            Ok(())
+        }));
+        Ok(DistinguishedName { rdns: rdns })
+    }
+
+    /// The RDNs of the name in the order they are encoded, which is usually
+    /// from the least specific (e.g. the country) to the most specific (e.g.
+    /// the common name).
+    pub fn rdns(&self) -> Rdns<'a> {
+        Rdns { reader: untrusted::Reader::new(self.rdns) }
+    }
+
+    /// The attributes of all the RDNs of the name, in the order of `rdns`.
+    pub fn attributes(&self) -> NameAttributes<'a> {
+        NameAttributes {
+            rdns: untrusted::Reader::new(self.rdns),
+            rdn: None,
+        }
+    }
+
+    /// The value of the name's last, i.e. usually most specific, commonName
+    /// (2.5.4.3) attribute, if it has one.
+    pub fn common_name(&self) -> Option<DirectoryString<'a>> {
+        self.attributes()
+            .filter(|attribute| attribute.type_id.as_slice() == ID_AT_CN)
+            .last()
+            .and_then(|attribute| attribute.value)
+    }
+
+    /// Whether the name is within the subtree of the directoryName name
+    /// constraint `subtree`, i.e. whether the RDNs of `subtree` are the first
+    /// RDNs of the name (RFC 5280 Section 4.2.1.10).
+    ///
+    /// RDNs are equal if they have the same attributes, in any order. String
+    /// values are compared by decoding them, so that e.g. a TeletexString
+    /// matches a UTF8String, with ASCII letters compared case-insensitively;
+    /// the rest of the RFC 4518 string preparation that RFC 5280 Section 7.1
+    /// asks for isn't done. Other values are compared exactly.
+    pub fn is_within(&self, subtree: &DistinguishedName) -> bool {
+        let mut rdns = self.rdns();
+        subtree.rdns().all(|subtree_rdn| match rdns.next() {
+            Some(rdn) => rdn.matches(&subtree_rdn),
+            None => false,
+        })
+    }
+}
+
+/// Formats the name as an RFC 4514 string, e.g. `CN=example,O=Example,C=US`,
+/// with the RDNs in reverse order of their encoding. Control characters in
+/// values are escaped as well, so that the result is safe to log.
+impl<'a> fmt::Display for DistinguishedName<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let count = self.rdns().count();
+        for i in (0..count).rev() {
+            if i + 1 != count {
+                try!(f.write_char(','));
+            }
+            try!(fmt::Display::fmt(&self.rdns().nth(i).unwrap(), f));
+        }
+        Ok(())
+    }
+}
+
+/// An iterator over the RDNs of a `DistinguishedName`.
+pub struct Rdns<'a> {
+    reader: untrusted::Reader<'a>,
+}
+
+impl<'a> Iterator for Rdns<'a> {
+    type Item = RelativeDistinguishedName<'a>;
+
+    fn next(&mut self) -> Option<RelativeDistinguishedName<'a>> {
+        if self.reader.at_end() {
+            return None;
+        }
+        // The name was already parsed successfully by
+        // `DistinguishedName::from`.
+        relative_distinguished_name(&mut self.reader).ok().map(|attributes| {
+            RelativeDistinguishedName { attributes: attributes }
+        })
+    }
+}
+
+/// A relative distinguished name (RDN): one or more attributes.
+#[derive(Clone, Copy, Debug)]
+pub struct RelativeDistinguishedName<'a> {
+    attributes: untrusted::Input<'a>,
+}
+
+impl<'a> RelativeDistinguishedName<'a> {
+    /// The attributes of the RDN, in the order they are encoded.
+    pub fn attributes(&self) -> NameAttributes<'a> {
+        NameAttributes {
+            rdns: untrusted::Reader::new(untrusted::Input::from(&[])),
+            rdn: Some(untrusted::Reader::new(self.attributes)),
+        }
+    }
+
+    // The attributes of an RDN have distinct types, so every attribute of
+    // `other` matching one of `self` is enough when there are as many of
+    // them.
+    fn matches(&self, other: &RelativeDistinguishedName) -> bool {
+        self.attributes().count() == other.attributes().count() &&
+        other.attributes().all(|b| {
+            self.attributes().any(|a| a.type_id == b.type_id && a.matches(&b))
+        })
+    }
+}
+
+/// Formats the RDN as in RFC 4514, with its attributes separated by `+`.
+impl<'a> fmt::Display for RelativeDistinguishedName<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, attribute) in self.attributes().enumerate() {
+            if i != 0 {
+                try!(f.write_char('+'));
+            }
+            try!(fmt::Display::fmt(&attribute, f));
+        }
+        Ok(())
+    }
+}
+
+/// An attribute of a `DistinguishedName`.
+#[derive(Clone, Copy, Debug)]
+pub struct NameAttribute<'a> {
+    /// The encoded value of the attribute's type OID, without the tag and
+    /// length, e.g. `[0x55, 0x04, 0x03]` for commonName (2.5.4.3).
+    pub type_id: Der<'a>,
+
+    /// The value of the attribute, or `None` if it isn't one of the string
+    /// types that `DirectoryString` supports.
+    pub value: Option<DirectoryString<'a>>,
+
+    /// The DER encoding, including the tag and length, of the value of the
+    /// attribute.
+    pub encoded_value: Der<'a>,
+}
+
+impl<'a> NameAttribute<'a> {
+    fn matches(&self, other: &NameAttribute) -> bool {
+        match (self.value, other.value) {
+            (Some(a), Some(b)) => {
+                let mut a = a.chars();
+                let mut b = b.chars();
+                loop {
+                    match (a.next(), b.next()) {
+                        (None, None) => { return true; },
+                        (Some(a), Some(b))
+                            if ascii_lowercase(a) == ascii_lowercase(b) => (),
+                        _ => { return false; }
+                    }
+                }
+            },
+            _ => self.encoded_value == other.encoded_value,
+        }
+    }
+}
+
+/// Formats the attribute as in RFC 4514, e.g. `CN=example`. The type is
+/// written as its RFC 4514 short name if it has one and as a dotted-decimal
+/// OID otherwise; values of other than the supported string types are
+/// written as `#` followed by the hex encoding of `encoded_value`.
+impl<'a> fmt::Display for NameAttribute<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let type_id = self.type_id.as_slice();
+        match SHORT_NAMES.iter().find(|&&(oid, _)| oid == type_id) {
+            Some(&(_, short_name)) => try!(f.write_str(short_name)),
+            None => try!(der::write_oid(f, type_id)),
+        }
+        try!(f.write_char('='));
+        match self.value {
+            Some(value) => write_escaped(f, value.chars().peekable()),
+            None => {
+                try!(f.write_char('#'));
+                for b in self.encoded_value.as_slice() {
+                    try!(write!(f, "{:02x}", b));
+                }
+                Ok(())
+            },
+        }
+    }
+}
+
+/// An iterator over the attributes of a `DistinguishedName` or of a
+/// `RelativeDistinguishedName`.
+pub struct NameAttributes<'a> {
+    rdns: untrusted::Reader<'a>,
+    rdn: Option<untrusted::Reader<'a>>,
+}
+
+impl<'a> Iterator for NameAttributes<'a> {
+    type Item = NameAttribute<'a>;
+
+    // The name was already parsed successfully by `DistinguishedName::from`.
+    fn next(&mut self) -> Option<NameAttribute<'a>> {
+        loop {
+            if let Some(ref mut rdn) = self.rdn {
+                if !rdn.at_end() {
+                    let (type_id, tag, value, encoded_value) =
+                        match attribute_type_and_value(rdn) {
+                            Ok(attribute) => attribute,
+                            Err(_) => { return None; }
+                        };
+                    return Some(NameAttribute {
+                        type_id: der::from_input(type_id),
+                        value: directory_string(tag, value).ok()
+                                                           .and_then(|s| s),
+                        encoded_value: der::from_input(encoded_value),
+                    });
+                }
+            }
+            if self.rdns.at_end() {
+                return None;
+            }
+            self.rdn = match relative_distinguished_name(&mut self.rdns) {
+                Ok(rdn) => Some(untrusted::Reader::new(rdn)),
+                Err(_) => { return None; }
+            };
+        }
+    }
+}
+
+/// A string attribute value of a distinguished name.
+///
+/// Besides the `DirectoryString` types UTF8String and PrintableString,
+/// webpki supports the legacy TeletexString, BMPString, and UniversalString
+/// types, which some government and older enterprise CAs still use, as well
+/// as IA5String, which is used for domainComponent and emailAddress
+/// attributes. Like most other implementations, webpki decodes
+/// TeletexString as ISO 8859-1 rather than as T.61.
+#[derive(Clone, Copy, Debug)]
+pub struct DirectoryString<'a> {
+    tag: u8,
+    value: untrusted::Input<'a>,
+}
+
+impl<'a> DirectoryString<'a> {
+    /// The tag of the string's ASN.1 type, e.g. 0x0c for UTF8String.
+    pub fn tag(&self) -> u8 { self.tag }
+
+    /// The encoded value of the string, without the tag and length.
+    pub fn value(&self) -> Der<'a> { der::from_input(self.value) }
+
+    /// The decoded characters of the string.
+    pub fn chars(&self) -> DirectoryStringChars<'a> {
+        DirectoryStringChars {
+            tag: self.tag,
+            reader: untrusted::Reader::new(self.value),
+        }
+    }
+}
+
+/// An iterator over the characters of a `DirectoryString`.
+pub struct DirectoryStringChars<'a> {
+    tag: u8,
+    reader: untrusted::Reader<'a>,
+}
+
+impl<'a> Iterator for DirectoryStringChars<'a> {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        if self.reader.at_end() {
+            return None;
+        }
+        // The string was already decoded successfully by `directory_string`.
+        read_char(self.tag, &mut self.reader).ok()
+    }
+}
+
+// id-at-commonName, 2.5.4.3.
+const ID_AT_CN: &'static [u8] = &[0x55, 0x04, 0x03];
+
+// The attribute types that have short names in RFC 4514 Section 3.
+const SHORT_NAMES: &'static [(&'static [u8], &'static str)] = &[
+    (&[0x55, 0x04, 0x03], "CN"),
+    (&[0x55, 0x04, 0x07], "L"),
+    (&[0x55, 0x04, 0x08], "ST"),
+    (&[0x55, 0x04, 0x0a], "O"),
+    (&[0x55, 0x04, 0x0b], "OU"),
+    (&[0x55, 0x04, 0x06], "C"),
+    (&[0x55, 0x04, 0x09], "STREET"),
+    (&[0x09, 0x92, 0x26, 0x89, 0x93, 0xf2, 0x2c, 0x64, 0x01, 0x19], "DC"),
+    (&[0x09, 0x92, 0x26, 0x89, 0x93, 0xf2, 0x2c, 0x64, 0x01, 0x01], "UID"),
+];
+
+const SET_TAG: u8 = CONSTRUCTED | 0x11;
+
+// The tags of the string types that `DirectoryString` supports.
+const UTF8_STRING_TAG: u8 = 0x0c;
+const PRINTABLE_STRING_TAG: u8 = 0x13;
+const TELETEX_STRING_TAG: u8 = 0x14;
+const IA5_STRING_TAG: u8 = 0x16;
+const UNIVERSAL_STRING_TAG: u8 = 0x1c;
+const BMP_STRING_TAG: u8 = 0x1e;
+
+fn relative_distinguished_name<'a>(input: &mut untrusted::Reader<'a>)
+                                   -> Result<untrusted::Input<'a>, Error> {
+    let (tag, rdn) = try!(der::read_tag_and_get_value(input));
+    if tag != SET_TAG || rdn.len() == 0 {
+        return Err(Error::BadDER);
+    }
+    Ok(rdn)
+}
+
+// The result is the encoded value of `type`, without the tag and length, the
+// tag and value of `value`, and the DER encoding of `value`, including the
+// tag and length.
+fn attribute_type_and_value<'a>(input: &mut untrusted::Reader<'a>)
+        -> Result<(untrusted::Input<'a>, u8, untrusted::Input<'a>,
+                   untrusted::Input<'a>), Error> {
+    der::nested(input, der::Tag::Sequence, Error::BadDER, |attribute| {
+        let type_id =
+            try!(der::expect_tag_and_get_value(attribute, der::Tag::OID));
+        let mark = attribute.mark();
+        let (tag, value) = try!(der::read_tag_and_get_value(attribute));
+        let encoded_value = attribute.get_input_between_marks(mark,
+                                                              attribute.mark())
+                                     .unwrap();
+        Ok((type_id, tag, value, encoded_value))
+    })
+}
+
+// `Ok(None)` if `tag` isn't one of the supported string types, or an error if
+// `value` isn't a valid encoding of a string of that type.
+fn directory_string<'a>(tag: u8, value: untrusted::Input<'a>)
+                        -> Result<Option<DirectoryString<'a>>, Error> {
+    match tag {
+        UTF8_STRING_TAG |
+        PRINTABLE_STRING_TAG |
+        TELETEX_STRING_TAG |
+        IA5_STRING_TAG |
+        UNIVERSAL_STRING_TAG |
+        BMP_STRING_TAG => (),
+        _ => { return Ok(None); }
+    }
+    try!(value.read_all(Error::BadDER, |value| {
+        while !value.at_end() {
+            let _ = try!(read_char(tag, value));
+        }
+        Ok(())
+    }));
+    Ok(Some(DirectoryString { tag: tag, value: value }))
+}
+
+fn read_char(tag: u8, input: &mut untrusted::Reader) -> Result<char, Error> {
+    fn read_u32(input: &mut untrusted::Reader, len: usize)
+                -> Result<u32, Error> {
+        let mut value = 0;
+        for _ in 0..len {
+            let b = try!(input.read_byte().map_err(|_| Error::BadDER));
+            value = (value << 8) | u32::from(b);
+        }
+        Ok(value)
+    }
+
+    let value = match tag {
+        PRINTABLE_STRING_TAG | IA5_STRING_TAG =>
+            match try!(read_u32(input, 1)) {
+                b @ 0...0x7f => b,
+                _ => { return Err(Error::BadDER); }
+            },
+
+        // ISO 8859-1 maps every byte to the code point of the same value.
+        TELETEX_STRING_TAG => try!(read_u32(input, 1)),
+
+        // UTF-16 (big-endian), which BMPStrings containing only characters in
+        // the Basic Multilingual Plane (UCS-2) are a subset of.
+        BMP_STRING_TAG => match try!(read_u32(input, 2)) {
+            high @ 0xd800...0xdbff => match try!(read_u32(input, 2)) {
+                low @ 0xdc00...0xdfff =>
+                    0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00),
+                _ => { return Err(Error::BadDER); }
+            },
+            value => value,
+        },
+
+        // UCS-4 (big-endian).
+        UNIVERSAL_STRING_TAG => try!(read_u32(input, 4)),
+
+        UTF8_STRING_TAG => {
+            let first = try!(read_u32(input, 1));
+            let (continuation_len, min, bits) = match first {
+                0...0x7f => (0, 0, first),
+                0xc0...0xdf => (1, 0x80, first & 0x1f),
+                0xe0...0xef => (2, 0x800, first & 0x0f),
+                0xf0...0xf7 => (3, 0x10000, first & 0x07),
+                _ => { return Err(Error::BadDER); }
+            };
+            let mut value = bits;
+            for _ in 0..continuation_len {
+                let b = try!(read_u32(input, 1));
+                if b & 0xc0 != 0x80 {
+                    return Err(Error::BadDER);
+                }
+                value = (value << 6) | (b & 0x3f);
+            }
+            if value < min { // An overlong encoding.
+                return Err(Error::BadDER);
+            }
+            value
+        },
+
+        _ => { return Err(Error::BadDER); }
+    };
+
+    // Surrogates and values above U+10FFFF aren't characters.
+    ::core::char::from_u32(value).ok_or(Error::BadDER)
+}
+
+fn ascii_lowercase(c: char) -> char {
+    match c {
+        'A'...'Z' => ((c as u8) + (b'a' - b'A')) as char,
+        _ => c,
+    }
+}
+
+// https://tools.ietf.org/html/rfc4514#section-2.4
+fn write_escaped<I>(f: &mut fmt::Formatter, mut chars: Peekable<I>)
+                    -> fmt::Result where I: Iterator<Item = char> {
+    let mut first = true;
+    while let Some(c) = chars.next() {
+        let last = chars.peek().is_none();
+        match c {
+            '"' | '+' | ',' | ';' | '<' | '>' | '\\' => {
+                try!(f.write_char('\\'));
+                try!(f.write_char(c));
+            },
+            ' ' if first || last => try!(f.write_str("\\ ")),
+            '#' if first => try!(f.write_str("\\#")),
+            '\u{0}'...'\u{1f}' | '\u{7f}' =>
+                try!(write!(f, "\\{:02x}", c as u8)),
+            _ => try!(f.write_char(c)),
+        }
+        first = false;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DistinguishedName, directory_string};
+    use untrusted;
+
+    // C=US as a PrintableString, and the same in lowercase as a UTF8String.
+    const RDN_C_PRINTABLE: &'static [u8] = &[
+        0x31, 0x0b, 0x30, 0x09, 0x06, 0x03, 0x55, 0x04, 0x06,
+        0x13, 0x02, b'U', b'S',
+    ];
+    const RDN_C_UTF8: &'static [u8] = &[
+        0x31, 0x0b, 0x30, 0x09, 0x06, 0x03, 0x55, 0x04, 0x06,
+        0x0c, 0x02, b'u', b's',
+    ];
+
+    // CN=foo as a BMPString, and CN=FOO as a TeletexString.
+    const RDN_CN_BMP: &'static [u8] = &[
+        0x31, 0x0f, 0x30, 0x0d, 0x06, 0x03, 0x55, 0x04, 0x03,
+        0x1e, 0x06, 0x00, b'f', 0x00, b'o', 0x00, b'o',
+    ];
+    const RDN_CN_TELETEX: &'static [u8] = &[
+        0x31, 0x0c, 0x30, 0x0a, 0x06, 0x03, 0x55, 0x04, 0x03,
+        0x14, 0x03, b'F', b'O', b'O',
+    ];
+
+    #[test]
+    fn is_within_test() {
+        let name = [RDN_C_PRINTABLE, RDN_CN_BMP].concat();
+        let name = DistinguishedName::from(&name).unwrap();
+        assert_eq!(2, name.rdns().count());
+        assert!(name.common_name().unwrap().chars().eq("foo".chars()));
+
+        // (subtree, whether `name` is within it)
+        let subtrees: &[(&[u8], bool)] = &[
+            (&[], true),
+            (RDN_C_UTF8, true),
+            (&[RDN_C_UTF8, RDN_CN_TELETEX].concat(), true),
+            (RDN_CN_TELETEX, false),
+            (&[RDN_C_UTF8, RDN_CN_TELETEX, RDN_C_UTF8].concat(), false),
+        ];
+        for &(subtree, expected) in subtrees {
+            let subtree = DistinguishedName::from(subtree).unwrap();
+            assert_eq!(expected, name.is_within(&subtree));
+        }
+    }
+
+    #[test]
+    fn directory_string_test() {
+        // A BMPString "\u{10000}" encoded as a surrogate pair, and a lone
+        // surrogate.
+        let pair = [0xd8, 0x00, 0xdc, 0x00];
+        assert!(directory_string(0x1e, untrusted::Input::from(&pair)).unwrap()
+                    .unwrap().chars().eq("\u{10000}".chars()));
+        assert!(directory_string(0x1e, untrusted::Input::from(&pair[..2]))
+                    .is_err());
+        // An overlong UTF-8 encoding of '/'.
+        let overlong = [0xc0, 0xaf];
+        assert!(directory_string(0x0c, untrusted::Input::from(&overlong))
+                    .is_err());
+    }
+
+    #[test]
+    fn display_test() {
+        // O=" a,b " (UTF8String) + 1.2.3.4=NULL, followed by CN=#x (IA5String).
+        let name = [
+            0x31, 0x17,
+              0x30, 0x0c, 0x06, 0x03, 0x55, 0x04, 0x0a,
+                0x0c, 0x05, b' ', b'a', b',', b'b', b' ',
+              0x30, 0x07, 0x06, 0x03, 0x2a, 0x03, 0x04, 0x05, 0x00,
+            0x31, 0x0b, 0x30, 0x09, 0x06, 0x03, 0x55, 0x04, 0x03,
+              0x16, 0x02, b'#', b'x',
+        ];
+        let name = DistinguishedName::from(&name).unwrap();
+        assert_eq!("CN=\\#x,O=\\ a\\,b\\ +1.2.3.4=#0500", format!("{}", name));
+    }
+}
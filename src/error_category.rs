@@ -0,0 +1,134 @@
+// Copyright 2016 Brian Smith.
+//
+// Permission to use, copy, modify, and/or distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHORS DISCLAIM ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHORS BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+use Error;
+
+/// A coarse classification of an `Error`, for applications that decide how
+/// to handle a verification failure, e.g. whether to ask the user, to retry
+/// with a different configuration, or to fail, without matching every
+/// `Error` variant.
+///
+/// New `Error` variants are assigned to one of the existing categories, so
+/// the categories are stable even when the errors aren't.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ErrorCategory {
+    /// No path to a trusted root could be built, e.g. because the issuer is
+    /// unknown, no roots were given, or a signature didn't verify. The
+    /// certificate may be valid for other trust anchors or with other
+    /// intermediate certificates.
+    Untrusted,
+
+    /// A certificate isn't valid at the time it is verified for: it is
+    /// expired or not valid yet. This often means that the local clock is
+    /// wrong.
+    Expired,
+
+    /// The certificate is valid, but not for the name it is verified for.
+    NameMismatch,
+
+    /// A certificate, or the name it is verified for, isn't well formed, so
+    /// it can't be verified at all.
+    Malformed,
+
+    /// A certificate is well formed but isn't allowed to be used as it is,
+    /// e.g. because of its extended key usage, a name or path length
+    /// constraint, a critical extension that isn't supported, or a
+    /// signature algorithm or key type that the `Policy` or the supported
+    /// signature algorithms don't allow.
+    PolicyViolation,
+
+    /// A certificate is revoked, or its revocation status couldn't be
+    /// determined but the `revocation::RevocationPolicy` requires it; only
+    /// the latter, `Error::RevocationStatusUnknown`, may succeed later.
+    Revoked,
+}
+
+impl ErrorCategory {
+    /// The category of `error`.
+    pub fn from_error(error: Error) -> ErrorCategory {
+        // There is intentionally no `_` arm, so that every new `Error` variant
+        // must be added here.
+        match error {
+            Error::InvalidSignatureForPublicKey |
+            Error::NoTrustAnchors |
+            Error::TooManyCandidateIssuers |
+            Error::TooManySignatureVerifications |
+            Error::UnknownIssuer => ErrorCategory::Untrusted,
+
+            Error::CertExpired |
+            Error::CertNotValidYet => ErrorCategory::Expired,
+
+            Error::CertNotValidForName => ErrorCategory::NameMismatch,
+
+            Error::BadDER |
+            Error::BadDERTime |
+            Error::DERElementTooLong |
+            Error::DERNestingTooDeep |
+            Error::DuplicateExtension |
+            Error::EmptySubjectAltName |
+            Error::EmptySubjectWithoutCriticalSubjectAltName |
+            Error::ExtensionValueInvalid |
+            Error::InputTooLong |
+            Error::InvalidCertValidity |
+            Error::InvalidReferenceName |
+            Error::MalformedDnsIdentifier |
+            Error::SignatureAlgorithmMismatch |
+            Error::UnsupportedCertVersion => ErrorCategory::Malformed,
+
+            Error::CAUsedAsEndEntity |
+            Error::EndEntityKeyTypeNotAllowed |
+            Error::EndEntityUsedAsCA |
+            Error::InvalidAcmeIdentifier |
+            Error::NameConstraintViolation |
+            Error::PathLenConstraintViolated |
+            Error::PolicyValidationFailed |
+            Error::PublicKeyTooSmall |
+            Error::RequiredEKUNotFound |
+            Error::UnsupportedCriticalExtension |
+            Error::UnsupportedSignatureAlgorithmForPublicKey |
+            Error::UnsupportedSignatureAlgorithm |
+            Error::ValidityPeriodTooLong =>
+                ErrorCategory::PolicyViolation,
+
+            Error::CertRevoked |
+            Error::RevocationStatusUnknown => ErrorCategory::Revoked,
+        }
+    }
+}
+
+impl From<Error> for ErrorCategory {
+    fn from(error: Error) -> ErrorCategory { ErrorCategory::from_error(error) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ErrorCategory;
+    use Error;
+
+    #[test]
+    fn test_from_error() {
+        assert_eq!(ErrorCategory::Untrusted,
+                   ErrorCategory::from(Error::UnknownIssuer));
+        assert_eq!(ErrorCategory::Expired,
+                   ErrorCategory::from(Error::CertNotValidYet));
+        assert_eq!(ErrorCategory::NameMismatch,
+                   ErrorCategory::from(Error::CertNotValidForName));
+        assert_eq!(ErrorCategory::Malformed,
+                   ErrorCategory::from(Error::BadDER));
+        assert_eq!(ErrorCategory::PolicyViolation,
+                   ErrorCategory::from(Error::RequiredEKUNotFound));
+        assert_eq!(ErrorCategory::Revoked,
+                   ErrorCategory::from(Error::CertRevoked));
+    }
+}
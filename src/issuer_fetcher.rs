@@ -0,0 +1,232 @@
+// Copyright 2016 Brian Smith.
+//
+// Permission to use, copy, modify, and/or distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHORS DISCLAIM ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHORS BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+use cert::{self, Cert};
+use core::cell::RefCell;
+use revocation::RevocationFilter;
+use std::vec::Vec;
+use verify_cert::{self, ChainInputs, Trace, VerifiedPath};
+use {der, Der, Error, Extensions};
+
+/// Supplies the certificates of the issuers of a certificate when path
+/// building can't find any among the intermediate certificates, e.g. by
+/// following the caIssuers URI of the certificate's authorityInfoAccess
+/// extension ("AIA chasing"). See `VerificationOptions::issuer_fetcher`.
+///
+/// webpki doesn't do any I/O itself, so the fetcher either blocks while it
+/// fetches the certificates or returns certificates that it fetched or
+/// cached ahead of time.
+pub trait IssuerFetcher {
+    /// The ASN.1 DER-encoded X.509 certificates that may have issued a
+    /// certificate whose `issuer` field has the value `issuer`, in the same
+    /// form as `TrustAnchor::subject`, and which has the extensions
+    /// `extensions`. The certificates don't need to be valid or to have the
+    /// right subject; path building checks them like the other intermediate
+    /// certificates.
+    ///
+    /// It is called at most once for each issuer during a verification.
+    fn fetch_issuers(&self, issuer: Der, extensions: Extensions)
+                     -> Vec<Vec<u8>>;
+}
+
+// Like `verify_cert::build_chain`, but whenever no trust anchor or
+// intermediate certificate has a subject that matches the issuer of a
+// certificate in a candidate path, `fetcher` is asked for its issuers, and if
+// it returns any new certificates then path building is started again with
+// them as additional intermediate certificates. The search ends when a path
+// is found or when no new certificates are fetched; the fetched certificates
+// count towards `Policy::max_intermediate_certs` and
+// `Policy::max_intermediate_certs_len` like the others.
+pub fn build_chain<'p, 'a>(inputs: &ChainInputs<'p, 'a>, cert: &Cert<'a>,
+                           revocation_filter: Option<&RevocationFilter>,
+                           trace: &Trace, fetcher: &IssuerFetcher)
+                           -> Result<VerifiedPath, Error> {
+    let intermediate_certs = inputs.intermediate_certs;
+    let mut fetched: Vec<Vec<u8>> = Vec::new();
+    let trace = FetchingTrace {
+        inner: trace,
+        fetcher: fetcher,
+        issuers_fetched: RefCell::new(Vec::new()),
+        new_certs: RefCell::new(Vec::new()),
+    };
+    loop {
+        let result = {
+            let mut all_certs = intermediate_certs.to_vec();
+            all_certs.extend(fetched.iter().map(|cert_der| &cert_der[..]));
+            let inputs = ChainInputs {
+                intermediate_certs: &all_certs,
+                ..*inputs
+            };
+            verify_cert::build_chain(&inputs, cert, revocation_filter,
+                                     &trace)
+        };
+        match result {
+            Ok(..) |
+            Err(Error::TooManyCandidateIssuers) |
+            Err(Error::TooManySignatureVerifications) => { return result; },
+            Err(..) => {}
+        }
+
+        let mut new_cert_found = false;
+        for cert_der in trace.new_certs.borrow_mut().drain(..) {
+            if intermediate_certs.iter().any(|c| *c == &cert_der[..]) ||
+               fetched.contains(&cert_der) {
+                continue;
+            }
+            fetched.push(cert_der);
+            new_cert_found = true;
+        }
+        if !new_cert_found {
+            return result;
+        }
+    }
+}
+
+// Fetches issuers for `issuer_subject_not_found`, and passes all of the steps
+// on to `inner`.
+struct FetchingTrace<'f> {
+    inner: &'f Trace,
+    fetcher: &'f IssuerFetcher,
+
+    // The issuers that `fetcher` was already asked about.
+    issuers_fetched: RefCell<Vec<Vec<u8>>>,
+
+    // What `fetcher` returned since the last time path building started.
+    new_certs: RefCell<Vec<Vec<u8>>>,
+}
+
+impl<'f> Trace for FetchingTrace<'f> {
+    fn cert_checked(&self, depth: usize, cert: &Cert,
+                    result: Result<(), Error>) {
+        self.inner.cert_checked(depth, cert, result);
+    }
+
+    fn trust_anchor_tried(&self, depth: usize, trust_anchor_index: usize,
+                          result: Result<(), Error>) {
+        self.inner.trust_anchor_tried(depth, trust_anchor_index, result);
+    }
+
+    fn intermediate_tried(&self, depth: usize, intermediate_index: usize,
+                          result: Result<VerifiedPath, Error>) {
+        self.inner.intermediate_tried(depth, intermediate_index, result);
+    }
+
+    fn intermediate_parsed(&self, intermediate_index: usize) {
+        self.inner.intermediate_parsed(intermediate_index);
+    }
+
+    fn signature_verified(&self) { self.inner.signature_verified(); }
+
+    fn trust_anchor_in_intermediates(&self, depth: usize,
+                                     intermediate_index: usize,
+                                     trust_anchor_index: usize) {
+        self.inner.trust_anchor_in_intermediates(depth, intermediate_index,
+                                                 trust_anchor_index);
+    }
+
+    fn issuer_subject_not_found(&self, depth: usize, cert: &Cert) {
+        self.inner.issuer_subject_not_found(depth, cert);
+        let issuer = cert.issuer.as_slice_less_safe();
+        if self.issuers_fetched.borrow().iter().any(|i| &i[..] == issuer) {
+            return;
+        }
+        self.issuers_fetched.borrow_mut().push(issuer.to_vec());
+        let certs = self.fetcher.fetch_issuers(der::from_input(cert.issuer),
+                                               cert::extensions(cert));
+        self.new_certs.borrow_mut().extend(certs);
+    }
+}
+
+#[cfg(all(test, feature = "trust_anchor_util", feature = "ecdsa"))]
+mod tests {
+    use core::cell::Cell;
+    use std::vec::Vec;
+    use super::IssuerFetcher;
+    use {Der, EndEntityCert, Error, Extensions, Policy, StatsCounter, Time,
+         trust_anchor_util, VerificationOptions, VerificationTimes,
+         ECDSA_P256_SHA256};
+
+    struct TestFetcher {
+        issuers: &'static [&'static [u8]],
+        calls: Cell<usize>,
+    }
+
+    impl IssuerFetcher for TestFetcher {
+        fn fetch_issuers(&self, _: Der, _: Extensions) -> Vec<Vec<u8>> {
+            self.calls.set(self.calls.get() + 1);
+            self.issuers.iter().map(|cert_der| cert_der.to_vec()).collect()
+        }
+    }
+
+    #[test]
+    fn test_issuer_fetcher() {
+        const INTERMEDIATE: &'static [u8] =
+            include_bytes!("../tests/data/ecdsa-intermediate.der");
+        const COLLISION_INTERMEDIATE: &'static [u8] =
+            include_bytes!("../tests/data/collision-intermediate.der");
+        let root: &[u8] = include_bytes!("../tests/data/ecdsa-root.der");
+        let ee = EndEntityCert::from(
+            include_bytes!("../tests/data/ecdsa-ee.der")).unwrap();
+        let anchors =
+            &[trust_anchor_util::cert_der_as_trust_anchor(root).unwrap()];
+        let times = VerificationTimes::at(
+            Time::from_ymdhms_utc(2027, 1, 1, 0, 0, 0).unwrap());
+        let policy = Policy::default();
+        let sig_algs = &[&ECDSA_P256_SHA256];
+
+        // The intermediate certificate is fetched; its index follows those of
+        // the intermediate certificates that were passed in.
+        let fetcher = TestFetcher { issuers: &[INTERMEDIATE],
+                                    calls: Cell::new(0) };
+        let rsa_intermediate: &[u8] =
+            include_bytes!("../tests/data/rsa-intermediate.der");
+        let counter = StatsCounter::new();
+        let path = ee.verify_is_valid_tls_server_cert_with_options(
+            &policy, sig_algs, anchors, &[rsa_intermediate], times,
+            &VerificationOptions::new().issuer_fetcher(&fetcher)
+                                       .observer(&counter)).unwrap();
+        assert_eq!(&[1], path.intermediate_indices());
+        assert_eq!(1, fetcher.calls.get());
+
+        // The observer sees the steps of both searches, so the intermediate
+        // certificate that was passed in was parsed twice.
+        assert_eq!(2 + 1, counter.stats().certs_parsed);
+
+        // The fetcher isn't needed when the intermediate certificate is sent.
+        let fetcher = TestFetcher { issuers: &[INTERMEDIATE],
+                                    calls: Cell::new(0) };
+        assert!(ee.verify_is_valid_tls_server_cert_with_options(
+                    &policy, sig_algs, anchors, &[INTERMEDIATE], times,
+                    &VerificationOptions::new().issuer_fetcher(&fetcher))
+                  .is_ok());
+        assert_eq!(0, fetcher.calls.get());
+
+        // The fetched certificate has the right subject but was issued by
+        // another root, whose certificate can't be fetched.
+        let fetcher = TestFetcher { issuers: &[COLLISION_INTERMEDIATE],
+                                    calls: Cell::new(0) };
+        assert_eq!(Err(Error::UnknownIssuer),
+                   ee.verify_is_valid_tls_server_cert_with_options(
+                       &policy, sig_algs, anchors, &[], times,
+                       &VerificationOptions::new().issuer_fetcher(&fetcher)));
+        assert_eq!(2, fetcher.calls.get());
+
+        let fetcher = TestFetcher { issuers: &[], calls: Cell::new(0) };
+        assert_eq!(Err(Error::UnknownIssuer),
+                   ee.verify_is_valid_tls_server_cert_with_options(
+                       &policy, sig_algs, anchors, &[], times,
+                       &VerificationOptions::new().issuer_fetcher(&fetcher)));
+        assert_eq!(1, fetcher.calls.get());
+    }
+}
@@ -0,0 +1,256 @@
+// Copyright 2016 Brian Smith.
+//
+// Permission to use, copy, modify, and/or distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHORS DISCLAIM ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHORS BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+//! Linting of certificates, for CA operators.
+//!
+//! Verification stops at the first problem it finds with a certificate. A CA
+//! operator checking a certificate before issuing it, or auditing the ones it
+//! has issued, instead wants to know about every problem at once, including
+//! ones that don't stop the certificate from being verified. `lint_cert_der`
+//! parses a certificate with the same parser that verification uses and
+//! returns a list of such findings.
+//!
+//! A scanner auditing certificates that were already issued also wants to
+//! know about the certificates that are too malformed to be verified at all.
+//! `lint_cert_der_lenient` reports the extensions whose values can't be
+//! parsed as findings, instead of failing like verification does.
+
+use cert::{self, EndEntityOrCA};
+use core::time::Duration;
+use std::vec::Vec;
+use untrusted;
+use {der, Der, Error, Policy};
+
+/// A problem that `lint_cert_der` found with a certificate.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Finding<'a> {
+    /// The certificate has a critical extension that webpki doesn't
+    /// understand, so verification rejects it with
+    /// `Error::UnsupportedCriticalExtension`. `id` is the encoded value of
+    /// the extension's OID, without the tag and length.
+    UnsupportedCriticalExtension {
+        /// The extension's OID.
+        id: Der<'a>,
+    },
+
+    /// The certificate is a CA certificate without a subjectKeyIdentifier
+    /// extension, which RFC 5280 Section 4.2.1.2 requires.
+    MissingSubjectKeyIdentifier,
+
+    /// The certificate is signed with MD2, MD5, or SHA-1, whose collision
+    /// resistance is broken.
+    WeakSignatureAlgorithm,
+
+    /// The certificate is an end-entity certificate whose validity period
+    /// is longer than the 398 days that the CA/Browser Forum Baseline
+    /// Requirements (Section 6.3.2) allow.
+    LongValidity {
+        /// The length of the validity period, in whole days.
+        days: u64,
+    },
+
+    /// The certificate has an extension that webpki understands, but whose
+    /// value can't be parsed, so verification rejects it with
+    /// `Error::BadDER`. This is only reported by `lint_cert_der_lenient`,
+    /// which lints the certificate as if it didn't have the extension.
+    MalformedExtension {
+        /// The extension's OID.
+        id: Der<'a>,
+    },
+}
+
+/// Parses the ASN.1 DER-encoded X.509 certificate `cert_der` and returns
+/// every `Finding` about it, in the order the fields are checked.
+///
+/// Unlike verification, an unsupported critical extension doesn't stop the
+/// parsing; each one is reported instead. A certificate that can't be
+/// parsed at all, e.g. because it isn't valid DER, still results in the
+/// error that verification would report.
+pub fn lint_cert_der<'a>(cert_der: &'a [u8])
+                         -> Result<Vec<Finding<'a>>, Error> {
+    lint(cert_der, false)
+}
+
+/// Like `lint_cert_der`, but an extension that webpki understands and whose
+/// value can't be parsed is reported as `Finding::MalformedExtension`
+/// instead of resulting in `Error::BadDER`. This is for scanners auditing
+/// certificates, which care most about exactly the certificates that
+/// verification rejects; verification itself stays strict.
+///
+/// A certificate whose structure outside of the extension values can't be
+/// parsed still results in an error.
+pub fn lint_cert_der_lenient<'a>(cert_der: &'a [u8])
+                                 -> Result<Vec<Finding<'a>>, Error> {
+    lint(cert_der, true)
+}
+
+fn lint<'a>(cert_der: &'a [u8], lenient: bool)
+            -> Result<Vec<Finding<'a>>, Error> {
+    let mut findings = Vec::new();
+    let cert = {
+        let mut extension_error = |id: untrusted::Input<'a>, err| {
+            findings.push(match err {
+                Error::UnsupportedCriticalExtension =>
+                    Finding::UnsupportedCriticalExtension {
+                        id: der::from_input(id),
+                    },
+                Error::BadDER if lenient =>
+                    Finding::MalformedExtension { id: der::from_input(id) },
+                _ => { return Err(err); }
+            });
+            Ok(())
+        };
+        try!(cert::parse_cert_with(untrusted::Input::from(cert_der),
+                                   EndEntityOrCA::EndEntity,
+                                   &Policy::default(), &mut extension_error))
+    };
+
+    let is_ca = match cert.basic_constraints {
+        Some(value) => match value.read_all(Error::BadDER, |value| {
+            let is_ca = try!(der::optional_boolean(value));
+            let _ = value.skip_to_end();
+            Ok(is_ca)
+        }) {
+            Ok(is_ca) => is_ca,
+            Err(..) if lenient => {
+                findings.push(Finding::MalformedExtension {
+                    id: From::from(&ID_CE_BASIC_CONSTRAINTS[..]),
+                });
+                false
+            },
+            Err(err) => { return Err(err); }
+        },
+        None => false,
+    };
+
+    if is_ca && cert.subject_key_id.is_none() {
+        findings.push(Finding::MissingSubjectKeyIdentifier);
+    }
+
+    let algorithm_id = try!(cert.signed_data.algorithm.read_all(
+            Error::BadDER, |algorithm| {
+        let algorithm_id =
+            try!(der::expect_tag_and_get_value(algorithm, der::Tag::OID));
+        let _ = algorithm.skip_to_end();
+        Ok(algorithm_id)
+    }));
+    if WEAK_SIGNATURE_ALGORITHM_OIDS.iter().any(|oid| algorithm_id == *oid) {
+        findings.push(Finding::WeakSignatureAlgorithm);
+    }
+
+    const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+    const MAX_END_ENTITY_VALIDITY_DAYS: u64 = 398;
+
+    let (not_before, not_after) =
+        try!(cert.validity.read_all(Error::BadDER, |value| {
+            let not_before = try!(der::time_choice(value));
+            let not_after = try!(der::time_choice(value));
+            Ok((not_before, not_after))
+        }));
+    let validity = match not_after.checked_duration_since(not_before) {
+        Some(validity) => validity,
+        None => { return Err(Error::InvalidCertValidity); }
+    };
+    if !is_ca &&
+       validity > Duration::from_secs(MAX_END_ENTITY_VALIDITY_DAYS *
+                                      SECONDS_PER_DAY) {
+        findings.push(Finding::LongValidity {
+            days: validity.as_secs() / SECONDS_PER_DAY,
+        });
+    }
+
+    Ok(findings)
+}
+
+static ID_CE_BASIC_CONSTRAINTS: [u8; 3] = oid![2, 5, 29, 19];
+
+// md2WithRSAEncryption, md5WithRSAEncryption, and sha1WithRSAEncryption
+// (RFC 3279 Section 2.2.1); the OIW sha1WithRSASignature, which webpki also
+// accepts; and ecdsa-with-SHA1 (RFC 3279 Section 2.2.3).
+static WEAK_SIGNATURE_ALGORITHM_OIDS: &'static [&'static [u8]] = &[
+    &oid_1_2_840_113549![1, 1, 2],
+    &oid_1_2_840_113549![1, 1, 4],
+    &oid_1_2_840_113549![1, 1, 5],
+    &oid!(1, 3, 14, 3, 2, 29),
+    &oid_1_2_840_10045![4, 1],
+];
+
+#[cfg(test)]
+mod tests {
+    use super::{Finding, lint_cert_der, lint_cert_der_lenient};
+    use {EndEntityCert, Error};
+
+    #[test]
+    fn test_lint_cert_der() {
+        let root = include_bytes!("../tests/data/ecdsa-root.der");
+        assert_eq!(Ok(0), lint_cert_der(root).map(|findings| findings.len()));
+
+        // The end-entity certificate is valid for 10 years.
+        let ee = include_bytes!("../tests/data/ecdsa-ee.der");
+        let findings = lint_cert_der(ee).unwrap();
+        assert_eq!(1, findings.len());
+        match findings[0] {
+            Finding::LongValidity { days } =>
+                assert!(days >= 3649 && days <= 3650),
+            finding => panic!("{:?}", finding),
+        }
+
+        let lint_ca = include_bytes!("../tests/data/lint-ca.der");
+        let findings = lint_cert_der(lint_ca).unwrap();
+        assert_eq!(4, findings.len());
+        static ID_1_2_3_4: [u8; 3] = oid!(1, 2, 3, 4);
+        static ID_1_2_3_5: [u8; 3] = oid!(1, 2, 3, 5);
+        assert_eq!(Finding::UnsupportedCriticalExtension {
+                       id: From::from(&ID_1_2_3_4[..]),
+                   }, findings[0]);
+        assert_eq!(Finding::UnsupportedCriticalExtension {
+                       id: From::from(&ID_1_2_3_5[..]),
+                   }, findings[1]);
+        assert_eq!(Finding::MissingSubjectKeyIdentifier, findings[2]);
+        assert_eq!(Finding::WeakSignatureAlgorithm, findings[3]);
+
+        assert_eq!(Err(Error::BadDER), lint_cert_der(&ee[..ee.len() - 1]));
+    }
+
+    #[test]
+    fn test_lint_cert_der_lenient() {
+        // The end-entity certificate with the SEQUENCE in the value of its
+        // extKeyUsage extension changed to a SET.
+        let mut ee = include_bytes!("../tests/data/ecdsa-ee.der").to_vec();
+        static EKU_VALUE: [u8; 6] = [0x55, 0x1d, 0x25, 0x04, 0x0c, 0x30];
+        let i = ee.windows(EKU_VALUE.len())
+                  .position(|window| window == EKU_VALUE)
+                  .unwrap() + EKU_VALUE.len() - 1;
+        ee[i] = 0x31;
+
+        // Verification and `lint_cert_der` are strict.
+        assert!(EndEntityCert::from(&ee).err() == Some(Error::BadDER));
+        assert_eq!(Err(Error::BadDER), lint_cert_der(&ee));
+
+        let findings = lint_cert_der_lenient(&ee).unwrap();
+        assert_eq!(2, findings.len());
+        static ID_CE_EXT_KEY_USAGE: [u8; 3] = oid![2, 5, 29, 37];
+        assert_eq!(Finding::MalformedExtension {
+                       id: From::from(&ID_CE_EXT_KEY_USAGE[..]),
+                   }, findings[0]);
+        match findings[1] {
+            Finding::LongValidity { .. } => (),
+            finding => panic!("{:?}", finding),
+        }
+
+        // Problems outside of the extension values are still errors.
+        assert_eq!(Err(Error::BadDER),
+                   lint_cert_der_lenient(&ee[..ee.len() - 1]));
+    }
+}
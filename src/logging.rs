@@ -0,0 +1,138 @@
+// Copyright 2016 Brian Smith.
+//
+// Permission to use, copy, modify, and/or distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHORS DISCLAIM ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHORS BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+// Debug events for the `log` crate, with the `log` feature. Path building
+// emits an event for each step that a `verify_cert::Trace` observes and for
+// its verdict, and DNS name matching for each name it compares and for its
+// verdict. The events are at the `debug` level, except for the ones for each
+// intermediate certificate parsed and each signature verified, which are at
+// the `trace` level. Names from certificates are formatted so that they are
+// safe to log; see `Escaped`.
+
+use cert::Cert;
+use core::fmt;
+use dn::DistinguishedName;
+use untrusted;
+use verify_cert::{Trace, VerifiedPath};
+use Error;
+
+// Logs the steps of path building, and passes them on to `inner`.
+pub struct LogTrace<'t> {
+    pub inner: &'t Trace,
+}
+
+impl<'t> Trace for LogTrace<'t> {
+    fn cert_checked(&self, depth: usize, cert: &Cert,
+                    result: Result<(), Error>) {
+        debug!("depth {}: checked {}: {:?}", depth, Name(cert.subject), result);
+        self.inner.cert_checked(depth, cert, result);
+    }
+
+    fn trust_anchor_tried(&self, depth: usize, trust_anchor_index: usize,
+                          result: Result<(), Error>) {
+        debug!("depth {}: tried trust anchor {} as the issuer: {:?}", depth,
+               trust_anchor_index, result);
+        self.inner.trust_anchor_tried(depth, trust_anchor_index, result);
+    }
+
+    fn intermediate_tried(&self, depth: usize, intermediate_index: usize,
+                          result: Result<VerifiedPath, Error>) {
+        debug!("depth {}: tried intermediate certificate {} as the issuer: \
+                {:?}", depth, intermediate_index, result.map(|_| ()));
+        self.inner.intermediate_tried(depth, intermediate_index, result);
+    }
+
+    fn intermediate_parsed(&self, intermediate_index: usize) {
+        trace!("parsing intermediate certificate {}", intermediate_index);
+        self.inner.intermediate_parsed(intermediate_index);
+    }
+
+    fn signature_verified(&self) {
+        trace!("verifying a signature");
+        self.inner.signature_verified();
+    }
+
+    fn trust_anchor_in_intermediates(&self, depth: usize,
+                                     intermediate_index: usize,
+                                     trust_anchor_index: usize) {
+        debug!("depth {}: skipped intermediate certificate {}, a copy of \
+                trust anchor {}", depth, intermediate_index,
+               trust_anchor_index);
+        self.inner.trust_anchor_in_intermediates(depth, intermediate_index,
+                                                 trust_anchor_index);
+    }
+
+    fn issuer_subject_not_found(&self, depth: usize, cert: &Cert) {
+        debug!("depth {}: no trust anchor or intermediate certificate has the \
+                subject {}", depth, Name(cert.issuer));
+        self.inner.issuer_subject_not_found(depth, cert);
+    }
+}
+
+pub fn log_path_result(result: &Result<VerifiedPath, Error>) {
+    match *result {
+        Ok(ref path) =>
+            debug!("found a path to trust anchor {} through intermediate \
+                    certificates {:?}", path.trust_anchor_index(),
+                   path.intermediate_indices()),
+        Err(err) => debug!("no valid path found: {:?}", err),
+    }
+}
+
+// Formats a distinguished name as an RFC 4514 string, or as escaped bytes if
+// it can't be parsed.
+struct Name<'a>(untrusted::Input<'a>);
+
+impl<'a> fmt::Display for Name<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = self.0.as_slice_less_safe();
+        match DistinguishedName::from(name) {
+            Ok(name) => write!(f, "\"{}\"", name),
+            Err(..) => write!(f, "(invalid name {})", Escaped(name)),
+        }
+    }
+}
+
+// Formats bytes, e.g. a DNS name, as an ASCII string in which every byte that
+// isn't a printable ASCII character, and every backslash and double quote,
+// is escaped as `\xHH`, so that names from certificates can't forge log
+// lines.
+pub struct Escaped<'a>(pub &'a [u8]);
+
+impl<'a> fmt::Display for Escaped<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(write!(f, "\""));
+        for &b in self.0 {
+            match b {
+                b'\\' | b'"' => try!(write!(f, "\\x{:02x}", b)),
+                0x20...0x7e => try!(write!(f, "{}", b as char)),
+                _ => try!(write!(f, "\\x{:02x}", b)),
+            }
+        }
+        write!(f, "\"")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Escaped;
+
+    #[test]
+    fn test_escaped() {
+        assert_eq!("\"www.example.com\"",
+                   format!("{}", Escaped(b"www.example.com")));
+        assert_eq!("\"a\\x0ab\\x22c\\x5c\\xff\"",
+                   format!("{}", Escaped(b"a\nb\"c\\\xff")));
+    }
+}
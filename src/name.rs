@@ -13,34 +13,653 @@
 // OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
 
 use cert::{Cert, EndEntityOrCA};
-use {der, Error};
+use core::cell::Cell;
+use core::iter;
+use dn::DistinguishedName;
+#[cfg(feature = "log")]
+use logging::Escaped;
+use ring::der::{CONSTRUCTED, CONTEXT_SPECIFIC};
+use {der, Der, Error, Policy};
+
+#[cfg(feature = "idna")]
+use core::str;
+
+#[cfg(feature = "idna")]
+use punycode;
+
+#[cfg(feature = "idna")]
+use std::string::String;
 use untrusted;
 
-pub fn verify_cert_dns_name(cert: &super::EndEntityCert,
+/// A reference identity of a server: the name or address that the client
+/// intended to connect to, against which the server's certificate is matched.
+#[derive(Clone, Copy, Debug)]
+pub enum ServerName<'a> {
+    /// A DNS host name. It is assumed to be a normalized ASCII (punycode if
+    /// non-ASCII) DNS name. It is matched against the certificate's dNSName
+    /// subjectAltName entries.
+    DnsName(&'a [u8]),
+
+    /// An IP address in network byte order: four bytes for an IPv4 address
+    /// or sixteen bytes for an IPv6 address. It is matched against the
+    /// certificate's iPAddress subjectAltName entries.
+    IpAddress(&'a [u8]),
+
+    /// An absolute URI with an authority component whose host is a DNS name,
+    /// e.g. `sip:` or `xmpp:` service URIs. Following RFC 6125 Section
+    /// 6.5.2, it is matched against the certificate's
+    /// uniformResourceIdentifier subjectAltName entries by comparing the
+    /// scheme (case-insensitively) and the host (as a DNS name, without
+    /// wildcards); any userinfo, port, path, query, or fragment is ignored.
+    Uri(&'a [u8]),
+
+    /// An RFC 4985 SRVName, a service name label prefixed with an underscore
+    /// followed by a DNS name, e.g. `_xmpp-server.example.com`. Following RFC
+    /// 6125 Section 6.5.1, it is matched against the certificate's
+    /// id-on-dnsSRV otherName subjectAltName entries by comparing the service
+    /// name case-insensitively and the domain as a DNS name, without
+    /// wildcards.
+    SrvName(&'a [u8]),
+
+    /// An email address of the form `local-part@domain`, e.g. the
+    /// ID_RFC822_ADDR identity of an IKE peer or the address of an S/MIME
+    /// signer; see `EndEntityCert::verify_is_valid_smime_cert`. It is
+    /// matched against the certificate's rfc822Name subjectAltName entries.
+    /// Following RFC 5280 Section 7.5, the local part is compared exactly and
+    /// the domain is compared case-insensitively, without wildcards.
+    Email(&'a [u8]),
+
+    /// An otherName of a type that webpki doesn't otherwise understand, e.g.
+    /// XMPP's id-on-xmppAddr. It is matched against the certificate's
+    /// otherName subjectAltName entries of the same type by comparing the
+    /// encoded values exactly.
+    OtherName {
+        /// The encoded value of the otherName's `type-id` OID, without the
+        /// tag and length.
+        type_id: &'a [u8],
+
+        /// The DER encoding, including the tag and length, of the expected
+        /// `value`.
+        value: &'a [u8],
+    },
+}
+
+/// How a reference DNS name matched a certificate; see
+/// `EndEntityCert::match_dns_name`.
+#[derive(Clone, Copy, Debug)]
+pub struct DnsNameMatch<'a> {
+    presented_id: untrusted::Input<'a>,
+}
+
+impl<'a> DnsNameMatch<'a> {
+    /// The dNSName subjectAltName entry that matched, exactly as it is
+    /// encoded in the certificate. Internationalized labels are A-labels,
+    /// e.g. `xn--bcher-kva`; see `presented_id_unicode`.
+    pub fn presented_id(&self) -> Der<'a> {
+        der::from_input(self.presented_id)
+    }
+
+    /// Whether the match was via a wildcard label (e.g. `*.example.com`)
+    /// rather than an exact match. Applications may want to refuse wildcard
+    /// matches for particularly sensitive hosts.
+    pub fn is_wildcard(&self) -> bool {
+        // `presented_dns_id_matches_reference_dns_id` only matches valid
+        // presented DNS IDs, in which a '*' can only be part of a wildcard
+        // label.
+        self.presented_id.as_slice_less_safe().contains(&b'*')
+    }
+
+    /// Whether any label of `presented_id` is an A-label, i.e. starts with
+    /// `xn--`. An application that displays the name, or that checks it for
+    /// homographs of other names, should then decode it to Unicode.
+    pub fn is_internationalized(&self) -> bool {
+        self.presented_id.as_slice_less_safe().split(|&b| b == b'.')
+            .any(is_a_label)
+    }
+
+    /// `presented_id` with each A-label decoded to its U-label, e.g.
+    /// `bücher.example` for `xn--bcher-kva.example`, or `None` if one of them
+    /// isn't valid Punycode.
+    ///
+    /// Only the Punycode is decoded; the U-labels aren't checked against the
+    /// IDNA2008 rules, and webpki's matching never uses them. They are for
+    /// display and for homograph checks done by the application.
+    #[cfg(feature = "idna")]
+    pub fn presented_id_unicode(&self) -> Option<String> {
+        let mut result = String::new();
+        let labels =
+            self.presented_id.as_slice_less_safe().split(|&b| b == b'.');
+        for (i, label) in labels.enumerate() {
+            if i > 0 {
+                result.push('.');
+            }
+            let label = if is_a_label(label) {
+                punycode::decode(&label[A_LABEL_PREFIX.len()..])
+            } else {
+                // A valid presented DNS ID is ASCII.
+                str::from_utf8(label).ok().map(String::from)
+            };
+            match label {
+                Some(label) => result.push_str(&label),
+                None => { return None; }
+            }
+        }
+        Some(result)
+    }
+}
+
+const A_LABEL_PREFIX: &'static [u8] = b"xn--";
+
+fn is_a_label(label: &[u8]) -> bool {
+    label.len() > A_LABEL_PREFIX.len() &&
+        ascii_eq_ignore_case(&label[..A_LABEL_PREFIX.len()], A_LABEL_PREFIX)
+}
+
+pub fn verify_cert_server_name(cert: &Cert, policy: &Policy,
+                               server_name: &ServerName) -> Result<(), Error> {
+    match *server_name {
+        ServerName::DnsName(dns_name) =>
+            verify_cert_dns_name(cert, policy,
+                                 untrusted::Input::from(dns_name)),
+        ServerName::IpAddress(ip_address) =>
+            verify_cert_ip_address(cert, untrusted::Input::from(ip_address)),
+        ServerName::Uri(uri) =>
+            verify_cert_uri(cert, untrusted::Input::from(uri)),
+        ServerName::SrvName(srv_name) =>
+            verify_cert_srv_name(cert, untrusted::Input::from(srv_name)),
+        ServerName::Email(email) =>
+            verify_cert_email(cert, untrusted::Input::from(email)),
+        ServerName::OtherName { type_id, value } =>
+            verify_cert_other_name(cert, untrusted::Input::from(type_id),
+                                   untrusted::Input::from(value)),
+    }
+}
+
+pub fn verify_cert_dns_name(cert: &Cert, policy: &Policy,
                             dns_name: untrusted::Input) -> Result<(), Error> {
-    let cert = &cert.inner;
+    verify_cert_dns_names(cert, policy, iter::once(dns_name)).map(|_| ())
+}
 
-    if !is_valid_reference_dns_id(dns_name) {
+pub fn match_cert_dns_name<'a>(cert: &Cert<'a>, policy: &Policy,
+                               dns_name: untrusted::Input)
+                               -> Result<DnsNameMatch<'a>, Error> {
+    match_cert_dns_names(cert, policy, iter::once(dns_name))
+        .map(|(_, dns_name_match)| dns_name_match)
+}
+
+pub fn verify_cert_dns_names<'b, Names>(cert: &Cert, policy: &Policy,
+                                        dns_names: Names)
+                                        -> Result<untrusted::Input<'b>, Error>
+                                        where Names: Iterator<Item=
+                                                        untrusted::Input<'b>> +
+                                                     Clone {
+    match_cert_dns_names(cert, policy, dns_names)
+        .map(|(dns_name, _)| dns_name)
+}
+
+// Scans the names in the certificate once, trying each presented DNS ID
+// against each of the reference DNS IDs in `dns_names`, and returns the first
+// reference DNS ID that matched along with the presented DNS ID it matched.
+fn match_cert_dns_names<'a, 'b, Names>(cert: &Cert<'a>, policy: &Policy,
+                                       dns_names: Names)
+                                       -> Result<(untrusted::Input<'b>,
+                                                  DnsNameMatch<'a>), Error>
+                                       where Names: Iterator<Item=
+                                                       untrusted::Input<'b>> +
+                                                    Clone {
+    if !dns_names.clone().all(|dns_name| {
+            is_valid_reference_dns_id(dns_name) ||
+            (policy.allow_domain_suffix_reference_ids &&
+             domain_suffix(dns_name).is_some())
+        }) {
+        return Err(Error::InvalidReferenceName);
+    }
+
+    let matched = Cell::new(None);
+
+    let result = iterate_names(cert.subject, cert.subject_alt_name,
+                               Err(Error::CertNotValidForName), &|name| {
+        if let GeneralName::DNSName(presented_id) = name {
+            for dns_name in dns_names.clone() {
+                let matches = presented_dns_id_matches_reference_id(
+                    presented_id, dns_name, policy);
+                #[cfg(feature = "log")]
+                debug!("DNS name {} against presented DNS ID {}: {:?}",
+                       Escaped(dns_name.as_slice_less_safe()),
+                       Escaped(presented_id.as_slice_less_safe()),
+                       matches);
+                match matches {
+                    Some(true) => {
+                        matched.set(Some((dns_name, DnsNameMatch {
+                            presented_id: presented_id,
+                        })));
+                        return NameIteration::Stop(Ok(()));
+                    },
+                    Some(false) => (),
+                    None => {
+                        return NameIteration::Stop(
+                            Err(Error::MalformedDnsIdentifier));
+                    },
+                }
+            }
+        }
+        NameIteration::KeepGoing
+    });
+    #[cfg(feature = "log")]
+    debug!("DNS name matching result: {:?}", result);
+    try!(result);
+
+    match matched.get() {
+        Some(result) => Ok(result),
+        None => unreachable!() // Only a match stops the iteration with `Ok`.
+    }
+}
+
+// Like `presented_dns_id_matches_reference_dns_id` for a reference ID, but
+// with `Policy::allow_domain_suffix_reference_ids`, a reference ID with a
+// leading dot, e.g. `.internal.corp`, matches a presented DNS ID with exactly
+// one more label, e.g. `api.internal.corp` or `*.internal.corp`. It doesn't
+// match `internal.corp` itself or `a.b.internal.corp`.
+fn presented_dns_id_matches_reference_id(presented_dns_id: untrusted::Input,
+                                         reference_id: untrusted::Input,
+                                         policy: &Policy) -> Option<bool> {
+    let domain = match domain_suffix(reference_id) {
+        Some(domain) if policy.allow_domain_suffix_reference_ids => domain,
+        _ => {
+            return presented_dns_id_matches_reference_dns_id(
+                presented_dns_id, reference_id, policy);
+        },
+    };
+
+    if !is_valid_dns_id(presented_dns_id, IDRole::PresentedID,
+                        AllowWildcards::Yes) {
+        return None;
+    }
+
+    // An absolute presented DNS ID or domain is treated the same as the
+    // equivalent relative one, as in
+    // `presented_dns_id_matches_reference_dns_id`.
+    fn relative(dns_id: &[u8]) -> &[u8] {
+        match dns_id.last() {
+            Some(&b'.') => &dns_id[..(dns_id.len() - 1)],
+            _ => dns_id,
+        }
+    }
+    let presented = relative(presented_dns_id.as_slice_less_safe());
+    let domain = relative(domain.as_slice_less_safe());
+
+    let (first_label, rest) = match presented.iter().position(|&b| b == b'.') {
+        Some(dot) => (&presented[..dot], &presented[(dot + 1)..]),
+        None => { return Some(false); }
+    };
+    if !ascii_eq_ignore_case(rest, domain) {
+        return Some(false);
+    }
+
+    // A wildcard label is valid for some host name in the domain, unless it
+    // is a partial-label wildcard that the policy doesn't accept.
+    let is_partial_label_wildcard =
+        first_label.len() > 1 && first_label.contains(&b'*');
+    Some(!is_partial_label_wildcard || policy.allow_partial_label_wildcards)
+}
+
+// The domain of a domain-suffix reference ID, i.e. the reference ID without
+// its leading dot, if it is one; see `presented_dns_id_matches_reference_id`.
+fn domain_suffix(reference_id: untrusted::Input) -> Option<untrusted::Input> {
+    let reference_id = reference_id.as_slice_less_safe();
+    if reference_id.first() != Some(&b'.') {
+        return None;
+    }
+    let domain = untrusted::Input::from(&reference_id[1..]);
+    if !is_valid_reference_dns_id(domain) {
+        return None;
+    }
+    Some(domain)
+}
+
+fn verify_cert_ip_address(cert: &Cert, ip_address: untrusted::Input)
+                          -> Result<(), Error> {
+    if ip_address.len() != 4 && ip_address.len() != 16 {
         return Err(Error::InvalidReferenceName);
     }
 
     iterate_names(cert.subject, cert.subject_alt_name,
                   Err(Error::CertNotValidForName), &|name| {
         match name {
-            GeneralName::DNSName(presented_id) => {
-                match presented_dns_id_matches_reference_dns_id(
-                        presented_id, IDRole::ReferenceID, dns_name) {
-                    Some(true) => { return NameIteration::Stop(Ok(())); },
-                    Some(false) => (),
-                    None => { return NameIteration::Stop(Err(Error::BadDER)); },
+            GeneralName::IPAddress(presented_id)
+                    if presented_id == ip_address =>
+                NameIteration::Stop(Ok(())),
+            _ => NameIteration::KeepGoing
+        }
+    })
+}
+
+fn verify_cert_uri(cert: &Cert, uri: untrusted::Input) -> Result<(), Error> {
+    let (reference_scheme, reference_host) =
+        match uri_scheme_and_host(uri) {
+            Some((scheme, host)) if is_valid_reference_dns_id(host) =>
+                (scheme, host),
+            _ => { return Err(Error::InvalidReferenceName); }
+        };
+
+    iterate_names(cert.subject, cert.subject_alt_name,
+                  Err(Error::CertNotValidForName), &|name| {
+        let presented_id = match name {
+            GeneralName::UniformResourceIdentifier(presented_id) =>
+                presented_id,
+            _ => { return NameIteration::KeepGoing; }
+        };
+        // Like mozilla::pkix does for other name types that it doesn't
+        // understand, we skip presented URIs that we can't parse instead of
+        // rejecting the certificate. Wildcards aren't allowed in URI-IDs.
+        let (scheme, host) = match uri_scheme_and_host(presented_id) {
+            Some((scheme, host))
+                if is_valid_dns_id(host, IDRole::PresentedID,
+                                   AllowWildcards::No) => (scheme, host),
+            _ => { return NameIteration::KeepGoing; }
+        };
+        if ascii_eq_ignore_case(scheme.as_slice_less_safe(),
+                                reference_scheme.as_slice_less_safe()) &&
+           presented_dns_id_matches_reference_dns_id(
+                host, reference_host, &Policy::default()) == Some(true) {
+            return NameIteration::Stop(Ok(()));
+        }
+        NameIteration::KeepGoing
+    })
+}
+
+// id-pkix  OBJECT IDENTIFIER ::= { 1 3 6 1 5 5 7 }
+// id-on    OBJECT IDENTIFIER ::= { id-pkix 8 }
+// id-on-dnsSRV OBJECT IDENTIFIER ::= { id-on 7 }
+static ID_ON_DNS_SRV: [u8; 8] = oid![1, 3, 6, 1, 5, 5, 7, 8, 7];
+
+fn verify_cert_srv_name(cert: &Cert, srv_name: untrusted::Input)
+                        -> Result<(), Error> {
+    let (reference_service, reference_domain) =
+        match srv_name_service_and_domain(srv_name) {
+            Some((service, domain)) if is_valid_reference_dns_id(domain) =>
+                (service, domain),
+            _ => { return Err(Error::InvalidReferenceName); }
+        };
+
+    iterate_names(cert.subject, cert.subject_alt_name,
+                  Err(Error::CertNotValidForName), &|name| {
+        let presented_id = match name {
+            GeneralName::OtherName(other_name) => {
+                match other_name_type_id_and_value(other_name) {
+                    Some((type_id, value))
+                        if type_id == &ID_ON_DNS_SRV[..] => value,
+                    _ => { return NameIteration::KeepGoing; }
                 }
             },
-            _ => ()
+            _ => { return NameIteration::KeepGoing; }
+        };
+        // SRVName ::= IA5String (SIZE (1..MAX))
+        const IA5_STRING_TAG: u8 = 0x16;
+        let presented_id =
+            match presented_id.read_all(Error::BadDER,
+                                        der::read_tag_and_get_value) {
+                Ok((IA5_STRING_TAG, presented_id)) => presented_id,
+                _ => { return NameIteration::KeepGoing; }
+            };
+        // As with URIs, presented SRVNames that we can't parse are skipped,
+        // and wildcards aren't allowed.
+        let (service, domain) =
+            match srv_name_service_and_domain(presented_id) {
+                Some((service, domain))
+                    if is_valid_dns_id(domain, IDRole::PresentedID,
+                                       AllowWildcards::No) =>
+                    (service, domain),
+                _ => { return NameIteration::KeepGoing; }
+            };
+        if ascii_eq_ignore_case(service.as_slice_less_safe(),
+                                reference_service.as_slice_less_safe()) &&
+           presented_dns_id_matches_reference_dns_id(
+                domain, reference_domain, &Policy::default()) == Some(true) {
+            return NameIteration::Stop(Ok(()));
         }
         NameIteration::KeepGoing
     })
 }
 
+// Splits an SRVName like `_xmpp-server.example.com` into the service name
+// (without the underscore) and the domain, e.g. `("xmpp-server",
+// "example.com")`. See https://tools.ietf.org/html/rfc4985#section-2. The
+// domain is validated as a DNS name by the caller.
+fn srv_name_service_and_domain<'a>(srv_name: untrusted::Input<'a>)
+        -> Option<(untrusted::Input<'a>, untrusted::Input<'a>)> {
+    srv_name.read_all((), |input| {
+        if input.read_byte() != Ok(b'_') {
+            return Err(());
+        }
+        let service = read_label(input);
+        if service.len() == 0 || !service.as_slice_less_safe().iter().all(|&b|
+                match b {
+                    b'a'...b'z' | b'A'...b'Z' | b'0'...b'9' | b'-' => true,
+                    _ => false
+                }) {
+            return Err(());
+        }
+        if input.read_byte() != Ok(b'.') {
+            return Err(());
+        }
+        Ok((service, input.skip_to_end()))
+    }).ok()
+}
+
+fn verify_cert_other_name(cert: &Cert, reference_type_id: untrusted::Input,
+                          reference_value: untrusted::Input)
+                          -> Result<(), Error> {
+    iterate_names(cert.subject, cert.subject_alt_name,
+                  Err(Error::CertNotValidForName), &|name| {
+        match name {
+            GeneralName::OtherName(other_name) => {
+                match other_name_type_id_and_value(other_name) {
+                    Some((type_id, value))
+                        if type_id == reference_type_id &&
+                           value == reference_value =>
+                        NameIteration::Stop(Ok(())),
+                    _ => NameIteration::KeepGoing
+                }
+            },
+            _ => NameIteration::KeepGoing
+        }
+    })
+}
+
+fn verify_cert_email(cert: &Cert, email: untrusted::Input)
+                     -> Result<(), Error> {
+    let (reference_local_part, reference_domain) =
+        match email_local_part_and_domain(email) {
+            Some((local_part, domain)) if is_valid_reference_dns_id(domain) =>
+                (local_part, domain),
+            _ => { return Err(Error::InvalidReferenceName); }
+        };
+
+    iterate_names(cert.subject, cert.subject_alt_name,
+                  Err(Error::CertNotValidForName), &|name| {
+        let presented_id = match name {
+            GeneralName::Rfc822Name(presented_id) => presented_id,
+            _ => { return NameIteration::KeepGoing; }
+        };
+        // As with URIs, presented email addresses that we can't parse are
+        // skipped.
+        let (local_part, domain) =
+            match email_local_part_and_domain(presented_id) {
+                Some(local_part_and_domain) => local_part_and_domain,
+                None => { return NameIteration::KeepGoing; }
+            };
+        if local_part == reference_local_part &&
+           ascii_eq_ignore_case(domain.as_slice_less_safe(),
+                                reference_domain.as_slice_less_safe()) {
+            return NameIteration::Stop(Ok(()));
+        }
+        NameIteration::KeepGoing
+    })
+}
+
+// Splits an email address like `vpn@example.com` into the local part and the
+// domain at the last `@`, e.g. `("vpn", "example.com")`. The local part must
+// be non-empty printable ASCII without spaces; quoted local parts are
+// compared like any other. The domain is validated as a DNS name by the
+// caller.
+fn email_local_part_and_domain<'a>(email: untrusted::Input<'a>)
+        -> Option<(untrusted::Input<'a>, untrusted::Input<'a>)> {
+    let email = email.as_slice_less_safe();
+    let at = match email.iter().rposition(|&b| b == b'@') {
+        Some(at) if at > 0 => at,
+        _ => { return None; }
+    };
+    let (local_part, domain) = (&email[..at], &email[(at + 1)..]);
+    if !local_part.iter().all(|&b| b > b' ' && b < 0x7f) {
+        return None;
+    }
+    Some((untrusted::Input::from(local_part), untrusted::Input::from(domain)))
+}
+
+// Microsoft's user principal name (UPN) otherName, which identifies an
+// Active Directory account, e.g. for smart card logon.
+//
+// id-ms-san-upn OBJECT IDENTIFIER ::= { 1 3 6 1 4 1 311 20 2 3 }
+// UPN ::= UTF8String
+static ID_MS_SAN_UPN: [u8; 10] =
+    oid![1, 3, 6, 1, 4, 1, 0x82, 0x37, 20, 2, 3];
+
+// Returns the first UPN in the certificate's subjectAltName, as the contents
+// of its UTF8String. As with SRVNames, UPNs that we can't parse are skipped.
+pub fn cert_user_principal_name<'a>(cert: &Cert<'a>)
+                                    -> Result<Option<untrusted::Input<'a>>,
+                                              Error> {
+    let found = Cell::new(None);
+    try!(iterate_names(cert.subject, cert.subject_alt_name, Ok(()), &|name| {
+        match name {
+            GeneralName::OtherName(other_name) => {
+                match user_principal_name(other_name) {
+                    Some(upn) => {
+                        found.set(Some(upn));
+                        NameIteration::Stop(Ok(()))
+                    },
+                    None => NameIteration::KeepGoing
+                }
+            },
+            _ => NameIteration::KeepGoing
+        }
+    }));
+    Ok(found.get())
+}
+
+// Verifies that one of the UPNs in the certificate's subjectAltName is `upn`.
+// Like Active Directory, the comparison is case-insensitive, but only for
+// ASCII characters.
+pub fn verify_cert_user_principal_name(cert: &Cert, upn: untrusted::Input)
+                                       -> Result<(), Error> {
+    if upn.len() == 0 {
+        return Err(Error::InvalidReferenceName);
+    }
+    iterate_names(cert.subject, cert.subject_alt_name,
+                  Err(Error::CertNotValidForName), &|name| {
+        match name {
+            GeneralName::OtherName(other_name) => {
+                match user_principal_name(other_name) {
+                    Some(presented_id)
+                        if ascii_eq_ignore_case(
+                                presented_id.as_slice_less_safe(),
+                                upn.as_slice_less_safe()) =>
+                        NameIteration::Stop(Ok(())),
+                    _ => NameIteration::KeepGoing
+                }
+            },
+            _ => NameIteration::KeepGoing
+        }
+    })
+}
+
+// The contents of the UTF8String of `other_name` if it is a UPN.
+fn user_principal_name<'a>(other_name: untrusted::Input<'a>)
+                           -> Option<untrusted::Input<'a>> {
+    const UTF8_STRING_TAG: u8 = 0x0c;
+    match other_name_type_id_and_value(other_name) {
+        Some((type_id, value)) if type_id == &ID_MS_SAN_UPN[..] => {
+            match value.read_all(Error::BadDER, der::read_tag_and_get_value) {
+                Ok((UTF8_STRING_TAG, upn)) => Some(upn),
+                _ => None
+            }
+        },
+        _ => None
+    }
+}
+
+// OtherName ::= SEQUENCE {
+//      type-id    OBJECT IDENTIFIER,
+//      value      [0] EXPLICIT ANY DEFINED BY type-id }
+//
+// The result is the encoded value of `type-id`, without the tag and length,
+// and the DER encoding of `value`, including the tag and length.
+fn other_name_type_id_and_value<'a>(other_name: untrusted::Input<'a>)
+        -> Option<(untrusted::Input<'a>, untrusted::Input<'a>)> {
+    other_name.read_all(Error::BadDER, |input| {
+        let type_id = try!(der::expect_tag_and_get_value(input, der::Tag::OID));
+        let value = try!(der::nested(input,
+                                     der::Tag::ContextSpecificConstructed0,
+                                     Error::BadDER,
+                                     |value| Ok(value.skip_to_end())));
+        Ok((type_id, value))
+    }).ok()
+}
+
+// Splits an absolute URI with an authority component into its scheme and the
+// host of its authority, e.g. `("sip", "example.com")` for
+// `sip://example.com:5060/x`. URIs with userinfo or with IP literal hosts
+// aren't supported. See https://tools.ietf.org/html/rfc3986#section-3.
+fn uri_scheme_and_host<'a>(uri: untrusted::Input<'a>)
+        -> Option<(untrusted::Input<'a>, untrusted::Input<'a>)> {
+    fn read_until<'a>(input: &mut untrusted::Reader<'a>, delimiters: &[u8],
+                      is_valid: &Fn(u8) -> bool)
+                      -> Result<untrusted::Input<'a>, ()> {
+        let start = input.mark();
+        while !input.at_end() && !delimiters.iter().any(|d| input.peek(*d)) {
+            let b = try!(input.read_byte().map_err(|_| ()));
+            if !is_valid(b) {
+                return Err(());
+            }
+        }
+        input.get_input_between_marks(start, input.mark()).map_err(|_| ())
+    }
+
+    uri.read_all((), |input| {
+        // scheme = ALPHA *( ALPHA / DIGIT / "+" / "-" / "." )
+        let scheme = try!(read_until(input, b":", &|b| match b {
+            b'a'...b'z' | b'A'...b'Z' | b'0'...b'9' | b'+' | b'-' | b'.' =>
+                true,
+            _ => false
+        }));
+        match scheme.as_slice_less_safe().first() {
+            Some(&b'a'...b'z') | Some(&b'A'...b'Z') => (),
+            _ => { return Err(()); }
+        }
+        for expected in b"://" {
+            if try!(input.read_byte().map_err(|_| ())) != *expected {
+                return Err(());
+            }
+        }
+
+        // The host is validated as a DNS name by the caller.
+        let host = try!(read_until(input, b":/?#", &|b| b != b'@'));
+        if input.peek(b':') {
+            let _ = try!(input.read_byte().map_err(|_| ()));
+            let _ = try!(read_until(input, b"/?#", &|b| match b {
+                b'0'...b'9' => true,
+                _ => false
+            }));
+        }
+
+        // Ignore the path, query, and fragment.
+        let _ = input.skip_to_end();
+
+        Ok((scheme, host))
+    }).ok()
+}
+
 // https://tools.ietf.org/html/rfc5280#section-4.2.1.10
 pub fn check_name_constraints<'a>(input: Option<&mut untrusted::Reader<'a>>,
                                   subordinate_certs: &Cert)
@@ -56,10 +675,10 @@ pub fn check_name_constraints<'a>(input: Option<&mut untrusted::Reader<'a>>,
         if !inner.peek(subtrees_tag as u8) {
             return Ok(None);
         }
-        let subtrees = try!(der::nested(inner, subtrees_tag,
-                                        Error::BadDER, |tagged| {
-            der::expect_tag_and_get_value(tagged, der::Tag::Sequence)
-        }));
+        // The fields are IMPLICITly tagged, so the context-specific tag
+        // replaces the `SEQUENCE` tag of `GeneralSubtrees`.
+        let subtrees =
+            try!(der::expect_tag_and_get_value(inner, subtrees_tag));
         Ok(Some(subtrees))
     }
 
@@ -68,6 +687,15 @@ pub fn check_name_constraints<'a>(input: Option<&mut untrusted::Reader<'a>>,
     let excluded_subtrees =
         try!(parse_subtrees(input, der::Tag::ContextSpecificConstructed1));
 
+    check_subtrees(permitted_subtrees, excluded_subtrees, subordinate_certs)
+}
+
+// Checks the names of `subordinate_certs`, and of each certificate below it in
+// the path, against the given subtrees, which are the values of the
+// `GeneralSubtrees` `SEQUENCE`s without their tags and lengths.
+pub fn check_subtrees(permitted_subtrees: Option<untrusted::Input>,
+                      excluded_subtrees: Option<untrusted::Input>,
+                      subordinate_certs: &Cert) -> Result<(), Error> {
     let mut child = subordinate_certs;
     loop {
         try!(iterate_names(child.subject, child.subject_alt_name, Ok(()),
@@ -77,7 +705,8 @@ pub fn check_name_constraints<'a>(input: Option<&mut untrusted::Reader<'a>>,
 
         child = match child.ee_or_ca {
             EndEntityOrCA::CA(child_cert) => child_cert,
-            EndEntityOrCA::EndEntity => { break; }
+            EndEntityOrCA::EndEntity |
+            EndEntityOrCA::TargetCA => { break; }
         };
     }
 
@@ -115,19 +744,15 @@ fn check_presented_id_conforms_to_constraints_in_subtree(
     let mut has_permitted_subtrees_mismatch = false;
 
     loop {
-        // http://tools.ietf.org/html/rfc5280#section-4.2.1.10: "Within this
-        // profile, the minimum and maximum fields are not used with any name
-        // forms, thus, the minimum MUST be zero, and maximum MUST be absent."
-        //
-        // Since the default value isn't allowed to be encoded according to the
-        // DER encoding rules for DEFAULT, this is equivalent to saying that
-        // neither minimum or maximum must be encoded.
         fn general_subtree<'b>(input: &mut untrusted::Reader<'b>)
                                -> Result<GeneralName<'b>, Error> {
             let general_subtree =
                 try!(der::expect_tag_and_get_value(input, der::Tag::Sequence));
-            general_subtree.read_all(Error::BadDER,
-                                     |subtree| general_name(subtree))
+            general_subtree.read_all(Error::BadDER, |subtree| {
+                let base = try!(general_name(subtree));
+                try!(general_subtree_distances(subtree));
+                Ok(base)
+            })
         }
 
         let base = match general_subtree(&mut constraints) {
@@ -138,14 +763,13 @@ fn check_presented_id_conforms_to_constraints_in_subtree(
         let matches = match (name, base) {
             (GeneralName::DNSName(name),
              GeneralName::DNSName(base)) =>
-                presented_dns_id_matches_reference_dns_id(
-                    name, IDRole::NameConstraint, base)
+                presented_dns_id_matches_dns_name_constraint(
+                    name, base, subtrees)
                         .ok_or(Error::BadDER),
 
             (GeneralName::DirectoryName(name),
              GeneralName::DirectoryName(base)) =>
-                presented_directory_name_matches_constraint(name, base,
-                                                            subtrees),
+                presented_directory_name_matches_constraint(name, base),
 
             (GeneralName::IPAddress(name),
              GeneralName::IPAddress(base)) =>
@@ -159,11 +783,23 @@ fn check_presented_id_conforms_to_constraints_in_subtree(
             // certificate." Later, the CABForum agreed to support non-critical
             // constraints, so it is important to reject the cert without
             // considering whether the name constraint it critical.
+            (GeneralName::Rfc822Name(..), GeneralName::Rfc822Name(..)) |
+            (GeneralName::UniformResourceIdentifier(..),
+             GeneralName::UniformResourceIdentifier(..)) |
+            (GeneralName::OtherName(..), GeneralName::OtherName(..)) =>
+                Err(Error::NameConstraintViolation),
             (GeneralName::Unsupported(name_tag),
              GeneralName::Unsupported(base_tag)) if name_tag == base_tag =>
                 Err(Error::NameConstraintViolation),
 
-            _ => Ok(false)
+            // The constraint is on another type of name, so it neither
+            // permits nor excludes `name`.
+            _ => {
+                if constraints.at_end() {
+                    break;
+                }
+                continue;
+            }
         };
 
         match (subtrees, matches) {
@@ -201,14 +837,15 @@ fn check_presented_id_conforms_to_constraints_in_subtree(
     }
 }
 
-// TODO: document this.
-fn presented_directory_name_matches_constraint<'a>(
-        name: untrusted::Input<'a>, constraint: untrusted::Input<'a>,
-        subtrees: Subtrees) -> Result<bool, Error> {
-    match subtrees {
-        Subtrees::PermittedSubtrees => Ok(name == constraint),
-        Subtrees::ExcludedSubtrees => Ok(true),
-    }
+// https://tools.ietf.org/html/rfc5280#section-4.2.1.10; see
+// `DistinguishedName::is_within`.
+fn presented_directory_name_matches_constraint(name: untrusted::Input,
+                                               constraint: untrusted::Input)
+                                               -> Result<bool, Error> {
+    let name = try!(DistinguishedName::from(name.as_slice_less_safe()));
+    let constraint =
+        try!(DistinguishedName::from(constraint.as_slice_less_safe()));
+    Ok(name.is_within(&constraint))
 }
 
 // https://tools.ietf.org/html/rfc5280#section-4.2.1.10 says:
@@ -257,7 +894,7 @@ fn presented_ip_address_matches_constraint(name: untrusted::Input,
         }
     }
 
-    return Ok(true);
+    Ok(true)
 }
 
 #[derive(Clone, Copy)]
@@ -266,28 +903,25 @@ enum NameIteration {
     Stop(Result<(), Error>)
 }
 
-fn iterate_names(subject: untrusted::Input,
-                 subject_alt_name: Option<untrusted::Input>,
-                 result_if_never_stopped_early: Result<(), Error>,
-                 f: &Fn(GeneralName) -> NameIteration) -> Result<(), Error> {
-    match subject_alt_name {
-        Some(subject_alt_name) => {
-            let mut subject_alt_name = untrusted::Reader::new(subject_alt_name);
-            // https://bugzilla.mozilla.org/show_bug.cgi?id=1143085: An empty
-            // subjectAltName is not legal, but some certificates have an empty
-            // subjectAltName. Since we don't support CN-IDs, the certificate
-            // will be rejected either way, but checking `at_end` before
-            // attempting to parse the first entry allows us to return a better
-            // error code.
-            while !subject_alt_name.at_end() {
-                let name = try!(general_name(&mut subject_alt_name));
-                match f(name) {
-                    NameIteration::Stop(result) => { return result; },
-                    NameIteration::KeepGoing => ()
-                }
+fn iterate_names<'a>(subject: untrusted::Input<'a>,
+                     subject_alt_name: Option<untrusted::Input<'a>>,
+                     result_if_never_stopped_early: Result<(), Error>,
+                     f: &Fn(GeneralName<'a>) -> NameIteration)
+                     -> Result<(), Error> {
+    if let Some(subject_alt_name) = subject_alt_name {
+        let mut subject_alt_name = untrusted::Reader::new(subject_alt_name);
+        // https://bugzilla.mozilla.org/show_bug.cgi?id=1143085: An empty
+        // subjectAltName is not legal, but some certificates have an empty
+        // subjectAltName. Since we don't support CN-IDs, the certificate will
+        // be rejected either way, but checking `at_end` before attempting to
+        // parse the first entry allows us to return a better error code.
+        while !subject_alt_name.at_end() {
+            let name = try!(general_name(&mut subject_alt_name));
+            match f(name) {
+                NameIteration::Stop(result) => { return result; },
+                NameIteration::KeepGoing => ()
             }
-        },
-        None => ()
+        }
     }
 
     match f(GeneralName::DirectoryName(subject)) {
@@ -303,9 +937,14 @@ fn iterate_names(subject: untrusted::Input,
 // `GeneralName` in other contexts.
 #[derive(Clone, Copy)]
 enum GeneralName<'a> {
+    Rfc822Name(untrusted::Input<'a>),
     DNSName(untrusted::Input<'a>),
     DirectoryName(untrusted::Input<'a>),
     IPAddress(untrusted::Input<'a>),
+    UniformResourceIdentifier(untrusted::Input<'a>),
+
+    // The contents of the `OtherName` SEQUENCE.
+    OtherName(untrusted::Input<'a>),
 
     // The value is the `tag & ~(der::CONTEXT_SPECIFIC | der::CONSTRUCTED)` so
     // that the name constraint checking matches tags regardless of whether
@@ -313,41 +952,190 @@ enum GeneralName<'a> {
     Unsupported(u8)
 }
 
+// The tags of the `GeneralName` alternatives.
+pub const OTHER_NAME_TAG: u8 = CONTEXT_SPECIFIC | CONSTRUCTED;
+pub const RFC822_NAME_TAG: u8 = CONTEXT_SPECIFIC | 1;
+pub const DNS_NAME_TAG: u8 = CONTEXT_SPECIFIC | 2;
+pub const X400_ADDRESS_TAG : u8 = CONTEXT_SPECIFIC | CONSTRUCTED | 3;
+pub const DIRECTORY_NAME_TAG: u8 = CONTEXT_SPECIFIC | CONSTRUCTED | 4;
+pub const EDI_PARTY_NAME_TAG: u8 = CONTEXT_SPECIFIC | CONSTRUCTED | 5;
+pub const UNIFORM_RESOURCE_IDENTIFIER_TAG: u8 = CONTEXT_SPECIFIC | 6;
+pub const IP_ADDRESS_TAG: u8 = CONTEXT_SPECIFIC | 7;
+pub const REGISTERED_ID_TAG: u8 = CONTEXT_SPECIFIC | 8;
+
 fn general_name<'a>(input: &mut untrusted::Reader<'a>)
                     -> Result<GeneralName<'a>, Error> {
-    use ring::der::{CONSTRUCTED, CONTEXT_SPECIFIC};
-    const OTHER_NAME_TAG: u8 = CONTEXT_SPECIFIC | CONSTRUCTED | 0;
-    const RFC822_NAME_TAG: u8 = CONTEXT_SPECIFIC | 1;
-    const DNS_NAME_TAG: u8 = CONTEXT_SPECIFIC | 2;
-    const X400_ADDRESS_TAG : u8 = CONTEXT_SPECIFIC | CONSTRUCTED | 3;
-    const DIRECTORY_NAME_TAG: u8 = CONTEXT_SPECIFIC | CONSTRUCTED | 4;
-    const EDI_PARTY_NAME_TAG: u8 = CONTEXT_SPECIFIC | CONSTRUCTED | 5;
-    const UNIFORM_RESOURCE_IDENTIFIER_TAG: u8 = CONTEXT_SPECIFIC | 6;
-    const IP_ADDRESS_TAG: u8 = CONTEXT_SPECIFIC | 7;
-    const REGISTERED_ID_TAG: u8 = CONTEXT_SPECIFIC | 8;
-
     let (tag, value) = try!(der::read_tag_and_get_value(input));
     let name = match tag {
+        RFC822_NAME_TAG => GeneralName::Rfc822Name(value),
         DNS_NAME_TAG => GeneralName::DNSName(value),
         DIRECTORY_NAME_TAG => GeneralName::DirectoryName(value),
         IP_ADDRESS_TAG => GeneralName::IPAddress(value),
+        UNIFORM_RESOURCE_IDENTIFIER_TAG =>
+            GeneralName::UniformResourceIdentifier(value),
+        OTHER_NAME_TAG => GeneralName::OtherName(value),
 
-        OTHER_NAME_TAG |
-        RFC822_NAME_TAG |
         X400_ADDRESS_TAG |
         EDI_PARTY_NAME_TAG |
-        UNIFORM_RESOURCE_IDENTIFIER_TAG |
         REGISTERED_ID_TAG =>
             GeneralName::Unsupported(tag & !(CONTEXT_SPECIFIC | CONSTRUCTED)),
 
+        // A context-specific tag that isn't one of the `GeneralName` choices,
+        // e.g. from a future extension of the CHOICE or a GREASE-style value,
+        // is skipped like the choices that we don't support, instead of making
+        // the whole certificate invalid. The known choices must still have
+        // the right form, so that e.g. a constructed dNSName can't be used to
+        // sneak a name past an excluded subtree.
+        _ if (tag & 0xc0) == CONTEXT_SPECIFIC &&
+             (tag & !(CONTEXT_SPECIFIC | CONSTRUCTED)) > 8 =>
+            GeneralName::Unsupported(tag & !(CONTEXT_SPECIFIC | CONSTRUCTED)),
+
         _ => return Err(Error::BadDER)
     };
     Ok(name)
 }
 
-// We do not distinguish between a syntactically-invalid presented_dns_id and
-// one that is syntactically valid but does not match reference_dns_id; in both
-// cases, the result is false.
+/// A name in the subjectAltName extension of a certificate. See
+/// `EndEntityCert::subject_alt_names`.
+///
+/// Each value is the value of the `GeneralName` alternative, without its tag
+/// and length.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SubjectAltName<'a> {
+    /// A dNSName, e.g. `www.example.com` or `*.example.com`.
+    DnsName(Der<'a>),
+
+    /// A directoryName: the value of the `Name` `SEQUENCE`.
+    DirectoryName(Der<'a>),
+
+    /// An iPAddress: a 4-byte IPv4 or 16-byte IPv6 address.
+    IpAddress(Der<'a>),
+
+    /// A uniformResourceIdentifier, e.g. `spiffe://example.com/service`.
+    Uri(Der<'a>),
+
+    /// An otherName: the contents of the `OtherName` `SEQUENCE`, i.e. the
+    /// `type-id` and the explicitly-tagged `value`.
+    OtherName(Der<'a>),
+
+    /// Any other kind of name: an rfc822Name, x400Address, ediPartyName, or
+    /// registeredID, or a context-specific tag that isn't a `GeneralName`
+    /// choice at all. Name verification skips these.
+    Other {
+        /// The tag of the name.
+        tag: u8,
+
+        /// The value of the name.
+        value: Der<'a>,
+    },
+}
+
+/// An iterator over the names in the subjectAltName extension of a
+/// certificate, in the order they are encoded.
+pub struct SubjectAltNames<'a> {
+    reader: Option<untrusted::Reader<'a>>,
+}
+
+// Every name is parsed before the iterator is returned, so iterating can't
+// fail.
+pub fn subject_alt_names<'a>(subject_alt_name: Option<untrusted::Input<'a>>)
+                             -> Result<SubjectAltNames<'a>, Error> {
+    if let Some(subject_alt_name) = subject_alt_name {
+        try!(subject_alt_name.read_all(Error::BadDER, |input| {
+            while !input.at_end() {
+                let _ = try!(general_name(input));
+            }
+            Ok(())
+        }));
+    }
+    Ok(SubjectAltNames {
+        reader: subject_alt_name.map(untrusted::Reader::new),
+    })
+}
+
+impl<'a> Iterator for SubjectAltNames<'a> {
+    type Item = SubjectAltName<'a>;
+
+    fn next(&mut self) -> Option<SubjectAltName<'a>> {
+        let (tag, value) = match self.reader {
+            Some(ref mut reader) if !reader.at_end() => {
+                match der::read_tag_and_get_value(reader) {
+                    Ok((tag, value)) => (tag, der::from_input(value)),
+                    Err(..) => { return None; } // Already checked.
+                }
+            },
+            _ => { return None; }
+        };
+        Some(match tag {
+            DNS_NAME_TAG => SubjectAltName::DnsName(value),
+            DIRECTORY_NAME_TAG => SubjectAltName::DirectoryName(value),
+            IP_ADDRESS_TAG => SubjectAltName::IpAddress(value),
+            UNIFORM_RESOURCE_IDENTIFIER_TAG => SubjectAltName::Uri(value),
+            OTHER_NAME_TAG => SubjectAltName::OtherName(value),
+            _ => SubjectAltName::Other { tag: tag, value: value },
+        })
+    }
+}
+
+// Reads the `minimum` and `maximum` fields that follow the `base` of a
+// `GeneralSubtree`:
+//
+//     minimum         [0]     BaseDistance DEFAULT 0,
+//     maximum         [1]     BaseDistance OPTIONAL }
+//
+//     BaseDistance ::= INTEGER (0..MAX)
+//
+// http://tools.ietf.org/html/rfc5280#section-4.2.1.10: "Within this profile,
+// the minimum and maximum fields are not used with any name forms, thus, the
+// minimum MUST be zero, and maximum MUST be absent."
+//
+// The DER encoding rules for DEFAULT don't allow a minimum of zero to be
+// encoded, so that is `Error::BadDER`, like any malformed `BaseDistance`. A
+// non-zero minimum or a maximum is well-formed, but no name form defines what
+// it means, so like mozilla::pkix we reject the subtree instead of guessing;
+// the error is `Error::ExtensionValueInvalid` so that it can be told apart
+// from a malformed encoding.
+pub fn general_subtree_distances(subtree: &mut untrusted::Reader)
+                                 -> Result<(), Error> {
+    const MINIMUM_TAG: u8 = CONTEXT_SPECIFIC;
+    const MAXIMUM_TAG: u8 = CONTEXT_SPECIFIC | 1;
+
+    // Returns whether the `BaseDistance` is zero.
+    fn base_distance(subtree: &mut untrusted::Reader, tag: u8)
+                     -> Result<Option<bool>, Error> {
+        if !subtree.peek(tag) {
+            return Ok(None);
+        }
+        let (_, value) = try!(der::read_tag_and_get_value(subtree));
+
+        // The value must be non-negative and minimally encoded.
+        let value = value.as_slice_less_safe();
+        if value.is_empty() || (value[0] & 0x80) != 0 ||
+           (value.len() > 1 && value[0] == 0 && (value[1] & 0x80) == 0) {
+            return Err(Error::BadDER);
+        }
+        Ok(Some(value.len() == 1 && value[0] == 0))
+    }
+
+    let minimum = try!(base_distance(subtree, MINIMUM_TAG));
+    let maximum = try!(base_distance(subtree, MAXIMUM_TAG));
+    match (minimum, maximum) {
+        (None, None) => Ok(()),
+        (Some(true), _) => Err(Error::BadDER),
+        _ => Err(Error::ExtensionValueInvalid),
+    }
+}
+
+// Unlike mozilla::pkix, we distinguish between a syntactically-invalid
+// presented_dns_id or reference_dns_id, for which the result is `None`, and
+// one that is syntactically valid but does not match, for which the result is
+// `Some(false)`. This allows the caller to report a malformed presented
+// identifier (`Error::MalformedDnsIdentifier`) differently from a simple
+// mismatch. Note that a presented identifier with an embedded NUL (or any
+// other byte that isn't allowed in a DNS name) is malformed.
+//
+// Comparisons are done case-insensitively for ASCII letters, as required by
+// RFC 6125 Section 6.4.1. Non-ASCII bytes are compared exactly.
 //
 // We assume that both presented_dns_id and reference_dns_id are encoded in
 // such a way that US-ASCII (7-bit) characters are encoded in one byte and no
@@ -355,19 +1143,39 @@ fn general_name<'a>(input: &mut untrusted::Reader<'a>)
 // 0-127. For example, UTF-8 is OK but UTF-16 is not.
 //
 // RFC6125 says that a wildcard label may be of the form <x>*<y>.<DNSID>, where
-// <x> and/or <y> may be empty. However, NSS requires <y> to be empty, and we
-// follow NSS's stricter policy by accepting wildcards only of the form
-// <x>*.<DNSID>, where <x> may be empty.
+// <x> and/or <y> may be empty. However, NSS requires <y> to be empty, and
+// Chromium requires both <x> and <y> to be empty. By default, we follow
+// Chromium's stricter policy: a presented DNS ID with a wildcard label that
+// isn't exactly "*" is syntactically valid, but it doesn't match anything.
+// `Policy::allow_partial_label_wildcards` enables the RFC 6125 semantics. In
+// either case, the wildcard must match at least one character, and it never
+// matches any part of an IDN A-label. A wildcard anywhere other than the
+// leftmost label, or more than one wildcard, makes the presented DNS ID
+// malformed:
 //
-// An relative presented DNS ID matches both an absolute reference ID and a
-// relative reference ID. Absolute presented DNS IDs are not supported:
+//      Presented ID       Reference ID      Default   Partial wildcards
+//      ----------------------------------------------------------------
+//      *.example.com      www.example.com   Match     Match
+//      *.example.com      example.com       Mismatch  Mismatch
+//      f*o.example.com    foo.example.com   Mismatch  Match
+//      f*o.example.com    fo.example.com    Mismatch  Mismatch
+//      f*.example.com     xn--f.example.com Mismatch  Mismatch
+//      **.example.com     www.example.com   Malformed Malformed
+//      *.*.example.com    a.b.example.com   Malformed Malformed
+//      www.*.example.com  www.a.example.com Malformed Malformed
+//
+// A presented DNS ID matches both an absolute reference ID and a relative
+// reference ID, regardless of whether the presented DNS ID is absolute. RFC
+// 5280 doesn't say anything about absolute presented DNS IDs, but some
+// certificates contain them, and the trailing dot doesn't change the meaning
+// of the name:
 //
 //      Presented ID   Reference ID  Result
 //      -------------------------------------
 //      example.com    example.com   Match
-//      example.com.   example.com   Mismatch
+//      example.com.   example.com   Match
 //      example.com    example.com.  Match
-//      example.com.   example.com.  Mismatch
+//      example.com.   example.com.  Match
 //
 // There are more subtleties documented inline in the code.
 //
@@ -442,8 +1250,10 @@ fn general_name<'a>(input: &mut untrusted::Reader<'a>)
 //     Q: Are name constraints allowed to be specified as absolute names?
 //        For example, does a presented ID of "example.com" match a name
 //        constraint of "example.com." and vice versa.
-//     A: Absolute names are not supported as presented IDs or name
-//        constraints. Only reference IDs may be absolute.
+//     A: Yes. Like Chromium, the trailing dot is ignored on both sides, so
+//        "example.com." means the same as "example.com" and ".example.com."
+//        means the same as ".example.com", whichever way the CA's tooling
+//        encoded them.
 //
 //     Q: Is "" a valid DNSName constraints? If so, what does it mean?
 //     A: Yes. Any valid presented DNSName can be formed "by simply adding zero
@@ -451,7 +1261,30 @@ fn general_name<'a>(input: &mut untrusted::Reader<'a>)
 //        excludedSubtrees DNSName constraint of "" forbids all DNSNames.
 //
 //     Q: Is "." a valid DNSName constraints? If so, what does it mean?
-//     A: No, because absolute names are not allowed (see above).
+//     A: Yes. It is the absolute form of "", so it means the same.
+//
+//     Q: Does a wildcard presented ID, e.g. "*.example.com", match a
+//        constraint?
+//     A: It depends on the kind of subtree, since a wildcard presented ID
+//        stands for all the names that it matches. It matches a
+//        permittedSubtrees constraint only if all of them do, i.e. if the
+//        labels after the wildcard label match it; for example,
+//        "*.example.com" matches "example.com" and ".example.com" but not
+//        "www.example.com". It matches an excludedSubtrees constraint if any
+//        of them does, so "*.example.com" also matches "www.example.com"
+//        there, but not ".www.example.com" or "a.b.example.com", since the
+//        wildcard only ever stands for one label.
+//
+//        The names that a wildcard matches are those that any relying party
+//        might match it with, not just the ones that webpki would: a
+//        constrained CA's exclusions must hold for every client of its
+//        certificates. So, regardless of `Policy`, a partial-label wildcard
+//        like "w*.example.com" matches an excludedSubtrees constraint of
+//        "www.example.com" and of "w.example.com", and "*.example.com"
+//        matches one of "xn--bcher-kva.example.com", even though webpki
+//        never matches those reference IDs with those presented IDs.
+//        Neither the policy nor the IDN rules can make a wildcard match
+//        more names, so they don't affect permittedSubtrees constraints.
 //
 // [0] RFC 6265 (Cookies) Domain Matching rules:
 //     http://tools.ietf.org/html/rfc6265#section-5.1.3
@@ -466,126 +1299,217 @@ fn general_name<'a>(input: &mut untrusted::Reader<'a>)
 //     incorporated into the spec:
 //     https://www.ietf.org/mail-archive/web/pkix/current/msg21192.html
 fn presented_dns_id_matches_reference_dns_id(
-        presented_dns_id: untrusted::Input, reference_dns_id_role: IDRole,
-        reference_dns_id: untrusted::Input) -> Option<bool> {
+        presented_dns_id: untrusted::Input, reference_dns_id: untrusted::Input,
+        policy: &Policy) -> Option<bool> {
     if !is_valid_dns_id(presented_dns_id, IDRole::PresentedID,
                         AllowWildcards::Yes) {
         return None;
     }
 
-    if !is_valid_dns_id(reference_dns_id, reference_dns_id_role,
+    if !is_valid_dns_id(reference_dns_id, IDRole::ReferenceID,
                         AllowWildcards::No) {
         return None;
     }
 
+    // Now that we know it is valid, treat an absolute presented DNS ID the
+    // same as the equivalent relative one.
+    let presented_dns_id = untrusted::Input::from(
+        without_trailing_dot(presented_dns_id.as_slice_less_safe()));
+
     let mut presented = untrusted::Reader::new(presented_dns_id);
     let mut reference = untrusted::Reader::new(reference_dns_id);
 
-    match reference_dns_id_role {
-        IDRole::ReferenceID => (),
+    // The first label is compared separately because it may be a wildcard
+    // label. `is_valid_dns_id` has already verified that no other label
+    // contains a wildcard.
+    let presented_label = read_label(&mut presented);
+    let reference_label = read_label(&mut reference);
+    if !presented_label_matches_reference_label(
+            presented_label.as_slice_less_safe(),
+            reference_label.as_slice_less_safe(), policy) {
+        return Some(false);
+    }
 
-        IDRole::NameConstraint
-            if presented_dns_id.len() > reference_dns_id.len() => {
+    while !presented.at_end() {
+        match (presented.read_byte(), reference.read_byte()) {
+            (Ok(p), Ok(r)) if ascii_lower(p) == ascii_lower(r) => (),
+            _ => { return Some(false); }
+        };
+    }
 
-            if reference_dns_id.len() == 0 {
-                // An empty constraint matches everything.
-                return Some(true);
-            }
+    // Allow a relative presented DNS ID to match an absolute reference DNS ID.
+    if !reference.at_end() {
+        match reference.read_byte() {
+            Ok(b'.') => (),
+            _ => { return Some(false); }
+        };
+        if !reference.at_end() {
+            return Some(false);
+        }
+    }
 
-            // If the reference ID starts with a dot then skip the prefix of
-            // the presented ID and start the comparison at the position of
-            // that dot. Examples:
-            //
-            //                                       Matches     Doesn't Match
-            //     -----------------------------------------------------------
-            //       original presented ID:  www.example.com    badexample.com
-            //                     skipped:  www                ba
-            //     presented ID w/o prefix:     .example.com      dexample.com
-            //                reference ID:     .example.com      .example.com
-            //
-            // If the reference ID does not start with a dot then we skip
-            // the prefix of the presented ID but also verify that the
-            // prefix ends with a dot. Examples:
-            //
-            //                                       Matches     Doesn't Match
-            //     -----------------------------------------------------------
-            //       original presented ID:  www.example.com    badexample.com
-            //                     skipped:  www                ba
-            //                 must be '.':     .                 d
-            //     presented ID w/o prefix:      example.com       example.com
-            //                reference ID:      example.com       example.com
-            //
-            if reference.peek(b'.') {
-                if presented.skip(presented_dns_id.len() -
-                                  reference_dns_id.len()).is_err() {
-                    unreachable!();
-                }
-            } else {
-                if presented.skip(presented_dns_id.len() -
-                                 reference_dns_id.len() - 1).is_err() {
-                    unreachable!();
-                }
-                if presented.read_byte() != Ok(b'.') {
-                    return Some(false);
-                }
-            }
-        },
+    assert!(presented.at_end());
+    assert!(reference.at_end());
+
+    Some(true)
+}
 
-        IDRole::NameConstraint => (),
+// Whether `presented_dns_id` is within the dNSName constraint `constraint`,
+// which is one of `subtrees`; see "Name constraints" above. Like
+// `presented_dns_id_matches_reference_dns_id`, the result is `None` if either
+// of them is malformed.
+fn presented_dns_id_matches_dns_name_constraint(
+        presented_dns_id: untrusted::Input, constraint: untrusted::Input,
+        subtrees: Subtrees) -> Option<bool> {
+    if !is_valid_dns_id(presented_dns_id, IDRole::PresentedID,
+                        AllowWildcards::Yes) {
+        return None;
+    }
 
-        IDRole::PresentedID => unreachable!()
+    // An absolute constraint means the same as the relative one; "." is the
+    // absolute form of "".
+    let constraint = without_trailing_dot(constraint.as_slice_less_safe());
+    if !is_valid_dns_id(untrusted::Input::from(constraint),
+                        IDRole::NameConstraint, AllowWildcards::No) {
+        return None;
     }
 
-    // Only allow wildcard labels that consist only of '*'.
-    if presented.peek(b'*') {
-        if presented.skip(1).is_err() {
-            unreachable!();
-        }
+    let presented = without_trailing_dot(presented_dns_id.as_slice_less_safe());
+    let (first_label, rest) =
+        match presented.iter().position(|&b| b == b'.') {
+            Some(dot) => (&presented[..dot], &presented[(dot + 1)..]),
+            None => (presented, &presented[..0]),
+        };
+    if !first_label.contains(&b'*') {
+        return Some(dns_name_is_within(presented, constraint));
+    }
 
-        loop {
-            if reference.read_byte().is_err() {
+    // Every name that the wildcard presented ID matches has at least one
+    // label before `rest`, so all of them are within the constraint if
+    // `rest` is within it without any leading dot. For an excludedSubtrees
+    // constraint, it is enough that one of them is; the only other way for
+    // that to happen is for the constraint to be one of them, e.g.
+    // "www.example.com" for "*.example.com".
+    let constraint_domain = match constraint.first() {
+        Some(&b'.') => &constraint[1..],
+        _ => constraint,
+    };
+    if dns_name_is_within(rest, constraint_domain) {
+        return Some(true);
+    }
+    match subtrees {
+        Subtrees::PermittedSubtrees => Some(false),
+        Subtrees::ExcludedSubtrees => {
+            if constraint.len() <= rest.len() + 1 ||
+               constraint[constraint.len() - rest.len() - 1] != b'.' ||
+               !ascii_eq_ignore_case(
+                   &constraint[(constraint.len() - rest.len())..], rest) {
                 return Some(false);
             }
-            if reference.peek(b'.') {
-                break;
-            }
-        }
+            let constraint_label =
+                &constraint[..(constraint.len() - rest.len() - 1)];
+            Some(!constraint_label.contains(&b'.') &&
+                 wildcard_label_may_match(first_label, constraint_label))
+        },
     }
+}
 
-    loop {
-        let presented_byte =
-            match (presented.read_byte(), reference.read_byte()) {
-                (Ok(p), Ok(r)) if p == r => p,
-                _ => { return Some(false); }
-            };
+// Whether any relying party might match the wildcard label `presented` with
+// `label`; see "Name constraints" above. This is deliberately broader than
+// `presented_label_matches_reference_label`: partial-label wildcards are
+// always allowed, the wildcard may match A-labels, and it may even match no
+// characters at all, as some implementations allow.
+fn wildcard_label_may_match(presented: &[u8], label: &[u8]) -> bool {
+    let wildcard_index = match presented.iter().position(|&b| b == b'*') {
+        Some(wildcard_index) => wildcard_index,
+        None => { return ascii_eq_ignore_case(presented, label); }
+    };
+    let prefix = &presented[..wildcard_index];
+    let suffix = &presented[(wildcard_index + 1)..];
+    label.len() >= prefix.len() + suffix.len() &&
+        ascii_eq_ignore_case(prefix, &label[..prefix.len()]) &&
+        ascii_eq_ignore_case(suffix, &label[(label.len() - suffix.len())..])
+}
 
-        if presented.at_end() {
-            // Don't allow presented IDs to be absolute.
-            if presented_byte == b'.' {
-                return None;
-            }
-            break;
-        }
+// Whether the valid relative DNS name `name` can be formed by adding zero or
+// more labels to the left of the valid relative DNSName constraint
+// `constraint`, or, if `constraint` starts with a dot, one or more labels.
+fn dns_name_is_within(name: &[u8], constraint: &[u8]) -> bool {
+    if constraint.is_empty() {
+        return true;
+    }
+    if name.len() < constraint.len() {
+        return false;
+    }
+    let suffix_start = name.len() - constraint.len();
+    if !ascii_eq_ignore_case(&name[suffix_start..], constraint) {
+        return false;
+    }
+    if constraint[0] == b'.' {
+        // `name` doesn't start with a dot, so at least one label precedes
+        // the suffix.
+        return suffix_start > 0;
     }
+    suffix_start == 0 || name[suffix_start - 1] == b'.'
+}
 
-    // Allow a relative presented DNS ID to match an absolute reference DNS ID,
-    // unless we're matching a name constraint.
-    if !reference.at_end() {
-        if reference_dns_id_role != IDRole::NameConstraint {
-            match reference.read_byte() {
-                Ok(b'.') => (),
-                _ => { return Some(false); }
-            };
-        }
-        if !reference.at_end() {
-            return Some(false);
-        }
+fn without_trailing_dot(dns_id: &[u8]) -> &[u8] {
+    match dns_id.last() {
+        Some(&b'.') => &dns_id[..(dns_id.len() - 1)],
+        _ => dns_id,
     }
+}
 
-    assert!(presented.at_end());
-    assert!(reference.at_end());
+// Reads up to, but not including, the next '.' or the end of the input.
+fn read_label<'b>(input: &mut untrusted::Reader<'b>) -> untrusted::Input<'b> {
+    let mark = input.mark();
+    while !input.at_end() && !input.peek(b'.') {
+        let _ = input.read_byte();
+    }
+    input.get_input_between_marks(mark, input.mark()).unwrap()
+}
+
+fn presented_label_matches_reference_label(presented: &[u8],
+                                           reference: &[u8], policy: &Policy)
+                                           -> bool {
+    let wildcard_index = match presented.iter().position(|&b| b == b'*') {
+        Some(wildcard_index) => wildcard_index,
+        None => { return ascii_eq_ignore_case(presented, reference); }
+    };
+
+    let prefix = &presented[..wildcard_index];
+    let suffix = &presented[(wildcard_index + 1)..];
+
+    let is_partial_label_wildcard = !prefix.is_empty() || !suffix.is_empty();
+    if is_partial_label_wildcard && !policy.allow_partial_label_wildcards {
+        return false;
+    }
+
+    // A wildcard never matches (part of) an IDN A-label.
+    if reference.len() >= 4 && ascii_eq_ignore_case(&reference[..4], b"xn--") {
+        return false;
+    }
 
-    return Some(true);
+    // The wildcard must match at least one character.
+    if reference.len() <= prefix.len() + suffix.len() {
+        return false;
+    }
+
+    ascii_eq_ignore_case(prefix, &reference[..prefix.len()]) &&
+        ascii_eq_ignore_case(suffix,
+                             &reference[(reference.len() - suffix.len())..])
+}
+
+pub fn ascii_eq_ignore_case(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() &&
+        a.iter().zip(b).all(|(&a, &b)| ascii_lower(a) == ascii_lower(b))
+}
+
+fn ascii_lower(b: u8) -> u8 {
+    match b {
+        b'A'...b'Z' => b + (b'a' - b'A'),
+        _ => b
+    }
 }
 
 #[derive(PartialEq)]
@@ -601,7 +1525,7 @@ enum IDRole {
   NameConstraint,
 }
 
-fn is_valid_reference_dns_id(hostname: untrusted::Input) -> bool {
+pub fn is_valid_reference_dns_id(hostname: untrusted::Input) -> bool {
     is_valid_dns_id(hostname, IDRole::ReferenceID, AllowWildcards::No)
 }
 
@@ -632,24 +1556,28 @@ fn is_valid_dns_id(hostname: untrusted::Input, id_role: IDRole,
     let mut label_is_all_numeric = false;
     let mut label_ends_with_hyphen = false;
 
-    // Only presented IDs are allowed to have wildcard labels. And, like
-    // Chromium, be stricter than RFC 6125 requires by insisting that a
-    // wildcard label consist only of '*'.
-    let is_wildcard = allow_wildcards == AllowWildcards::Yes &&
-                     input.peek(b'*');
-    let mut is_first_byte = !is_wildcard;
-    if is_wildcard {
-        if input.read_byte() != Ok(b'*') ||
-           input.read_byte() != Ok(b'.') {
-            return false;
-        }
-        dot_count += 1;
-    }
+    let mut is_wildcard = false;
+    let mut is_first_byte = true;
 
     loop {
         const MAX_LABEL_LENGTH: usize = 63;
 
         match input.read_byte() {
+            // Only presented IDs are allowed to have a wildcard, and only one,
+            // in the leftmost label. Whether a wildcard label other than "*"
+            // can match anything is decided by
+            // `presented_label_matches_reference_label`.
+            Ok(b'*') if allow_wildcards == AllowWildcards::Yes &&
+                        dot_count == 0 && !is_wildcard => {
+                is_wildcard = true;
+                label_is_all_numeric = false;
+                label_ends_with_hyphen = false;
+                label_length += 1;
+                if label_length > MAX_LABEL_LENGTH {
+                    return false;
+                }
+            },
+
             Ok(b'-') => {
                 if label_length == 0 {
                     return false; // Labels must not start with a hyphen.
@@ -703,9 +1631,9 @@ fn is_valid_dns_id(hostname: untrusted::Input, id_role: IDRole,
         }
     }
 
-    // Only reference IDs, not presented IDs or name constraints, may be
+    // Reference IDs and presented IDs, but not name constraints, may be
     // absolute.
-    if label_length == 0 && id_role != IDRole::ReferenceID {
+    if label_length == 0 && id_role == IDRole::NameConstraint {
         return false;
     }
 
@@ -744,3 +1672,429 @@ fn is_valid_dns_id(hostname: untrusted::Input, id_role: IDRole,
 
     true
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{DnsNameMatch, email_local_part_and_domain,
+                general_subtree_distances,
+                presented_dns_id_matches_dns_name_constraint,
+                presented_dns_id_matches_reference_dns_id,
+                presented_dns_id_matches_reference_id,
+                srv_name_service_and_domain, subject_alt_names,
+                SubjectAltName, Subtrees, uri_scheme_and_host,
+                user_principal_name};
+    use untrusted;
+    use {Der, Error, Policy};
+
+    const PRESENTED_MATCHES_REFERENCE: &'static [(&'static [u8],
+                                                  &'static [u8],
+                                                  Option<bool>)] = &[
+        (b"example.com", b"example.com", Some(true)),
+        (b"example.com", b"example.org", Some(false)),
+        (b"example.com", b"www.example.com", Some(false)),
+        (b"www.example.com", b"example.com", Some(false)),
+
+        // Case-insensitivity.
+        (b"EXAMPLE.COM", b"example.com", Some(true)),
+        (b"example.com", b"EXAMPLE.COM", Some(true)),
+        (b"ExAmPlE.CoM", b"eXaMpLe.cOm", Some(true)),
+        (b"*.EXAMPLE.com", b"www.example.COM", Some(true)),
+
+        // Absolute names.
+        (b"example.com", b"example.com.", Some(true)),
+        (b"example.com.", b"example.com", Some(true)),
+        (b"example.com.", b"example.com.", Some(true)),
+        (b"example.com.", b"example.org.", Some(false)),
+        (b"*.example.com.", b"www.example.com", Some(true)),
+        (b"example.com..", b"example.com", None),
+        (b".", b"example.com", None),
+
+        // Malformed presented identifiers are distinguished from mismatches.
+        (b"example\x00.com", b"example.com", None),
+        (b"example.com\x00", b"example.com", None),
+        (b"\x00", b"example.com", None),
+        (b"", b"example.com", None),
+        (b"exa mple.com", b"example.com", None),
+        (b"-example.com", b"example.com", None),
+
+        // Wildcards.
+        (b"*.example.com", b"www.example.com", Some(true)),
+        (b"*.example.com", b"example.com", Some(false)),
+        (b"*.example.com", b"www.sub.example.com", Some(false)),
+        (b"*.com", b"example.com", None),
+        (b"*.xn--example.com", b"www.xn--example.com", Some(true)),
+        (b"*.example.com", b"xn--www.example.com", Some(false)),
+        (b"xn--*.example.com", b"xn--www.example.com", None),
+        (b"**.example.com", b"www.example.com", None),
+        (b"*.*.example.com", b"www.sub.example.com", None),
+        (b"www.*.example.com", b"www.sub.example.com", None),
+        (b"www.example.*", b"www.example.com", None),
+    ];
+
+    // (presented, reference, result by default, result with partial-label
+    // wildcards allowed)
+    type PartialLabelWildcardCase =
+        (&'static [u8], &'static [u8], Option<bool>, Option<bool>);
+    const PARTIAL_LABEL_WILDCARDS: &'static [PartialLabelWildcardCase] = &[
+        (b"f*o.example.com", b"foo.example.com", Some(false), Some(true)),
+        (b"f*o.example.com", b"fooooo.example.com", Some(false), Some(true)),
+        (b"f*o.example.com", b"FOO.example.com", Some(false), Some(true)),
+        (b"f*o.example.com", b"fo.example.com", Some(false), Some(false)),
+        (b"f*o.example.com", b"bar.example.com", Some(false), Some(false)),
+        (b"f*.example.com", b"foo.example.com", Some(false), Some(true)),
+        (b"f*.example.com", b"f.example.com", Some(false), Some(false)),
+        (b"*o.example.com", b"foo.example.com", Some(false), Some(true)),
+        (b"*o.example.com", b"foo.sub.example.com", Some(false), Some(false)),
+        (b"x*.example.com", b"xn--abc.example.com", Some(false), Some(false)),
+        (b"f*o*.example.com", b"fooo.example.com", None, None),
+        (b"f*o.*.example.com", b"foo.sub.example.com", None, None),
+        (b"foo.b*r.example.com", b"foo.bar.example.com", None, None),
+        (b"*.example.com", b"www.example.com", Some(true), Some(true)),
+    ];
+
+    #[test]
+    fn presented_matches_reference_test() {
+        for &(presented, reference, expected_result) in
+                PRESENTED_MATCHES_REFERENCE {
+            let actual_result = presented_dns_id_matches_reference_dns_id(
+                untrusted::Input::from(presented),
+                untrusted::Input::from(reference), &Policy::default());
+            assert_eq!(expected_result, actual_result);
+        }
+    }
+
+    #[test]
+    fn partial_label_wildcards_test() {
+        let default_policy = Policy::default();
+        let partial_policy = Policy {
+            allow_partial_label_wildcards: true,
+            ..Policy::default()
+        };
+
+        for &(presented, reference, expected_by_default, expected_if_allowed)
+                in PARTIAL_LABEL_WILDCARDS {
+            let presented = untrusted::Input::from(presented);
+            let reference = untrusted::Input::from(reference);
+            assert_eq!(expected_by_default,
+                       presented_dns_id_matches_reference_dns_id(
+                            presented, reference, &default_policy));
+            assert_eq!(expected_if_allowed,
+                       presented_dns_id_matches_reference_dns_id(
+                            presented, reference, &partial_policy));
+        }
+    }
+
+    // (presented, reference, expected result with
+    // `Policy::allow_domain_suffix_reference_ids`).
+    const DOMAIN_SUFFIX_REFERENCE_IDS: &'static [(&'static [u8],
+                                                  &'static [u8],
+                                                  Option<bool>)] = &[
+        (b"api.internal.corp", b".internal.corp", Some(true)),
+        (b"API.Internal.Corp", b".internal.corp", Some(true)),
+        (b"api.internal.corp.", b".internal.corp", Some(true)),
+        (b"api.internal.corp", b".internal.corp.", Some(true)),
+        (b"*.internal.corp", b".internal.corp", Some(true)),
+        (b"a*.internal.corp", b".internal.corp", Some(false)),
+        (b"internal.corp", b".internal.corp", Some(false)),
+        (b"a.b.internal.corp", b".internal.corp", Some(false)),
+        (b"apiinternal.corp", b".internal.corp", Some(false)),
+        (b"api.internal.corp.evil", b".internal.corp", Some(false)),
+        (b"api..internal.corp", b".internal.corp", None),
+        // Reference IDs without a leading dot are matched as usual.
+        (b"api.internal.corp", b"api.internal.corp", Some(true)),
+        (b"*.internal.corp", b"api.internal.corp", Some(true)),
+        // A lone dot or two leading dots aren't domain-suffix reference IDs.
+        (b"api.internal.corp", b"..internal.corp", None),
+        (b"api.internal.corp", b".", None),
+    ];
+
+    #[test]
+    fn domain_suffix_reference_ids_test() {
+        let policy = Policy {
+            allow_domain_suffix_reference_ids: true,
+            ..Policy::default()
+        };
+        for &(presented, reference, expected_result) in
+                DOMAIN_SUFFIX_REFERENCE_IDS {
+            assert_eq!(expected_result,
+                       presented_dns_id_matches_reference_id(
+                           untrusted::Input::from(presented),
+                           untrusted::Input::from(reference), &policy));
+        }
+
+        // By default, a reference ID with a leading dot is invalid.
+        assert_eq!(None,
+                   presented_dns_id_matches_reference_id(
+                       untrusted::Input::from(b"api.internal.corp"),
+                       untrusted::Input::from(b".internal.corp"),
+                       &Policy::default()));
+    }
+
+    // (presented, constraint, expected result in permittedSubtrees, expected
+    // result in excludedSubtrees).
+    type DnsNameConstraintCase =
+        (&'static [u8], &'static [u8], Option<bool>, Option<bool>);
+    const DNS_NAME_CONSTRAINTS: &'static [DnsNameConstraintCase] = &[
+        (b"example.com", b"example.com", Some(true), Some(true)),
+        (b"www.example.com", b"example.com", Some(true), Some(true)),
+        (b"WWW.EXAMPLE.com", b"example.COM", Some(true), Some(true)),
+        (b"badexample.com", b"example.com", Some(false), Some(false)),
+        (b"example.org", b"example.com", Some(false), Some(false)),
+
+        // A leading dot matches only subdomains.
+        (b"example.com", b".example.com", Some(false), Some(false)),
+        (b"www.example.com", b".example.com", Some(true), Some(true)),
+        (b"a.b.example.com", b".example.com", Some(true), Some(true)),
+        (b"badexample.com", b".example.com", Some(false), Some(false)),
+
+        // Trailing dots are ignored on both sides.
+        (b"example.com", b"example.com.", Some(true), Some(true)),
+        (b"example.com.", b"example.com", Some(true), Some(true)),
+        (b"www.example.com.", b"example.com.", Some(true), Some(true)),
+        (b"www.example.com", b".example.com.", Some(true), Some(true)),
+        (b"example.com", b".example.com.", Some(false), Some(false)),
+
+        // "" and "." match everything.
+        (b"example.com", b"", Some(true), Some(true)),
+        (b"example.com", b".", Some(true), Some(true)),
+        (b"*.example.com", b".", Some(true), Some(true)),
+
+        // A wildcard matches a permittedSubtrees constraint only if all the
+        // names it matches do, and an excludedSubtrees constraint if any of
+        // them does.
+        (b"*.example.com", b"example.com", Some(true), Some(true)),
+        (b"*.example.com", b".example.com", Some(true), Some(true)),
+        (b"*.example.com.", b".example.com.", Some(true), Some(true)),
+        (b"*.a.example.com", b".example.com", Some(true), Some(true)),
+        (b"*.example.com", b"www.example.com", Some(false), Some(true)),
+        (b"*.example.com", b"WWW.EXAMPLE.COM.", Some(false), Some(true)),
+        (b"*.example.com", b".www.example.com", Some(false), Some(false)),
+        (b"*.example.com", b"a.b.example.com", Some(false), Some(false)),
+        (b"*.example.com", b"www.badexample.com", Some(false), Some(false)),
+        (b"*.a.example.com", b"www.example.com", Some(false), Some(false)),
+        // An excludedSubtrees constraint is matched by a wildcard if any
+        // relying party might match them, whatever the `Policy`: including
+        // with a partial-label wildcard, an A-label, or an empty match.
+        (b"w*.example.com", b"www.example.com", Some(false), Some(true)),
+        (b"w*.example.com", b"w.example.com", Some(false), Some(true)),
+        (b"*w.example.com", b"WWW.example.com", Some(false), Some(true)),
+        (b"f*o.example.com", b"bar.example.com", Some(false), Some(false)),
+        (b"w*.example.com", b".example.com", Some(true), Some(true)),
+        (b"*.example.com", b"xn--bcher-kva.example.com", Some(false),
+         Some(true)),
+
+        (b"example.com", b"..example.com", None, None),
+        (b"example.com", b"example.com..", None, None),
+        (b"example.com", b"*.example.com", None, None),
+        (b"example..com", b"example.com", None, None),
+    ];
+
+    #[test]
+    fn dns_name_constraints_test() {
+        for &(presented, constraint, expected_if_permitted,
+              expected_if_excluded) in DNS_NAME_CONSTRAINTS {
+            let presented = untrusted::Input::from(presented);
+            let constraint = untrusted::Input::from(constraint);
+            assert_eq!(expected_if_permitted,
+                       presented_dns_id_matches_dns_name_constraint(
+                           presented, constraint,
+                           Subtrees::PermittedSubtrees));
+            assert_eq!(expected_if_excluded,
+                       presented_dns_id_matches_dns_name_constraint(
+                           presented, constraint,
+                           Subtrees::ExcludedSubtrees));
+        }
+    }
+
+    fn dns_name_match<'a>(presented_id: &'a [u8]) -> DnsNameMatch<'a> {
+        DnsNameMatch { presented_id: untrusted::Input::from(presented_id) }
+    }
+
+    #[test]
+    fn dns_name_match_internationalized_test() {
+        assert!(!dns_name_match(b"www.example.com").is_internationalized());
+        assert!(!dns_name_match(b"xn--.example.com").is_internationalized());
+        assert!(dns_name_match(b"www.xn--bcher-kva.example")
+                    .is_internationalized());
+        assert!(dns_name_match(b"*.XN--BCHER-KVA.example")
+                    .is_internationalized());
+    }
+
+    #[cfg(feature = "idna")]
+    #[test]
+    fn dns_name_match_unicode_test() {
+        fn unicode(presented_id: &[u8]) -> Option<::std::string::String> {
+            dns_name_match(presented_id).presented_id_unicode()
+        }
+
+        assert_eq!(Some("www.example.com"),
+                   unicode(b"www.example.com").as_ref().map(|s| &s[..]));
+        assert_eq!(Some("*.bücher.example"),
+                   unicode(b"*.xn--bcher-kva.example").as_ref()
+                                                      .map(|s| &s[..]));
+        assert_eq!(Some("bücher.example"),
+                   unicode(b"XN--bcher-KVA.example").as_ref()
+                                                    .map(|s| &s[..]));
+        assert_eq!(None, unicode(b"xn--bcher-k.example"));
+    }
+
+    // (input, Some((first part, second part))) or (input, None) if the input
+    // can't be split.
+    type SplitCase = (&'static [u8], Option<(&'static [u8], &'static [u8])>);
+
+    // (uri, Some((scheme, host))) or (uri, None) if unsupported.
+    const URI_SCHEME_AND_HOST: &'static [SplitCase] = &[
+        (b"sip://example.com", Some((b"sip", b"example.com"))),
+        (b"SIP://Example.com:5060/x?y#z", Some((b"SIP", b"Example.com"))),
+        (b"xmpp+s.1-a://example.com/", Some((b"xmpp+s.1-a", b"example.com"))),
+        (b"https://example.com:", Some((b"https", b"example.com"))),
+        (b"https://example.com?q", Some((b"https", b"example.com"))),
+        (b"https://", Some((b"https", b""))),
+        (b"https://user@example.com", None),
+        (b"https://example.com:44x3", None),
+        (b"https:example.com", None),
+        (b"https:/example.com", None),
+        (b"1https://example.com", None),
+        (b"://example.com", None),
+        (b"example.com", None),
+        (b"", None),
+    ];
+
+    // (srv_name, Some((service, domain))) or (srv_name, None) if malformed.
+    const SRV_NAME_SERVICE_AND_DOMAIN: &'static [SplitCase] = &[
+        (b"_xmpp-server.example.com", Some((b"xmpp-server", b"example.com"))),
+        (b"_SIP.Example.com", Some((b"SIP", b"Example.com"))),
+        (b"_sip.", Some((b"sip", b""))),
+        (b"_.example.com", None),
+        (b"_s_p.example.com", None),
+        (b"sip.example.com", None),
+        (b"_sip", None),
+        (b"", None),
+    ];
+
+    #[test]
+    fn srv_name_service_and_domain_test() {
+        for &(srv_name, expected) in SRV_NAME_SERVICE_AND_DOMAIN {
+            let actual =
+                srv_name_service_and_domain(untrusted::Input::from(srv_name))
+                    .map(|(service, domain)| (service.as_slice_less_safe(),
+                                              domain.as_slice_less_safe()));
+            assert_eq!(expected, actual);
+        }
+    }
+
+    // (email, Some((local_part, domain))) or (email, None) if malformed.
+    const EMAIL_LOCAL_PART_AND_DOMAIN: &'static [SplitCase] = &[
+        (b"vpn@example.com", Some((b"vpn", b"example.com"))),
+        (b"VPN+gw@Example.com", Some((b"VPN+gw", b"Example.com"))),
+        (b"\"a@b\"@example.com", Some((b"\"a@b\"", b"example.com"))),
+        (b"vpn@", Some((b"vpn", b""))),
+        (b"@example.com", None),
+        (b"v n@example.com", None),
+        (b"example.com", None),
+        (b"", None),
+    ];
+
+    #[test]
+    fn email_local_part_and_domain_test() {
+        for &(email, expected) in EMAIL_LOCAL_PART_AND_DOMAIN {
+            let actual =
+                email_local_part_and_domain(untrusted::Input::from(email))
+                    .map(|(local_part, domain)|
+                         (local_part.as_slice_less_safe(),
+                          domain.as_slice_less_safe()));
+            assert_eq!(expected, actual);
+        }
+    }
+
+    #[test]
+    fn user_principal_name_test() {
+        // The contents of an otherName with the given last byte of the type-id
+        // and tag of the value, with the value "a@b".
+        fn other_name(type_id_last: u8, value_tag: u8) -> [u8; 19] {
+            [0x06, 0x0a, 0x2b, 0x06, 0x01, 0x04, 0x01, 0x82, 0x37, 0x14,
+             0x02, type_id_last,
+             0xa0, 0x05, value_tag, 0x03, b'a', b'@', b'b']
+        }
+        let upn = other_name(3, 0x0c);
+        assert_eq!(Some(&b"a@b"[..]),
+                   user_principal_name(untrusted::Input::from(&upn))
+                       .map(|upn| upn.as_slice_less_safe()));
+        let not_upn = other_name(4, 0x0c);
+        assert!(user_principal_name(untrusted::Input::from(&not_upn))
+                    .is_none());
+        let not_utf8 = other_name(3, 0x16);
+        assert!(user_principal_name(untrusted::Input::from(&not_utf8))
+                    .is_none());
+    }
+
+    // The encoded `minimum` and `maximum` fields of a `GeneralSubtree`, and
+    // the expected result.
+    const GENERAL_SUBTREE_DISTANCES: &'static [(&'static [u8],
+                                                Result<(), Error>)] = &[
+        (b"", Ok(())),
+        (b"\x80\x01\x01", Err(Error::ExtensionValueInvalid)),
+        (b"\x80\x02\x00\x80", Err(Error::ExtensionValueInvalid)),
+        (b"\x81\x01\x00", Err(Error::ExtensionValueInvalid)),
+        (b"\x80\x01\x01\x81\x01\x02", Err(Error::ExtensionValueInvalid)),
+
+        // DER doesn't allow the DEFAULT minimum to be encoded.
+        (b"\x80\x01\x00", Err(Error::BadDER)),
+        (b"\x80\x01\x00\x81\x01\x02", Err(Error::BadDER)),
+
+        // Malformed `BaseDistance`s.
+        (b"\x80\x00", Err(Error::BadDER)),
+        (b"\x80\x01\xff", Err(Error::BadDER)),
+        (b"\x81\x02\x00\x01", Err(Error::BadDER)),
+        (b"\x80\x01", Err(Error::BadDER)),
+    ];
+
+    #[test]
+    fn general_subtree_distances_test() {
+        for &(encoded, expected) in GENERAL_SUBTREE_DISTANCES {
+            let actual = untrusted::Input::from(encoded).read_all(
+                Error::BadDER, |subtree| general_subtree_distances(subtree));
+            assert_eq!(expected, actual);
+        }
+    }
+
+    #[test]
+    fn subject_alt_names_test() {
+        // A registeredID (1.2.3), an unknown tag [9], and a dNSName.
+        const NAMES: &'static [u8] = &[
+            0x88, 0x02, 0x2a, 0x03,
+            0xa9, 0x02, 0x05, 0x00,
+            0x82, 0x01, b'a',
+        ];
+        let names = subject_alt_names(Some(untrusted::Input::from(NAMES)))
+            .unwrap().collect::<::std::vec::Vec<_>>();
+        assert_eq!(&[
+            SubjectAltName::Other { tag: 0x88, value: Der::from(&NAMES[2..4]) },
+            SubjectAltName::Other { tag: 0xa9, value: Der::from(&NAMES[6..8]) },
+            SubjectAltName::DnsName(Der::from(&NAMES[10..])),
+        ], &names[..]);
+
+        assert_eq!(0, subject_alt_names(None).unwrap().count());
+
+        // A constructed dNSName, and a universal tag.
+        const MALFORMED: &'static [&'static [u8]] = &[
+            &[0xa2, 0x03, 0x04, 0x01, b'a'],
+            &[0x04, 0x01, b'a'],
+        ];
+        for &names in MALFORMED {
+            assert_eq!(Some(Error::BadDER),
+                       subject_alt_names(Some(untrusted::Input::from(names)))
+                           .err());
+        }
+    }
+
+    #[test]
+    fn uri_scheme_and_host_test() {
+        for &(uri, expected) in URI_SCHEME_AND_HOST {
+            let actual = uri_scheme_and_host(untrusted::Input::from(uri))
+                .map(|(scheme, host)| (scheme.as_slice_less_safe(),
+                                       host.as_slice_less_safe()));
+            assert_eq!(expected, actual);
+        }
+    }
+}
@@ -0,0 +1,396 @@
+// Copyright 2016 Brian Smith.
+//
+// Permission to use, copy, modify, and/or distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHORS DISCLAIM ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHORS BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+//! Inspecting and generating name constraints.
+//!
+//! ```ASN.1
+//! NameConstraints ::= SEQUENCE {
+//!      permittedSubtrees       [0]     GeneralSubtrees OPTIONAL,
+//!      excludedSubtrees        [1]     GeneralSubtrees OPTIONAL }
+//!
+//! GeneralSubtrees ::= SEQUENCE SIZE (1..MAX) OF GeneralSubtree
+//!
+//! GeneralSubtree ::= SEQUENCE {
+//!      base                    GeneralName,
+//!      minimum         [0]     BaseDistance DEFAULT 0,
+//!      maximum         [1]     BaseDistance OPTIONAL }
+//! ```
+//!
+//! Like everywhere else in webpki, name constraints are represented by the
+//! value of the `NameConstraints` `SEQUENCE`, without its tag and length; this
+//! is the form used by `TrustAnchor::name_constraints`.
+
+use {cert, der, Error, Policy};
+use name::{self, DIRECTORY_NAME_TAG, DNS_NAME_TAG, IP_ADDRESS_TAG};
+use untrusted;
+
+#[cfg(feature = "std")]
+use {der_writer, std};
+
+/// Parsed name constraints.
+#[derive(Clone, Copy, Debug)]
+pub struct NameConstraints<'a> {
+    permitted_subtrees: Option<untrusted::Input<'a>>,
+    excluded_subtrees: Option<untrusted::Input<'a>>,
+}
+
+impl<'a> NameConstraints<'a> {
+    /// Parses the value of a DER-encoded `NameConstraints`, e.g. the value of
+    /// `TrustAnchor::name_constraints`.
+    ///
+    /// Every subtree is parsed, so iterating over the subtrees of the result
+    /// can't fail.
+    pub fn from(value: &'a [u8]) -> Result<NameConstraints<'a>, Error> {
+        let value = untrusted::Input::from(value);
+        let name_constraints = try!(value.read_all(Error::BadDER, |input| {
+            let permitted_subtrees = try!(parse_subtrees(
+                input, der::Tag::ContextSpecificConstructed0));
+            let excluded_subtrees = try!(parse_subtrees(
+                input, der::Tag::ContextSpecificConstructed1));
+            Ok(NameConstraints {
+                permitted_subtrees: permitted_subtrees,
+                excluded_subtrees: excluded_subtrees,
+            })
+        }));
+
+        for subtrees in [name_constraints.permitted_subtrees,
+                         name_constraints.excluded_subtrees].iter() {
+            if let Some(subtrees) = *subtrees {
+                try!(subtrees.read_all(Error::BadDER, |input| {
+                    while !input.at_end() {
+                        let _ = try!(general_subtree(input));
+                    }
+                    Ok(())
+                }));
+            }
+        }
+
+        Ok(name_constraints)
+    }
+
+    /// The `permittedSubtrees`, in the order they are encoded. If there are
+    /// any subtrees of a given type then every name of that type must be
+    /// within at least one of them.
+    pub fn permitted_subtrees(&self) -> GeneralSubtrees<'a> {
+        GeneralSubtrees::new(self.permitted_subtrees)
+    }
+
+    /// The `excludedSubtrees`, in the order they are encoded. No name may be
+    /// within any of them.
+    pub fn excluded_subtrees(&self) -> GeneralSubtrees<'a> {
+        GeneralSubtrees::new(self.excluded_subtrees)
+    }
+
+    /// Checks the names of the ASN.1 DER-encoded X.509 certificate
+    /// `cert_der` against the name constraints, the same way they are checked
+    /// when the name constraints are those of the certificate's issuer.
+    ///
+    /// Both the end-entity and CA certificates can be checked; for example,
+    /// an auditor can check that a sub-CA's own names are within the
+    /// constraints of its parent. The certificate is parsed, but it is NOT
+    /// otherwise validated.
+    ///
+    /// A wildcard dNSName, e.g. `*.example.com`, is within a permitted
+    /// subtree only if every name it may match is, and within an excluded
+    /// subtree if any name it may match is, according to the most lenient
+    /// wildcard matching rules of any relying party: e.g. an excluded
+    /// `www.example.com` rejects `w*.example.com` whether or not the
+    /// `Policy` allows partial-label wildcards. So the result only depends
+    /// on the `Policy`'s limits on parsing the certificate.
+    pub fn check_cert(&self, policy: &Policy, cert_der: &[u8])
+                      -> Result<(), Error> {
+        let cert = try!(cert::parse_cert(untrusted::Input::from(cert_der),
+                                         cert::EndEntityOrCA::EndEntity,
+                                         policy));
+        name::check_subtrees(self.permitted_subtrees, self.excluded_subtrees,
+                             &cert)
+    }
+}
+
+/// The `base` of a `GeneralSubtree`.
+///
+/// The values are the values of the corresponding `GeneralName` alternative,
+/// without their tags and lengths.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GeneralSubtree<'a> {
+    /// A DNS name constraint, e.g. `example.com`.
+    DnsName(&'a [u8]),
+
+    /// A directory name constraint: the value of a DER-encoded `Name`.
+    DirectoryName(&'a [u8]),
+
+    /// An IP address range, e.g. 192.0.2.0/24 is the address
+    /// `[192, 0, 2, 0]` with the mask `[255, 255, 255, 0]`. The address and
+    /// the mask are both either 4 bytes (IPv4) or 16 bytes (IPv6) long.
+    IpAddress {
+        /// The address, in network byte order.
+        address: &'a [u8],
+
+        /// The mask, in network byte order.
+        mask: &'a [u8],
+    },
+
+    /// A constraint of a type that webpki doesn't interpret. webpki rejects
+    /// any certificate containing a name of the same type. `tag` is the tag
+    /// of the `GeneralName` alternative.
+    Other {
+        /// The tag of the `GeneralName` alternative.
+        tag: u8,
+
+        /// The value of the `GeneralName` alternative.
+        value: &'a [u8],
+    },
+}
+
+/// An iterator over `GeneralSubtrees`.
+pub struct GeneralSubtrees<'a> {
+    reader: Option<untrusted::Reader<'a>>,
+}
+
+impl<'a> GeneralSubtrees<'a> {
+    fn new(subtrees: Option<untrusted::Input<'a>>) -> GeneralSubtrees<'a> {
+        GeneralSubtrees { reader: subtrees.map(untrusted::Reader::new) }
+    }
+}
+
+impl<'a> Iterator for GeneralSubtrees<'a> {
+    type Item = GeneralSubtree<'a>;
+
+    fn next(&mut self) -> Option<GeneralSubtree<'a>> {
+        match self.reader {
+            Some(ref mut reader) if !reader.at_end() =>
+                // The subtrees were already parsed successfully by
+                // `NameConstraints::from`.
+                general_subtree(reader).ok(),
+            _ => None
+        }
+    }
+}
+
+fn parse_subtrees<'a>(input: &mut untrusted::Reader<'a>, subtrees_tag: der::Tag)
+                      -> Result<Option<untrusted::Input<'a>>, Error> {
+    if !input.peek(subtrees_tag as u8) {
+        return Ok(None);
+    }
+    // See `name::check_name_constraints`.
+    let subtrees = try!(der::expect_tag_and_get_value(input, subtrees_tag));
+    // `SIZE (1..MAX)`.
+    if subtrees.len() == 0 {
+        return Err(Error::BadDER);
+    }
+    Ok(Some(subtrees))
+}
+
+// Like `verify_cert`, we don't accept `minimum` or `maximum`; see
+// `name::general_subtree_distances`.
+fn general_subtree<'a>(input: &mut untrusted::Reader<'a>)
+                       -> Result<GeneralSubtree<'a>, Error> {
+    der::nested(input, der::Tag::Sequence, Error::BadDER, |subtree| {
+        let (tag, value) = try!(der::read_tag_and_get_value(subtree));
+        let value = value.as_slice_less_safe();
+        let base = match tag {
+            DNS_NAME_TAG => GeneralSubtree::DnsName(value),
+            DIRECTORY_NAME_TAG => GeneralSubtree::DirectoryName(value),
+            IP_ADDRESS_TAG => {
+                if value.len() != 8 && value.len() != 32 {
+                    return Err(Error::BadDER);
+                }
+                let (address, mask) = value.split_at(value.len() / 2);
+                GeneralSubtree::IpAddress { address: address, mask: mask }
+            },
+            _ => GeneralSubtree::Other { tag: tag, value: value },
+        };
+        try!(name::general_subtree_distances(subtree));
+        Ok(base)
+    })
+}
+
+/// Encodes name constraints with the given subtrees, in the form used by
+/// `TrustAnchor::name_constraints`. Either list may be empty, in which case
+/// the corresponding field is omitted.
+///
+/// This is the inverse of `NameConstraints::from`, so it can be used to
+/// generate name constraints for trust anchors, e.g. in a build script.
+///
+/// Returns `Error::DERElementTooLong` if the subtrees of either list are
+/// together longer than `MAX_DER_ELEMENT_LEN` when encoded, since
+/// `NameConstraints::from` wouldn't accept the result.
+///
+/// Panics if an `IpAddress` subtree's address and mask don't both have length
+/// 4 or both have length 16.
+#[cfg(feature = "std")]
+pub fn encode(permitted_subtrees: &[GeneralSubtree],
+              excluded_subtrees: &[GeneralSubtree])
+              -> Result<std::vec::Vec<u8>, Error> {
+    fn encode_subtrees(out: &mut der_writer::Output, tag: der::Tag,
+                       subtrees: &[GeneralSubtree]) -> Result<(), Error> {
+        if subtrees.is_empty() {
+            return Ok(());
+        }
+        der_writer::write_nested(out, tag as u8, |out| {
+            for subtree in subtrees {
+                try!(der_writer::write_sequence(out, |out| match *subtree {
+                    GeneralSubtree::DnsName(value) =>
+                        der_writer::write_tlv(out, DNS_NAME_TAG, value),
+                    GeneralSubtree::DirectoryName(value) =>
+                        der_writer::write_tlv(out, DIRECTORY_NAME_TAG, value),
+                    GeneralSubtree::IpAddress { address, mask } => {
+                        assert!(address.len() == mask.len() &&
+                                (address.len() == 4 || address.len() == 16));
+                        let mut value = address.to_vec();
+                        value.extend_from_slice(mask);
+                        der_writer::write_tlv(out, IP_ADDRESS_TAG, &value)
+                    },
+                    GeneralSubtree::Other { tag, value } =>
+                        der_writer::write_tlv(out, tag, value),
+                }));
+            }
+            Ok(())
+        })
+    }
+
+    let mut out = std::vec::Vec::new();
+    try!(encode_subtrees(&mut out, der::Tag::ContextSpecificConstructed0,
+                         permitted_subtrees));
+    try!(encode_subtrees(&mut out, der::Tag::ContextSpecificConstructed1,
+                         excluded_subtrees));
+    Ok(out)
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::{encode, GeneralSubtree, NameConstraints};
+    use std;
+    use Error;
+
+    #[test]
+    fn test_encode_and_parse() {
+        let permitted = [
+            GeneralSubtree::DnsName(b"example.com"),
+            GeneralSubtree::IpAddress { address: &[192, 0, 2, 0],
+                                        mask: &[255, 255, 255, 0] },
+        ];
+        let excluded = [
+            GeneralSubtree::DnsName(b"bad.example.com"),
+            GeneralSubtree::Other { tag: 0x81, value: b"example.com" },
+        ];
+
+        let cases: [(&[GeneralSubtree], &[GeneralSubtree]); 4] = [
+            (&permitted, &excluded),
+            (&permitted, &[]),
+            (&[], &excluded),
+            (&[], &[]),
+        ];
+        for &(permitted, excluded) in cases.iter() {
+            let encoded = encode(permitted, excluded).unwrap();
+            let parsed = NameConstraints::from(&encoded).unwrap();
+            assert_eq!(permitted,
+                       &parsed.permitted_subtrees()
+                              .collect::<std::vec::Vec<_>>()[..]);
+            assert_eq!(excluded,
+                       &parsed.excluded_subtrees()
+                              .collect::<std::vec::Vec<_>>()[..]);
+        }
+    }
+
+    // ecdsa-wildcard-ee has the dNSNames www.example.net, mail.example.net,
+    // example.org, example.com, and *.example.com.
+    #[cfg(feature = "ecdsa")]
+    #[test]
+    fn test_check_cert_wildcard() {
+        use Policy;
+
+        let ee: &[u8] = include_bytes!("../tests/data/ecdsa-wildcard-ee.der");
+        let check = |permitted: &[GeneralSubtree],
+                     excluded: &[GeneralSubtree]| {
+            let encoded = encode(permitted, excluded).unwrap();
+            NameConstraints::from(&encoded).unwrap()
+                .check_cert(&Policy::default(), ee)
+        };
+        let permitted = [
+            GeneralSubtree::DnsName(b"example.com"),
+            GeneralSubtree::DnsName(b"example.net"),
+            GeneralSubtree::DnsName(b"example.org"),
+        ];
+
+        assert_eq!(Ok(()), check(&permitted, &[]));
+        assert_eq!(Err(Error::NameConstraintViolation),
+                   check(&[GeneralSubtree::DnsName(b"www.example.com"),
+                           GeneralSubtree::DnsName(b"example.net"),
+                           GeneralSubtree::DnsName(b"example.org")], &[]));
+
+        // Any name that *.example.com may match is excluded, including an
+        // IDN that webpki itself would never match it with.
+        let excluded: [&[u8]; 3] = [
+            b"www.example.com",
+            b"xn--bcher-kva.example.com",
+            b".example.com",
+        ];
+        for excluded in excluded.iter() {
+            assert_eq!(Err(Error::NameConstraintViolation),
+                       check(&permitted,
+                             &[GeneralSubtree::DnsName(excluded)]));
+        }
+
+        // *.example.com can't match a name with more than one label before
+        // example.com.
+        let not_excluded: [&[u8]; 2] =
+            [b"a.b.example.com", b".www.example.com"];
+        for excluded in not_excluded.iter() {
+            assert_eq!(Ok(()),
+                       check(&permitted,
+                             &[GeneralSubtree::DnsName(excluded)]));
+        }
+    }
+
+    #[test]
+    fn test_encode_too_long() {
+        let long_name = [b'a'; 0x8000];
+        let ok = [GeneralSubtree::DnsName(&long_name)];
+        assert!(encode(&ok, &ok).is_ok());
+        let too_long = [GeneralSubtree::DnsName(&long_name),
+                        GeneralSubtree::DnsName(&long_name)];
+        assert_eq!(Err(Error::DERElementTooLong), encode(&too_long, &[]));
+        assert_eq!(Err(Error::DERElementTooLong), encode(&[], &too_long));
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        let invalid: [&[u8]; 4] = [
+            // Empty permittedSubtrees.
+            &[0xa0, 0x00],
+            // excludedSubtrees before permittedSubtrees.
+            &[0xa1, 0x04, 0x30, 0x02, 0x82, 0x00,
+              0xa0, 0x04, 0x30, 0x02, 0x82, 0x00],
+            // An IP address constraint that is too short.
+            &[0xa0, 0x06, 0x30, 0x04, 0x87, 0x02, 0x0a, 0xff],
+            // A subtree with `minimum`.
+            &[0xa0, 0x07, 0x30, 0x05, 0x82, 0x00, 0x80, 0x01, 0x00],
+        ];
+        for value in invalid.iter() {
+            assert_eq!(Error::BadDER,
+                       NameConstraints::from(value).unwrap_err());
+        }
+
+        // Subtrees with a non-zero `minimum` and with a `maximum`.
+        let unsupported: [&[u8]; 2] = [
+            &[0xa0, 0x07, 0x30, 0x05, 0x82, 0x00, 0x80, 0x01, 0x01],
+            &[0xa1, 0x07, 0x30, 0x05, 0x82, 0x00, 0x81, 0x01, 0x00],
+        ];
+        for value in unsupported.iter() {
+            assert_eq!(Error::ExtensionValueInvalid,
+                       NameConstraints::from(value).unwrap_err());
+        }
+    }
+}
@@ -0,0 +1,1040 @@
+// Copyright 2016 Brian Smith.
+//
+// Permission to use, copy, modify, and/or distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHORS DISCLAIM ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHORS BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+//! Online Certificate Status Protocol (OCSP) support.
+//!
+//! See [RFC 6960](https://tools.ietf.org/html/rfc6960) and the lightweight
+//! profile of it in [RFC 5019](https://tools.ietf.org/html/rfc5019).
+//!
+//! Nothing here allocates: requests are written to a fixed-size buffer and
+//! responses are parsed in place, so stapled responses can be verified
+//! without the `std` feature, e.g. by embedded TLS clients. Responses longer
+//! than `MAX_RESPONSE_LEN` are rejected, so a buffer of that size is always
+//! enough to receive one.
+
+use {cert, constant_time, der, der_writer, Der, EndEntityCert, Error, Policy,
+     SignatureAlgorithm, time, TrustAnchor};
+use cert::EndEntityOrCA;
+use revocation::{CertStatus, RevocationInfo};
+use ring::digest;
+use signed_data;
+use untrusted;
+use verify_cert;
+
+const SHA1_OUTPUT_LEN: usize = 20;
+
+// The value of the `AlgorithmIdentifier` { id-sha1, NULL } of a `CertID`, RFC
+// 3279 Section 2.1.
+const SHA1_ALGORITHM_ID: &'static [u8] =
+    &[0x06, 0x05, 0x2b, 0x0e, 0x03, 0x02, 0x1a, 0x05, 0x00];
+
+/// An OCSP `CertID`, which identifies the certificate whose status is being
+/// requested or reported.
+///
+/// ```ASN.1
+/// CertID ::= SEQUENCE {
+///     hashAlgorithm       AlgorithmIdentifier,
+///     issuerNameHash      OCTET STRING, -- Hash of issuer's DN
+///     issuerKeyHash       OCTET STRING, -- Hash of issuer's public key
+///     serialNumber        CertificateSerialNumber }
+/// ```
+///
+/// The hash algorithm is always SHA-1, as RFC 5019 Section 2.1.1 requires;
+/// in practice, many OCSP responders don't support anything else.
+///
+/// `CertId`s are compared and hashed by value, so they can be used as the
+/// keys of a `revocation::OcspCache`.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct CertId<'a> {
+    issuer_name_hash: [u8; SHA1_OUTPUT_LEN],
+    issuer_key_hash: [u8; SHA1_OUTPUT_LEN],
+    serial_number: Der<'a>,
+}
+
+impl<'a> CertId<'a> {
+    /// The SHA-1 hash of the DER encoding of the certificate's `issuer`
+    /// field, including its tag and length.
+    pub fn issuer_name_hash(&self) -> &[u8] { &self.issuer_name_hash }
+
+    /// The SHA-1 hash of the value of the `subjectPublicKey` `BIT STRING` of
+    /// the issuer's certificate, excluding the tag, length, and number of
+    /// unused bits.
+    pub fn issuer_key_hash(&self) -> &[u8] { &self.issuer_key_hash }
+
+    /// The certificate's serial number, in the same form as
+    /// `EndEntityCert::serial_number`.
+    pub fn serial_number(&self) -> Der<'a> { self.serial_number }
+}
+
+/// Computes the `CertID` of `cert`, given the ASN.1 DER-encoded X.509
+/// certificate of its issuer, `issuer_cert_der`.
+///
+/// The issuer's certificate is not validated in any way other than checking
+/// that its subject is `cert`'s issuer; if it isn't, `Error::UnknownIssuer`
+/// is returned.
+pub fn cert_id<'a>(cert: &EndEntityCert<'a>, issuer_cert_der: &[u8])
+                   -> Result<CertId<'a>, Error> {
+    let cert = &cert.inner;
+    let issuer = try!(cert::parse_cert(untrusted::Input::from(issuer_cert_der),
+                                       EndEntityOrCA::CA(cert),
+                                       &Policy::default()));
+    if issuer.subject != cert.issuer {
+        return Err(Error::UnknownIssuer);
+    }
+
+    Ok(CertId {
+        issuer_name_hash: sha1_of_sequence(cert.issuer),
+        issuer_key_hash: try!(key_hash(&issuer)),
+        serial_number: der::from_input(cert.serial),
+    })
+}
+
+// The SHA-1 hash of the value of the `subjectPublicKey` `BIT STRING` of
+// `cert`, as used in `CertID` and in the `byKey` form of `ResponderID`.
+fn key_hash(cert: &cert::Cert) -> Result<[u8; SHA1_OUTPUT_LEN], Error> {
+    let key = try!(cert.spki.read_all(Error::BadDER, |spki| {
+        let _algorithm =
+            try!(der::expect_tag_and_get_value(spki, der::Tag::Sequence));
+        der::bit_string_with_no_unused_bits(spki)
+    }));
+    Ok(sha1(key.as_slice_less_safe()))
+}
+
+fn sha1_of_sequence(value: untrusted::Input) -> [u8; SHA1_OUTPUT_LEN] {
+    sha1_output(der::digest_of_sequence(&digest::SHA1, value))
+}
+
+fn sha1(value: &[u8]) -> [u8; SHA1_OUTPUT_LEN] {
+    sha1_output(digest::digest(&digest::SHA1, value))
+}
+
+fn sha1_output(digest: digest::Digest) -> [u8; SHA1_OUTPUT_LEN] {
+    let mut result = [0u8; SHA1_OUTPUT_LEN];
+    for (r, d) in result.iter_mut().zip(digest.as_ref()) {
+        *r = *d;
+    }
+    result
+}
+
+/// Verifies that `responder_cert` is a valid delegated OCSP responder
+/// certificate for responses about certificates issued by the CA whose
+/// ASN.1 DER-encoded X.509 certificate is `issuer_cert_der`, as RFC 6960
+/// Section 4.2.2.2 requires.
+///
+/// The responder certificate must be an end-entity certificate issued
+/// directly by the issuer and it must explicitly allow id-kp-OCSPSigning in
+/// its extended key usage extension; the issuer is not required to allow
+/// id-kp-OCSPSigning. The issuer's certificate is not validated in any way
+/// other than checking that it can be parsed, so it must be the certificate
+/// of a CA that the caller has already verified, e.g. the issuer in the
+/// verified path of the certificate whose status is being checked.
+///
+/// The caller must still check the responder certificate's revocation
+/// status unless `responder_cert_has_no_check` returns `true`.
+pub fn verify_delegated_responder_cert(
+        responder_cert: &EndEntityCert, issuer_cert_der: &[u8],
+        supported_sig_algs: &[&SignatureAlgorithm], time: time::Time)
+        -> Result<(), Error> {
+    let policy = Policy::default();
+    let issuer = try!(cert::parse_cert(untrusted::Input::from(issuer_cert_der),
+                                       EndEntityOrCA::TargetCA, &policy));
+    let issuer = [TrustAnchor {
+        subject: issuer.subject.as_slice_less_safe(),
+        spki: issuer.spki.as_slice_less_safe(),
+        name_constraints:
+            issuer.name_constraints.map(|nc| nc.as_slice_less_safe()),
+    }];
+    let inputs = verify_cert::ChainInputs {
+        policy: &policy,
+        required_eku_if_present: verify_cert::EKU_OCSP_SIGNING,
+        supported_sig_algs: supported_sig_algs,
+        trust_anchors: &issuer,
+        intermediate_certs: &[],
+        times: verify_cert::VerificationTimes::at(time),
+    };
+    verify_cert::build_chain(&inputs, &responder_cert.inner, None,
+                             &verify_cert::NoTrace)
+        .map(|_| ())
+}
+
+/// Returns `true` if `responder_cert` has the id-pkix-ocsp-nocheck
+/// extension, which means that, as a delegated OCSP responder certificate,
+/// it doesn't need to be checked for revocation (RFC 6960 Section
+/// 4.2.2.2.1). Such certificates are usually short-lived.
+pub fn responder_cert_has_no_check(responder_cert: &EndEntityCert) -> bool {
+    responder_cert.inner.ocsp_no_check
+}
+
+/// The `ResponderID` of an OCSP response, which identifies the certificate
+/// whose key signed the response: either the issuer of the certificate whose
+/// status is reported or a delegated responder.
+///
+/// ```ASN.1
+/// ResponderID ::= CHOICE {
+///    byName   [1] Name,
+///    byKey    [2] KeyHash }
+///
+/// KeyHash ::= OCTET STRING -- SHA-1 hash of responder's public key
+///                          -- (excluding the tag and length fields)
+/// ```
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ResponderId<'a> {
+    /// The responder's subject name, in the same form as
+    /// `TrustAnchor::subject` and `CaCert::subject`: the value of the `Name`
+    /// `SEQUENCE` without its tag and length.
+    ByName(Der<'a>),
+
+    /// The SHA-1 hash of the value of the responder's `subjectPublicKey`
+    /// `BIT STRING`, excluding the tag, length, and number of unused bits,
+    /// in the same form as `CertId::issuer_key_hash`.
+    ByKey(Der<'a>),
+}
+
+impl<'a> ResponderId<'a> {
+    /// Parses the DER-encoded `ResponderID` `responder_id`, including its
+    /// context-specific tag, as it appears in the `responderID` field of a
+    /// `ResponseData`.
+    pub fn from(responder_id: &'a [u8]) -> Result<ResponderId<'a>, Error> {
+        const BY_NAME: u8 = 0x80 | der::CONSTRUCTED | 1;
+        const BY_KEY: u8 = 0x80 | der::CONSTRUCTED | 2;
+
+        untrusted::Input::from(responder_id).read_all(Error::BadDER, |input| {
+            let (tag, value) = try!(der::read_tag_and_get_value(input));
+            match tag {
+                BY_NAME => value.read_all(Error::BadDER, |value| {
+                    let name = try!(der::expect_tag_and_get_value(
+                                        value, der::Tag::Sequence));
+                    Ok(ResponderId::ByName(der::from_input(name)))
+                }),
+                BY_KEY => value.read_all(Error::BadDER, |value| {
+                    let key_hash = try!(der::expect_tag_and_get_value(
+                                            value, der::Tag::OctetString));
+                    if key_hash.len() != SHA1_OUTPUT_LEN {
+                        return Err(Error::BadDER);
+                    }
+                    Ok(ResponderId::ByKey(der::from_input(key_hash)))
+                }),
+                _ => Err(Error::BadDER),
+            }
+        })
+    }
+
+    /// Returns `true` if the response was signed by the key of
+    /// `responder_cert` according to this `ResponderID`.
+    ///
+    /// `responder_cert` is either a delegated responder certificate, which
+    /// must also be checked with `verify_delegated_responder_cert`, or the
+    /// certificate of the issuer of the certificate whose status is reported,
+    /// parsed as an `EndEntityCert`. A response is often checked against both:
+    /// first the issuer, then the delegated responder certificates included in
+    /// the response.
+    ///
+    /// Names are compared exactly, byte for byte, as mozilla::pkix does. The
+    /// comparison is done in constant time; see `constant_time`.
+    pub fn matches(&self, responder_cert: &EndEntityCert) -> bool {
+        let responder_cert = &responder_cert.inner;
+        match *self {
+            ResponderId::ByName(name) =>
+                constant_time::slices_are_equal(
+                    name.as_slice(),
+                    responder_cert.subject.as_slice_less_safe()),
+            ResponderId::ByKey(key_hash) =>
+                match self::key_hash(responder_cert) {
+                    Ok(ref cert_key_hash) =>
+                        constant_time::slices_are_equal(key_hash.as_slice(),
+                                                        cert_key_hash),
+                    Err(..) => false,
+                },
+        }
+    }
+}
+
+/// The length of the nonce in requests built by `build_request`.
+///
+/// RFC 8954 Section 2.1 recommends 32-byte nonces and limits nonces to at most
+/// 32 bytes.
+pub const NONCE_LEN: usize = 32;
+
+/// The maximum length of a DER-encoded OCSP request built by `build_request`.
+pub const MAX_REQUEST_LEN: usize = 143;
+
+/// Builds a DER-encoded, unsigned `OCSPRequest` for the status of `cert`,
+/// given the ASN.1 DER-encoded X.509 certificate of its issuer,
+/// `issuer_cert_der`, writing it to `out`. The result is the prefix of `out`
+/// that contains the request.
+///
+/// If `nonce` is not `None` then the request will include a nonce extension
+/// (RFC 6960 Section 4.4.1) containing it. The nonce should be generated by a
+/// secure random number generator. Many responders, including those that
+/// serve pre-generated responses as described in RFC 5019, ignore nonces.
+///
+/// Sending the request to the OCSP responder is the caller's responsibility.
+///
+/// ```ASN.1
+/// OCSPRequest ::= SEQUENCE {
+///     tbsRequest                  TBSRequest,
+///     optionalSignature   [0]     EXPLICIT Signature OPTIONAL }
+///
+/// TBSRequest ::= SEQUENCE {
+///     version             [0]     EXPLICIT Version DEFAULT v1,
+///     requestorName       [1]     EXPLICIT GeneralName OPTIONAL,
+///     requestList                 SEQUENCE OF Request,
+///     requestExtensions   [2]     EXPLICIT Extensions OPTIONAL }
+///
+/// Request ::= SEQUENCE {
+///     reqCert                     CertID,
+///     singleRequestExtensions [0] EXPLICIT Extensions OPTIONAL }
+/// ```
+pub fn build_request<'b>(cert: &EndEntityCert,
+                         issuer_cert_der: &[u8],
+                         nonce: Option<&[u8; NONCE_LEN]>,
+                         out: &'b mut [u8; MAX_REQUEST_LEN])
+                         -> Result<&'b [u8], Error> {
+    let cert_id = try!(cert_id(cert, issuer_cert_der));
+    encode_request(&cert_id, nonce, out)
+}
+
+fn encode_request<'b>(cert_id: &CertId, nonce: Option<&[u8; NONCE_LEN]>,
+                      out: &'b mut [u8; MAX_REQUEST_LEN])
+                      -> Result<&'b [u8], Error> {
+    const SEQUENCE: u8 = der::Tag::Sequence as u8;
+    const CONTEXT_SPECIFIC_CONSTRUCTED_2: u8 = 0x80 | der::CONSTRUCTED | 2;
+
+    // id-pkix-ocsp-nonce, RFC 6960 Section 4.4.1.
+    const ID_PKIX_OCSP_NONCE: &'static [u8] =
+        &oid![1, 3, 6, 1, 5, 5, 7, 48, 1, 2];
+
+    let serial = cert_id.serial_number.as_slice();
+
+    let mut out = der_writer::SliceOutput::new(out);
+    try!(der_writer::write_sequence(&mut out, |ocsp_request| {
+        der_writer::write_sequence(ocsp_request, |tbs_request| {
+            try!(der_writer::write_sequence(tbs_request, |request_list| {
+                der_writer::write_sequence(request_list, |request| {
+                    der_writer::write_sequence(request, |req_cert| {
+                        try!(der_writer::write_tlv(req_cert, SEQUENCE,
+                                                   SHA1_ALGORITHM_ID));
+                        try!(der_writer::write_octet_string(
+                                 req_cert, &cert_id.issuer_name_hash));
+                        try!(der_writer::write_octet_string(
+                                 req_cert, &cert_id.issuer_key_hash));
+                        der_writer::write_positive_integer(req_cert, serial)
+                    })
+                })
+            }));
+            let nonce = match nonce {
+                Some(nonce) => nonce,
+                None => { return Ok(()); },
+            };
+            // Extension { id-pkix-ocsp-nonce, OCTET STRING { Nonce } }, where
+            // `Nonce ::= OCTET STRING` per RFC 8954.
+            der_writer::write_nested(tbs_request,
+                                     CONTEXT_SPECIFIC_CONSTRUCTED_2,
+                                     |request_extensions| {
+                der_writer::write_sequence(request_extensions, |extensions| {
+                    der_writer::write_sequence(extensions, |extension| {
+                        try!(der_writer::write_oid(extension,
+                                                   ID_PKIX_OCSP_NONCE));
+                        der_writer::write_nested(
+                            extension, der::Tag::OctetString as u8,
+                            |extn_value| {
+                                der_writer::write_octet_string(extn_value,
+                                                               nonce)
+                            })
+                    })
+                })
+            })
+        })
+    }));
+    Ok(out.into_slice())
+}
+
+/// The maximum length of a DER-encoded OCSP response that `Response::from`
+/// accepts. Longer responses are rejected with `Error::InputTooLong` before
+/// they are parsed.
+///
+/// A response about one certificate is usually less than 2KB long, or about
+/// 3KB with a delegated responder certificate, so this leaves room for an RSA
+/// 4096-bit responder certificate or a few extra certificates.
+pub const MAX_RESPONSE_LEN: usize = 8 * 1024;
+
+/// A parsed OCSP response whose `responseStatus` is `successful` and whose
+/// `responseBytes` contain a `BasicOCSPResponse`.
+///
+/// ```ASN.1
+/// OCSPResponse ::= SEQUENCE {
+///    responseStatus         OCSPResponseStatus,
+///    responseBytes          [0] EXPLICIT ResponseBytes OPTIONAL }
+///
+/// ResponseBytes ::= SEQUENCE {
+///    responseType   OBJECT IDENTIFIER,
+///    response       OCTET STRING }
+///
+/// BasicOCSPResponse ::= SEQUENCE {
+///    tbsResponseData      ResponseData,
+///    signatureAlgorithm   AlgorithmIdentifier,
+///    signature            BIT STRING,
+///    certs            [0] EXPLICIT SEQUENCE OF Certificate OPTIONAL }
+///
+/// ResponseData ::= SEQUENCE {
+///    version              [0] EXPLICIT Version DEFAULT v1,
+///    responderID              ResponderID,
+///    producedAt               GeneralizedTime,
+///    responses                SEQUENCE OF SingleResponse,
+///    responseExtensions   [1] EXPLICIT Extensions OPTIONAL }
+///
+/// SingleResponse ::= SEQUENCE {
+///    certID                       CertID,
+///    certStatus                   CertStatus,
+///    thisUpdate                   GeneralizedTime,
+///    nextUpdate         [0]       EXPLICIT GeneralizedTime OPTIONAL,
+///    singleExtensions   [1]       EXPLICIT Extensions OPTIONAL }
+///
+/// CertStatus ::= CHOICE {
+///    good        [0]     IMPLICIT NULL,
+///    revoked     [1]     IMPLICIT RevokedInfo,
+///    unknown     [2]     IMPLICIT UnknownInfo }
+/// ```
+///
+/// Responses with any critical extension, in `responseExtensions` or in any
+/// `singleExtensions`, are rejected with
+/// `Error::UnsupportedCriticalExtension`. Non-critical extensions, like the
+/// nonce, are ignored.
+pub struct Response<'a> {
+    signed_data: signed_data::SignedData<'a>,
+    responder_id: ResponderId<'a>,
+    produced_at: time::Time,
+
+    // The contents of `responses` and of `certs`, which is empty if it is
+    // absent.
+    responses: untrusted::Input<'a>,
+    certs: untrusted::Input<'a>,
+}
+
+impl<'a> Response<'a> {
+    /// Parses the DER-encoded `OCSPResponse` `response_der`, e.g. a stapled
+    /// response from a TLS `CertificateStatus` message.
+    ///
+    /// A response whose `responseStatus` isn't `successful`, e.g.
+    /// `tryLater`, doesn't say anything about any certificate, so it is
+    /// rejected with `Error::RevocationStatusUnknown`. Every `SingleResponse`
+    /// is parsed and checked. The signature isn't verified; see
+    /// `verify_signed_by`.
+    pub fn from(response_der: &'a [u8]) -> Result<Response<'a>, Error> {
+        const SUCCESSFUL: u8 = 0;
+        const ENUMERATED: u8 = 0x0a;
+
+        // id-pkix-ocsp-basic, RFC 6960 Section 4.2.1.
+        static ID_PKIX_OCSP_BASIC: [u8; 9] =
+            oid![1, 3, 6, 1, 5, 5, 7, 48, 1, 1];
+
+        if response_der.len() > MAX_RESPONSE_LEN {
+            return Err(Error::InputTooLong);
+        }
+
+        let response = try!(untrusted::Input::from(response_der).read_all(
+                Error::BadDER, |input| {
+            der::nested(input, der::Tag::Sequence, Error::BadDER, |response| {
+                let (tag, status) = try!(der::read_tag_and_get_value(response));
+                if tag != ENUMERATED || status.len() != 1 {
+                    return Err(Error::BadDER);
+                }
+                if status.as_slice_less_safe()[0] != SUCCESSFUL {
+                    return Err(Error::RevocationStatusUnknown);
+                }
+                der::nested(response, der::Tag::ContextSpecificConstructed0,
+                            Error::BadDER, |tagged| {
+                    der::nested(tagged, der::Tag::Sequence, Error::BadDER,
+                                |bytes| {
+                        let response_type = try!(der::expect_tag_and_get_value(
+                                                 bytes, der::Tag::OID));
+                        if response_type != &ID_PKIX_OCSP_BASIC[..] {
+                            return Err(Error::BadDER);
+                        }
+                        der::expect_tag_and_get_value(bytes,
+                                                      der::Tag::OctetString)
+                    })
+                })
+            })
+        }));
+
+        response.read_all(Error::BadDER, |input| {
+            der::nested(input, der::Tag::Sequence, Error::BadDER,
+                        parse_basic_response)
+        })
+    }
+
+    /// The response's `responderID`.
+    pub fn responder_id(&self) -> ResponderId<'a> { self.responder_id }
+
+    /// The response's `producedAt`.
+    pub fn produced_at(&self) -> time::Time { self.produced_at }
+
+    /// The ASN.1 DER-encoded X.509 certificates included in the response,
+    /// usually just a delegated responder certificate, if any.
+    pub fn certs(&self) -> ResponseCerts<'a> {
+        ResponseCerts { reader: untrusted::Reader::new(self.certs) }
+    }
+
+    /// Verifies the response's signature, given the ASN.1 DER-encoded X.509
+    /// certificate of the issuer of the certificates it is about,
+    /// `issuer_cert_der`.
+    ///
+    /// The response must be signed either by the issuer or by a delegated
+    /// responder whose certificate is included in the response and is valid
+    /// at `time` according to `verify_delegated_responder_cert`; otherwise,
+    /// `Error::UnknownIssuer` is returned. The delegated responder
+    /// certificate's revocation status isn't checked; see
+    /// `responder_cert_has_no_check`.
+    ///
+    /// As for `verify_delegated_responder_cert`, the issuer's certificate is
+    /// not validated in any way, so it must be the certificate of a CA that
+    /// the caller has already verified.
+    pub fn verify_signed_by(&self, supported_sig_algs: &[&SignatureAlgorithm],
+                            issuer_cert_der: &[u8], time: time::Time)
+                            -> Result<(), Error> {
+        let issuer = try!(EndEntityCert::from(issuer_cert_der));
+        if self.responder_id.matches(&issuer) {
+            return signed_data::verify_signed_data(
+                &Policy::default(), supported_sig_algs, issuer.inner.spki,
+                &self.signed_data);
+        }
+        for cert_der in self.certs() {
+            let responder_cert = match EndEntityCert::from(cert_der) {
+                Ok(responder_cert) => responder_cert,
+                Err(..) => { continue; }
+            };
+            if !self.responder_id.matches(&responder_cert) {
+                continue;
+            }
+            try!(verify_delegated_responder_cert(&responder_cert,
+                                                 issuer_cert_der,
+                                                 supported_sig_algs, time));
+            return signed_data::verify_signed_data(
+                &Policy::default(), supported_sig_algs,
+                responder_cert.inner.spki, &self.signed_data);
+        }
+        Err(Error::UnknownIssuer)
+    }
+
+    /// What the response says about the status of the certificate identified
+    /// by `cert_id`, for `revocation::RevocationChecker`, or `None` if the
+    /// response is about other certificates only.
+    ///
+    /// Only `SingleResponse`s whose `CertID` uses SHA-1, like the ones
+    /// requested by `build_request`, are considered. The response's signature
+    /// must already have been verified with `verify_signed_by`.
+    pub fn revocation_info(&self, cert_id: &CertId) -> Option<RevocationInfo> {
+        let mut responses = untrusted::Reader::new(self.responses);
+        while !responses.at_end() {
+            // The responses were already checked by `from`.
+            let single = read_single_response(&mut responses).unwrap();
+            if single.is_for(cert_id) {
+                return Some(single.info);
+            }
+        }
+        None
+    }
+}
+
+fn parse_basic_response<'a>(basic: &mut untrusted::Reader<'a>)
+                            -> Result<Response<'a>, Error> {
+    let (tbs, signed_data) = try!(signed_data::parse_signed_data(basic));
+    let certs = if basic.at_end() {
+        untrusted::Input::from(&[])
+    } else {
+        try!(der::nested(basic, der::Tag::ContextSpecificConstructed0,
+                         Error::BadDER, |tagged| {
+            der::expect_tag_and_get_value(tagged, der::Tag::Sequence)
+        }))
+    };
+    try!(certs.read_all(Error::BadDER, |certs| {
+        while !certs.at_end() {
+            let _ = try!(der::expect_tag_and_get_value(certs,
+                                                       der::Tag::Sequence));
+        }
+        Ok(())
+    }));
+
+    tbs.read_all(Error::BadDER, |tbs| {
+        if tbs.peek(der::Tag::ContextSpecificConstructed0 as u8) {
+            try!(der::nested(tbs, der::Tag::ContextSpecificConstructed0,
+                             Error::BadDER, |version| {
+                // v1 is the only version.
+                match try!(der::small_nonnegative_integer(version)) {
+                    0 => Ok(()),
+                    _ => Err(Error::BadDER),
+                }
+            }));
+        }
+
+        let mark1 = tbs.mark();
+        let _ = try!(der::read_tag_and_get_value(tbs));
+        let mark2 = tbs.mark();
+        let responder_id = try!(ResponderId::from(
+            tbs.get_input_between_marks(mark1, mark2).unwrap()
+               .as_slice_less_safe()));
+
+        let produced_at = try!(generalized_time(tbs));
+        let responses =
+            try!(der::expect_tag_and_get_value(tbs, der::Tag::Sequence));
+        try!(responses.read_all(Error::BadDER, |responses| {
+            while !responses.at_end() {
+                let _ = try!(read_single_response(responses));
+            }
+            Ok(())
+        }));
+        if !tbs.at_end() {
+            try!(der::nested(tbs, der::Tag::ContextSpecificConstructed1,
+                             Error::BadDER, |tagged| {
+                der::nested(tagged, der::Tag::Sequence, Error::BadDER,
+                            cert::check_no_critical_extensions)
+            }));
+        }
+
+        Ok(Response {
+            signed_data: signed_data,
+            responder_id: responder_id,
+            produced_at: produced_at,
+            responses: responses,
+            certs: certs,
+        })
+    })
+}
+
+/// An iterator over the certificates included in an OCSP response. See
+/// `Response::certs`.
+pub struct ResponseCerts<'a> {
+    reader: untrusted::Reader<'a>,
+}
+
+impl<'a> Iterator for ResponseCerts<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<&'a [u8]> {
+        if self.reader.at_end() {
+            return None;
+        }
+        // The certificates were already checked to be `SEQUENCE`s by
+        // `Response::from`.
+        let mark1 = self.reader.mark();
+        let _ = der::expect_tag_and_get_value(&mut self.reader,
+                                              der::Tag::Sequence).unwrap();
+        let mark2 = self.reader.mark();
+        Some(self.reader.get_input_between_marks(mark1, mark2).unwrap()
+                        .as_slice_less_safe())
+    }
+}
+
+struct SingleResponse<'a> {
+    hash_algorithm: untrusted::Input<'a>,
+    issuer_name_hash: untrusted::Input<'a>,
+    issuer_key_hash: untrusted::Input<'a>,
+    serial_number: untrusted::Input<'a>,
+    info: RevocationInfo,
+}
+
+impl<'a> SingleResponse<'a> {
+    fn is_for(&self, cert_id: &CertId) -> bool {
+        signed_data::algorithm_identifiers_are_equal(
+            self.hash_algorithm, untrusted::Input::from(SHA1_ALGORITHM_ID),
+            true) &&
+        self.issuer_name_hash == &cert_id.issuer_name_hash[..] &&
+        self.issuer_key_hash == &cert_id.issuer_key_hash[..] &&
+        self.serial_number == cert_id.serial_number.as_slice()
+    }
+}
+
+fn read_single_response<'a>(input: &mut untrusted::Reader<'a>)
+                            -> Result<SingleResponse<'a>, Error> {
+    const GOOD: u8 = 0x80;
+    const REVOKED: u8 = 0x80 | der::CONSTRUCTED | 1;
+    const UNKNOWN: u8 = 0x80 | 2;
+
+    der::nested(input, der::Tag::Sequence, Error::BadDER, |single| {
+        let (hash_algorithm, issuer_name_hash, issuer_key_hash,
+             serial_number) =
+                try!(der::nested(single, der::Tag::Sequence, Error::BadDER,
+                                 |cert_id| {
+            let hash_algorithm = try!(der::expect_tag_and_get_value(
+                                          cert_id, der::Tag::Sequence));
+            let issuer_name_hash = try!(der::expect_tag_and_get_value(
+                                            cert_id, der::Tag::OctetString));
+            let issuer_key_hash = try!(der::expect_tag_and_get_value(
+                                           cert_id, der::Tag::OctetString));
+            let serial_number = try!(der::positive_integer(cert_id));
+            Ok((hash_algorithm, issuer_name_hash, issuer_key_hash,
+                serial_number))
+        }));
+
+        let (tag, value) = try!(der::read_tag_and_get_value(single));
+        let status = match (tag, value.len()) {
+            (GOOD, 0) => CertStatus::Good,
+            (UNKNOWN, 0) => CertStatus::Unknown,
+            (REVOKED, _) => {
+                // RevokedInfo ::= SEQUENCE {
+                //     revocationTime              GeneralizedTime,
+                //     revocationReason    [0]     EXPLICIT CRLReason OPTIONAL }
+                try!(value.read_all(Error::BadDER, |revoked_info| {
+                    let _ = try!(generalized_time(revoked_info));
+                    if !revoked_info.at_end() {
+                        let _ = try!(der::expect_tag_and_get_value(
+                            revoked_info,
+                            der::Tag::ContextSpecificConstructed0));
+                    }
+                    Ok(())
+                }));
+                CertStatus::Revoked
+            },
+            _ => { return Err(Error::BadDER); }
+        };
+
+        let this_update = try!(generalized_time(single));
+        let next_update =
+            if single.peek(der::Tag::ContextSpecificConstructed0 as u8) {
+                Some(try!(der::nested(single,
+                                      der::Tag::ContextSpecificConstructed0,
+                                      Error::BadDER, generalized_time)))
+            } else {
+                None
+            };
+        if !single.at_end() {
+            try!(der::nested(single, der::Tag::ContextSpecificConstructed1,
+                             Error::BadDER, |tagged| {
+                der::nested(tagged, der::Tag::Sequence, Error::BadDER,
+                            cert::check_no_critical_extensions)
+            }));
+        }
+
+        Ok(SingleResponse {
+            hash_algorithm: hash_algorithm,
+            issuer_name_hash: issuer_name_hash,
+            issuer_key_hash: issuer_key_hash,
+            serial_number: serial_number,
+            info: RevocationInfo {
+                status: status,
+                this_update: this_update,
+                next_update: next_update,
+            },
+        })
+    })
+}
+
+// OCSP responses use `GeneralizedTime` only, unlike certificates and CRLs.
+fn generalized_time(input: &mut untrusted::Reader)
+                    -> Result<time::Time, Error> {
+    der::nested(input, der::Tag::GeneralizedTime, Error::BadDER,
+                |value| der::time_value(value, false))
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::{CertId, encode_request, MAX_REQUEST_LEN, MAX_RESPONSE_LEN,
+                NONCE_LEN, ResponderId, Response, SHA1_ALGORITHM_ID};
+    use revocation::CertStatus;
+    use std::vec::Vec;
+    use {der, der_writer, Der, Error, Time};
+    use untrusted;
+
+    // Checks that the request is well-formed and returns the `CertID` and
+    // `requestExtensions` (if any) contents.
+    fn parse_request<'a>(request: &'a [u8])
+            -> (untrusted::Input<'a>, Option<untrusted::Input<'a>>) {
+        untrusted::Input::from(request).read_all(Error::BadDER, |input| {
+            der::nested(input, der::Tag::Sequence, Error::BadDER, |ocsp| {
+                der::nested(ocsp, der::Tag::Sequence, Error::BadDER, |tbs| {
+                    let cert_id = try!(der::nested(tbs, der::Tag::Sequence,
+                                                   Error::BadDER, |list| {
+                        der::nested(list, der::Tag::Sequence, Error::BadDER,
+                                    |request| {
+                            der::expect_tag_and_get_value(request,
+                                                          der::Tag::Sequence)
+                        })
+                    }));
+                    let extensions = if tbs.at_end() {
+                        None
+                    } else {
+                        let (tag, value) =
+                            try!(der::read_tag_and_get_value(tbs));
+                        assert_eq!(0xa2, tag);
+                        Some(value)
+                    };
+                    Ok((cert_id, extensions))
+                })
+            })
+        }).unwrap()
+    }
+
+    #[test]
+    fn test_encode_request() {
+        const NONCE: [u8; NONCE_LEN] = [0x5a; NONCE_LEN];
+
+        // Largest and smallest serial numbers, with and without the high bit
+        // set.
+        let serials: [&[u8]; 4] = [&[0xff; 20], &[0x7f; 20], &[0x80], &[1]];
+
+        for serial in serials.iter() {
+            let cert_id = CertId {
+                issuer_name_hash: [0x11; 20],
+                issuer_key_hash: [0x22; 20],
+                serial_number: Der::from(*serial),
+            };
+            for nonce in [None, Some(&NONCE)].iter() {
+                let mut out = [0u8; MAX_REQUEST_LEN];
+                let encoded = encode_request(&cert_id, *nonce, &mut out)
+                                  .unwrap();
+                let (encoded_cert_id, extensions) = parse_request(encoded);
+
+                let serial_number = encoded_cert_id.read_all(Error::BadDER,
+                                                             |input| {
+                    let _ = try!(der::expect_tag_and_get_value(
+                                    input, der::Tag::Sequence));
+                    let name_hash = try!(der::expect_tag_and_get_value(
+                                            input, der::Tag::OctetString));
+                    assert_eq!(&[0x11; 20][..],
+                               name_hash.as_slice_less_safe());
+                    let key_hash = try!(der::expect_tag_and_get_value(
+                                            input, der::Tag::OctetString));
+                    assert_eq!(&[0x22; 20][..], key_hash.as_slice_less_safe());
+                    der::positive_integer(input)
+                }).unwrap();
+                assert_eq!(*serial, serial_number.as_slice_less_safe());
+                assert_eq!(nonce.is_some(), extensions.is_some());
+
+                if serial.len() == 20 && serial[0] == 0xff && nonce.is_some() {
+                    assert_eq!(MAX_REQUEST_LEN, encoded.len());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_responder_id_from() {
+        let by_name = [0xa1, 0x04, 0x30, 0x02, 0x31, 0x00];
+        assert_eq!(Ok(ResponderId::ByName(Der::from(&[0x31, 0x00][..]))),
+                   ResponderId::from(&by_name));
+
+        let mut by_key = [0x11; 24];
+        by_key[..4].copy_from_slice(&[0xa2, 22, 0x04, 20]);
+        assert_eq!(Ok(ResponderId::ByKey(Der::from(&[0x11; 20][..]))),
+                   ResponderId::from(&by_key));
+
+        // Wrong length of key hash.
+        assert_eq!(Err(Error::BadDER),
+                   ResponderId::from(&[0xa2, 0x03, 0x04, 0x01, 0x11]));
+        // Implicit instead of explicit tagging.
+        assert_eq!(Err(Error::BadDER),
+                   ResponderId::from(&[0xa1, 0x02, 0x31, 0x00]));
+        // Unknown tag.
+        assert_eq!(Err(Error::BadDER),
+                   ResponderId::from(&[0xa3, 0x02, 0x30, 0x00]));
+        // Trailing data.
+        assert_eq!(Err(Error::BadDER),
+                   ResponderId::from(&[0xa1, 0x02, 0x30, 0x00, 0x00]));
+    }
+
+    fn tlv(tag: u8, value: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        der_writer::write_tlv(&mut out, tag, value).unwrap();
+        out
+    }
+
+    // GeneralizedTime 1970-01-01 00:00:00 plus `hours`.
+    fn time(hours: u8) -> Vec<u8> {
+        tlv(0x18, format!("19700101{:02}0000Z", hours).as_bytes())
+    }
+
+    // An `Extensions` `SEQUENCE` with a nonce extension, critical or not.
+    fn nonce_extensions(critical: bool) -> Vec<u8> {
+        static ID_PKIX_OCSP_NONCE: [u8; 9] =
+            oid![1, 3, 6, 1, 5, 5, 7, 48, 1, 2];
+        let critical: &[u8] = if critical { &[0x01, 0x01, 0xff] } else { &[] };
+        tlv(0x30, &tlv(0x30, &[&tlv(0x06, &ID_PKIX_OCSP_NONCE)[..], critical,
+                               &tlv(0x04, &tlv(0x04, &[0x5a; 4]))].concat()))
+    }
+
+    // A `SingleResponse` about the certificate with the serial number
+    // `serial` and the `CertID` hashes of `cert_id`, below, valid for two
+    // hours.
+    fn single_response(serial: u8, status: &[u8],
+                       extensions: Option<&[u8]>) -> Vec<u8> {
+        let cert_id = tlv(0x30, &[&tlv(0x30, SHA1_ALGORITHM_ID)[..],
+                                  &tlv(0x04, &[0x11; 20]),
+                                  &tlv(0x04, &[0x22; 20]),
+                                  &tlv(0x02, &[serial])].concat());
+        let mut value = [&cert_id[..], status, &time(0),
+                         &tlv(0xa0, &time(2))].concat();
+        if let Some(extensions) = extensions {
+            value.extend_from_slice(&tlv(0xa1, extensions));
+        }
+        tlv(0x30, &value)
+    }
+
+    fn cert_id<'a>(serial: &'a [u8]) -> CertId<'a> {
+        CertId {
+            issuer_name_hash: [0x11; 20],
+            issuer_key_hash: [0x22; 20],
+            serial_number: Der::from(serial),
+        }
+    }
+
+    // An `OCSPResponse` with the status `status` and a `BasicOCSPResponse`
+    // with a bogus ecdsa-with-SHA256 signature.
+    fn build_response(status: u8, responder_id: &[u8],
+                      responses: &[Vec<u8>], extensions: Option<&[u8]>,
+                      certs: &[&[u8]]) -> Vec<u8> {
+        static ID_PKIX_OCSP_BASIC: [u8; 9] =
+            oid![1, 3, 6, 1, 5, 5, 7, 48, 1, 1];
+        const ALGORITHM: &'static [u8] =
+            &[0x30, 0x0a, 0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04,
+              0x03, 0x02];
+
+        let mut tbs = [responder_id, &time(0),
+                       &tlv(0x30, &responses.concat())].concat();
+        if let Some(extensions) = extensions {
+            tbs.extend_from_slice(&tlv(0xa1, extensions));
+        }
+        let mut basic = [&tlv(0x30, &tbs)[..], ALGORITHM,
+                         &[0x03, 0x03, 0x00, 0x30, 0x00]].concat();
+        if !certs.is_empty() {
+            basic.extend_from_slice(&tlv(0xa0, &tlv(0x30, &certs.concat())));
+        }
+        let response_bytes = tlv(0x30, &[&tlv(0x06, &ID_PKIX_OCSP_BASIC)[..],
+                                         &tlv(0x04, &tlv(0x30, &basic))]
+                                            .concat());
+        tlv(0x30, &[&[0x0a, 0x01, status][..],
+                    &tlv(0xa0, &response_bytes)].concat())
+    }
+
+    const BY_KEY: &'static [u8] = &[
+        0xa2, 22, 0x04, 20,
+        0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x33,
+        0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x33,
+    ];
+
+    #[test]
+    fn test_response_from() {
+        const GOOD: &'static [u8] = &[0x80, 0x00];
+        const UNKNOWN: &'static [u8] = &[0x82, 0x00];
+        // RevokedInfo { revocationTime, revocationReason keyCompromise }.
+        let revoked = tlv(0xa1, &[&time(1)[..],
+                                  &tlv(0xa0, &[0x0a, 0x01, 0x01])].concat());
+        let extensions = nonce_extensions(false);
+        let cert = [0x30, 0x00];
+
+        let der = build_response(0, BY_KEY,
+                                 &[single_response(1, GOOD, None),
+                                   single_response(2, &revoked,
+                                                   Some(&extensions)),
+                                   single_response(3, UNKNOWN, None)],
+                                 Some(&extensions), &[&cert, &cert]);
+        let response = Response::from(&der).unwrap();
+        assert_eq!(ResponderId::ByKey(Der::from(&[0x33; 20][..])),
+                   response.responder_id());
+        assert_eq!(Time::from_seconds_since_unix_epoch(0),
+                   response.produced_at());
+        assert_eq!(&[&cert[..], &cert[..]][..],
+                   &response.certs().collect::<Vec<_>>()[..]);
+
+        let expected = [(1, CertStatus::Good), (2, CertStatus::Revoked),
+                        (3, CertStatus::Unknown)];
+        for &(serial, status) in expected.iter() {
+            let info = response.revocation_info(&cert_id(&[serial])).unwrap();
+            assert_eq!(status, info.status);
+            assert_eq!(Time::from_seconds_since_unix_epoch(0),
+                       info.this_update);
+            assert_eq!(Some(Time::from_seconds_since_unix_epoch(2 * 3600)),
+                       info.next_update);
+        }
+        assert!(response.revocation_info(&cert_id(&[4])).is_none());
+        let mut other_issuer = cert_id(&[1]);
+        other_issuer.issuer_key_hash = [0x33; 20];
+        assert!(response.revocation_info(&other_issuer).is_none());
+
+        // Without extensions or certificates.
+        let der = build_response(0, BY_KEY, &[single_response(1, GOOD, None)],
+                                 None, &[]);
+        let response = Response::from(&der).unwrap();
+        assert_eq!(0, response.certs().count());
+        assert_eq!(Some(CertStatus::Good),
+                   response.revocation_info(&cert_id(&[1]))
+                           .map(|info| info.status));
+    }
+
+    #[test]
+    fn test_response_from_errors() {
+        const GOOD: &'static [u8] = &[0x80, 0x00];
+        let responses = [single_response(1, GOOD, None)];
+
+        // tryLater.
+        let der = build_response(3, BY_KEY, &responses, None, &[]);
+        assert_eq!(Some(Error::RevocationStatusUnknown),
+                   Response::from(&der).err());
+
+        let critical = nonce_extensions(true);
+        let der = build_response(0, BY_KEY, &responses, Some(&critical), &[]);
+        assert_eq!(Some(Error::UnsupportedCriticalExtension),
+                   Response::from(&der).err());
+        let der = build_response(0, BY_KEY,
+                                 &[single_response(1, GOOD, Some(&critical))],
+                                 None, &[]);
+        assert_eq!(Some(Error::UnsupportedCriticalExtension),
+                   Response::from(&der).err());
+
+        // A status with contents.
+        let der = build_response(0, BY_KEY,
+                                 &[single_response(1, &[0x80, 0x01, 0x00],
+                                                   None)],
+                                 None, &[]);
+        assert_eq!(Some(Error::BadDER), Response::from(&der).err());
+
+        // Too long, even though it is otherwise valid.
+        let cert = tlv(0x30, &[0; MAX_RESPONSE_LEN]);
+        let der = build_response(0, BY_KEY, &responses, None, &[&cert]);
+        assert_eq!(Some(Error::InputTooLong), Response::from(&der).err());
+
+        let der = build_response(0, BY_KEY, &responses, None, &[]);
+        assert_eq!(Some(Error::BadDER),
+                   Response::from(&der[..der.len() - 1]).err());
+    }
+
+    #[cfg(feature = "ecdsa")]
+    #[test]
+    fn test_response_verify_signed_by() {
+        use {EndEntityCert, ECDSA_P256_SHA256, ECDSA_P384_SHA256};
+
+        const GOOD: &'static [u8] = &[0x80, 0x00];
+        let ee = include_bytes!("../tests/data/ecdsa-ee.der");
+        let issuer = include_bytes!("../tests/data/ecdsa-intermediate.der");
+        let algs = &[&ECDSA_P256_SHA256, &ECDSA_P384_SHA256];
+        let time = Time::from_seconds_since_unix_epoch(1500000000);
+
+        // Signed by neither the issuer nor an included certificate.
+        let der = build_response(0, BY_KEY, &[single_response(1, GOOD, None)],
+                                 None, &[&[0x30, 0x00]]);
+        let response = Response::from(&der).unwrap();
+        assert_eq!(Err(Error::UnknownIssuer),
+                   response.verify_signed_by(algs, issuer, time));
+
+        // Signed by the issuer, but the signature is bogus.
+        let ee = EndEntityCert::from(ee).unwrap();
+        let by_name = tlv(0xa1, &tlv(0x30, ee.issuer().as_slice()));
+        let der = build_response(0, &by_name,
+                                 &[single_response(1, GOOD, None)], None,
+                                 &[]);
+        let response = Response::from(&der).unwrap();
+        assert_eq!(Err(Error::InvalidSignatureForPublicKey),
+                   response.verify_signed_by(algs, issuer, time));
+    }
+}
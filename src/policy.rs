@@ -0,0 +1,392 @@
+// Copyright 2016 Brian Smith.
+//
+// Permission to use, copy, modify, and/or distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHORS DISCLAIM ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHORS BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+use core::time::Duration;
+use der::MAX_DER_ELEMENT_LEN;
+use signed_data::{KeyType, SignatureRequirements};
+
+/// Tunable aspects of certificate and name verification.
+///
+/// The defaults (`Policy::default()`) are what the verification functions
+/// that don't take a `Policy` use. They are the strictest settings that are
+/// compatible with the Web PKI; each field documents the (usually more
+/// lenient) alternative.
+#[derive(Clone, Copy, Debug)]
+pub struct Policy {
+    /// Whether a presented DNS ID may have a wildcard label of the form
+    /// `<x>*<y>` where `<x>` and/or `<y>` are not empty, e.g.
+    /// `f*o.example.com`, as RFC 6125 Section 6.4.3 allows.
+    ///
+    /// When this is `false` (the default), such a presented DNS ID never
+    /// matches any reference DNS ID, though it doesn't cause the certificate
+    /// to be rejected. Regardless of this setting, the wildcard must match at
+    /// least one character, and a presented DNS ID with a wildcard in any
+    /// label other than the leftmost one, or with more than one wildcard,
+    /// is malformed.
+    pub allow_partial_label_wildcards: bool,
+
+    /// Whether a reference DNS ID with a leading dot, e.g. `.internal.corp`,
+    /// is accepted and matches any presented DNS ID that is exactly one label
+    /// under that domain, e.g. `api.internal.corp`, as some service meshes
+    /// require. It doesn't match `internal.corp` itself or names more than
+    /// one label under it, like `a.b.internal.corp`. A presented wildcard
+    /// DNS ID like `*.internal.corp` also matches it.
+    ///
+    /// When this is `false` (the default), such a reference DNS ID is
+    /// rejected with `Error::InvalidReferenceName`. Name constraints are not
+    /// affected by this setting.
+    pub allow_domain_suffix_reference_ids: bool,
+
+    /// Whether the `signatureAlgorithm` and `tbsCertificate.signature` of a
+    /// certificate are allowed to differ in whether the `parameters` of the
+    /// `AlgorithmIdentifier` are absent or `NULL`.
+    ///
+    /// RFC 5280 requires them to be identical, and by default (`false`) a
+    /// difference results in `Error::SignatureAlgorithmMismatch`. However,
+    /// some issuers (notably some Java-based CAs) encode one with a `NULL`
+    /// and the other without it; mozilla::pkix tolerates this.
+    pub allow_signature_algorithm_null_parameter_mismatch: bool,
+
+    /// Whether a `NULL` `parameters` field is accepted in the signature
+    /// algorithm `AlgorithmIdentifier` of an algorithm whose parameters must
+    /// otherwise be absent, i.e. ECDSA.
+    ///
+    /// RSA PKCS#1 signature algorithms always accept both the `NULL` and
+    /// absent forms, because both are common. By default (`false`), ECDSA
+    /// signature algorithms accept only the absent form, as RFC 5758 requires,
+    /// and a `NULL` results in `Error::UnsupportedSignatureAlgorithm`.
+    pub allow_null_signature_algorithm_parameters: bool,
+
+    /// The maximum number of times that path building tries an intermediate
+    /// certificate as the issuer of a certificate, in total over all the
+    /// candidate paths. When the limit is reached, verification fails with
+    /// `Error::TooManyCandidateIssuers`.
+    ///
+    /// This bounds the work done for a large or pathological set of
+    /// intermediate certificates. Identical copies of an intermediate
+    /// certificate are only tried once, so they don't count against the
+    /// limit. The default, 100, is far more than any real-world path needs.
+    pub max_candidate_issuers: usize,
+
+    /// The maximum number of signatures that a path-building search
+    /// verifies, in total over all the candidate paths. A signature that
+    /// candidate paths share, e.g. above two versions of an intermediate
+    /// certificate with the same issuer, is only verified once. When the
+    /// limit is reached, verification fails with
+    /// `Error::TooManySignatureVerifications`.
+    ///
+    /// Verifying signatures is by far the most expensive part of path
+    /// building, so this bounds the CPU time that a malicious peer can make a
+    /// handshake take, e.g. with many intermediate certificates that have the
+    /// same subject and key identifier as the real issuer. The default, 8, is
+    /// enough for a path of the maximum length, which has 7 signatures, plus
+    /// one signature for a rejected candidate. Each of the searches of
+    /// `VerificationOptions::prefer_earlier_trust_anchors` and
+    /// `VerificationOptions::issuer_fetcher` has its own limit.
+    pub max_signature_verifications: usize,
+
+    /// Whether to accept certificates that violate the subjectAltName rules of
+    /// RFC 5280 Section 4.2.1.6: that a certificate with an empty subject must
+    /// have a critical subjectAltName extension, and that a subjectAltName
+    /// extension must contain at least one name.
+    ///
+    /// By default (`false`), such certificates are rejected with
+    /// `Error::EmptySubjectWithoutCriticalSubjectAltName` and
+    /// `Error::EmptySubjectAltName`, respectively. This catches certificates
+    /// with an empty subject that would otherwise be treated inconsistently
+    /// by software that ignores non-critical extensions. Some old private
+    /// PKIs issue certificates with an empty subjectAltName; set this to
+    /// `true` to accept them, as mozilla::pkix does.
+    pub allow_nonconformant_subject_alt_name: bool,
+
+    /// How far in the future a certificate's `notBefore` time may be before
+    /// verification fails with `Error::CertNotValidYet`.
+    ///
+    /// ACME CAs issue certificates that are deployed within seconds of
+    /// issuance, so a client whose clock is slightly behind would otherwise
+    /// reject them spuriously. The default is 60 seconds; set it to zero to
+    /// compare `notBefore` with the verification time exactly. The
+    /// `notAfter` time is always checked exactly.
+    pub not_before_grace_period: Duration,
+
+    /// Whether every path must have a valid certificate policy, i.e. the
+    /// initial-explicit-policy input of RFC 5280 Section 6.1.1. Any policy is
+    /// acceptable.
+    ///
+    /// Regardless of this setting, the policyConstraints and
+    /// inhibitAnyPolicy extensions of the certificates in the path can
+    /// require a valid policy, as in bridge and federal PKIs.
+    pub require_explicit_policy: bool,
+
+    /// Whether policy mapping is inhibited, i.e. the
+    /// initial-policy-mapping-inhibit input of RFC 5280 Section 6.1.1.
+    pub inhibit_policy_mapping: bool,
+
+    /// Whether anyPolicy in a certificate's certificatePolicies extension is
+    /// ignored, i.e. the initial-any-policy-inhibit input of RFC 5280 Section
+    /// 6.1.1.
+    pub inhibit_any_policy: bool,
+
+    /// Whether a certificate may have a critical subjectDirectoryAttributes
+    /// extension, whose contents webpki ignores.
+    ///
+    /// By default (`false`), such a certificate is rejected with
+    /// `Error::UnsupportedCriticalExtension`, as for any other critical
+    /// extension that webpki doesn't process. Some TPM vendors mark the
+    /// extension critical in endorsement key certificates; see
+    /// `Policy::tcg_credential_profile`.
+    pub allow_critical_subject_directory_attributes: bool,
+
+    /// Whether a certificate may contain more than one instance of an
+    /// extension that webpki doesn't process, which RFC 5280 Section 4.2
+    /// forbids.
+    ///
+    /// By default (`false`), such a certificate is rejected with
+    /// `Error::DuplicateExtension`. Regardless of this setting, a certificate
+    /// with more than one instance of an extension that webpki does process,
+    /// e.g. two subjectAltName extensions, is always rejected, because
+    /// different implementations would disagree about which one applies.
+    pub allow_duplicate_extensions: bool,
+
+    /// The maximum length, in bytes, of the DER encoding of a certificate
+    /// that is parsed by `EndEntityCert::from_with_policy`,
+    /// `CaCert::from_with_policy`, or path building. Longer certificates are
+    /// rejected with `Error::InputTooLong` without being parsed.
+    ///
+    /// The default is the longest certificate that can be parsed at all; see
+    /// `MAX_DER_ELEMENT_LEN`. Real-world certificates are rarely longer than
+    /// a few kilobytes, so memory-constrained targets can set a much lower
+    /// limit.
+    pub max_cert_len: usize,
+
+    /// The maximum total length, in bytes, of the intermediate certificates
+    /// given to a verification function. If they are longer, verification
+    /// fails with `Error::InputTooLong` before any of them is parsed.
+    ///
+    /// This bounds the work done for a malicious peer that sends a huge
+    /// chain. The default, 1 MiB, is far more than any real-world chain
+    /// needs.
+    pub max_intermediate_certs_len: usize,
+
+    /// The maximum number of intermediate certificates given to a
+    /// verification function, counting identical copies of the same
+    /// certificate. If there are more, verification fails with
+    /// `Error::InputTooLong` before any of them is parsed.
+    ///
+    /// Finding the copies takes time quadratic in the number of
+    /// certificates, so `max_intermediate_certs_len` alone doesn't bound it
+    /// well when the certificates are small. The default, 64, is also the
+    /// most that is supported; higher limits have no effect.
+    pub max_intermediate_certs: usize,
+
+    /// The maximum depth of nested constructed DER elements, e.g.
+    /// `SEQUENCE`s, in a certificate that is parsed by
+    /// `EndEntityCert::from_with_policy`, `CaCert::from_with_policy`, or path
+    /// building, counting the outer `Certificate` `SEQUENCE` as depth 1.
+    /// Deeper certificates are rejected with `Error::DERNestingTooDeep`.
+    ///
+    /// Real certificates nest about six levels deep, which is also the depth
+    /// at which webpki parses the fields of extensions, so a lower limit
+    /// rejects every certificate. The default, 16, leaves plenty of room
+    /// while keeping the work done on hostile input predictable.
+    pub max_der_nesting_depth: usize,
+
+    /// The maximum length, in bytes, of the value of any DER element in a
+    /// certificate that is parsed by `EndEntityCert::from_with_policy`,
+    /// `CaCert::from_with_policy`, or path building. Certificates with longer
+    /// elements are rejected with `Error::DERElementTooLong`.
+    ///
+    /// The default is `MAX_DER_ELEMENT_LEN`, the longest value that can be
+    /// parsed at all; higher limits have no effect. Unlike `max_cert_len`,
+    /// this is checked as the certificate is parsed.
+    pub max_der_element_len: usize,
+
+    /// The types of public key that an end-entity certificate may have, or
+    /// `None` (the default) for any type that the signature algorithms
+    /// support.
+    ///
+    /// A certificate with a key of any other type is rejected with
+    /// `Error::EndEntityKeyTypeNotAllowed` by `EndEntityCert::from_with_policy`
+    /// and by verification. This lets an organization that is migrating away
+    /// from RSA enforce e.g. `Some(&[KeyType::EcdsaP256, KeyType::EcdsaP384])`
+    /// for its end-entity certificates while its CAs still sign with RSA keys;
+    /// the keys of CA certificates are only constrained by the supported
+    /// signature algorithms.
+    pub end_entity_key_types: Option<&'static [KeyType]>,
+
+    /// Whether the extended key usage extensions of CA certificates are
+    /// ignored.
+    ///
+    /// By default (`false`), like Microsoft's implementation and
+    /// mozilla::pkix, an EKU extension in a CA certificate restricts the
+    /// usages of the certificates that the CA issues, and a path through a CA
+    /// whose EKU extension doesn't allow the usage being verified is
+    /// rejected with `Error::RequiredEKUNotFound`. RFC 5280 doesn't give the
+    /// extension any meaning in CA certificates, and some private PKIs have
+    /// CAs whose EKU allows e.g. only clientAuth but that also issue
+    /// serverAuth certificates; set this to `true` to accept such paths. The
+    /// EKU extension of the end-entity certificate is always checked.
+    pub ignore_ca_eku: bool,
+
+    /// Minimum strengths for the signatures of the certificates in the path,
+    /// or `None` (the default) to accept every signature that one of the
+    /// supported signature algorithms accepts.
+    ///
+    /// This lets a single list of supported signature algorithms be shared
+    /// by policies of different strictness. A signature with a digest that
+    /// is too short is rejected with `Error::UnsupportedSignatureAlgorithm`,
+    /// and one made with a key that is too small with
+    /// `Error::PublicKeyTooSmall`. The requirements also apply to the
+    /// signatures of CRLs and OCSP responses that are checked with the
+    /// policy.
+    pub signature_requirements: Option<SignatureRequirements>,
+
+    /// The longest validity period, from `notBefore` to `notAfter`, that an
+    /// end-entity certificate may have, or `None` (the default) for no limit.
+    ///
+    /// A certificate with a longer validity period is rejected with
+    /// `Error::ValidityPeriodTooLong`. The CA/Browser Forum Baseline
+    /// Requirements (Section 6.3.2) limit publicly-trusted TLS server
+    /// certificates to 398 days. The validity periods of CA certificates are
+    /// not limited.
+    pub max_end_entity_validity: Option<Duration>,
+}
+
+impl Policy {
+    /// A policy for verifying the TPM endorsement key (EK) and attestation
+    /// identity key (AIK) certificates of the Trusted Computing Group's
+    /// credential profiles, e.g. for remote attestation, with
+    /// `Usage::TcgEndorsementKey` or `Usage::TcgAttestationIdentityKey`.
+    ///
+    /// Many EK certificates have an empty subject with a non-critical
+    /// subjectAltName that contains only the TPM's manufacturer, model, and
+    /// version, and some mark their subjectDirectoryAttributes extension
+    /// critical. So `allow_nonconformant_subject_alt_name` and
+    /// `allow_critical_subject_directory_attributes` are set; everything
+    /// else is as in `Policy::default()`.
+    pub fn tcg_credential_profile() -> Policy {
+        Policy {
+            allow_nonconformant_subject_alt_name: true,
+            allow_critical_subject_directory_attributes: true,
+            ..Policy::default()
+        }
+    }
+
+    /// A policy for verifying the certificates of IKE peers, e.g. in IPsec
+    /// VPN daemons, with `Usage::IpsecIke`. The peer's identity is usually
+    /// matched with `ServerName::IpAddress` or `ServerName::Email`, or with
+    /// `ServerName::DnsName` for an FQDN identity.
+    ///
+    /// Only `allow_nonconformant_subject_alt_name` differs from
+    /// `Policy::default()`: the certificates of site-to-site gateways and
+    /// road warriors often have an empty subject with a non-critical
+    /// subjectAltName that contains only an IP address or an email address.
+    pub fn ike_profile() -> Policy {
+        Policy {
+            allow_nonconformant_subject_alt_name: true,
+            ..Policy::default()
+        }
+    }
+
+    /// A policy for services whose peers all use recently-issued
+    /// certificates, like Mozilla's "Modern" TLS configuration.
+    ///
+    /// It is `intermediate_profile` with `end_entity_key_types` limited to
+    /// ECDSA P-256 and P-384, so RSA end-entity keys are rejected.
+    ///
+    /// The profiles (`modern_profile`, `intermediate_profile`, and
+    /// `legacy_profile`) are the recommended way to configure verification;
+    /// start from the strictest one that the expected peers allow. Their
+    /// settings only change in releases that may break compatibility, and
+    /// every change is noted in the release notes. None of them ever matches
+    /// a name against a certificate's subject common name; webpki only uses
+    /// the subjectAltName extension.
+    pub fn modern_profile() -> Policy {
+        Policy {
+            end_entity_key_types: Some(&[KeyType::EcdsaP256,
+                                         KeyType::EcdsaP384]),
+            ..Policy::intermediate_profile()
+        }
+    }
+
+    /// A policy for general-purpose services, like Mozilla's "Intermediate"
+    /// TLS configuration.
+    ///
+    /// Compared to `Policy::default()`, it sets `signature_requirements` to
+    /// `SignatureRequirements::default()`, which rejects SHA-1, RSA keys
+    /// shorter than 2048 bits, and ECDSA keys on curves smaller than P-256
+    /// anywhere in the path, and `max_end_entity_validity` to 398 days, the
+    /// CA/Browser Forum's limit for TLS server certificates. See
+    /// `modern_profile`.
+    pub fn intermediate_profile() -> Policy {
+        Policy {
+            signature_requirements: Some(SignatureRequirements::default()),
+            max_end_entity_validity:
+                Some(Duration::from_secs(398 * 24 * 60 * 60)),
+            ..Policy::default()
+        }
+    }
+
+    /// A policy for services that must accept certificates from old or
+    /// nonconformant PKIs, like Mozilla's "Old" TLS configuration.
+    ///
+    /// Each of its changes to `Policy::default()` relaxes a check that some
+    /// deployed certificates fail: `allow_partial_label_wildcards` for names
+    /// like `f*.example.com`, the two `NULL` signature algorithm parameter
+    /// settings, `allow_nonconformant_subject_alt_name`, and
+    /// `allow_duplicate_extensions` for extensions that webpki doesn't
+    /// process. `signature_requirements` stays unset, so SHA-1 signatures are
+    /// accepted if their algorithms are passed; pass only the algorithms that
+    /// the peers really need. See `modern_profile`.
+    pub fn legacy_profile() -> Policy {
+        Policy {
+            allow_partial_label_wildcards: true,
+            allow_signature_algorithm_null_parameter_mismatch: true,
+            allow_null_signature_algorithm_parameters: true,
+            allow_nonconformant_subject_alt_name: true,
+            allow_duplicate_extensions: true,
+            ..Policy::default()
+        }
+    }
+}
+
+impl Default for Policy {
+    fn default() -> Policy {
+        Policy {
+            allow_partial_label_wildcards: false,
+            allow_domain_suffix_reference_ids: false,
+            allow_signature_algorithm_null_parameter_mismatch: false,
+            allow_null_signature_algorithm_parameters: false,
+            max_candidate_issuers: 100,
+            max_signature_verifications: 8,
+            allow_nonconformant_subject_alt_name: false,
+            not_before_grace_period: Duration::from_secs(60),
+            require_explicit_policy: false,
+            inhibit_policy_mapping: false,
+            inhibit_any_policy: false,
+            allow_critical_subject_directory_attributes: false,
+            allow_duplicate_extensions: false,
+            // The outer tag and a three-byte length, plus the value.
+            max_cert_len: 1 + 3 + MAX_DER_ELEMENT_LEN,
+            max_intermediate_certs_len: 1024 * 1024,
+            max_intermediate_certs: 64,
+            max_der_nesting_depth: 16,
+            max_der_element_len: MAX_DER_ELEMENT_LEN,
+            end_entity_key_types: None,
+            ignore_ca_eku: false,
+            signature_requirements: None,
+            max_end_entity_validity: None,
+        }
+    }
+}
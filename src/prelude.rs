@@ -0,0 +1,49 @@
+// Copyright 2016 Brian Smith.
+//
+// Permission to use, copy, modify, and/or distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHORS DISCLAIM ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHORS BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+//! The types that almost every user of webpki needs, for importing with
+//! `use webpki::prelude::*;`.
+//!
+//! Only items that are expected to stay as they are for the foreseeable
+//! future are exported here, so that code that imports the prelude isn't
+//! affected by changes elsewhere in the API. Items are added to the prelude
+//! only in releases that may contain breaking changes, since a new name can
+//! conflict with a name that the importing code already uses.
+//!
+//! The signature algorithms are exported according to the `ecdsa` and `rsa`
+//! features.
+
+pub use {EndEntityCert, Error, ServerName, SignatureAlgorithm, Time,
+         TrustAnchor};
+
+#[cfg(feature = "ecdsa")]
+pub use {
+    ECDSA_P256_SHA1,
+    ECDSA_P256_SHA256,
+    ECDSA_P256_SHA384,
+    ECDSA_P256_SHA512,
+    ECDSA_P384_SHA1,
+    ECDSA_P384_SHA256,
+    ECDSA_P384_SHA384,
+    ECDSA_P384_SHA512,
+};
+
+#[cfg(feature = "rsa")]
+pub use {
+    RSA_PKCS1_2048_8192_SHA1,
+    RSA_PKCS1_2048_8192_SHA256,
+    RSA_PKCS1_2048_8192_SHA384,
+    RSA_PKCS1_2048_8192_SHA512,
+    RSA_PKCS1_3072_8192_SHA384,
+};
@@ -0,0 +1,156 @@
+// Copyright 2016 Brian Smith.
+//
+// Permission to use, copy, modify, and/or distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHORS DISCLAIM ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHORS BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+// Punycode decoding, https://tools.ietf.org/html/rfc3492, for displaying the
+// U-labels of internationalized domain names; see
+// `DnsNameMatch::presented_id_unicode`. Only the Punycode is decoded; the
+// result isn't checked against the IDNA2008 rules for U-labels.
+
+use core::char;
+use std::string::String;
+use std::vec::Vec;
+
+const BASE: u32 = 36;
+const T_MIN: u32 = 1;
+const T_MAX: u32 = 26;
+const SKEW: u32 = 38;
+const DAMP: u32 = 700;
+const INITIAL_BIAS: u32 = 72;
+const INITIAL_N: u32 = 128;
+
+// Decodes the Punycode `input`, i.e. an A-label without its "xn--" prefix.
+// Returns `None` if `input` isn't valid Punycode, or if it decodes to only
+// ASCII characters, in which case it isn't the encoding of a U-label.
+pub fn decode(input: &[u8]) -> Option<String> {
+    // RFC 3492 Section 3.1: The basic code points are all the ones before
+    // the last delimiter, if there is one.
+    let (basic, extended) =
+        match input.iter().rposition(|&b| b == b'-') {
+            Some(delimiter) => (&input[..delimiter], &input[delimiter + 1..]),
+            None => (&input[..0], input),
+        };
+    if extended.is_empty() {
+        return None;
+    }
+
+    let mut output: Vec<char> = Vec::new();
+    for &b in basic {
+        if b >= 0x80 {
+            return None;
+        }
+        output.push(b as char);
+    }
+
+    // RFC 3492 Section 6.2.
+    let mut n = INITIAL_N;
+    let mut i: u32 = 0;
+    let mut bias = INITIAL_BIAS;
+    let mut digits = extended.iter();
+    loop {
+        let old_i = i;
+        let mut w: u32 = 1;
+        let mut k = BASE;
+        loop {
+            let digit = match digits.next() {
+                Some(&b) => match b {
+                    b'a'...b'z' => b - b'a',
+                    b'A'...b'Z' => b - b'A',
+                    b'0'...b'9' => b - b'0' + 26,
+                    _ => { return None; }
+                },
+                None => { return None; }
+            } as u32;
+            i = match digit.checked_mul(w).and_then(|dw| i.checked_add(dw)) {
+                Some(i) => i,
+                None => { return None; }
+            };
+            let t = if k <= bias {
+                T_MIN
+            } else if k >= bias + T_MAX {
+                T_MAX
+            } else {
+                k - bias
+            };
+            if digit < t {
+                break;
+            }
+            w = match w.checked_mul(BASE - t) {
+                Some(w) => w,
+                None => { return None; }
+            };
+            k += BASE;
+        }
+
+        let num_points = output.len() as u32 + 1;
+        bias = adapt(i - old_i, num_points, old_i == 0);
+        n = match n.checked_add(i / num_points) {
+            Some(n) => n,
+            None => { return None; }
+        };
+        i %= num_points;
+        match char::from_u32(n) {
+            Some(c) => output.insert(i as usize, c),
+            None => { return None; }
+        }
+        i += 1;
+
+        if digits.as_slice().is_empty() {
+            break;
+        }
+    }
+
+    Some(output.into_iter().collect())
+}
+
+// RFC 3492 Section 6.1.
+fn adapt(delta: u32, num_points: u32, first_time: bool) -> u32 {
+    let mut delta = if first_time { delta / DAMP } else { delta / 2 };
+    delta += delta / num_points;
+    let mut k = 0;
+    while delta > ((BASE - T_MIN) * T_MAX) / 2 {
+        delta /= BASE - T_MIN;
+        k += BASE;
+    }
+    k + (BASE - T_MIN + 1) * delta / (delta + SKEW)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::decode;
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(Some("bücher"), decode(b"bcher-kva").as_ref()
+                                                       .map(|s| &s[..]));
+        assert_eq!(Some("münchen"), decode(b"mnchen-3ya").as_ref()
+                                                         .map(|s| &s[..]));
+        assert_eq!(Some("例え"), decode(b"r8jz45g").as_ref().map(|s| &s[..]));
+        // RFC 3492 Section 7.1 (L), mixed case.
+        assert_eq!(Some("3年B組金八先生"),
+                   decode(b"3B-ww4c5e180e575a65lsy2b").as_ref()
+                                                    .map(|s| &s[..]));
+
+        // Only basic code points.
+        assert_eq!(None, decode(b"example-"));
+        assert_eq!(None, decode(b""));
+        // Not a digit.
+        assert_eq!(None, decode(b"bcher-kv_"));
+        // Truncated.
+        assert_eq!(None, decode(b"bcher-k"));
+        // Overflow.
+        assert_eq!(None, decode(b"99999999999999"));
+        // A non-ASCII basic code point.
+        assert_eq!(None, decode(b"b\xc3\xbccher-kva"));
+    }
+}
@@ -0,0 +1,542 @@
+// Copyright 2016 Brian Smith.
+//
+// Permission to use, copy, modify, and/or distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHORS DISCLAIM ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHORS BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+//! Deciding whether a certificate is revoked from the revocation information
+//! that is available for it.
+//!
+//! webpki doesn't fetch OCSP responses or CRLs itself. Instead, the caller
+//! reports what each source says about a certificate as a `RevocationInfo`,
+//! e.g. with `crl::CertRevocationList::revocation_info`, and
+//! `RevocationChecker` combines them in one place with a fixed order of
+//! precedence, so that every application resolves conflicting or stale
+//! information the same way.
+//!
+//! Pre-aggregated revocation information, like a CRLite filter cascade or a
+//! list of certificates with compromised keys, can instead be shipped to
+//! clients as a `RevocationFilter`, which is consulted for every certificate
+//! during path building.
+//!
+//! What OCSP responses say can be remembered across handshakes in an
+//! `OcspCache`, so that the same response isn't fetched or verified again
+//! while it is fresh; see `RevocationChecker::check_cert_with_ocsp_cache`.
+
+use core::time::Duration;
+use ocsp::CertId;
+use {der, EndEntityCert, Error, Time, VerificationTimes};
+
+/// The status of a certificate according to one source of revocation
+/// information.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CertStatus {
+    /// The certificate isn't revoked.
+    Good,
+
+    /// The certificate is revoked.
+    Revoked,
+
+    /// The source doesn't know the status of the certificate, e.g. an OCSP
+    /// response with the status `unknown`.
+    Unknown,
+}
+
+/// What one source, e.g. an OCSP response or a CRL, says about the status of
+/// a certificate.
+#[derive(Clone, Copy, Debug)]
+pub struct RevocationInfo {
+    /// The status of the certificate.
+    pub status: CertStatus,
+
+    /// When the information was produced: the `thisUpdate` of the OCSP
+    /// `SingleResponse` or of the CRL.
+    pub this_update: Time,
+
+    /// When newer information will be available: the `nextUpdate` of the
+    /// OCSP `SingleResponse` or of the CRL, if any.
+    pub next_update: Option<Time>,
+}
+
+impl RevocationInfo {
+    fn is_fresh(&self, policy: &RevocationPolicy, time: Time) -> bool {
+        if time < self.this_update {
+            return false;
+        }
+        let expires = match self.next_update {
+            Some(next_update) => Some(next_update),
+            None => self.this_update
+                        .checked_add(policy.max_age_without_next_update),
+        };
+        match expires {
+            Some(expires) => time <= expires,
+            None => true, // The expiration time can't be represented.
+        }
+    }
+}
+
+/// How revocation information is evaluated by a `RevocationChecker`.
+#[derive(Clone, Copy, Debug)]
+pub struct RevocationPolicy {
+    /// How long information without a `next_update` is considered fresh
+    /// after its `this_update`.
+    pub max_age_without_next_update: Duration,
+
+    /// Whether a certificate is accepted when there is no fresh information
+    /// about its status ("soft fail"). When this is `false`, such a
+    /// certificate is rejected with `Error::RevocationStatusUnknown`
+    /// ("hard fail").
+    pub allow_unknown_status: bool,
+
+    /// If set, certificates whose validity period is no longer than this are
+    /// exempt from needing fresh revocation information, e.g.
+    /// `Duration::from_secs(10 * 24 * 60 * 60)` for the "short-lived"
+    /// certificates of the CA/Browser Forum Baseline Requirements. Like those
+    /// requirements and RFC 5280 Section 4.1.2.5, the validity period
+    /// includes both `notBefore` and `notAfter`. Only applies to
+    /// `RevocationChecker::check_cert`.
+    pub short_lived_threshold: Option<Duration>,
+}
+
+impl Default for RevocationPolicy {
+    /// Soft fail, considering information without a `next_update` fresh for
+    /// seven days, which is the longest validity period of an OCSP response
+    /// that the CA/Browser Forum Baseline Requirements allow for subscriber
+    /// certificates (Section 4.9.10), and without a short-lived certificate
+    /// exemption.
+    fn default() -> RevocationPolicy {
+        RevocationPolicy {
+            max_age_without_next_update: Duration::from_secs(7 * 24 * 60 * 60),
+            allow_unknown_status: true,
+            short_lived_threshold: None,
+        }
+    }
+}
+
+/// The source of the information that a `RevocationChecker` based its
+/// decision on.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RevocationSource {
+    /// A stapled OCSP response.
+    StapledOcsp,
+
+    /// A CRL.
+    Crl,
+
+    /// `RevocationPolicy::short_lived_threshold`, because no source had fresh
+    /// information about the certificate and the certificate is short-lived.
+    ShortLived,
+
+    /// `RevocationPolicy::allow_unknown_status`, because no source had fresh
+    /// information about the certificate.
+    PolicyDefault,
+}
+
+/// Decides whether a certificate is revoked.
+///
+/// The sources of information are considered in this order:
+///
+/// 1. A `Revoked` status from any source, fresh or not, since a revocation is
+///    permanent.
+/// 2. A fresh stapled OCSP response with a known status.
+/// 3. A fresh CRL with a known status.
+/// 4. The short-lived certificate exemption,
+///    `RevocationPolicy::short_lived_threshold`.
+/// 5. The policy default, `RevocationPolicy::allow_unknown_status`.
+#[derive(Clone, Copy, Debug)]
+pub struct RevocationChecker {
+    policy: RevocationPolicy,
+}
+
+impl RevocationChecker {
+    /// A checker that evaluates revocation information according to
+    /// `policy`.
+    pub fn new(policy: RevocationPolicy) -> RevocationChecker {
+        RevocationChecker { policy: policy }
+    }
+
+    /// Decides whether a certificate is revoked at `times.current_time`,
+    /// given what a stapled OCSP response and a (possibly cached) CRL say
+    /// about it. `times` should be the times the certificate was verified
+    /// with, so that a certificate verified at a historical validity time is
+    /// still checked against current revocation information.
+    ///
+    /// Returns the source that the certificate was accepted on the basis of,
+    /// `Err(Error::CertRevoked)` if it is revoked, or
+    /// `Err(Error::RevocationStatusUnknown)` if there is no fresh information
+    /// about it and the policy requires some.
+    pub fn check(&self, stapled_ocsp: Option<&RevocationInfo>,
+                 crl: Option<&RevocationInfo>, times: VerificationTimes)
+                 -> Result<RevocationSource, Error> {
+        self.check_sources(stapled_ocsp, crl, times.current_time, false)
+    }
+
+    /// Like `check`, but also applies `RevocationPolicy::short_lived_threshold`
+    /// to `cert`.
+    pub fn check_cert(&self, cert: &EndEntityCert,
+                      stapled_ocsp: Option<&RevocationInfo>,
+                      crl: Option<&RevocationInfo>, times: VerificationTimes)
+                      -> Result<RevocationSource, Error> {
+        let short_lived = match self.policy.short_lived_threshold {
+            Some(threshold) => try!(validity_period(cert)) <= threshold,
+            None => false,
+        };
+        self.check_sources(stapled_ocsp, crl, times.current_time, short_lived)
+    }
+
+    /// Like `check_cert`, but the OCSP information is looked up in `cache`
+    /// by `cert_id` first, and `ocsp` is only called if the cache has no
+    /// fresh information. `ocsp` obtains the information from an OCSP
+    /// response, e.g. by verifying the stapled response or by fetching one,
+    /// and what it returns is put in the cache.
+    ///
+    /// A cached `Revoked` status is used even if it isn't fresh, since a
+    /// revocation is permanent. The information is reported as coming from
+    /// `RevocationSource::StapledOcsp` whether or not it was cached.
+    pub fn check_cert_with_ocsp_cache(
+            &self, cert: &EndEntityCert, cert_id: &CertId, cache: &OcspCache,
+            ocsp: &Fn() -> Option<RevocationInfo>,
+            crl: Option<&RevocationInfo>, times: VerificationTimes)
+            -> Result<RevocationSource, Error> {
+        let cached = match cache.get(cert_id) {
+            Some(info) if info.status == CertStatus::Revoked ||
+                          info.is_fresh(&self.policy, times.current_time) =>
+                Some(info),
+            _ => None,
+        };
+        let ocsp = match cached {
+            Some(info) => Some(info),
+            None => {
+                let info = ocsp();
+                if let Some(ref info) = info {
+                    cache.put(cert_id, info);
+                }
+                info
+            },
+        };
+        self.check_cert(cert, ocsp.as_ref(), crl, times)
+    }
+
+    fn check_sources(&self, stapled_ocsp: Option<&RevocationInfo>,
+              crl: Option<&RevocationInfo>, time: Time, short_lived: bool)
+              -> Result<RevocationSource, Error> {
+        let sources = [
+            (RevocationSource::StapledOcsp, stapled_ocsp),
+            (RevocationSource::Crl, crl),
+        ];
+
+        for &(_, info) in sources.iter() {
+            if let Some(&RevocationInfo { status: CertStatus::Revoked, .. }) =
+                    info {
+                return Err(Error::CertRevoked);
+            }
+        }
+
+        for &(source, info) in sources.iter() {
+            if let Some(info) = info {
+                if info.status == CertStatus::Good &&
+                   info.is_fresh(&self.policy, time) {
+                    return Ok(source);
+                }
+            }
+        }
+
+        if short_lived {
+            Ok(RevocationSource::ShortLived)
+        } else if self.policy.allow_unknown_status {
+            Ok(RevocationSource::PolicyDefault)
+        } else {
+            Err(Error::RevocationStatusUnknown)
+        }
+    }
+}
+
+/// A cache of what OCSP responses say about certificates, keyed by the OCSP
+/// `CertID` of the certificate. See
+/// `RevocationChecker::check_cert_with_ocsp_cache`.
+///
+/// The cache is shared between verifications, so it must do its own
+/// locking. It may discard entries at any time; `RevocationInfo::this_update`
+/// and `RevocationInfo::next_update` are the validity window of an entry,
+/// after which the checker won't use it anyway unless it is `Revoked`.
+pub trait OcspCache {
+    /// The information cached for the certificate identified by `cert_id`,
+    /// if any.
+    fn get(&self, cert_id: &CertId) -> Option<RevocationInfo>;
+
+    /// Caches `info`, obtained from an OCSP response, for the certificate
+    /// identified by `cert_id`, replacing any previous information.
+    fn put(&self, cert_id: &CertId, info: &RevocationInfo);
+}
+
+/// An `OcspCache` that doesn't cache anything.
+#[derive(Clone, Copy, Debug)]
+pub struct NoOcspCache;
+
+impl OcspCache for NoOcspCache {
+    fn get(&self, _: &CertId) -> Option<RevocationInfo> { None }
+    fn put(&self, _: &CertId, _: &RevocationInfo) {}
+}
+
+/// A compact, pre-aggregated set of revoked certificates, e.g. a CRLite-style
+/// Bloom filter cascade, which allows clients to check revocation without
+/// fetching OCSP responses or CRLs. See
+/// `VerificationOptions::revocation_filter`.
+///
+/// As in CRLite, a certificate is identified by the SHA-256 hash of the
+/// DER-encoded `SubjectPublicKeyInfo`, including the tag and length, of its
+/// issuer, and by its serial number. The filter is consulted for every
+/// certificate of a candidate path once its signature has been verified, so
+/// a revoked intermediate certificate makes path building try other paths.
+pub trait RevocationFilter {
+    /// Whether the certificate with the big-endian serial number
+    /// `serial_number`, without any leading zero byte, issued by the key
+    /// with the hash `issuer_spki_hash` is revoked at `time`, which is the
+    /// `VerificationTimes::current_time` of the verification. A filter that
+    /// doesn't record when revocations took effect can ignore `time`.
+    fn is_revoked(&self, issuer_spki_hash: &[u8], serial_number: &[u8],
+                  time: Time) -> bool;
+}
+
+/// A revoked certificate in a `RevokedCertList`, identified as for
+/// `RevocationFilter::is_revoked`.
+#[derive(Clone, Copy, Debug)]
+pub struct RevokedCert<'a> {
+    /// The SHA-256 hash of the issuer's DER-encoded `SubjectPublicKeyInfo`.
+    pub issuer_spki_hash: &'a [u8],
+
+    /// The certificate's serial number, as for `EndEntityCert::serial_number`.
+    pub serial_number: &'a [u8],
+}
+
+/// A `RevocationFilter` that is an exact list of revoked certificates, e.g. a
+/// list of certificates whose keys are known to be compromised. The list is
+/// searched linearly, so it is meant for short lists; large sets should use
+/// a probabilistic structure like a filter cascade instead.
+#[derive(Clone, Copy, Debug)]
+pub struct RevokedCertList<'a> {
+    revoked: &'a [RevokedCert<'a>],
+}
+
+impl<'a> RevokedCertList<'a> {
+    /// A filter in which exactly the certificates in `revoked` are revoked.
+    pub fn new(revoked: &'a [RevokedCert<'a>]) -> RevokedCertList<'a> {
+        RevokedCertList { revoked: revoked }
+    }
+}
+
+impl<'a> RevocationFilter for RevokedCertList<'a> {
+    fn is_revoked(&self, issuer_spki_hash: &[u8], serial_number: &[u8],
+                  _: Time) -> bool {
+        self.revoked.iter().any(|revoked| {
+            revoked.issuer_spki_hash == issuer_spki_hash &&
+            revoked.serial_number == serial_number
+        })
+    }
+}
+
+// The validity period of `cert`, counting both `notBefore` and `notAfter`, so
+// that a certificate valid for exactly ten days has a `notAfter` one second
+// less than ten days after its `notBefore`.
+fn validity_period(cert: &EndEntityCert) -> Result<Duration, Error> {
+    cert.inner.validity.read_all(Error::BadDER, |value| {
+        let not_before = try!(der::time_choice(value));
+        let not_after = try!(der::time_choice(value));
+        not_after.checked_duration_since(not_before)
+                 .map(|period| period + Duration::from_secs(1))
+                 .ok_or(Error::InvalidCertValidity)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CertStatus, NoOcspCache, OcspCache, RevocationChecker,
+                RevocationFilter, RevocationInfo, RevocationPolicy,
+                RevocationSource, RevokedCert, RevokedCertList};
+    use core::cell::Cell;
+    use ocsp::{self, CertId};
+    use {EndEntityCert, Error, Time, VerificationTimes};
+
+    fn info(status: CertStatus, this_update: u64, next_update: Option<u64>)
+            -> RevocationInfo {
+        RevocationInfo {
+            status: status,
+            this_update: Time::from_seconds_since_unix_epoch(this_update),
+            next_update: next_update.map(Time::from_seconds_since_unix_epoch),
+        }
+    }
+
+    #[test]
+    fn test_precedence() {
+        let soft = RevocationChecker::new(RevocationPolicy::default());
+        let hard = RevocationChecker::new(RevocationPolicy {
+            allow_unknown_status: false,
+            ..RevocationPolicy::default()
+        });
+        let time = Time::from_seconds_since_unix_epoch(1000);
+        let times = VerificationTimes::at(time);
+
+        let good = info(CertStatus::Good, 900, Some(1100));
+        let stale = info(CertStatus::Good, 800, Some(900));
+        let future = info(CertStatus::Good, 1100, Some(1200));
+        let unknown = info(CertStatus::Unknown, 900, Some(1100));
+        let revoked = info(CertStatus::Revoked, 800, Some(900));
+
+        assert_eq!(Ok(RevocationSource::StapledOcsp),
+                   hard.check(Some(&good), Some(&good), times));
+        assert_eq!(Ok(RevocationSource::Crl),
+                   hard.check(Some(&stale), Some(&good), times));
+        assert_eq!(Ok(RevocationSource::Crl),
+                   hard.check(Some(&unknown), Some(&good), times));
+        assert_eq!(Ok(RevocationSource::Crl),
+                   hard.check(Some(&future), Some(&good), times));
+        assert_eq!(Err(Error::CertRevoked),
+                   hard.check(Some(&good), Some(&revoked), times));
+        assert_eq!(Err(Error::RevocationStatusUnknown),
+                   hard.check(Some(&stale), None, times));
+        assert_eq!(Ok(RevocationSource::PolicyDefault),
+                   soft.check(Some(&stale), None, times));
+        assert_eq!(Ok(RevocationSource::PolicyDefault),
+                   soft.check(None, None, times));
+        assert_eq!(Ok(RevocationSource::ShortLived),
+                   hard.check_sources(Some(&stale), None, time, true));
+        assert_eq!(Ok(RevocationSource::StapledOcsp),
+                   hard.check_sources(Some(&good), None, time, true));
+        assert_eq!(Err(Error::CertRevoked),
+                   hard.check_sources(Some(&revoked), None, time, true));
+
+        // The information is evaluated at the current time, not at the time
+        // the validity periods of the certificates were checked at.
+        let signed_earlier = VerificationTimes::signed_at(
+            Time::from_seconds_since_unix_epoch(500), time);
+        assert_eq!(Ok(RevocationSource::StapledOcsp),
+                   hard.check(Some(&good), None, signed_earlier));
+    }
+
+    #[test]
+    fn test_max_age_without_next_update() {
+        let checker = RevocationChecker::new(RevocationPolicy {
+            allow_unknown_status: false,
+            ..RevocationPolicy::default()
+        });
+        let week = 7 * 24 * 60 * 60;
+        let crl = info(CertStatus::Good, 1000, None);
+        assert_eq!(Ok(RevocationSource::Crl),
+                   checker.check(None, Some(&crl), VerificationTimes::at(
+                       Time::from_seconds_since_unix_epoch(1000 + week))));
+        assert_eq!(Err(Error::RevocationStatusUnknown),
+                   checker.check(None, Some(&crl), VerificationTimes::at(
+                       Time::from_seconds_since_unix_epoch(1001 + week))));
+    }
+
+    // Caches the information about one certificate.
+    struct OneEntryCache {
+        cert_id: CertId<'static>,
+        info: Cell<Option<RevocationInfo>>,
+    }
+
+    impl OcspCache for OneEntryCache {
+        fn get(&self, cert_id: &CertId) -> Option<RevocationInfo> {
+            if *cert_id != self.cert_id {
+                return None;
+            }
+            self.info.get()
+        }
+
+        fn put(&self, _: &CertId, info: &RevocationInfo) {
+            self.info.set(Some(*info));
+        }
+    }
+
+    #[test]
+    fn test_ocsp_cache() {
+        let ee_der: &[u8] = include_bytes!("../tests/data/ecdsa-ee.der");
+        let issuer_der: &[u8] =
+            include_bytes!("../tests/data/ecdsa-intermediate.der");
+        let ee = EndEntityCert::from(ee_der).unwrap();
+        let cert_id = ocsp::cert_id(&ee, issuer_der).unwrap();
+        let checker = RevocationChecker::new(RevocationPolicy {
+            allow_unknown_status: false,
+            ..RevocationPolicy::default()
+        });
+        let times =
+            VerificationTimes::at(Time::from_seconds_since_unix_epoch(1000));
+
+        let calls = Cell::new(0);
+        let good = || {
+            calls.set(calls.get() + 1);
+            Some(info(CertStatus::Good, 900, Some(1100)))
+        };
+        let none = || {
+            calls.set(calls.get() + 1);
+            None
+        };
+
+        let cache = OneEntryCache {
+            cert_id: cert_id,
+            info: Cell::new(None),
+        };
+        assert_eq!(Ok(RevocationSource::StapledOcsp),
+                   checker.check_cert_with_ocsp_cache(
+                       &ee, &cert_id, &cache, &good, None, times));
+        assert_eq!(1, calls.get());
+        assert_eq!(Ok(RevocationSource::StapledOcsp),
+                   checker.check_cert_with_ocsp_cache(
+                       &ee, &cert_id, &cache, &none, None, times));
+        assert_eq!(1, calls.get());
+
+        // The serial number is the same but the issuer's key isn't, so the
+        // cached information isn't used. collision-intermediate has the same
+        // subject as ecdsa-intermediate.
+        let other_cert_id = ocsp::cert_id(&ee, include_bytes!(
+            "../tests/data/collision-intermediate.der")).unwrap();
+        assert_eq!(cert_id.serial_number(), other_cert_id.serial_number());
+        assert!(cert_id != other_cert_id);
+        assert_eq!(Err(Error::RevocationStatusUnknown),
+                   checker.check_cert_with_ocsp_cache(
+                       &ee, &other_cert_id, &cache, &none, None, times));
+        assert_eq!(2, calls.get());
+
+        // The cached information is stale.
+        assert_eq!(Err(Error::RevocationStatusUnknown),
+                   checker.check_cert_with_ocsp_cache(
+                       &ee, &cert_id, &cache, &none, None,
+                       VerificationTimes::at(
+                           Time::from_seconds_since_unix_epoch(1200))));
+        assert_eq!(3, calls.get());
+
+        // A revocation is used even when it is stale.
+        cache.info.set(Some(info(CertStatus::Revoked, 800, Some(900))));
+        assert_eq!(Err(Error::CertRevoked),
+                   checker.check_cert_with_ocsp_cache(
+                       &ee, &cert_id, &cache, &good, None, times));
+        assert_eq!(3, calls.get());
+
+        assert_eq!(Err(Error::RevocationStatusUnknown),
+                   checker.check_cert_with_ocsp_cache(
+                       &ee, &cert_id, &NoOcspCache, &none, None, times));
+        assert_eq!(4, calls.get());
+    }
+
+    #[test]
+    fn test_revoked_cert_list() {
+        let revoked = [
+            RevokedCert { issuer_spki_hash: &[1; 32], serial_number: &[1] },
+            RevokedCert { issuer_spki_hash: &[2; 32], serial_number: &[2] },
+        ];
+        let list = RevokedCertList::new(&revoked);
+        let time = Time::from_seconds_since_unix_epoch(1000);
+        assert!(list.is_revoked(&[1; 32], &[1], time));
+        assert!(list.is_revoked(&[2; 32], &[2], time));
+        assert!(!list.is_revoked(&[1; 32], &[2], time));
+        assert!(!list.is_revoked(&[3; 32], &[1], time));
+    }
+}
@@ -0,0 +1,327 @@
+// Copyright 2016 Brian Smith.
+//
+// Permission to use, copy, modify, and/or distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHORS DISCLAIM ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHORS BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+use {AllocScope, Error, TrustAnchor, Usage};
+use std;
+use std::vec::Vec;
+use trust_anchor_util::cert_der_as_trust_anchor;
+
+#[cfg(feature = "openssl_dir")]
+use {der, verify_cert};
+#[cfg(feature = "openssl_dir")]
+use std::path::PathBuf;
+#[cfg(feature = "openssl_dir")]
+use untrusted;
+
+/// A root certificate loaded from a platform root store, together with the
+/// usages that the platform trusts it for.
+#[derive(Clone, Debug)]
+pub struct PlatformRoot {
+    /// The DER-encoded certificate.
+    pub cert_der: Vec<u8>,
+
+    /// The usages that the root is trusted for. Platform trust settings,
+    /// e.g. the trust settings of Security.framework, the enhanced key usage
+    /// property of a CryptoAPI certificate context, or the trust and reject
+    /// lists of an OpenSSL "TRUSTED CERTIFICATE", are mapped onto these.
+    pub usages: Vec<Usage>,
+}
+
+impl PlatformRoot {
+    /// Interprets the certificate as a `TrustAnchor`; see
+    /// `cert_der_as_trust_anchor`.
+    pub fn trust_anchor<'a>(&'a self) -> Result<TrustAnchor<'a>, Error> {
+        cert_der_as_trust_anchor(&self.cert_der)
+    }
+
+    /// Whether the root is trusted for `usage`.
+    pub fn is_trusted_for(&self, usage: Usage) -> bool {
+        self.usages.contains(&usage)
+    }
+}
+
+/// An error loading roots from a platform root store.
+#[derive(Debug)]
+pub enum RootStoreError {
+    /// The store couldn't be read.
+    Io(std::io::Error),
+
+    /// The store contains data that isn't in the expected format.
+    InvalidData,
+}
+
+impl From<std::io::Error> for RootStoreError {
+    fn from(error: std::io::Error) -> RootStoreError {
+        RootStoreError::Io(error)
+    }
+}
+
+/// A source of root certificates and their trust settings, such as an
+/// operating system's root store.
+///
+/// This is the integration point for applications that want to offer "use
+/// the system roots" while still verifying certificates with webpki's
+/// semantics: the platform decides which roots are trusted for which
+/// usages, and webpki does the path building and validation, e.g. with a
+/// `TrustDomains` built from `trust_anchors_for_usage`. Providers for
+/// Security.framework and CryptoAPI need bindings to those platforms, so
+/// they are expected to live outside of webpki; `OpensslDirectory` provides
+/// the OpenSSL certificate directory layout used by most Unix systems.
+pub trait RootStoreProvider {
+    /// Loads the roots of the store. Roots that the platform distrusts for
+    /// every usage must be omitted.
+    fn load_roots(&self) -> Result<Vec<PlatformRoot>, RootStoreError>;
+}
+
+/// The trust anchors of `roots` that are trusted for `usage`, in the order
+/// of `roots`.
+///
+/// Like platform verifiers do, a root whose certificate webpki can't parse is
+/// skipped rather than making the whole store unusable. Use
+/// `PlatformRoot::trust_anchor` to detect such roots.
+pub fn trust_anchors_for_usage<'a>(roots: &'a [PlatformRoot], usage: Usage)
+                                   -> Vec<TrustAnchor<'a>> {
+    let mut trust_anchors = Vec::new();
+    let _ = trust_anchors_for_usage_in(roots, usage, &mut trust_anchors);
+    trust_anchors
+}
+
+/// Like `trust_anchors_for_usage`, but the trust anchors are appended to
+/// `out`, e.g. a `BumpArena` that is reused whenever the roots are reloaded.
+/// Fails with `Error::InputTooLong`, after appending the trust anchors that
+/// fit, if `out` runs out of room.
+pub fn trust_anchors_for_usage_in<'a>(roots: &'a [PlatformRoot], usage: Usage,
+                                      out: &mut AllocScope<TrustAnchor<'a>>)
+                                      -> Result<(), Error> {
+    for root in roots.iter().filter(|root| root.is_trusted_for(usage)) {
+        if let Ok(trust_anchor) = root.trust_anchor() {
+            try!(out.push(trust_anchor));
+        }
+    }
+    Ok(())
+}
+
+/// A `RootStoreProvider` for a directory in the layout that OpenSSL's
+/// `X509_LOOKUP_hash_dir` uses, e.g. `/etc/ssl/certs`.
+///
+/// Every file in the directory is read, and every PEM-encoded "CERTIFICATE"
+/// and "TRUSTED CERTIFICATE" in it is loaded; other files, like CRLs, are
+/// ignored. A "CERTIFICATE" is trusted for all usages, like OpenSSL trusts
+/// it. The usages of a "TRUSTED CERTIFICATE" are restricted by the trust
+/// and reject lists of its auxiliary trust information, e.g. as set by
+/// `openssl x509 -addtrust serverAuth`. A certificate that is in the
+/// directory more than once, e.g. through the hash symlinks created by
+/// `c_rehash`, is loaded once, with the trust settings of the file whose
+/// name sorts first.
+#[cfg(feature = "openssl_dir")]
+#[derive(Clone, Debug)]
+pub struct OpensslDirectory {
+    path: PathBuf,
+}
+
+#[cfg(feature = "openssl_dir")]
+impl OpensslDirectory {
+    /// A provider for the directory at `path`.
+    pub fn new<P: Into<PathBuf>>(path: P) -> OpensslDirectory {
+        OpensslDirectory { path: path.into() }
+    }
+}
+
+#[cfg(feature = "openssl_dir")]
+impl RootStoreProvider for OpensslDirectory {
+    fn load_roots(&self) -> Result<Vec<PlatformRoot>, RootStoreError> {
+        use std::io::Read;
+
+        let mut paths = Vec::new();
+        for entry in try!(std::fs::read_dir(&self.path)) {
+            paths.push(try!(entry).path());
+        }
+        paths.sort();
+
+        let mut roots: Vec<PlatformRoot> = Vec::new();
+        for path in paths {
+            if !path.is_file() {
+                continue;
+            }
+            let mut contents = std::string::String::new();
+            let _ = try!(try!(std::fs::File::open(&path))
+                             .read_to_string(&mut contents));
+            for root in try!(parse_pem_roots(&contents)) {
+                if !roots.iter().any(|r| r.cert_der == root.cert_der) {
+                    roots.push(root);
+                }
+            }
+        }
+        Ok(roots)
+    }
+}
+
+#[cfg(feature = "openssl_dir")]
+const ALL_USAGES: &'static [Usage] = &[
+    Usage::TlsServer,
+    Usage::TlsClient,
+    Usage::CodeSigning,
+    Usage::EmailProtection,
+    Usage::TcgEndorsementKey,
+    Usage::TcgAttestationIdentityKey,
+    Usage::TimeStamping,
+    Usage::IpsecIke,
+];
+
+// The roots in the PEM blocks of `contents`. Roots that are rejected for all
+// usages are omitted.
+#[cfg(feature = "openssl_dir")]
+fn parse_pem_roots(contents: &str)
+                   -> Result<Vec<PlatformRoot>, RootStoreError> {
+    use rustc_serialize::base64::FromBase64;
+
+    let mut roots = Vec::new();
+    let mut lines = contents.lines().map(|line| line.trim());
+    while let Some(line) = lines.next() {
+        let trusted = match line {
+            "-----BEGIN CERTIFICATE-----" => false,
+            "-----BEGIN TRUSTED CERTIFICATE-----" => true,
+            _ => continue,
+        };
+        let mut base64 = std::string::String::new();
+        loop {
+            match lines.next() {
+                Some(line) if line.starts_with("-----END ") => break,
+                Some(line) => base64.push_str(line),
+                None => return Err(RootStoreError::InvalidData),
+            }
+        }
+        let der = try!(base64.from_base64()
+                             .map_err(|_| RootStoreError::InvalidData));
+        let root = if trusted {
+            try!(trusted_certificate(&der).ok_or(RootStoreError::InvalidData))
+        } else {
+            PlatformRoot { cert_der: der, usages: ALL_USAGES.to_vec() }
+        };
+        if !root.usages.is_empty() {
+            roots.push(root);
+        }
+    }
+    Ok(roots)
+}
+
+// An OpenSSL "TRUSTED CERTIFICATE" is a certificate followed by:
+//
+// X509_CERT_AUX ::= SEQUENCE {
+//      trust      SEQUENCE OF OBJECT IDENTIFIER OPTIONAL,
+//      reject     [0] IMPLICIT SEQUENCE OF OBJECT IDENTIFIER OPTIONAL,
+//      alias      UTF8String OPTIONAL,
+//      keyid      OCTET STRING OPTIONAL,
+//      other      [1] IMPLICIT SEQUENCE OF AlgorithmIdentifier OPTIONAL }
+//
+// A usage is trusted if its extended key usage, or anyExtendedKeyUsage, is in
+// `trust` (or if `trust` is absent), and neither is in `reject`.
+#[cfg(feature = "openssl_dir")]
+fn trusted_certificate(der: &[u8]) -> Option<PlatformRoot> {
+    // anyExtendedKeyUsage, 2.5.29.37.0.
+    const ANY_EKU: &'static [u8] = &[0x55, 0x1d, 0x25, 0x00];
+
+    fn read_oids<'a>(input: &mut untrusted::Reader<'a>, tag: u8)
+                     -> Result<Option<untrusted::Input<'a>>, Error> {
+        if !input.peek(tag) {
+            return Ok(None);
+        }
+        let (_, oids) = try!(der::read_tag_and_get_value(input));
+        try!(oids.read_all(Error::BadDER, |oids| {
+            while !oids.at_end() {
+                let _ = try!(der::expect_tag_and_get_value(oids,
+                                                           der::Tag::OID));
+            }
+            Ok(())
+        }));
+        Ok(Some(oids))
+    }
+
+    fn contains(oids: untrusted::Input, oid: &[u8]) -> bool {
+        let mut oids = untrusted::Reader::new(oids);
+        while !oids.at_end() {
+            match der::expect_tag_and_get_value(&mut oids, der::Tag::OID) {
+                Ok(value) if value.as_slice_less_safe() == oid => {
+                    return true;
+                },
+                Ok(_) => (),
+                Err(_) => { return false; },
+            }
+        }
+        false
+    }
+
+    let parsed = untrusted::Input::from(der).read_all(Error::BadDER, |input| {
+        let mark = input.mark();
+        let _ = try!(der::expect_tag_and_get_value(input, der::Tag::Sequence));
+        let cert = input.get_input_between_marks(mark, input.mark()).unwrap();
+        let (trust, reject) = try!(der::nested(input, der::Tag::Sequence,
+                                               Error::BadDER, |aux| {
+            let trust = try!(read_oids(aux, der::Tag::Sequence as u8));
+            let reject = try!(read_oids(aux, 0xa0));
+            let _ = aux.skip_to_end();
+            Ok((trust, reject))
+        }));
+        Ok((cert, trust, reject))
+    });
+    let (cert, trust, reject) = match parsed {
+        Ok(parsed) => parsed,
+        Err(_) => { return None; }
+    };
+
+    let listed = |oids: Option<untrusted::Input>, usage: Usage| match oids {
+        Some(oids) => contains(oids, ANY_EKU) ||
+                      contains(oids, verify_cert::usage_eku(usage).oid_value),
+        None => false,
+    };
+    let usages = ALL_USAGES.iter()
+        .cloned()
+        .filter(|&usage| trust.is_none() || listed(trust, usage))
+        .filter(|&usage| !listed(reject, usage))
+        .collect();
+
+    Some(PlatformRoot {
+        cert_der: cert.as_slice_less_safe().to_vec(),
+        usages: usages,
+    })
+}
+
+#[cfg(all(test, feature = "openssl_dir"))]
+mod tests {
+    use super::parse_pem_roots;
+    use Usage;
+
+    // A self-signed certificate, as output by `openssl x509 -trustout
+    // -addtrust serverAuth -addtrust clientAuth -addreject clientAuth`.
+    const TRUSTED_CERTIFICATE: &'static str = concat!(
+        "-----BEGIN TRUSTED CERTIFICATE-----\n",
+        "MIIBcjCCARmgAwIBAgIUWa0mihFPQEWwvoTZ+XKVz80XXVUwCgYIKoZIzj0EAwIw\n",
+        "DzENMAsGA1UEAwwEcm9vdDAeFw0yNjEwMTYwOTQwNTlaFw0zNjEwMTMwOTQwNTla\n",
+        "MA8xDTALBgNVBAMMBHJvb3QwWTATBgcqhkjOPQIBBggqhkjOPQMBBwNCAATZn0zX\n",
+        "aNdZj9Y6S21OMVzXBPlbSn/tydEveQAXyHNaiGcNwjn6GPPT/jFg4AZqT0jUA1M4\n",
+        "a2iJXXlHihpldkGoo1MwUTAdBgNVHQ4EFgQUbH3oTjo/YxYTxVzoaEXR6/eaR4sw\n",
+        "HwYDVR0jBBgwFoAUbH3oTjo/YxYTxVzoaEXR6/eaR4swDwYDVR0TAQH/BAUwAwEB\n",
+        "/zAKBggqhkjOPQQDAgNHADBEAiAZe3m1mtgWFSAHmiyYdskatt4resyhF0+0TYf9\n",
+        "AjITywIgXCybBKE7MdSiC3S+o3W/bx7Z7nhDO2LR/Kf+BQf/J58wIjAUBggrBgEF\n",
+        "BQcDAQYIKwYBBQUHAwKgCgYIKwYBBQUHAwI=\n",
+        "-----END TRUSTED CERTIFICATE-----\n");
+
+    #[test]
+    fn trusted_certificate_test() {
+        let roots = parse_pem_roots(TRUSTED_CERTIFICATE).unwrap();
+        assert_eq!(1, roots.len());
+        assert_eq!(&[Usage::TlsServer][..], &roots[0].usages[..]);
+        assert!(roots[0].trust_anchor().is_ok());
+    }
+}
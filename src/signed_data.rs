@@ -12,13 +12,17 @@
 // ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
 // OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
 
-use {der, Error};
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::slice;
+use {der, Der, Error, Policy};
 use ring::signature;
 use untrusted;
 
 /// X.509 certificates and related items that are signed are almost always
 /// encoded in the format "tbs||signatureAlgorithm||signature". This structure
 /// captures this pattern.
+#[derive(Clone)]
 pub struct SignedData<'a> {
     /// The signed data. This would be `tbsCertificate` in the case of an X.509
     /// certificate, `tbsResponseData` in the case of an OCSP response, and the
@@ -86,6 +90,28 @@ pub fn parse_signed_data<'a>(der: &mut untrusted::Reader<'a>)
         }))
 }
 
+/// Like `parse_signed_data`, but the signed data may be longer than
+/// `MAX_DER_ELEMENT_LEN`, as the `tbsCertList` of a large CRL is.
+pub fn parse_long_signed_data<'a>(der: &mut untrusted::Reader<'a>)
+                                  -> Result<(untrusted::Input<'a>,
+                                             SignedData<'a>), Error> {
+    let mark1 = der.mark();
+    let tbs =
+        try!(der::expect_tag_and_get_long_value(der, der::Tag::Sequence));
+    let mark2 = der.mark();
+    let data = der.get_input_between_marks(mark1, mark2).unwrap();
+    let algorithm = try!(der::expect_tag_and_get_value(der,
+                                                       der::Tag::Sequence));
+    let signature = try!(der::bit_string_with_no_unused_bits(der));
+
+    Ok((tbs,
+        SignedData {
+            data: data,
+            algorithm: algorithm,
+            signature: signature
+        }))
+}
+
 /// Verify `signed_data` using the public key in the DER-encoded
 /// SubjectPublicKeyInfo `spki` using one of the algorithms in
 /// `supported_algorithms`.
@@ -95,7 +121,12 @@ pub fn parse_signed_data<'a>(der: &mut untrusted::Reader<'a>)
 /// ordering of the algorithms in `supported_algorithms` does not really matter,
 /// but generally more common algorithms should go first, as it is scanned
 /// linearly for matches.
-pub fn verify_signed_data(supported_algorithms: &[&SignatureAlgorithm],
+///
+/// `policy.allow_null_signature_algorithm_parameters` controls whether a
+/// `NULL` parameter is accepted for algorithms that otherwise require the
+/// parameters to be absent.
+pub fn verify_signed_data(policy: &Policy,
+                          supported_algorithms: &[&SignatureAlgorithm],
                           spki_value: untrusted::Input,
                           signed_data: &SignedData) -> Result<(), Error> {
     // We need to verify the signature in `signed_data` using the public key
@@ -126,19 +157,20 @@ pub fn verify_signed_data(supported_algorithms: &[&SignatureAlgorithm],
 
     let mut found_signature_alg_match = false;
     for supported_alg in supported_algorithms {
-        if !supported_alg.signature_alg_oids.into_iter()
-                                            .any(|oid| algorithm_id == *oid) {
+        if !signature_algorithm_matches(policy, supported_alg, algorithm_id,
+                                        parameters) {
             continue;
         }
 
-        if !supported_alg.public_key_alg.shared
-                         .allowed_signature_alg_parameters
-                         .into_iter().any(|allowed| parameters == *allowed) {
-            continue;
-        }
-
-        match verify_signature(supported_alg, spki_value, signed_data.data,
-                               signed_data.signature) {
+        let result = match policy.signature_requirements {
+            Some(ref requirements) =>
+                verify_signature_with_requirements(
+                    requirements, supported_alg, spki_value,
+                    signed_data.data, signed_data.signature),
+            None => verify_signature(supported_alg, spki_value,
+                                     signed_data.data, signed_data.signature),
+        };
+        match result {
             Err(Error::UnsupportedSignatureAlgorithmForPublicKey) => {
                 found_signature_alg_match = true;
                 continue;
@@ -154,27 +186,191 @@ pub fn verify_signed_data(supported_algorithms: &[&SignatureAlgorithm],
     }
 }
 
+// Returns whether a signature `AlgorithmIdentifier` with the given OID and
+// parameters identifies `supported_alg`.
+fn signature_algorithm_matches(policy: &Policy,
+                               supported_alg: &SignatureAlgorithm,
+                               algorithm_id: untrusted::Input,
+                               parameters: untrusted::Input) -> bool {
+    if !supported_alg.signature_alg_oids.iter()
+                                        .any(|oid| algorithm_id == *oid) {
+        return false;
+    }
+
+    let allowed_parameters =
+        supported_alg.public_key_alg.shared.allowed_signature_alg_parameters;
+    allowed_parameters.iter().any(|allowed| parameters == *allowed) ||
+        (policy.allow_null_signature_algorithm_parameters &&
+         parameters == NULL_OR_ABSENT_PARAMETERS[1] &&
+         allowed_parameters.iter()
+             .any(|allowed| *allowed == NULL_OR_ABSENT_PARAMETERS[0]))
+}
+
+// Checks that the signature `AlgorithmIdentifier` value `algorithm` identifies
+// one of `supported_algorithms`, without verifying any signature; the
+// signer's key might not be of the right type for it.
+pub fn check_signature_algorithm_is_supported(
+        policy: &Policy, supported_algorithms: &[&SignatureAlgorithm],
+        algorithm: untrusted::Input) -> Result<(), Error> {
+    let (algorithm_id, parameters) =
+            try!(algorithm.read_all(Error::BadDER, |input| {
+        let algorithm_id = try!(der::expect_tag_and_get_value(input,
+                                                              der::Tag::OID));
+        Ok((algorithm_id, input.skip_to_end()))
+    }));
+    let min_digest_bits = match policy.signature_requirements {
+        Some(ref requirements) => requirements.min_digest_bits,
+        None => 0,
+    };
+    if supported_algorithms.iter().any(|supported_alg| {
+        supported_alg.digest_bits >= min_digest_bits &&
+            signature_algorithm_matches(policy, supported_alg, algorithm_id,
+                                        parameters)
+    }) {
+        Ok(())
+    } else {
+        Err(Error::UnsupportedSignatureAlgorithm)
+    }
+}
+
+/// Returns whether the `AlgorithmIdentifier` values `a` and `b` (without their
+/// outer `SEQUENCE` tags) are equal.
+///
+/// If `null_equals_absent` is true then an absent `parameters` field is
+/// considered equal to a `NULL` one, like mozilla::pkix does; some
+/// certificate issuers encode the `AlgorithmIdentifier` with the `NULL` in
+/// one place and without it in the other.
+pub fn algorithm_identifiers_are_equal(a: untrusted::Input,
+                                       b: untrusted::Input,
+                                       null_equals_absent: bool) -> bool {
+    if a == b {
+        return true;
+    }
+    if !null_equals_absent {
+        return false;
+    }
+
+    fn parse_algorithm_identifier<'a>(input: untrusted::Input<'a>)
+            -> Result<(untrusted::Input<'a>, untrusted::Input<'a>), Error> {
+        input.read_all(Error::BadDER, |input| {
+            let algorithm_id =
+                try!(der::expect_tag_and_get_value(input, der::Tag::OID));
+            Ok((algorithm_id, input.skip_to_end()))
+        })
+    }
+
+    fn is_null_or_absent(parameters: untrusted::Input) -> bool {
+        parameters == NULL_OR_ABSENT_PARAMETERS[0] ||
+            parameters == NULL_OR_ABSENT_PARAMETERS[1]
+    }
+
+    match (parse_algorithm_identifier(a), parse_algorithm_identifier(b)) {
+        (Ok((a_oid, a_parameters)), Ok((b_oid, b_parameters))) =>
+            a_oid == b_oid && is_null_or_absent(a_parameters) &&
+                is_null_or_absent(b_parameters),
+        _ => false
+    }
+}
+
 pub fn verify_signature(signature_alg: &SignatureAlgorithm,
                         spki_value: untrusted::Input, msg: untrusted::Input,
                         signature: untrusted::Input) -> Result<(), Error> {
     let spki = try!(parse_spki_value(spki_value));
-    if spki.algorithm_oid !=
-            signature_alg.public_key_alg.shared.spki_algorithm_oid {
+    if !signature_alg.public_key_alg.matches(&spki) {
         return Err(Error::UnsupportedSignatureAlgorithmForPublicKey);
     }
 
-    match (spki.curve_oid, signature_alg.public_key_alg.curve_oid) {
-        (None, None) => (),
-        (Some(spki_oid), Some(supported_oid))
-                if spki_oid == supported_oid => (),
-        _ => { return Err(Error::UnsupportedSignatureAlgorithmForPublicKey); },
-    };
-
     signature::verify(signature_alg.verification_alg, spki.key_value, msg,
                       signature)
         .map_err(|_| Error::InvalidSignatureForPublicKey)
 }
 
+/// Minimum strengths for signatures, independent of the supported signature
+/// algorithms. See `EndEntityCert::verify_signature_with_requirements`.
+///
+/// The list of supported signature algorithms for verifying certificate
+/// chains often has to include weak algorithms, e.g. for old intermediate
+/// certificates. Signatures that the peer makes during the handshake, e.g.
+/// TLS 1.2's `ServerKeyExchange`, don't have that excuse, and shouldn't be
+/// weakened by accidentally reusing that list for them.
+///
+/// The default requires a digest of at least 256 bits, so SHA-1 is
+/// rejected, RSA keys of at least 2048 bits, and ECDSA keys on curves of at
+/// least 256 bits.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct SignatureRequirements {
+    /// The minimum length in bits of the signature algorithm's digest.
+    /// Signatures with shorter digests are rejected with
+    /// `Error::UnsupportedSignatureAlgorithm`.
+    pub min_digest_bits: usize,
+
+    /// The minimum length in bits of the modulus of an RSA key. Smaller
+    /// keys are rejected with `Error::PublicKeyTooSmall`.
+    pub min_rsa_modulus_bits: usize,
+
+    /// The minimum size in bits of the curve of an ECDSA key. Keys on
+    /// smaller curves are rejected with `Error::PublicKeyTooSmall`.
+    pub min_ecdsa_curve_bits: usize,
+}
+
+impl Default for SignatureRequirements {
+    fn default() -> SignatureRequirements {
+        SignatureRequirements {
+            min_digest_bits: 256,
+            min_rsa_modulus_bits: 2048,
+            min_ecdsa_curve_bits: 256,
+        }
+    }
+}
+
+// Like `verify_signature`, but first checks `signature_alg` and the key in
+// `spki_value` against `requirements`.
+pub fn verify_signature_with_requirements(
+        requirements: &SignatureRequirements,
+        signature_alg: &SignatureAlgorithm, spki_value: untrusted::Input,
+        msg: untrusted::Input, signature: untrusted::Input)
+        -> Result<(), Error> {
+    if signature_alg.digest_bits < requirements.min_digest_bits {
+        return Err(Error::UnsupportedSignatureAlgorithm);
+    }
+
+    let spki = try!(parse_spki_value(spki_value));
+    if !signature_alg.public_key_alg.matches(&spki) {
+        return Err(Error::UnsupportedSignatureAlgorithmForPublicKey);
+    }
+    let (key_bits, min_key_bits) =
+        match signature_alg.public_key_alg.curve_bits {
+            Some(curve_bits) =>
+                (curve_bits, requirements.min_ecdsa_curve_bits),
+            None => (try!(rsa_modulus_bits(spki.key_value)),
+                     requirements.min_rsa_modulus_bits),
+        };
+    if key_bits < min_key_bits {
+        return Err(Error::PublicKeyTooSmall);
+    }
+
+    verify_signature(signature_alg, spki_value, msg, signature)
+}
+
+// The length in bits of the modulus of the RSA public key `key_value`, an
+// `RSAPublicKey` (RFC 3447 Appendix A.1.1).
+fn rsa_modulus_bits(key_value: untrusted::Input) -> Result<usize, Error> {
+    let modulus = try!(key_value.read_all(Error::BadDER, |input| {
+        der::nested(input, der::Tag::Sequence, Error::BadDER, |input| {
+            let modulus = try!(der::positive_integer(input));
+            let _ = try!(der::positive_integer(input));
+            Ok(modulus)
+        })
+    }));
+    let modulus = modulus.as_slice_less_safe();
+    match modulus.iter().position(|&b| b != 0) {
+        Some(first) =>
+            Ok(((modulus.len() - first) * 8) -
+               (modulus[first].leading_zeros() as usize)),
+        None => Err(Error::BadDER),
+    }
+}
+
 
 struct SubjectPublicKeyInfo<'a> {
     algorithm_oid: untrusted::Input<'a>,
@@ -182,6 +378,59 @@ struct SubjectPublicKeyInfo<'a> {
     key_value: untrusted::Input<'a>,
 }
 
+/// A type of subject public key. See `Policy::end_entity_key_types`.
+///
+/// Unlike the `SignatureAlgorithm`s, these are available regardless of the
+/// `ecdsa` and `rsa` features, since they only identify keys.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum KeyType {
+    /// An ECDSA key on the P-256 curve (secp256r1).
+    EcdsaP256,
+
+    /// An ECDSA key on the P-384 curve (secp384r1).
+    EcdsaP384,
+
+    /// An RSA key of any size.
+    Rsa,
+}
+
+impl KeyType {
+    fn matches(self, spki: &SubjectPublicKeyInfo) -> bool {
+        // id-ecPublicKey, secp256r1, and secp384r1 from RFC 5480 Section 2.1.1;
+        // rsaEncryption from RFC 3279 Section 2.3.1.
+        static EC_PUBLIC_KEY_OID: [u8; 7] = oid_1_2_840_10045![2, 1];
+        static P256_OID: [u8; 8] = oid_1_2_840_10045![3, 1, 7];
+        static P384_OID: [u8; 5] = oid_1_3_132![0, 34];
+        static RSA_ENCRYPTION_OID: [u8; 9] = oid_1_2_840_113549![1, 1, 1];
+
+        let (algorithm_oid, curve_oid): (&[u8], Option<&[u8]>) = match self {
+            KeyType::EcdsaP256 => (&EC_PUBLIC_KEY_OID, Some(&P256_OID)),
+            KeyType::EcdsaP384 => (&EC_PUBLIC_KEY_OID, Some(&P384_OID)),
+            KeyType::Rsa => (&RSA_ENCRYPTION_OID, None),
+        };
+        spki.algorithm_oid == algorithm_oid &&
+            spki.curve_oid.map(|oid| oid.as_slice_less_safe()) == curve_oid
+    }
+}
+
+// Checks that the public key in `spki_value` is of one of the types allowed by
+// `policy.end_entity_key_types`. A key that can't be parsed isn't of any of
+// them.
+pub fn check_end_entity_key_type(policy: &Policy,
+                                 spki_value: untrusted::Input)
+                                 -> Result<(), Error> {
+    let key_types = match policy.end_entity_key_types {
+        Some(key_types) => key_types,
+        None => { return Ok(()); }
+    };
+    match parse_spki_value(spki_value) {
+        Ok(ref spki) if key_types.iter()
+                                 .any(|key_type| key_type.matches(spki)) =>
+            Ok(()),
+        _ => Err(Error::EndEntityKeyTypeNotAllowed),
+    }
+}
+
 // Parse the public key into an algorithm OID, an optional curve OID, and the
 // key value. The caller needs to check whether these match the
 // `PublicKeyAlgorithm` for the `SignatureAlgorithm` that is matched when
@@ -220,10 +469,111 @@ fn parse_spki_value(input: untrusted::Input)
 
 
 /// A signature algorithm.
+///
+/// It is formatted as the name of its constant, e.g. `ECDSA_P256_SHA256`,
+/// and two `SignatureAlgorithm`s are equal if they are the same constant.
 pub struct SignatureAlgorithm {
+    name: &'static str,
+    signature_alg_id: &'static [u8],
     signature_alg_oids: &'static [&'static [u8]],
+
+    // The length of the digest, e.g. 160 for SHA-1.
+    digest_bits: usize,
+
     public_key_alg: &'static PublicKeyAlgorithm,
-    verification_alg: &'static signature::VerificationAlgorithm,
+    // `Sync` so that `SignatureAlgorithm`s, and the lists of supported
+    // algorithms that refer to them, can be shared between threads.
+    verification_alg: &'static (signature::VerificationAlgorithm + Sync),
+}
+
+impl SignatureAlgorithm {
+    /// The name of the constant, e.g. `ECDSA_P256_SHA256`.
+    pub fn name(&self) -> &'static str { self.name }
+
+    /// The value of the `AlgorithmIdentifier` that identifies the algorithm
+    /// in a signature, e.g. in the `signatureAlgorithm` field of a
+    /// certificate, without the outer `SEQUENCE` tag and length, as in
+    /// `SignatureAlgorithmIds`. The parameters are encoded as the RFCs
+    /// require, e.g. as `NULL` for RSA PKCS#1 1.5 and absent for ECDSA;
+    /// verification also accepts some other encodings.
+    pub fn signature_alg_id(&self) -> Der<'static> {
+        Der::from(self.signature_alg_id)
+    }
+
+    /// The value of the `AlgorithmIdentifier` of the `subjectPublicKeyInfo`
+    /// of the keys that the algorithm verifies signatures with, without the
+    /// outer `SEQUENCE` tag and length, e.g. id-ecPublicKey with the
+    /// algorithm's curve.
+    pub fn public_key_alg_id(&self) -> Der<'static> {
+        Der::from(self.public_key_alg.alg_id)
+    }
+
+    /// Whether the algorithm verifies signatures with the public key `spki`,
+    /// the value of a `SubjectPublicKeyInfo` in the same form as
+    /// `TrustAnchor::spki`, i.e. whether the key is of the algorithm's type
+    /// and, for ECDSA, on the algorithm's curve. See
+    /// `EndEntityCert::compatible_signature_algorithms`.
+    ///
+    /// The key itself isn't checked; e.g. an RSA key that is too short for
+    /// the algorithm is still compatible.
+    pub fn is_compatible_with_spki(&self, spki: &[u8]) -> bool {
+        match parse_spki_value(untrusted::Input::from(spki)) {
+            Ok(ref spki) => self.public_key_alg.matches(spki),
+            Err(..) => false,
+        }
+    }
+}
+
+/// The signature algorithms of a slice that are compatible with a public key.
+/// See `EndEntityCert::compatible_signature_algorithms`.
+#[derive(Clone)]
+pub struct CompatibleSignatureAlgorithms<'a, 's> {
+    spki: untrusted::Input<'a>,
+    algorithms: slice::Iter<'s, &'s SignatureAlgorithm>,
+}
+
+pub fn compatible_signature_algorithms<'a, 's>(
+        spki: untrusted::Input<'a>, algorithms: &'s [&'s SignatureAlgorithm])
+        -> CompatibleSignatureAlgorithms<'a, 's> {
+    CompatibleSignatureAlgorithms {
+        spki: spki,
+        algorithms: algorithms.iter(),
+    }
+}
+
+impl<'a, 's> Iterator for CompatibleSignatureAlgorithms<'a, 's> {
+    type Item = &'s SignatureAlgorithm;
+
+    fn next(&mut self) -> Option<&'s SignatureAlgorithm> {
+        let spki = self.spki.as_slice_less_safe();
+        self.algorithms.by_ref()
+                       .find(|alg| alg.is_compatible_with_spki(spki))
+                       .cloned()
+    }
+}
+
+impl fmt::Debug for SignatureAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name)
+    }
+}
+
+impl fmt::Display for SignatureAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name)
+    }
+}
+
+impl PartialEq for SignatureAlgorithm {
+    fn eq(&self, other: &SignatureAlgorithm) -> bool {
+        self.name == other.name
+    }
+}
+
+impl Eq for SignatureAlgorithm {}
+
+impl Hash for SignatureAlgorithm {
+    fn hash<H: Hasher>(&self, state: &mut H) { self.name.hash(state) }
 }
 
 // RFC 5758 Section 3.2 (ECDSA with SHA-2), and RFC 3279 Section 2.2.3 (ECDSA
@@ -233,57 +583,89 @@ pub struct SignatureAlgorithm {
 // the other signature algorithms we support and for compatibility.
 
 /// ECDSA signatures using the P-256 curve and SHA-1. Deprecated.
+#[cfg(feature = "ecdsa")]
 pub static ECDSA_P256_SHA1: SignatureAlgorithm = SignatureAlgorithm {
+    name: "ECDSA_P256_SHA1",
+    signature_alg_id: ECDSA_SHA1_ALG_ID,
     signature_alg_oids: &[ECDSA_SHA1_OID],
+    digest_bits: 160,
     public_key_alg: &ECDSA_P256,
     verification_alg: &signature::ECDSA_P256_SHA1_ASN1,
 };
 
 /// ECDSA signatures using the P-256 curve and SHA-256.
+#[cfg(feature = "ecdsa")]
 pub static ECDSA_P256_SHA256: SignatureAlgorithm = SignatureAlgorithm {
+    name: "ECDSA_P256_SHA256",
+    signature_alg_id: ECDSA_SHA256_ALG_ID,
     signature_alg_oids: &[ECDSA_SHA256_OID],
+    digest_bits: 256,
     public_key_alg: &ECDSA_P256,
     verification_alg: &signature::ECDSA_P256_SHA256_ASN1,
 };
 
 /// ECDSA signatures using the P-256 curve and SHA-384. Deprecated.
+#[cfg(feature = "ecdsa")]
 pub static ECDSA_P256_SHA384: SignatureAlgorithm = SignatureAlgorithm {
+    name: "ECDSA_P256_SHA384",
+    signature_alg_id: ECDSA_SHA384_ALG_ID,
     signature_alg_oids: &[ECDSA_SHA384_OID],
+    digest_bits: 384,
     public_key_alg: &ECDSA_P256,
     verification_alg: &signature::ECDSA_P256_SHA384_ASN1,
 };
 
 /// ECDSA signatures using the P-256 curve and SHA-512. Deprecated.
+#[cfg(feature = "ecdsa")]
 pub static ECDSA_P256_SHA512: SignatureAlgorithm = SignatureAlgorithm {
+    name: "ECDSA_P256_SHA512",
+    signature_alg_id: ECDSA_SHA512_ALG_ID,
     signature_alg_oids: &[ECDSA_SHA512_OID],
+    digest_bits: 512,
     public_key_alg: &ECDSA_P256,
     verification_alg: &signature::ECDSA_P256_SHA512_ASN1,
 };
 
 /// ECDSA signatures using the P-384 curve and SHA-1. Deprecated.
+#[cfg(feature = "ecdsa")]
 pub static ECDSA_P384_SHA1: SignatureAlgorithm = SignatureAlgorithm {
+    name: "ECDSA_P384_SHA1",
+    signature_alg_id: ECDSA_SHA1_ALG_ID,
     signature_alg_oids: &[ECDSA_SHA1_OID],
+    digest_bits: 160,
     public_key_alg: &ECDSA_P384,
     verification_alg: &signature::ECDSA_P384_SHA1_ASN1,
 };
 
 /// ECDSA signatures using the P-384 curve and SHA-256. Deprecated.
+#[cfg(feature = "ecdsa")]
 pub static ECDSA_P384_SHA256: SignatureAlgorithm = SignatureAlgorithm {
+    name: "ECDSA_P384_SHA256",
+    signature_alg_id: ECDSA_SHA256_ALG_ID,
     signature_alg_oids: &[ECDSA_SHA256_OID],
+    digest_bits: 256,
     public_key_alg: &ECDSA_P384,
     verification_alg: &signature::ECDSA_P384_SHA256_ASN1,
 };
 
 /// ECDSA signatures using the P-384 curve and SHA-384.
+#[cfg(feature = "ecdsa")]
 pub static ECDSA_P384_SHA384: SignatureAlgorithm = SignatureAlgorithm {
+    name: "ECDSA_P384_SHA384",
+    signature_alg_id: ECDSA_SHA384_ALG_ID,
     signature_alg_oids: &[ECDSA_SHA384_OID],
+    digest_bits: 384,
     public_key_alg: &ECDSA_P384,
     verification_alg: &signature::ECDSA_P384_SHA384_ASN1,
 };
 
 /// ECDSA signatures using the P-384 curve and SHA-512. Deprecated.
+#[cfg(feature = "ecdsa")]
 pub static ECDSA_P384_SHA512: SignatureAlgorithm = SignatureAlgorithm {
+    name: "ECDSA_P384_SHA512",
+    signature_alg_id: ECDSA_SHA512_ALG_ID,
     signature_alg_oids: &[ECDSA_SHA512_OID],
+    digest_bits: 512,
     public_key_alg: &ECDSA_P384,
     verification_alg: &signature::ECDSA_P384_SHA512_ASN1,
 };
@@ -291,36 +673,56 @@ pub static ECDSA_P384_SHA512: SignatureAlgorithm = SignatureAlgorithm {
 
 /// RSA PKCS#1 1.5 signatures using SHA-1 for keys of 2048-8192 bits.
 /// Deprecated.
+#[cfg(feature = "rsa")]
 pub static RSA_PKCS1_2048_8192_SHA1: SignatureAlgorithm = SignatureAlgorithm {
+    name: "RSA_PKCS1_2048_8192_SHA1",
+    signature_alg_id: RSA_PKCS1_SHA1_ALG_ID,
     signature_alg_oids: &[RSA_PKCS1_SHA1_OID, RSA_PKCS1_SHA1_OSE_OID],
+    digest_bits: 160,
     public_key_alg: &RSA_PKCS1,
     verification_alg: &signature::RSA_PKCS1_2048_8192_SHA1,
 };
 
 /// RSA PKCS#1 1.5 signatures using SHA-256 for keys of 2048-8192 bits.
+#[cfg(feature = "rsa")]
 pub static RSA_PKCS1_2048_8192_SHA256: SignatureAlgorithm = SignatureAlgorithm {
+    name: "RSA_PKCS1_2048_8192_SHA256",
+    signature_alg_id: RSA_PKCS1_SHA256_ALG_ID,
     signature_alg_oids: &[RSA_PKCS1_SHA256_OID],
+    digest_bits: 256,
     public_key_alg: &RSA_PKCS1,
     verification_alg: &signature::RSA_PKCS1_2048_8192_SHA256,
 };
 
 /// RSA PKCS#1 1.5 signatures using SHA-384 for keys of 2048-8192 bits.
+#[cfg(feature = "rsa")]
 pub static RSA_PKCS1_2048_8192_SHA384: SignatureAlgorithm = SignatureAlgorithm {
+    name: "RSA_PKCS1_2048_8192_SHA384",
+    signature_alg_id: RSA_PKCS1_SHA384_ALG_ID,
     signature_alg_oids: &[RSA_PKCS1_SHA384_OID],
+    digest_bits: 384,
     public_key_alg: &RSA_PKCS1,
     verification_alg: &signature::RSA_PKCS1_2048_8192_SHA384,
 };
 
 /// RSA PKCS#1 1.5 signatures using SHA-512 for keys of 2048-8192 bits.
+#[cfg(feature = "rsa")]
 pub static RSA_PKCS1_2048_8192_SHA512: SignatureAlgorithm = SignatureAlgorithm {
+    name: "RSA_PKCS1_2048_8192_SHA512",
+    signature_alg_id: RSA_PKCS1_SHA512_ALG_ID,
     signature_alg_oids: &[RSA_PKCS1_SHA512_OID],
+    digest_bits: 512,
     public_key_alg: &RSA_PKCS1,
     verification_alg: &signature::RSA_PKCS1_2048_8192_SHA512,
 };
 
 /// RSA PKCS#1 1.5 signatures using SHA-384 for keys of 3072-8192 bits.
+#[cfg(feature = "rsa")]
 pub static RSA_PKCS1_3072_8192_SHA384: SignatureAlgorithm = SignatureAlgorithm {
+    name: "RSA_PKCS1_3072_8192_SHA384",
+    signature_alg_id: RSA_PKCS1_SHA384_ALG_ID,
     signature_alg_oids: &[RSA_PKCS1_SHA384_OID],
+    digest_bits: 384,
     public_key_alg: &RSA_PKCS1,
     verification_alg: &signature::RSA_PKCS1_3072_8192_SHA384,
 };
@@ -329,23 +731,63 @@ pub static RSA_PKCS1_3072_8192_SHA384: SignatureAlgorithm = SignatureAlgorithm {
 struct PublicKeyAlgorithm {
     shared: &'static PublicKeyAlgorithmSharedInfo,
     curve_oid: Option<&'static [u8]>,
+
+    // The size of the curve in bits, if any. The size of an RSA key is that
+    // of its modulus, so it varies from key to key.
+    curve_bits: Option<usize>,
+
+    // The value of the `AlgorithmIdentifier` of the `SubjectPublicKeyInfo`.
+    alg_id: &'static [u8],
+}
+
+impl PublicKeyAlgorithm {
+    // Whether `spki` is a key of this type, with the same curve, if any.
+    fn matches(&self, spki: &SubjectPublicKeyInfo) -> bool {
+        if spki.algorithm_oid != self.shared.spki_algorithm_oid {
+            return false;
+        }
+        match (spki.curve_oid, self.curve_oid) {
+            (None, None) => true,
+            (Some(spki_oid), Some(supported_oid)) => spki_oid == supported_oid,
+            _ => false,
+        }
+    }
 }
 
+#[cfg(feature = "ecdsa")]
 static ECDSA_P256: PublicKeyAlgorithm = PublicKeyAlgorithm {
     shared: &ECDSA_SHARED,
     curve_oid: Some(&oid_1_2_840_10045![3, 1, 7]),
+    curve_bits: Some(256),
+    alg_id: &[
+        0x06, 0x07, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01, // id-ecPublicKey
+        0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07, // P-256
+    ],
 };
 
+#[cfg(feature = "ecdsa")]
 static ECDSA_P384: PublicKeyAlgorithm = PublicKeyAlgorithm {
     shared: &ECDSA_SHARED,
     curve_oid: Some(&oid_1_3_132![0, 34]),
+    curve_bits: Some(384),
+    alg_id: &[
+        0x06, 0x07, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01, // id-ecPublicKey
+        0x06, 0x05, 0x2b, 0x81, 0x04, 0x00, 0x22, // P-384
+    ],
 };
 
 // RFC 3279 Section 2.3.1 says "The parameters field MUST have ASN.1 type
 // NULL for this algorithm identifier."
+#[cfg(feature = "rsa")]
 static RSA_PKCS1: PublicKeyAlgorithm = PublicKeyAlgorithm {
     shared: &RSA_PKCS1_SHARED,
     curve_oid: None,
+    curve_bits: None,
+    alg_id: &[
+        // rsaEncryption
+        0x06, 0x09, 0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x01,
+        0x05, 0x00, // NULL
+    ],
 };
 
 
@@ -362,14 +804,17 @@ struct PublicKeyAlgorithmSharedInfo {
 }
 
 // id-ecPublicKey from RFC 3279 Section 2.3.5 & RFC 5480 Section 2.1.1
+#[cfg(feature = "ecdsa")]
 const ECDSA_SHARED: PublicKeyAlgorithmSharedInfo = PublicKeyAlgorithmSharedInfo {
     spki_algorithm_oid: &oid_1_2_840_10045![2, 1],
 
     // RFC 5758 Section 3.2 (ECDSA with SHA-2), and RFC 3279 Section 2.2.3
     // (ECDSA with SHA-1) say that parameters must be omitted in signatures.
+    // `Policy::allow_null_signature_algorithm_parameters` relaxes this.
     allowed_signature_alg_parameters: &[&[]],
 };
 
+#[cfg(feature = "rsa")]
 const RSA_PKCS1_SHARED: PublicKeyAlgorithmSharedInfo =
         PublicKeyAlgorithmSharedInfo {
     spki_algorithm_oid: &oid_1_2_840_113549![1, 1, 1],
@@ -378,34 +823,81 @@ const RSA_PKCS1_SHARED: PublicKeyAlgorithmSharedInfo =
     // for RSA PKCS#1 must be encoded as NULL; we relax that requirement by
     // allowing the NULL to be omitted, to match all the other signature
     // algorithms we support and for compatibility.
-    allowed_signature_alg_parameters: &[&[], &[0x05, 0x00]], // Optional NULL.
+    allowed_signature_alg_parameters: NULL_OR_ABSENT_PARAMETERS,
 };
 
+const NULL_OR_ABSENT_PARAMETERS: &'static [&'static [u8]] = &[
+    &[],
+    &[0x05, 0x00], // NULL
+];
+
 // TODO: add documentation for all this stuff.
 
-const ECDSA_SHA1_OID: &'static [u8] = &oid_1_2_840_10045![4, 1, 1];
+#[cfg(feature = "ecdsa")]
+const ECDSA_SHA1_OID: &'static [u8] = &oid_1_2_840_10045![4, 1];
+#[cfg(feature = "ecdsa")]
 const ECDSA_SHA256_OID: &'static [u8] = &oid_1_2_840_10045![4, 3, 2];
+#[cfg(feature = "ecdsa")]
 const ECDSA_SHA384_OID: &'static [u8] = &oid_1_2_840_10045![4, 3, 3];
+#[cfg(feature = "ecdsa")]
 const ECDSA_SHA512_OID: &'static [u8] = &oid_1_2_840_10045![4, 3, 4];
 
+#[cfg(feature = "rsa")]
 const RSA_PKCS1_SHA1_OID: &'static [u8] = &oid_1_2_840_113549![1, 1, 5];
+#[cfg(feature = "rsa")]
 const RSA_PKCS1_SHA256_OID: &'static [u8] = &oid_1_2_840_113549![1, 1, 11];
+#[cfg(feature = "rsa")]
 const RSA_PKCS1_SHA384_OID: &'static [u8] = &oid_1_2_840_113549![1, 1, 12];
+#[cfg(feature = "rsa")]
 const RSA_PKCS1_SHA512_OID: &'static [u8] = &oid_1_2_840_113549![1, 1, 13];
 
+// The values of the signature `AlgorithmIdentifier`s, i.e. the OIDs above
+// with their tags and lengths, followed by `NULL` parameters for RSA.
+#[cfg(feature = "ecdsa")]
+const ECDSA_SHA1_ALG_ID: &'static [u8] =
+    &[0x06, 0x07, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x01];
+#[cfg(feature = "ecdsa")]
+const ECDSA_SHA256_ALG_ID: &'static [u8] =
+    &[0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02];
+#[cfg(feature = "ecdsa")]
+const ECDSA_SHA384_ALG_ID: &'static [u8] =
+    &[0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x03];
+#[cfg(feature = "ecdsa")]
+const ECDSA_SHA512_ALG_ID: &'static [u8] =
+    &[0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x04];
+
+#[cfg(feature = "rsa")]
+const RSA_PKCS1_SHA1_ALG_ID: &'static [u8] =
+    &[0x06, 0x09, 0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x05,
+      0x05, 0x00];
+#[cfg(feature = "rsa")]
+const RSA_PKCS1_SHA256_ALG_ID: &'static [u8] =
+    &[0x06, 0x09, 0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0b,
+      0x05, 0x00];
+#[cfg(feature = "rsa")]
+const RSA_PKCS1_SHA384_ALG_ID: &'static [u8] =
+    &[0x06, 0x09, 0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0c,
+      0x05, 0x00];
+#[cfg(feature = "rsa")]
+const RSA_PKCS1_SHA512_ALG_ID: &'static [u8] =
+    &[0x06, 0x09, 0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0d,
+      0x05, 0x00];
+
 // NIST Open Systems Environment (OSE) Implementor's Workshop (OIW)
 // http://www.oiw.org/agreements/stable/12s-9412.txt (no longer works).
 // http://www.imc.org/ietf-pkix/old-archive-97/msg01166.html
 // We need to support this non-PKIX OID for compatibility.
+#[cfg(feature = "rsa")]
 const RSA_PKCS1_SHA1_OSE_OID: &'static [u8] = &oid!(1, 3, 14, 3, 2, 29);
 
 
-#[cfg(test)]
+// The tests use algorithms from both families.
+#[cfg(all(test, feature = "ecdsa", feature = "rsa"))]
 mod tests {
     use rustc_serialize::base64::FromBase64;
     use std;
     use std::io::BufRead;
-    use {der, Error, signed_data};
+    use {der, Error, Policy, signed_data};
     use untrusted;
 
     // TODO: The expected results need to be modified for SHA-1 deprecation.
@@ -421,7 +913,23 @@ mod tests {
 
     fn test_verify_signed_data(file_name: &str,
                                expected_result: Result<(), Error>) {
-        let tsd = parse_test_signed_data(file_name);
+        test_verify_signed_data_with_policy(file_name, &Policy::default(),
+                                            &[], expected_result)
+    }
+
+    // `extra_algorithm_parameters` is appended to the algorithm identifier
+    // from the test file.
+    fn test_verify_signed_data_with_policy(file_name: &str, policy: &Policy,
+                                           extra_algorithm_parameters: &[u8],
+                                           expected_result: Result<(), Error>) {
+        let mut tsd = parse_test_signed_data(file_name);
+        if !extra_algorithm_parameters.is_empty() {
+            // The test files' algorithm identifiers are all short enough to
+            // use the single-byte length encoding.
+            assert!(tsd.algorithm[1] < 0x80);
+            tsd.algorithm[1] += extra_algorithm_parameters.len() as u8;
+            tsd.algorithm.extend_from_slice(extra_algorithm_parameters);
+        }
         let spki_value = untrusted::Input::from(&tsd.spki);
         let spki_value = spki_value.read_all(Error::BadDER, |input| {
             der::expect_tag_and_get_value(input, der::Tag::Sequence)
@@ -451,7 +959,7 @@ mod tests {
 
         assert_eq!(expected_result,
                    signed_data::verify_signed_data(
-                        &SUPPORTED_ALGORITHMS_IN_TESTS, spki_value,
+                        policy, &SUPPORTED_ALGORITHMS_IN_TESTS, spki_value,
                         &signed_data));
     }
 
@@ -630,6 +1138,179 @@ mod tests {
     test_verify_signed_data!(test_rsa2048_pkcs1_sha512,
                              "rsa2048-pkcs1-sha512.pem", Ok(()));
 
+    #[test]
+    fn test_ecdsa_null_signature_algorithm_parameters() {
+        const NULL: &'static [u8] = &[0x05, 0x00];
+        let lenient = Policy {
+            allow_null_signature_algorithm_parameters: true,
+            ..Policy::default()
+        };
+
+        test_verify_signed_data_with_policy(
+            "ecdsa-secp384r1-sha256.pem", &Policy::default(), NULL,
+            Err(Error::UnsupportedSignatureAlgorithm));
+        test_verify_signed_data_with_policy(
+            "ecdsa-secp384r1-sha256.pem", &lenient, NULL, Ok(()));
+
+        // Other parameters are still rejected.
+        test_verify_signed_data_with_policy(
+            "ecdsa-secp384r1-sha256.pem", &lenient, &[0x02, 0x01, 0x00],
+            Err(Error::UnsupportedSignatureAlgorithm));
+    }
+
+    #[test]
+    fn test_algorithm_identifiers_are_equal() {
+        // sha256WithRSAEncryption, with and without NULL parameters.
+        const RSA_SHA256_NULL: &'static [u8] =
+            &[0x06, 0x09, 0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0b,
+              0x05, 0x00];
+        const RSA_SHA256_ABSENT: &'static [u8] =
+            &[0x06, 0x09, 0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0b];
+        // sha384WithRSAEncryption, with NULL parameters.
+        const RSA_SHA384_NULL: &'static [u8] =
+            &[0x06, 0x09, 0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0c,
+              0x05, 0x00];
+        // sha256WithRSAEncryption, with bogus parameters.
+        const RSA_SHA256_INTEGER: &'static [u8] =
+            &[0x06, 0x09, 0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0b,
+              0x02, 0x01, 0x00];
+
+        let null = untrusted::Input::from(RSA_SHA256_NULL);
+        let absent = untrusted::Input::from(RSA_SHA256_ABSENT);
+        let sha384 = untrusted::Input::from(RSA_SHA384_NULL);
+        let integer = untrusted::Input::from(RSA_SHA256_INTEGER);
+
+        for &lenient in &[false, true] {
+            assert!(signed_data::algorithm_identifiers_are_equal(null, null,
+                                                                 lenient));
+            assert!(!signed_data::algorithm_identifiers_are_equal(null, sha384,
+                                                                  lenient));
+            assert!(!signed_data::algorithm_identifiers_are_equal(null, integer,
+                                                                  lenient));
+        }
+        assert!(!signed_data::algorithm_identifiers_are_equal(null, absent,
+                                                              false));
+        assert!(signed_data::algorithm_identifiers_are_equal(null, absent,
+                                                             true));
+        assert!(signed_data::algorithm_identifiers_are_equal(absent, null,
+                                                             true));
+    }
+
+    #[cfg(feature = "ecdsa")]
+    #[test]
+    fn test_signature_algorithm_format_and_eq() {
+        use std::collections::HashSet;
+        use {ECDSA_P256_SHA256, ECDSA_P384_SHA384};
+
+        assert_eq!("ECDSA_P256_SHA256", format!("{}", ECDSA_P256_SHA256));
+        assert_eq!("ECDSA_P384_SHA384", format!("{:?}", ECDSA_P384_SHA384));
+        assert_eq!(ECDSA_P256_SHA256, ECDSA_P256_SHA256);
+        assert!(ECDSA_P256_SHA256 != ECDSA_P384_SHA384);
+
+        let mut set = HashSet::new();
+        assert!(set.insert(&ECDSA_P256_SHA256));
+        assert!(set.insert(&ECDSA_P384_SHA384));
+        assert!(!set.insert(&ECDSA_P256_SHA256));
+    }
+
+    // Each algorithm's own `AlgorithmIdentifier`s identify it.
+    #[test]
+    fn test_signature_algorithm_ids() {
+        for alg in SUPPORTED_ALGORITHMS_IN_TESTS.iter() {
+            let signature_alg_id =
+                untrusted::Input::from(alg.signature_alg_id().as_slice());
+            assert_eq!(Ok(()),
+                       signed_data::check_signature_algorithm_is_supported(
+                           &Policy::default(), &[*alg], signature_alg_id));
+
+            let public_key_alg_id =
+                untrusted::Input::from(alg.public_key_alg_id().as_slice());
+            let (algorithm_oid, curve_oid) =
+                public_key_alg_id.read_all(Error::BadDER, |input| {
+                    let algorithm_oid = try!(der::expect_tag_and_get_value(
+                        input, der::Tag::OID));
+                    let curve_oid = if input.peek(der::Tag::OID as u8) {
+                        Some(try!(der::expect_tag_and_get_value(
+                            input, der::Tag::OID)))
+                    } else {
+                        try!(der::null(input));
+                        None
+                    };
+                    Ok((algorithm_oid, curve_oid))
+                }).unwrap();
+            assert_eq!(alg.public_key_alg.shared.spki_algorithm_oid,
+                       algorithm_oid.as_slice_less_safe());
+            assert_eq!(alg.public_key_alg.curve_oid,
+                       curve_oid.map(|oid| oid.as_slice_less_safe()));
+        }
+    }
+
+    fn test_verify_signature_with_requirements(
+            file_name: &str, signature_alg: &signed_data::SignatureAlgorithm,
+            requirements: &signed_data::SignatureRequirements,
+            expected_result: Result<(), Error>) {
+        let tsd = parse_test_signed_data(file_name);
+        let spki_value = untrusted::Input::from(&tsd.spki);
+        let spki_value = spki_value.read_all(Error::BadDER, |input| {
+            der::expect_tag_and_get_value(input, der::Tag::Sequence)
+        }).unwrap();
+        let signature = untrusted::Input::from(&tsd.signature);
+        let signature = signature.read_all(Error::BadDER, |input| {
+            der::bit_string_with_no_unused_bits(input)
+        }).unwrap();
+        assert_eq!(expected_result,
+                   signed_data::verify_signature_with_requirements(
+                       requirements, signature_alg, spki_value,
+                       untrusted::Input::from(&tsd.data), signature));
+    }
+
+    #[test]
+    fn test_signature_requirements() {
+        use signed_data::SignatureRequirements;
+        use {ECDSA_P384_SHA1, ECDSA_P384_SHA256, RSA_PKCS1_2048_8192_SHA256,
+             RSA_PKCS1_2048_8192_SHA512};
+
+        let default = SignatureRequirements::default();
+        test_verify_signature_with_requirements(
+            "ecdsa-secp384r1-sha256.pem", &ECDSA_P384_SHA256, &default,
+            Ok(()));
+        test_verify_signature_with_requirements(
+            "rsa2048-pkcs1-sha512.pem", &RSA_PKCS1_2048_8192_SHA512,
+            &default, Ok(()));
+
+        // The digest is checked before anything else.
+        test_verify_signature_with_requirements(
+            "ecdsa-secp384r1-sha256.pem", &ECDSA_P384_SHA1, &default,
+            Err(Error::UnsupportedSignatureAlgorithm));
+        test_verify_signature_with_requirements(
+            "ecdsa-secp384r1-sha256.pem", &ECDSA_P384_SHA256,
+            &SignatureRequirements { min_digest_bits: 384, ..default },
+            Err(Error::UnsupportedSignatureAlgorithm));
+
+        test_verify_signature_with_requirements(
+            "ecdsa-secp384r1-sha256.pem", &ECDSA_P384_SHA256,
+            &SignatureRequirements { min_ecdsa_curve_bits: 521, ..default },
+            Err(Error::PublicKeyTooSmall));
+        test_verify_signature_with_requirements(
+            "rsa2048-pkcs1-sha512.pem", &RSA_PKCS1_2048_8192_SHA512,
+            &SignatureRequirements { min_rsa_modulus_bits: 3072, ..default },
+            Err(Error::PublicKeyTooSmall));
+
+        // A 1024-bit key is too small by default; the signature is never
+        // verified.
+        test_verify_signature_with_requirements(
+            "rsa-pkcs1-sha256.pem", &RSA_PKCS1_2048_8192_SHA256, &default,
+            Err(Error::PublicKeyTooSmall));
+        test_verify_signature_with_requirements(
+            "rsa-pkcs1-sha256.pem", &RSA_PKCS1_2048_8192_SHA256,
+            &SignatureRequirements { min_rsa_modulus_bits: 1024, ..default },
+            Err(Error::InvalidSignatureForPublicKey));
+
+        test_verify_signature_with_requirements(
+            "ecdsa-using-rsa-key.pem", &ECDSA_P384_SHA256, &default,
+            Err(Error::UnsupportedSignatureAlgorithmForPublicKey));
+    }
+
     struct TestSignedData {
         spki: std::vec::Vec<u8>,
         data: std::vec::Vec<u8>,
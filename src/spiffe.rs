@@ -0,0 +1,324 @@
+// Copyright 2016 Brian Smith.
+//
+// Permission to use, copy, modify, and/or distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHORS DISCLAIM ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHORS BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+//! SPIFFE workload identities in X.509 certificates (X.509-SVIDs).
+//!
+//! See the SPIFFE-ID and X.509-SVID specifications in
+//! <https://github.com/spiffe/spiffe/tree/main/standards>.
+//!
+//! Each SPIFFE trust domain has its own CAs, which are distributed as a trust
+//! bundle, and with federation, e.g. through gRPC's xDS SPIFFE bundle map, a
+//! workload trusts the bundles of several trust domains. An SVID must be
+//! verified with the bundle of the trust domain in its SPIFFE ID only;
+//! otherwise any federated domain could issue identities in every other
+//! one. `TrustBundles` keeps the bundles apart and verifies an SVID and its
+//! SPIFFE ID in one call.
+
+use {EndEntityCert, Error, Policy, SignatureAlgorithm, SubjectAltName,
+     TrustAnchor, TrustAnchorSet, Usage, VerificationOptions,
+     VerificationTimes, VerifiedPath};
+use std::vec::Vec;
+use trust_anchor_util;
+use verification_options;
+use verify_cert;
+
+// SPIFFE-ID Section 2.3 limits SPIFFE IDs to 2048 bytes.
+const MAX_SPIFFE_ID_LEN: usize = 2048;
+
+/// A SPIFFE ID, e.g. `spiffe://example.org/ns/default/sa/web`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct SpiffeId<'a> {
+    uri: &'a [u8],
+    trust_domain: &'a [u8],
+    path: &'a [u8],
+}
+
+impl<'a> SpiffeId<'a> {
+    /// Parses the SPIFFE ID `uri`, returning `Error::InvalidReferenceName`
+    /// if it isn't a valid one.
+    ///
+    /// Following SPIFFE-ID Section 2, the scheme must be `spiffe` and the
+    /// trust domain may only contain lowercase letters, digits, `.`, `-`,
+    /// and `_`. Each segment of the path must be non-empty, may only
+    /// contain letters, digits, `.`, `-`, and `_`, and must not be `.` or
+    /// `..`. Ports, userinfo, queries, and fragments aren't allowed.
+    pub fn from(uri: &'a [u8]) -> Result<SpiffeId<'a>, Error> {
+        parse_spiffe_id(uri).ok_or(Error::InvalidReferenceName)
+    }
+
+    /// The whole SPIFFE ID, as it was parsed.
+    pub fn uri(&self) -> &'a [u8] { self.uri }
+
+    /// The trust domain, e.g. `example.org`.
+    pub fn trust_domain(&self) -> &'a [u8] { self.trust_domain }
+
+    /// The path, e.g. `/ns/default/sa/web`, which is empty for the SPIFFE
+    /// ID of the trust domain itself.
+    pub fn path(&self) -> &'a [u8] { self.path }
+}
+
+fn parse_spiffe_id<'a>(uri: &'a [u8]) -> Option<SpiffeId<'a>> {
+    const PREFIX: &'static [u8] = b"spiffe://";
+
+    if uri.len() > MAX_SPIFFE_ID_LEN || !uri.starts_with(PREFIX) {
+        return None;
+    }
+    let rest = &uri[PREFIX.len()..];
+    let trust_domain_len =
+        rest.iter().position(|&b| b == b'/').unwrap_or(rest.len());
+    let (trust_domain, path) = rest.split_at(trust_domain_len);
+    if !is_valid_trust_domain(trust_domain) {
+        return None;
+    }
+    // The path is either empty or a sequence of "/"-prefixed segments.
+    if !path.is_empty() &&
+       !path[1..].split(|&b| b == b'/').all(is_valid_path_segment) {
+        return None;
+    }
+    Some(SpiffeId {
+        uri: uri,
+        trust_domain: trust_domain,
+        path: path,
+    })
+}
+
+fn is_valid_trust_domain(trust_domain: &[u8]) -> bool {
+    !trust_domain.is_empty() &&
+        trust_domain.iter().all(|&b| match b {
+            b'a'...b'z' | b'0'...b'9' | b'.' | b'-' | b'_' => true,
+            _ => false,
+        })
+}
+
+fn is_valid_path_segment(segment: &[u8]) -> bool {
+    !segment.is_empty() && segment != b"." && segment != b".." &&
+        segment.iter().all(|&b| match b {
+            b'a'...b'z' | b'A'...b'Z' | b'0'...b'9' | b'.' | b'-' | b'_' =>
+                true,
+            _ => false,
+        })
+}
+
+/// The trust bundles of a set of federated SPIFFE trust domains.
+///
+/// The bundles are loaded from the DER-encoded CA certificates in them, e.g.
+/// the decoded `x5c` values of the `x509-svid` keys of a SPIFFE bundle, which
+/// must outlive the `TrustBundles`.
+#[derive(Debug, Default)]
+pub struct TrustBundles<'a> {
+    bundles: Vec<(&'a [u8], Vec<TrustAnchor<'a>>)>,
+}
+
+impl<'a> TrustBundles<'a> {
+    /// Trust bundles without any trust domains.
+    pub fn new() -> TrustBundles<'a> {
+        TrustBundles::default()
+    }
+
+    /// Loads the bundle of `trust_domain`, e.g. `example.org`, whose CA
+    /// certificates are `ca_certs`, replacing the previous bundle of the
+    /// trust domain, if any, as when the bundle is refreshed.
+    ///
+    /// The certificates are converted to trust anchors with
+    /// `trust_anchor_util::cert_der_as_trust_anchor`. If the trust domain
+    /// isn't valid, `Error::InvalidReferenceName` is returned; if any of the
+    /// certificates can't be parsed, its error is. Either way the previous
+    /// bundle is kept.
+    pub fn load_bundle(&mut self, trust_domain: &'a [u8],
+                       ca_certs: &[&'a [u8]]) -> Result<(), Error> {
+        if !is_valid_trust_domain(trust_domain) {
+            return Err(Error::InvalidReferenceName);
+        }
+        let mut trust_anchors = Vec::with_capacity(ca_certs.len());
+        for cert_der in ca_certs {
+            trust_anchors.push(
+                try!(trust_anchor_util::cert_der_as_trust_anchor(cert_der)));
+        }
+        match self.bundles.iter().position(|&(domain, _)| {
+            domain == trust_domain
+        }) {
+            Some(i) => { self.bundles[i].1 = trust_anchors; },
+            None => { self.bundles.push((trust_domain, trust_anchors)); },
+        }
+        Ok(())
+    }
+
+    /// The trust anchors of `trust_domain`, which are empty if its bundle
+    /// hasn't been loaded.
+    pub fn trust_anchors<'s>(&'s self, trust_domain: &[u8])
+                             -> TrustAnchorSet<'s> {
+        TrustAnchorSet::new(
+            self.bundles.iter()
+                        .find(|&&(domain, _)| domain == trust_domain)
+                        .map(|(_, trust_anchors)| &trust_anchors[..])
+                        .unwrap_or(&[]))
+    }
+
+    /// Verifies that `cert` is a valid X.509-SVID for `usage`, e.g.
+    /// `Usage::TlsClient` for a client in a mutually-authenticated TLS
+    /// connection, and returns its SPIFFE ID along with the verified path.
+    ///
+    /// The certificate must have exactly one URI subjectAltName entry, which
+    /// must be a valid SPIFFE ID, as X.509-SVID Section 2 requires;
+    /// otherwise, `Error::CertNotValidForName` is returned. The path is then
+    /// built to the trust anchors of the SPIFFE ID's trust domain only, so
+    /// `Error::NoTrustAnchors` is returned if its bundle hasn't been loaded.
+    /// The other parameters are as for
+    /// `EndEntityCert::verify_is_valid_tls_server_cert_with_options`.
+    ///
+    /// Whether the SPIFFE ID is authorized to do anything is up to the
+    /// caller.
+    pub fn verify_svid<'c>(&self, cert: &EndEntityCert<'c>, policy: &Policy,
+                           usage: Usage,
+                           supported_sig_algs: &[&SignatureAlgorithm],
+                           intermediate_certs: &[&'c [u8]],
+                           times: VerificationTimes,
+                           options: &VerificationOptions)
+                           -> Result<(SpiffeId<'c>, VerifiedPath), Error> {
+        let spiffe_id = try!(svid_spiffe_id(cert));
+        let trust_anchors = self.trust_anchors(spiffe_id.trust_domain());
+        let inputs = verify_cert::ChainInputs {
+            policy: policy,
+            required_eku_if_present: verify_cert::usage_eku(usage),
+            supported_sig_algs: supported_sig_algs,
+            trust_anchors: trust_anchors.trust_anchors(),
+            intermediate_certs: intermediate_certs,
+            times: times,
+        };
+        let path = try!(verification_options::build_chain(&inputs, &cert.inner,
+                                                          options));
+        Ok((spiffe_id, path))
+    }
+}
+
+// The SPIFFE ID of the X.509-SVID `cert`: its only URI subjectAltName entry.
+fn svid_spiffe_id<'a>(cert: &EndEntityCert<'a>)
+                      -> Result<SpiffeId<'a>, Error> {
+    let mut uris = try!(cert.subject_alt_names()).filter_map(|name| {
+        match name {
+            SubjectAltName::Uri(uri) => Some(uri.as_slice()),
+            _ => None,
+        }
+    });
+    match (uris.next(), uris.next()) {
+        (Some(uri), None) =>
+            parse_spiffe_id(uri).ok_or(Error::CertNotValidForName),
+        _ => Err(Error::CertNotValidForName),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SpiffeId;
+    use Error;
+
+    #[test]
+    fn test_spiffe_id_from() {
+        let id = SpiffeId::from(b"spiffe://example.org/ns/default/sa/web")
+                     .unwrap();
+        assert_eq!(b"example.org", id.trust_domain());
+        assert_eq!(b"/ns/default/sa/web", id.path());
+
+        let id = SpiffeId::from(b"spiffe://example_1.org").unwrap();
+        assert_eq!(b"example_1.org", id.trust_domain());
+        assert_eq!(b"", id.path());
+
+        const INVALID: &'static [&'static [u8]] = &[
+            b"https://example.org/web",
+            b"SPIFFE://example.org/web",
+            b"spiffe://",
+            b"spiffe:///web",
+            b"spiffe://Example.org/web",
+            b"spiffe://example.org:8443/web",
+            b"spiffe://user@example.org/web",
+            b"spiffe://example.org/",
+            b"spiffe://example.org//web",
+            b"spiffe://example.org/web/",
+            b"spiffe://example.org/./web",
+            b"spiffe://example.org/../web",
+            b"spiffe://example.org/web?x=1",
+            b"spiffe://example.org/web#x",
+            b"spiffe://example.org/w%20b",
+        ];
+        for uri in INVALID {
+            assert_eq!(Err(Error::InvalidReferenceName), SpiffeId::from(uri));
+        }
+    }
+
+    #[cfg(feature = "ecdsa")]
+    #[test]
+    fn test_verify_svid() {
+        use super::TrustBundles;
+        use {ECDSA_P256_SHA256, EndEntityCert, Policy, Time, Usage,
+             VerificationOptions, VerificationTimes};
+
+        let org_root = include_bytes!("../tests/data/spiffe-org-root.der");
+        let net_root = include_bytes!("../tests/data/spiffe-net-root.der");
+        let svid = EndEntityCert::from(
+            include_bytes!("../tests/data/spiffe-ee.der")).unwrap();
+        let rogue_svid = EndEntityCert::from(
+            include_bytes!("../tests/data/spiffe-rogue-ee.der")).unwrap();
+        let dns_ee = EndEntityCert::from(
+            include_bytes!("../tests/data/ecdsa-ee.der")).unwrap();
+        let time = Time::from_ymdhms_utc(2027, 1, 1, 0, 0, 0).unwrap();
+        let times = VerificationTimes::at(time);
+        let algs = &[&ECDSA_P256_SHA256];
+        let policy = Policy::default();
+        let options = &VerificationOptions::new();
+
+        let mut bundles = TrustBundles::new();
+        assert_eq!(Err(Error::NoTrustAnchors),
+                   bundles.verify_svid(&svid, &policy, Usage::TlsClient,
+                                       algs, &[], times, options)
+                          .map(|_| ()));
+
+        bundles.load_bundle(b"example.org", &[org_root]).unwrap();
+        bundles.load_bundle(b"example.net", &[net_root]).unwrap();
+        let (spiffe_id, path) =
+            bundles.verify_svid(&svid, &policy, Usage::TlsClient, algs, &[],
+                                times, options).unwrap();
+        assert_eq!(&b"spiffe://example.org/ns/default/sa/web"[..],
+                   spiffe_id.uri());
+        assert_eq!(0, path.trust_anchor_index());
+        assert!(bundles.verify_svid(&svid, &policy, Usage::TlsServer, algs,
+                                    &[], times, options).is_ok());
+
+        // The example.net root is trusted, but not for example.org.
+        assert_eq!(Err(Error::UnknownIssuer),
+                   bundles.verify_svid(&rogue_svid, &policy, Usage::TlsClient,
+                                       algs, &[], times, options)
+                          .map(|_| ()));
+
+        // Not an SVID.
+        assert_eq!(Err(Error::CertNotValidForName),
+                   bundles.verify_svid(&dns_ee, &policy, Usage::TlsServer,
+                                       algs, &[], times, options)
+                          .map(|_| ()));
+
+        // Reloading a bundle replaces it.
+        bundles.load_bundle(b"example.org", &[net_root]).unwrap();
+        assert_eq!(1, bundles.trust_anchors(b"example.org")
+                             .trust_anchors().len());
+        assert!(bundles.verify_svid(&rogue_svid, &policy, Usage::TlsClient,
+                                    algs, &[], times, options).is_ok());
+
+        assert_eq!(Err(Error::InvalidReferenceName),
+                   bundles.load_bundle(b"Example.org", &[org_root]));
+        assert_eq!(Err(Error::BadDER),
+                   bundles.load_bundle(b"example.org",
+                                       &[&org_root[..org_root.len() - 1]]));
+        assert_eq!(0, bundles.trust_anchors(b"example.com")
+                             .trust_anchors().len());
+    }
+}
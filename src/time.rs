@@ -12,17 +12,114 @@
 // ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
 // OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
 
-// XXX: This depends on the `std` modulue. TODO: Create a `#![no_std]`
-// interface.
+//! Time and calendar conversions for certificate validation.
+//!
+//! These don't depend on the `std` crate so that they can be used in `no_std`
+//! contexts, e.g. to produce human-readable expiration information from the
+//! times webpki parsed from a certificate.
 
+use Error;
+use core::time::Duration;
+
+#[cfg(feature = "std")]
 use std;
-use super::Error;
 
-pub type Time = std::time::SystemTime;
+/// A point in time, as used for certificate validation: the number of seconds
+/// since the Unix epoch (January 1, 1970 00:00:00 UTC), ignoring leap seconds.
+///
+/// Times before the Unix epoch aren't supported.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub struct Time(u64);
+
+impl Time {
+    /// The time `seconds_since_unix_epoch` seconds after the Unix epoch.
+    pub fn from_seconds_since_unix_epoch(seconds_since_unix_epoch: u64)
+                                         -> Time {
+        Time(seconds_since_unix_epoch)
+    }
+
+    /// The given date and time, UTC, in the Gregorian calendar. `month` is 1
+    /// for January through 12 for December.
+    ///
+    /// Returns `Err(Error::BadDERTime)` if the components don't form a valid
+    /// date and time or if it is before the Unix epoch.
+    pub fn from_ymdhms_utc(year: u64, month: u64, day_of_month: u64,
+                           hours: u64, minutes: u64, seconds: u64)
+                           -> Result<Time, Error> {
+        seconds_since_unix_epoch(year, month, day_of_month, hours, minutes,
+                                 seconds).map(Time)
+    }
+
+    /// Converts a `std::time::SystemTime` to a `Time`. Fails if `time` is
+    /// before the Unix epoch.
+    #[cfg(feature = "std")]
+    pub fn try_from(time: std::time::SystemTime)
+                    -> Result<Time, std::time::SystemTimeError> {
+        time.duration_since(std::time::UNIX_EPOCH)
+            .map(|duration| Time(duration.as_secs()))
+    }
+
+    /// The number of seconds since the Unix epoch.
+    pub fn seconds_since_unix_epoch(&self) -> u64 { self.0 }
+
+    /// The time `duration` after this time, or `None` if that can't be
+    /// represented. Any fractional second in `duration` is ignored.
+    ///
+    /// This and `checked_sub` are intended for computing things like clock
+    /// skew allowances and revalidation deadlines without `std`.
+    pub fn checked_add(&self, duration: Duration) -> Option<Time> {
+        self.0.checked_add(duration.as_secs()).map(Time)
+    }
+
+    /// The time `duration` before this time, or `None` if that is before the
+    /// Unix epoch. Any fractional second in `duration` is ignored.
+    pub fn checked_sub(&self, duration: Duration) -> Option<Time> {
+        self.0.checked_sub(duration.as_secs()).map(Time)
+    }
+
+    /// The amount of time from `earlier` to this time, or `None` if `earlier`
+    /// is later than this time.
+    pub fn checked_duration_since(&self, earlier: Time) -> Option<Duration> {
+        self.0.checked_sub(earlier.0).map(Duration::from_secs)
+    }
+
+    /// The date and time, UTC, in the Gregorian calendar.
+    pub fn to_date_time(&self) -> DateTime {
+        date_time_from_seconds_since_unix_epoch(self.0)
+    }
+}
+
+/// A date and time, UTC, in the Gregorian calendar.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct DateTime {
+    /// The year, e.g. 2016.
+    pub year: u64,
+
+    /// The month, 1 for January through 12 for December.
+    pub month: u64,
+
+    /// The day of the month, starting at 1.
+    pub day_of_month: u64,
+
+    /// The hour, 0 through 23.
+    pub hours: u64,
 
-pub fn time_from_ymdhms_utc(year: u64, month: u64, day_of_month: u64,
+    /// The minute, 0 through 59.
+    pub minutes: u64,
+
+    /// The second, 0 through 59.
+    pub seconds: u64,
+}
+
+fn seconds_since_unix_epoch(year: u64, month: u64, day_of_month: u64,
                             hours: u64, minutes: u64, seconds: u64)
-                            -> Result<Time, Error> {
+                            -> Result<u64, Error> {
+    if month < 1 || month > 12 ||
+       day_of_month < 1 || day_of_month > days_in_month(year, month) ||
+       hours > 23 || minutes > 59 || seconds > 59 {
+        return Err(Error::BadDERTime);
+    }
+
     let days_before_year_since_unix_epoch =
         try!(days_before_year_since_unix_epoch(year));
 
@@ -50,7 +147,7 @@ pub fn time_from_ymdhms_utc(year: u64, month: u64, day_of_month: u64,
         10 => JAN + feb + MAR + APR + MAY + JUN + JUL + AUG + SEP,
         11 => JAN + feb + MAR + APR + MAY + JUN + JUL + AUG + SEP + OCT,
         12 => JAN + feb + MAR + APR + MAY + JUN + JUL + AUG + SEP + OCT + NOV,
-        _ => unreachable!() // `read_two_digits` already bounds-checked it.
+        _ => unreachable!() // Checked above.
     };
 
     let days_before = days_before_year_since_unix_epoch +
@@ -61,8 +158,7 @@ pub fn time_from_ymdhms_utc(year: u64, month: u64, day_of_month: u64,
                                    (minutes               * 60) +
                                    seconds;
 
-    Ok(std::time::UNIX_EPOCH +
-        std::time::Duration::from_secs(seconds_since_unix_epoch))
+    Ok(seconds_since_unix_epoch)
 }
 
 fn days_before_year_since_unix_epoch(year: u64) -> Result<u64, Error> {
@@ -84,12 +180,16 @@ fn days_before_year_ad(year: u64) -> u64 {
         + ((year - 1) / 400)  // except years divisible by 400.
 }
 
+/// The number of days in the given month (1 for January through 12 for
+/// December) of the given year of the Gregorian calendar.
+///
+/// Panics if `month` isn't in the range 1 through 12.
 pub fn days_in_month(year: u64, month: u64) -> u64 {
     match month {
         1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
         4 | 6 | 9 | 11 => 30,
         2 => days_in_feb(year),
-        _ => unreachable!() // `read_two_digits` already bounds-checked it.
+        _ => panic!("invalid month")
     }
 }
 
@@ -103,12 +203,124 @@ fn days_in_feb(year: u64) -> u64 {
 
 const DAYS_BEFORE_UNIX_EPOCH_AD: u64 = 719162;
 
+// The inverse of `seconds_since_unix_epoch`, using the algorithm from
+// http://howardhinnant.github.io/date_algorithms.html#civil_from_days.
+fn date_time_from_seconds_since_unix_epoch(seconds_since_unix_epoch: u64)
+                                           -> DateTime {
+    let days = seconds_since_unix_epoch / (24 * 60 * 60);
+    let seconds_in_day = seconds_since_unix_epoch % (24 * 60 * 60);
+
+    // Shift the epoch to March 1, 0000, so that the leap day, if any, is the
+    // last day of the (March-based) year.
+    let z = days + 719468;
+    let era = z / 146097; // The number of 400-year eras.
+    let day_of_era = z - (era * 146097); // [0, 146096]
+    let year_of_era = (day_of_era - (day_of_era / 1460) +
+                       (day_of_era / 36524) - (day_of_era / 146096)) / 365;
+    let day_of_year = day_of_era -
+                      ((365 * year_of_era) + (year_of_era / 4) -
+                       (year_of_era / 100)); // [0, 365]
+    let month_from_march = ((5 * day_of_year) + 2) / 153; // [0, 11]
+    let day_of_month = day_of_year - (((153 * month_from_march) + 2) / 5) + 1;
+    let month = if month_from_march < 10 { month_from_march + 3 }
+                else { month_from_march - 9 };
+    let year = (era * 400) + year_of_era + if month <= 2 { 1 } else { 0 };
+
+    DateTime {
+        year: year,
+        month: month,
+        day_of_month: day_of_month,
+        hours: seconds_in_day / (60 * 60),
+        minutes: (seconds_in_day / 60) % 60,
+        seconds: seconds_in_day % 60,
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{DAYS_BEFORE_UNIX_EPOCH_AD, days_before_year_ad};
+    use super::{DateTime, DAYS_BEFORE_UNIX_EPOCH_AD, days_before_year_ad,
+                Time};
+    use Error;
+    use core::time::Duration;
 
     #[test]
     fn test_days_before_unix_epoch() {
         assert_eq!(DAYS_BEFORE_UNIX_EPOCH_AD, days_before_year_ad(1970));
     }
+
+    // (year, month, day_of_month, hours, minutes, seconds,
+    //  seconds since the Unix epoch)
+    const DATE_TIMES: &'static [(u64, u64, u64, u64, u64, u64, u64)] = &[
+        (1970, 1, 1, 0, 0, 0, 0),
+        (1970, 1, 1, 23, 59, 59, 86399),
+        (1999, 12, 31, 23, 59, 59, 946684799),
+        (2000, 2, 29, 12, 0, 0, 951825600),
+        (2000, 3, 1, 0, 0, 0, 951868800),
+        (2016, 2, 29, 1, 2, 3, 1456707723),
+        (2038, 1, 19, 3, 14, 8, 2147483648),
+        (2100, 3, 1, 0, 0, 0, 4107542400),
+        (9999, 12, 31, 23, 59, 59, 253402300799),
+    ];
+
+    #[test]
+    fn test_date_time_conversions() {
+        for &(year, month, day_of_month, hours, minutes, seconds, expected) in
+                DATE_TIMES {
+            let time = Time::from_ymdhms_utc(year, month, day_of_month, hours,
+                                             minutes, seconds).unwrap();
+            assert_eq!(expected, time.seconds_since_unix_epoch());
+            assert_eq!(DateTime {
+                           year: year,
+                           month: month,
+                           day_of_month: day_of_month,
+                           hours: hours,
+                           minutes: minutes,
+                           seconds: seconds,
+                       },
+                       time.to_date_time());
+        }
+    }
+
+    #[test]
+    fn test_invalid_date_times() {
+        let invalid = [
+            (1969, 12, 31, 23, 59, 59),
+            (2015, 2, 29, 0, 0, 0),
+            (2016, 0, 1, 0, 0, 0),
+            (2016, 13, 1, 0, 0, 0),
+            (2016, 4, 31, 0, 0, 0),
+            (2016, 1, 0, 0, 0, 0),
+            (2016, 1, 1, 24, 0, 0),
+            (2016, 1, 1, 0, 60, 0),
+            (2016, 1, 1, 0, 0, 60),
+        ];
+        for &(year, month, day_of_month, hours, minutes, seconds) in
+                invalid.iter() {
+            assert_eq!(Err(Error::BadDERTime),
+                       Time::from_ymdhms_utc(year, month, day_of_month, hours,
+                                             minutes, seconds));
+        }
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let time = Time::from_seconds_since_unix_epoch(1000);
+        let minute = Duration::from_secs(60);
+        assert_eq!(Some(Time::from_seconds_since_unix_epoch(1060)),
+                   time.checked_add(minute));
+        assert_eq!(Some(Time::from_seconds_since_unix_epoch(940)),
+                   time.checked_sub(minute));
+        assert_eq!(Some(Time::from_seconds_since_unix_epoch(1000)),
+                   time.checked_add(Duration::from_millis(999)));
+        assert_eq!(None, time.checked_sub(Duration::from_secs(1001)));
+        assert_eq!(None,
+                   Time::from_seconds_since_unix_epoch(u64::MAX)
+                       .checked_add(Duration::from_secs(1)));
+        assert_eq!(Some(minute),
+                   time.checked_add(minute).unwrap()
+                       .checked_duration_since(time));
+        assert_eq!(None,
+                   time.checked_duration_since(time.checked_add(minute)
+                                                   .unwrap()));
+    }
 }
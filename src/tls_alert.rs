@@ -0,0 +1,160 @@
+// Copyright 2016 Brian Smith.
+//
+// Permission to use, copy, modify, and/or distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHORS DISCLAIM ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHORS BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+use Error;
+
+/// A TLS `AlertDescription` (RFC 5246 Section 7.2) that a TLS implementation
+/// should send when certificate verification fails.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TlsAlert {
+    /// `bad_certificate`: the certificate was corrupt or contained signatures
+    /// that did not verify correctly, or is otherwise invalid.
+    BadCertificate,
+
+    /// `unsupported_certificate`: the certificate was of an unsupported type,
+    /// e.g. it used an unsupported algorithm or critical extension, or it
+    /// isn't valid for the intended usage.
+    UnsupportedCertificate,
+
+    /// `certificate_expired`: the certificate has expired or is not currently
+    /// valid.
+    CertificateExpired,
+
+    /// `certificate_revoked`: the certificate was revoked by its issuer.
+    CertificateRevoked,
+
+    /// `certificate_unknown`: some other issue arose in processing the
+    /// certificate, e.g. its revocation status couldn't be determined.
+    CertificateUnknown,
+
+    /// `unknown_ca`: no valid certificate chain to a trust anchor could be
+    /// built.
+    UnknownCA,
+
+    /// `internal_error`: the verification failed because of the local
+    /// application's inputs rather than because of the peer's certificate.
+    InternalError,
+}
+
+// The wire encodings of the alerts.
+const DESCRIPTIONS: [(TlsAlert, u8); 7] = [
+    (TlsAlert::BadCertificate, 42),
+    (TlsAlert::UnsupportedCertificate, 43),
+    (TlsAlert::CertificateRevoked, 44),
+    (TlsAlert::CertificateExpired, 45),
+    (TlsAlert::CertificateUnknown, 46),
+    (TlsAlert::UnknownCA, 48),
+    (TlsAlert::InternalError, 80),
+];
+
+impl TlsAlert {
+    /// The alert to send for `error`.
+    ///
+    /// This mapping is intended to be used by all TLS implementations that
+    /// use webpki so that they report errors consistently.
+    pub fn from_error(error: Error) -> TlsAlert {
+        // There is intentionally no `_` arm, so that every new `Error` variant
+        // must be added here.
+        match error {
+            Error::BadDER |
+            Error::BadDERTime |
+            Error::CAUsedAsEndEntity |
+            Error::CertNotValidForName |
+            Error::DERElementTooLong |
+            Error::DERNestingTooDeep |
+            Error::DuplicateExtension |
+            Error::EmptySubjectAltName |
+            Error::EmptySubjectWithoutCriticalSubjectAltName |
+            Error::EndEntityUsedAsCA |
+            Error::ExtensionValueInvalid |
+            Error::InputTooLong |
+            Error::InvalidAcmeIdentifier |
+            Error::InvalidCertValidity |
+            Error::InvalidSignatureForPublicKey |
+            Error::MalformedDnsIdentifier |
+            Error::NameConstraintViolation |
+            Error::PathLenConstraintViolated |
+            Error::PolicyValidationFailed |
+            Error::SignatureAlgorithmMismatch => TlsAlert::BadCertificate,
+
+            Error::EndEntityKeyTypeNotAllowed |
+            Error::PublicKeyTooSmall |
+            Error::RequiredEKUNotFound |
+            Error::UnsupportedCertVersion |
+            Error::UnsupportedCriticalExtension |
+            Error::UnsupportedSignatureAlgorithmForPublicKey |
+            Error::UnsupportedSignatureAlgorithm |
+            Error::ValidityPeriodTooLong =>
+                TlsAlert::UnsupportedCertificate,
+
+            Error::CertExpired |
+            Error::CertNotValidYet => TlsAlert::CertificateExpired,
+
+            Error::CertRevoked => TlsAlert::CertificateRevoked,
+
+            Error::RevocationStatusUnknown => TlsAlert::CertificateUnknown,
+
+            Error::NoTrustAnchors |
+            Error::TooManyCandidateIssuers |
+            Error::TooManySignatureVerifications |
+            Error::UnknownIssuer => TlsAlert::UnknownCA,
+
+            Error::InvalidReferenceName => TlsAlert::InternalError,
+        }
+    }
+
+    /// The value of the alert's `AlertDescription` on the wire.
+    pub fn description(self) -> u8 {
+        DESCRIPTIONS.iter()
+                    .find(|&&(alert, _)| alert == self)
+                    .map(|&(_, description)| description)
+                    .unwrap() // Every alert is in `DESCRIPTIONS`.
+    }
+
+    /// The alert with the given wire-encoded `AlertDescription`, if it is one
+    /// of the alerts that is used for certificate verification failures.
+    pub fn from_description(description: u8) -> Option<TlsAlert> {
+        DESCRIPTIONS.iter()
+                    .find(|&&(_, d)| d == description)
+                    .map(|&(alert, _)| alert)
+    }
+}
+
+impl From<Error> for TlsAlert {
+    fn from(error: Error) -> TlsAlert { TlsAlert::from_error(error) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DESCRIPTIONS, TlsAlert};
+    use Error;
+
+    #[test]
+    fn test_descriptions() {
+        for &(alert, description) in DESCRIPTIONS.iter() {
+            assert_eq!(description, alert.description());
+            assert_eq!(Some(alert), TlsAlert::from_description(description));
+        }
+        assert_eq!(None, TlsAlert::from_description(0)); // close_notify
+        assert_eq!(None, TlsAlert::from_description(40)); // handshake_failure
+    }
+
+    #[test]
+    fn test_from_error() {
+        assert_eq!(TlsAlert::UnknownCA, TlsAlert::from(Error::UnknownIssuer));
+        assert_eq!(TlsAlert::CertificateExpired,
+                   TlsAlert::from(Error::CertNotValidYet));
+        assert_eq!(45, TlsAlert::from(Error::CertExpired).description());
+    }
+}
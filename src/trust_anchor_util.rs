@@ -14,21 +14,30 @@
 
 //! Utilities for efficiently embedding trust anchors in programs.
 
-use {Error, TrustAnchor};
+use {der, der_writer, Error, Policy, TrustAnchor};
 use cert::{EndEntityOrCA, parse_cert};
+use ring::digest;
 use std;
 use untrusted;
 
+#[cfg(feature = "certdata")]
+pub use certdata::{Certdata, CertdataDistrust, CertdataError, CertdataRoot,
+                   parse_certdata};
+
+pub use root_store::{PlatformRoot, RootStoreError, RootStoreProvider,
+                     trust_anchors_for_usage, trust_anchors_for_usage_in};
+
+#[cfg(feature = "openssl_dir")]
+pub use root_store::OpensslDirectory;
+
 /// Interprets the given DER-encoded certificate as a `TrustAnchor`. The
 /// certificate is not validated. In particular, there is no check that the
 /// certificate is self-signed or even that the certificate has the cA basic
 /// constraint.
-pub fn cert_der_as_trust_anchor<'a>(cert_der: untrusted::Input<'a>)
+pub fn cert_der_as_trust_anchor<'a>(cert_der: &'a [u8])
                                     -> Result<TrustAnchor<'a>, Error> {
-    // XXX: `EndEntityOrCA::EndEntity` is used instead of `EndEntityOrCA::CA`
-    // because we don't have a refernce to a child cert, which is needed for
-    // `EndEntityOrCA::CA`. For this purpose, it doesn't matter.
-    let cert = try!(parse_cert(cert_der, EndEntityOrCA::EndEntity));
+    let cert = try!(parse_cert(untrusted::Input::from(cert_der),
+                               EndEntityOrCA::TargetCA, &Policy::default()));
     Ok(TrustAnchor {
         subject: cert.subject.as_slice_less_safe(),
         spki: cert.spki.as_slice_less_safe(),
@@ -37,6 +46,269 @@ pub fn cert_der_as_trust_anchor<'a>(cert_der: untrusted::Input<'a>)
     })
 }
 
+/// Two trust anchors with the same subject and subjectPublicKeyInfo, but with
+/// different name constraints. See `dedup_trust_anchors`.
+///
+/// The indexes are positions in the input of the function that found the
+/// conflict; the first one is lower.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct NameConstraintsConflict {
+    /// The index of the first of the trust anchors.
+    pub first_index: usize,
+
+    /// The index of the other trust anchor.
+    pub second_index: usize,
+}
+
+/// Removes duplicate trust anchors, i.e. the ones with the same subject and
+/// subjectPublicKeyInfo as an earlier one, keeping the order of the rest.
+///
+/// A duplicate with name constraints that differ from the earlier one's, or
+/// that has name constraints when the earlier one has none or vice versa, is
+/// a conflict that has to be resolved by whoever maintains the sources: it
+/// isn't clear whether the constraints were added or removed on purpose, and
+/// silently keeping either one could either break or widen the set of
+/// certificates the root is trusted for. The first conflict is returned.
+pub fn dedup_trust_anchors<'a>(trust_anchors: &[TrustAnchor<'a>])
+        -> Result<std::vec::Vec<TrustAnchor<'a>>, NameConstraintsConflict> {
+    let mut deduped: std::vec::Vec<(usize, TrustAnchor<'a>)> =
+        std::vec::Vec::new();
+    for (index, trust_anchor) in trust_anchors.iter().enumerate() {
+        let earlier = deduped.iter().find(|&(_, kept)| {
+            kept.subject == trust_anchor.subject &&
+                kept.spki == trust_anchor.spki
+        });
+        if let Some(&(kept_index, ref kept)) = earlier {
+            if kept.name_constraints != trust_anchor.name_constraints {
+                return Err(NameConstraintsConflict {
+                    first_index: kept_index,
+                    second_index: index,
+                });
+            }
+            continue;
+        }
+        deduped.push((index, TrustAnchor {
+            subject: trust_anchor.subject,
+            spki: trust_anchor.spki,
+            name_constraints: trust_anchor.name_constraints,
+        }));
+    }
+    Ok(deduped.into_iter().map(|(_, trust_anchor)| trust_anchor).collect())
+}
+
+/// Merges the trust anchors from multiple sources, e.g. a platform root store
+/// and an application's additional roots, into one list without duplicates.
+///
+/// The result is the same regardless of how the sources were loaded: the
+/// anchors of the first source come first, in their order, followed by the
+/// anchors of the next source that aren't duplicates, and so on, which is
+/// also the priority order for `TrustAnchorSet`. Duplicates and conflicts
+/// are as for `dedup_trust_anchors` of the concatenation of the sources, and
+/// the indexes of a conflict are positions in that concatenation.
+pub fn merge_trust_anchors<'a>(sources: &[&[TrustAnchor<'a>]])
+        -> Result<std::vec::Vec<TrustAnchor<'a>>, NameConstraintsConflict> {
+    let mut all = std::vec::Vec::new();
+    for source in sources {
+        all.extend(source.iter().map(|trust_anchor| TrustAnchor {
+            subject: trust_anchor.subject,
+            spki: trust_anchor.spki,
+            name_constraints: trust_anchor.name_constraints,
+        }));
+    }
+    dedup_trust_anchors(&all)
+}
+
+/// Encodes `trust_anchor` as an RFC 5914 `TrustAnchorInfo`, for exchanging
+/// minimized trust anchors with other PKI software; see
+/// https://tools.ietf.org/html/rfc5914#section-2.
+///
+/// The result contains the `pubKey`, a `keyId` computed with method (1) of
+/// RFC 5280 Section 4.2.1.2 (the SHA-1 hash of the public key), and
+/// `certPath` controls with the `taName` and, if the trust anchor has name
+/// constraints, the `nameConstr`. It fails with `Error::BadDER` if the
+/// trust anchor's `spki` is malformed.
+///
+/// `trust_anchor_from_der` is the inverse of this function.
+pub fn trust_anchor_to_der(trust_anchor: &TrustAnchor)
+                           -> Result<std::vec::Vec<u8>, Error> {
+    const SEQUENCE: u8 = der::Tag::Sequence as u8;
+    const NAME_CONSTR_TAG: u8 = der::Tag::ContextSpecificConstructed3 as u8;
+
+    let public_key = try!(untrusted::Input::from(trust_anchor.spki)
+        .read_all(Error::BadDER, |spki| {
+            let _algorithm =
+                try!(der::expect_tag_and_get_value(spki, der::Tag::Sequence));
+            der::bit_string_with_no_unused_bits(spki)
+        }));
+    let key_id = digest::digest(&digest::SHA1,
+                                public_key.as_slice_less_safe());
+
+    let mut out = std::vec::Vec::new();
+    try!(der_writer::write_sequence(&mut out, |out| {
+        try!(der_writer::write_tlv(out, SEQUENCE, trust_anchor.spki));
+        try!(der_writer::write_octet_string(out, key_id.as_ref()));
+        der_writer::write_sequence(out, |out| {
+            try!(der_writer::write_tlv(out, SEQUENCE, trust_anchor.subject));
+            match trust_anchor.name_constraints {
+                Some(name_constraints) =>
+                    der_writer::write_tlv(out, NAME_CONSTR_TAG,
+                                          name_constraints),
+                None => Ok(()),
+            }
+        })
+    }));
+    Ok(out)
+}
+
+/// Interprets an RFC 5914 `TrustAnchorInfo` as a `TrustAnchor`; see
+/// https://tools.ietf.org/html/rfc5914#section-2.
+///
+/// The `TrustAnchorInfo` must have `certPath` controls, since the `taName`
+/// is the subject of the trust anchor. The `keyId`, `taTitle`, extensions,
+/// and the trust anchor's certificate, if the controls include one, aren't
+/// used by webpki, so they are ignored.
+///
+/// The `nameConstr` becomes the trust anchor's name constraints. RFC 5937
+/// requires the other controls, the `policySet`, the `policyFlags`, and the
+/// `pathLenConstraint`, to be enforced for every path that terminates at the
+/// trust anchor, but a `TrustAnchor` can't carry them. A `TrustAnchorInfo`
+/// that has any of them, other than `policyFlags` with none of the flags
+/// set, is rejected with `Error::UnsupportedCriticalExtension`.
+pub fn trust_anchor_from_der<'a>(trust_anchor_info_der: &'a [u8])
+                                 -> Result<TrustAnchor<'a>, Error> {
+    untrusted::Input::from(trust_anchor_info_der).read_all(Error::BadDER,
+                                                           |input| {
+        der::nested(input, der::Tag::Sequence, Error::BadDER,
+                    trust_anchor_info)
+    })
+}
+
+/// Parses an RFC 5914 `TrustAnchorList`, the format in which some
+/// governments distribute their trust anchors; see
+/// https://tools.ietf.org/html/rfc5914#section-4.
+///
+/// A trust anchor that is a `Certificate` is interpreted as by
+/// `cert_der_as_trust_anchor`; a `TrustAnchorInfo` is interpreted as by
+/// `trust_anchor_from_der`, so the whole list is rejected if one of them has
+/// constraints that a `TrustAnchor` can't carry. A `TBSCertificate` isn't
+/// supported and results in `Error::BadDER`. A list that is distributed in a
+/// signed CMS `ContentInfo` must be verified and unwrapped by the caller.
+pub fn parse_trust_anchor_list<'a>(trust_anchor_list_der: &'a [u8])
+        -> Result<std::vec::Vec<TrustAnchor<'a>>, Error> {
+    const CERTIFICATE_TAG: u8 = der::Tag::Sequence as u8;
+    const TA_INFO_TAG: u8 = 0x80 | der::CONSTRUCTED | 2;
+
+    let mut trust_anchors = std::vec::Vec::new();
+    try!(untrusted::Input::from(trust_anchor_list_der).read_all(
+            Error::BadDER, |input| {
+        let list = try!(der::expect_tag_and_get_long_value(
+            input, der::Tag::Sequence));
+        list.read_all(Error::BadDER, |list| {
+            while !list.at_end() {
+                let mark1 = list.mark();
+                let (tag, value) = try!(der::read_tag_and_get_value(list));
+                let mark2 = list.mark();
+                let trust_anchor = match tag {
+                    CERTIFICATE_TAG => {
+                        let cert_der =
+                            try!(list.get_input_between_marks(mark1, mark2)
+                                     .map_err(|_| Error::BadDER));
+                        try!(cert_der_as_trust_anchor(
+                            cert_der.as_slice_less_safe()))
+                    },
+                    TA_INFO_TAG => try!(value.read_all(Error::BadDER, |tagged| {
+                        der::nested(tagged, der::Tag::Sequence, Error::BadDER,
+                                    trust_anchor_info)
+                    })),
+                    _ => { return Err(Error::BadDER); }
+                };
+                trust_anchors.push(trust_anchor);
+            }
+            Ok(())
+        })
+    }));
+    if trust_anchors.is_empty() {
+        // TrustAnchorList ::= SEQUENCE SIZE (1..MAX) OF TrustAnchorChoice
+        return Err(Error::BadDER);
+    }
+    Ok(trust_anchors)
+}
+
+// Parses the contents of a `TrustAnchorInfo` `SEQUENCE`.
+fn trust_anchor_info<'a>(info: &mut untrusted::Reader<'a>)
+                         -> Result<TrustAnchor<'a>, Error> {
+    // The context-specific tags of the optional fields.
+    const CERTIFICATE_TAG: u8 = der::Tag::ContextSpecificConstructed0 as u8;
+    const POLICY_SET_TAG: u8 = der::Tag::ContextSpecificConstructed1 as u8;
+    const POLICY_FLAGS_TAG: u8 = 0x80 | 2;
+    const NAME_CONSTR_TAG: u8 = der::Tag::ContextSpecificConstructed3 as u8;
+    const PATH_LEN_CONSTRAINT_TAG: u8 = 0x80 | 4;
+    const UTF8_STRING_TAG: u8 = 0x0c;
+
+    fn optional<'a>(input: &mut untrusted::Reader<'a>, tag: u8)
+                    -> Result<Option<untrusted::Input<'a>>, Error> {
+        if !input.peek(tag) {
+            return Ok(None);
+        }
+        der::read_tag_and_get_value(input).map(|(_, value)| Some(value))
+    }
+
+    // The version is v1, the DEFAULT, so DER requires it to be omitted.
+    let spki = try!(der::expect_tag_and_get_value(info, der::Tag::Sequence));
+    let _key_id =
+        try!(der::expect_tag_and_get_value(info, der::Tag::OctetString));
+    let _ta_title = try!(optional(info, UTF8_STRING_TAG));
+    let trust_anchor = try!(der::nested(info, der::Tag::Sequence,
+                                        Error::BadDER, |cert_path| {
+        let subject =
+            try!(der::expect_tag_and_get_value(cert_path, der::Tag::Sequence));
+        let _certificate = try!(optional(cert_path, CERTIFICATE_TAG));
+        let policy_set = try!(optional(cert_path, POLICY_SET_TAG));
+        let policy_flags = match try!(optional(cert_path, POLICY_FLAGS_TAG)) {
+            Some(flags) => try!(cert_policy_flags(flags)),
+            None => 0,
+        };
+        let name_constraints = try!(optional(cert_path, NAME_CONSTR_TAG));
+        let path_len_constraint =
+            try!(optional(cert_path, PATH_LEN_CONSTRAINT_TAG));
+        if policy_set.is_some() || policy_flags != 0 ||
+           path_len_constraint.is_some() {
+            return Err(Error::UnsupportedCriticalExtension);
+        }
+        Ok(TrustAnchor {
+            subject: subject.as_slice_less_safe(),
+            spki: spki.as_slice_less_safe(),
+            name_constraints:
+                name_constraints.map(|nc| nc.as_slice_less_safe()),
+        })
+    }));
+    // The extensions and the title's language tag.
+    let _ = info.skip_to_end();
+    Ok(trust_anchor)
+}
+
+// CertPolicyFlags ::= BIT STRING {
+//     inhibitPolicyMapping    (0),
+//     requireExplicitPolicy   (1),
+//     inhibitAnyPolicy        (2) }
+//
+// Returns the first byte of the bits, which contains all the named ones.
+fn cert_policy_flags(value: untrusted::Input) -> Result<u8, Error> {
+    value.read_all(Error::BadDER, |value| {
+        let unused_bits =
+            try!(value.read_byte().map_err(|_| Error::BadDER));
+        if value.at_end() {
+            return if unused_bits == 0 { Ok(0) } else { Err(Error::BadDER) };
+        }
+        if unused_bits > 7 {
+            return Err(Error::BadDER);
+        }
+        let flags = try!(value.read_byte().map_err(|_| Error::BadDER));
+        let _ = value.skip_to_end();
+        Ok(flags)
+    })
+}
+
 /// Generates code for hard-coding the given trust anchors into a program. This
 /// is designed to be used in a build script. `name` is the name of the public
 /// static variable that will contain the TrustAnchor array.
@@ -52,3 +324,227 @@ pub fn generate_code_for_trust_anchors(name: &str,
 
     decl + &value
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{cert_der_as_trust_anchor, dedup_trust_anchors,
+                merge_trust_anchors, NameConstraintsConflict,
+                parse_trust_anchor_list, PlatformRoot, trust_anchor_from_der,
+                trust_anchor_to_der, trust_anchors_for_usage,
+                trust_anchors_for_usage_in};
+    use {der, der_writer, AllocScope, BumpArena, Error, TrustAnchor, Usage};
+    use std::vec::Vec;
+
+    const A: TrustAnchor<'static> = TrustAnchor {
+        subject: b"a", spki: b"1", name_constraints: None,
+    };
+    const B: TrustAnchor<'static> = TrustAnchor {
+        subject: b"b", spki: b"1", name_constraints: None,
+    };
+    const A_NEW_KEY: TrustAnchor<'static> = TrustAnchor {
+        subject: b"a", spki: b"2", name_constraints: None,
+    };
+    const A_CONSTRAINED: TrustAnchor<'static> = TrustAnchor {
+        subject: b"a", spki: b"1", name_constraints: Some(b"nc"),
+    };
+
+    fn subjects_and_spkis<'a>(trust_anchors: &[TrustAnchor<'a>])
+                              -> ::std::vec::Vec<(&'a [u8], &'a [u8])> {
+        trust_anchors.iter().map(|ta| (ta.subject, ta.spki)).collect()
+    }
+
+    #[test]
+    fn test_dedup_trust_anchors() {
+        let deduped = dedup_trust_anchors(&[B, A, A_NEW_KEY, B, A]).unwrap();
+        assert_eq!(subjects_and_spkis(&[B, A, A_NEW_KEY]),
+                   subjects_and_spkis(&deduped));
+
+        assert_eq!(Some(NameConstraintsConflict {
+                       first_index: 1,
+                       second_index: 3,
+                   }),
+                   dedup_trust_anchors(&[B, A, A_NEW_KEY, A_CONSTRAINED])
+                       .err());
+    }
+
+    #[test]
+    fn test_merge_trust_anchors() {
+        let merged =
+            merge_trust_anchors(&[&[A_NEW_KEY, B], &[], &[B, A]]).unwrap();
+        assert_eq!(subjects_and_spkis(&[A_NEW_KEY, B, A]),
+                   subjects_and_spkis(&merged));
+
+        assert_eq!(Some(NameConstraintsConflict {
+                       first_index: 0,
+                       second_index: 2,
+                   }),
+                   merge_trust_anchors(&[&[A_CONSTRAINED, B], &[A]]).err());
+    }
+
+    #[test]
+    fn test_trust_anchors_for_usage() {
+        let root = |cert_der: &[u8], usages: &[Usage]| PlatformRoot {
+            cert_der: cert_der.to_vec(),
+            usages: usages.to_vec(),
+        };
+        let ecdsa_root: &[u8] =
+            include_bytes!("../tests/data/ecdsa-root.der");
+        let rsa_root: &[u8] = include_bytes!("../tests/data/rsa-root.der");
+        let roots = [
+            root(ecdsa_root, &[Usage::TlsServer, Usage::TlsClient]),
+            root(b"not a certificate", &[Usage::TlsServer]),
+            root(rsa_root, &[Usage::CodeSigning]),
+            root(rsa_root, &[Usage::TlsServer]),
+        ];
+        let expected = [roots[0].trust_anchor().unwrap(),
+                        roots[3].trust_anchor().unwrap()];
+
+        let trust_anchors = trust_anchors_for_usage(&roots, Usage::TlsServer);
+        assert_eq!(subjects_and_spkis(&expected),
+                   subjects_and_spkis(&trust_anchors));
+
+        let mut storage = [A; 2];
+        let mut arena = BumpArena::new(&mut storage);
+        assert_eq!(Ok(()),
+                   trust_anchors_for_usage_in(&roots, Usage::TlsServer,
+                                              &mut arena));
+        assert_eq!(subjects_and_spkis(&expected),
+                   subjects_and_spkis(arena.as_slice()));
+
+        arena.clear();
+        assert_eq!(Err(Error::InputTooLong),
+                   trust_anchors_for_usage_in(&roots, Usage::TlsClient,
+                                              &mut arena)
+                       .and_then(|()| {
+                           trust_anchors_for_usage_in(&roots,
+                                                      Usage::TlsServer,
+                                                      &mut arena)
+                       }));
+        assert_eq!(2, arena.as_slice().len());
+    }
+
+    #[test]
+    fn test_trust_anchor_to_der() {
+        let root =
+            cert_der_as_trust_anchor(include_bytes!(
+                "../tests/data/ecdsa-root.der")).unwrap();
+        let constrained = TrustAnchor {
+            name_constraints: Some(&[0xa0, 0x05, 0x30, 0x03, 0x82, 0x01, b'a']),
+            ..root
+        };
+        for trust_anchor in &[TrustAnchor { ..constrained }, root] {
+            let encoded = trust_anchor_to_der(trust_anchor).unwrap();
+            let decoded = trust_anchor_from_der(&encoded).unwrap();
+            assert_eq!(format!("{:?}", trust_anchor),
+                       format!("{:?}", decoded));
+        }
+
+        // The keyId is the SHA-1 hash of the public key, which is also the
+        // root's subjectKeyIdentifier.
+        const KEY_ID: &'static [u8] = &[
+            0x04, 0x14,
+            0x7a, 0xdd, 0x85, 0x6b, 0x19, 0x67, 0xe3, 0xe4, 0xf3, 0xd3,
+            0x5c, 0xeb, 0xf2, 0xb5, 0x55, 0x2b, 0x82, 0x90, 0xae, 0x39,
+        ];
+        let encoded = trust_anchor_to_der(&constrained).unwrap();
+        assert!(encoded.windows(KEY_ID.len()).any(|w| w == KEY_ID));
+
+        assert_eq!(Some(Error::BadDER), trust_anchor_to_der(&A).err());
+        assert_eq!(Some(Error::BadDER),
+                   trust_anchor_from_der(&encoded[..(encoded.len() - 1)])
+                       .err());
+    }
+
+    #[test]
+    fn test_parse_trust_anchor_list() {
+        const SEQUENCE: u8 = der::Tag::Sequence as u8;
+        const TA_INFO_TAG: u8 = 0x80 | der::CONSTRUCTED | 2;
+
+        fn write_tlv(out: &mut Vec<u8>, tag: u8, value: &[u8]) {
+            der_writer::write_tlv(out, tag, value).unwrap();
+        }
+
+        // A TrustAnchorInfo for `trust_anchor` with the given `certPath`
+        // controls after the `taName`.
+        fn trust_anchor_info(trust_anchor: &TrustAnchor,
+                             controls: &[(u8, &[u8])]) -> Vec<u8> {
+            let mut cert_path = Vec::new();
+            write_tlv(&mut cert_path, SEQUENCE, trust_anchor.subject);
+            for &(tag, value) in controls {
+                write_tlv(&mut cert_path, tag, value);
+            }
+            let mut info = Vec::new();
+            write_tlv(&mut info, SEQUENCE, trust_anchor.spki);
+            der_writer::write_octet_string(&mut info, &[0; 20]).unwrap();
+            write_tlv(&mut info, 0x0c, b"title");
+            write_tlv(&mut info, SEQUENCE, &cert_path);
+            let mut encoded = Vec::new();
+            write_tlv(&mut encoded, SEQUENCE, &info);
+            encoded
+        }
+
+        // A TrustAnchorList of `certificate` followed by `infos`.
+        fn trust_anchor_list(certificate: &[u8], infos: &[&[u8]])
+                             -> Vec<u8> {
+            let mut list = Vec::new();
+            list.extend_from_slice(certificate);
+            for info in infos {
+                write_tlv(&mut list, TA_INFO_TAG, info);
+            }
+            let mut encoded = Vec::new();
+            write_tlv(&mut encoded, SEQUENCE, &list);
+            encoded
+        }
+
+        let ecdsa_root: &[u8] =
+            include_bytes!("../tests/data/ecdsa-root.der");
+        let rsa_root =
+            cert_der_as_trust_anchor(include_bytes!(
+                "../tests/data/rsa-root.der")).unwrap();
+
+        const NAME_CONSTRAINTS: &'static [u8] =
+            &[0xa0, 0x05, 0x30, 0x03, 0x82, 0x01, b'a'];
+        // policyFlags with none of the flags set doesn't constrain anything.
+        let constrained = trust_anchor_info(&rsa_root, &[
+            (0x82, &[0x00]),
+            (0xa3, NAME_CONSTRAINTS),
+        ]);
+        let encoded = trust_anchor_list(ecdsa_root, &[
+            &trust_anchor_to_der(&rsa_root).unwrap(),
+            &constrained,
+        ]);
+
+        let trust_anchors = parse_trust_anchor_list(&encoded).unwrap();
+        assert_eq!(3, trust_anchors.len());
+        assert_eq!(
+            format!("{:?}", cert_der_as_trust_anchor(ecdsa_root).unwrap()),
+            format!("{:?}", trust_anchors[0]));
+        assert_eq!(format!("{:?}", rsa_root),
+                   format!("{:?}", trust_anchors[1]));
+        assert_eq!(rsa_root.subject, trust_anchors[2].subject);
+        assert_eq!(Some(NAME_CONSTRAINTS), trust_anchors[2].name_constraints);
+
+        // policySet { anyPolicy }, policyFlags { requireExplicitPolicy },
+        // and pathLenConstraint 2 can't be carried by a TrustAnchor, so
+        // they must not be silently dropped.
+        const POLICY_SET: &'static [u8] =
+            &[0x30, 0x06, 0x06, 0x04, 0x55, 0x1d, 0x20, 0x00];
+        for control in &[(0xa1, POLICY_SET), (0x82, &[0x06, 0x40][..]),
+                         (0x84, &[0x02][..])] {
+            let unsupported = trust_anchor_info(&rsa_root, &[*control]);
+            assert_eq!(Some(Error::UnsupportedCriticalExtension),
+                       trust_anchor_from_der(&unsupported).err());
+            assert_eq!(Some(Error::UnsupportedCriticalExtension),
+                       parse_trust_anchor_list(
+                           &trust_anchor_list(ecdsa_root, &[&unsupported]))
+                           .err());
+        }
+
+        // An empty list, and a TBSCertificate.
+        assert_eq!(Some(Error::BadDER),
+                   parse_trust_anchor_list(&[0x30, 0x00]).err());
+        assert_eq!(Some(Error::BadDER),
+                   parse_trust_anchor_list(&[0x30, 0x04, 0xa1, 0x02, 0x30,
+                                             0x00]).err());
+    }
+}
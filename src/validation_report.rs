@@ -0,0 +1,268 @@
+// Copyright 2016 Brian Smith.
+//
+// Permission to use, copy, modify, and/or distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHORS DISCLAIM ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHORS BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+use {AllocScope, Error};
+use cert::Cert;
+use core::cell::{Cell, Ref, RefCell};
+use std::vec::Vec;
+use verification_options::Observer;
+use verify_cert::{self, Trace, VerifiedPath};
+
+/// A record of how a certificate was verified: each certificate that was
+/// considered, the checks that were done, the errors that caused candidate
+/// paths to be rejected, and the final decision.
+///
+/// This is intended for compliance evidence and for diagnosing verification
+/// failures; it contains only plain data, so that it is easy to serialize in
+/// whatever format the application needs.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ValidationReport {
+    /// The steps of the verification, in the order in which they completed.
+    /// In particular, the steps for a candidate issuer's own path are
+    /// recorded before the step recording the result of trying that issuer.
+    pub entries: Vec<ReportEntry>,
+
+    /// The final decision; the same value that the corresponding
+    /// verification function without a report would have returned.
+    pub result: Result<VerifiedPath, Error>,
+}
+
+impl ValidationReport {
+    /// The errors recorded in `entries`, without duplicates, in the order
+    /// described in the documentation of `Error`. When every candidate path
+    /// was rejected, `result` has only the first of them; this lists the
+    /// other reasons too.
+    pub fn rejections(&self) -> Vec<Error> {
+        let mut errors: Vec<Error> = Vec::new();
+        for entry in &self.entries {
+            let error = match *entry {
+                ReportEntry::CertChecked { result: Err(err), .. } |
+                ReportEntry::TrustAnchorTried { result: Err(err), .. } => err,
+                ReportEntry::IntermediateTried { result: Err(err), .. } => err,
+                _ => { continue; }
+            };
+            if !errors.contains(&error) {
+                errors.push(error);
+            }
+        }
+        errors.sort_by(|a, b| verify_cert::error_precedence(*a)
+                                  .cmp(&verify_cert::error_precedence(*b))
+                                  .reverse());
+        errors
+    }
+}
+
+/// A step of a verification recorded in a `ValidationReport`.
+///
+/// `depth` is the position of the certificate that the step concerns in the
+/// candidate path: 0 for the certificate being verified, 1 for its issuer,
+/// and so on.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ReportEntry {
+    /// The checks of a certificate that don't depend on its issuer were
+    /// done: the consistency of its signature algorithm identifiers, its
+    /// validity period, its basic constraints, and its extended key usage.
+    CertChecked {
+        /// The position of the certificate in the candidate path.
+        depth: usize,
+
+        /// The certificate's DER-encoded subject `Name`, without the tag and
+        /// length.
+        subject: Vec<u8>,
+
+        /// The certificate's serial number; see
+        /// `EndEntityCert::serial_number`.
+        serial_number: Vec<u8>,
+
+        /// The result of the checks.
+        result: Result<(), Error>,
+    },
+
+    /// A trust anchor whose subject matches the issuer of the certificate at
+    /// `depth` was tried as its issuer: its name constraints, if any, were
+    /// checked and the signatures along the path were verified.
+    TrustAnchorTried {
+        /// The position of the certificate in the candidate path.
+        depth: usize,
+
+        /// The index of the trust anchor in the `trust_anchors` slice.
+        trust_anchor_index: usize,
+
+        /// The result of the checks.
+        result: Result<(), Error>,
+    },
+
+    /// An intermediate certificate whose subject matches the issuer of the
+    /// certificate at `depth` was tried as its issuer.
+    IntermediateTried {
+        /// The position of the certificate in the candidate path.
+        depth: usize,
+
+        /// The index of the intermediate certificate in the
+        /// `intermediate_certs` slice.
+        intermediate_index: usize,
+
+        /// The result of building the rest of the path through the
+        /// intermediate certificate.
+        result: Result<VerifiedPath, Error>,
+    },
+
+    /// An intermediate certificate whose subject matches the issuer of the
+    /// certificate at `depth` wasn't tried as its issuer because it is a
+    /// self-issued copy of a trust anchor, which was tried directly instead.
+    ///
+    /// This doesn't affect the result, but it means the peer is sending a
+    /// root certificate that it doesn't need to send, which wastes bandwidth
+    /// and is worth reporting to the peer's operator.
+    RedundantTrustAnchor {
+        /// The position of the certificate in the candidate path.
+        depth: usize,
+
+        /// The index of the intermediate certificate in the
+        /// `intermediate_certs` slice.
+        intermediate_index: usize,
+
+        /// The index of the trust anchor in the `trust_anchors` slice.
+        trust_anchor_index: usize,
+    },
+
+    /// Neither a trust anchor nor an intermediate certificate has a subject
+    /// that matches the issuer of the certificate at `depth`, so no issuer
+    /// was tried at all. This usually means that the trust anchor for the
+    /// certificate isn't loaded or that the peer didn't send an
+    /// intermediate certificate that it needs to send.
+    IssuerNotFound {
+        /// The position of the certificate in the candidate path.
+        depth: usize,
+
+        /// The certificate's DER-encoded issuer `Name`, without the tag and
+        /// length.
+        issuer: Vec<u8>,
+    },
+}
+
+/// Records the steps of a verification for a `ValidationReport`, as the
+/// `VerificationOptions::observer`.
+///
+/// The entries are kept in an `AllocScope`, a `Vec` unless the recorder is
+/// created with `ReportRecorder::in_scope`, e.g. with a `BumpArena` that a
+/// server reuses for every verification it records. The subjects and serial
+/// numbers of the `ReportEntry::CertChecked` entries are copied to the heap
+/// either way.
+#[derive(Default)]
+pub struct ReportRecorder<S = Vec<ReportEntry>> {
+    entries: RefCell<S>,
+    truncated: Cell<bool>,
+}
+
+impl ReportRecorder {
+    /// A recorder that has recorded nothing yet.
+    pub fn new() -> ReportRecorder {
+        ReportRecorder::in_scope(Vec::new())
+    }
+
+    /// The report of the recorded steps, with `result`, the result of the
+    /// verification.
+    pub fn into_report(self, result: Result<VerifiedPath, Error>)
+                       -> ValidationReport {
+        ValidationReport {
+            entries: self.entries.into_inner(),
+            result: result,
+        }
+    }
+}
+
+impl<S: AllocScope<ReportEntry>> ReportRecorder<S> {
+    /// A recorder that appends the entries to `entries`.
+    pub fn in_scope(entries: S) -> ReportRecorder<S> {
+        ReportRecorder {
+            entries: RefCell::new(entries),
+            truncated: Cell::new(false),
+        }
+    }
+
+    /// The entries that have been recorded, in the order of
+    /// `ValidationReport::entries`.
+    pub fn entries<'r>(&'r self) -> Ref<'r, [ReportEntry]> {
+        Ref::map(self.entries.borrow(), |entries| entries.as_slice())
+    }
+
+    /// Whether any entries were dropped because the scope ran out of room.
+    /// The entries that were recorded are then the first steps of the
+    /// verification.
+    pub fn is_truncated(&self) -> bool { self.truncated.get() }
+
+    /// The scope, with the entries that have been recorded.
+    pub fn into_scope(self) -> S { self.entries.into_inner() }
+
+    fn record(&self, entry: ReportEntry) {
+        if self.entries.borrow_mut().push(entry).is_err() {
+            self.truncated.set(true);
+        }
+    }
+}
+
+impl<S: AllocScope<ReportEntry>> Observer for ReportRecorder<S> {
+    fn trace(&self) -> &Trace { self }
+}
+
+impl<S: AllocScope<ReportEntry>> Trace for ReportRecorder<S> {
+    fn cert_checked(&self, depth: usize, cert: &Cert,
+                    result: Result<(), Error>) {
+        self.record(ReportEntry::CertChecked {
+            depth: depth,
+            subject: cert.subject.as_slice_less_safe().to_vec(),
+            serial_number: cert.serial.as_slice_less_safe().to_vec(),
+            result: result,
+        });
+    }
+
+    fn trust_anchor_tried(&self, depth: usize, trust_anchor_index: usize,
+                          result: Result<(), Error>) {
+        self.record(ReportEntry::TrustAnchorTried {
+            depth: depth,
+            trust_anchor_index: trust_anchor_index,
+            result: result,
+        });
+    }
+
+    fn intermediate_tried(&self, depth: usize, intermediate_index: usize,
+                          result: Result<VerifiedPath, Error>) {
+        self.record(ReportEntry::IntermediateTried {
+            depth: depth,
+            intermediate_index: intermediate_index,
+            result: result,
+        });
+    }
+
+    fn intermediate_parsed(&self, _: usize) {}
+    fn signature_verified(&self) {}
+
+    fn trust_anchor_in_intermediates(&self, depth: usize,
+                                     intermediate_index: usize,
+                                     trust_anchor_index: usize) {
+        self.record(ReportEntry::RedundantTrustAnchor {
+            depth: depth,
+            intermediate_index: intermediate_index,
+            trust_anchor_index: trust_anchor_index,
+        });
+    }
+
+    fn issuer_subject_not_found(&self, depth: usize, cert: &Cert) {
+        self.record(ReportEntry::IssuerNotFound {
+            depth: depth,
+            issuer: cert.issuer.as_slice_less_safe().to_vec(),
+        });
+    }
+}
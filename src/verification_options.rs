@@ -0,0 +1,137 @@
+// Copyright 2016 Brian Smith.
+//
+// Permission to use, copy, modify, and/or distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHORS DISCLAIM ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHORS BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+use cert::Cert;
+#[cfg(feature = "std")]
+use issuer_fetcher;
+use revocation::RevocationFilter;
+use verify_cert::{self, ChainInputs, NoTrace, Trace, VerifiedPath};
+use {Error, TrustAnchor};
+#[cfg(feature = "std")]
+use IssuerFetcher;
+
+/// Optional extensions of path building, which every verification function
+/// of `EndEntityCert` that builds a path for a particular usage accepts.
+///
+/// `VerificationOptions::new()` is plain path building. Each of the other
+/// methods adds one option, and the options can be combined freely, e.g.
+/// `VerificationOptions::new().revocation_filter(&filter).observer(&counter)`
+/// to count the work done by a verification that also checks revocation.
+#[derive(Clone, Copy, Default)]
+pub struct VerificationOptions<'o> {
+    revocation_filter: Option<&'o RevocationFilter>,
+    observer: Option<&'o Observer>,
+    prefer_earlier_trust_anchors: bool,
+    #[cfg(feature = "std")]
+    issuer_fetcher: Option<&'o IssuerFetcher>,
+}
+
+impl<'o> VerificationOptions<'o> {
+    /// No options.
+    pub fn new() -> VerificationOptions<'o> {
+        VerificationOptions::default()
+    }
+
+    /// Also rejects any path containing a certificate that `filter` says is
+    /// revoked. Like other errors found in a path that reaches a trust
+    /// anchor, a revoked certificate (`Error::CertRevoked`) makes path
+    /// building try the other paths, and if none of them is valid, the
+    /// result is `Error::CertRevoked`; see `Error` for the order of the
+    /// errors.
+    pub fn revocation_filter(self, filter: &'o RevocationFilter)
+                             -> VerificationOptions<'o> {
+        VerificationOptions { revocation_filter: Some(filter), ..self }
+    }
+
+    /// Reports every step of path building to `observer`, e.g. a
+    /// `StatsCounter` or a `ReportRecorder`. When path building is done more
+    /// than once, because of `prefer_earlier_trust_anchors` or
+    /// `issuer_fetcher`, the steps of all of the searches are reported.
+    pub fn observer(self, observer: &'o Observer) -> VerificationOptions<'o> {
+        VerificationOptions { observer: Some(observer), ..self }
+    }
+
+    /// If the certificate is valid for more than one of the trust anchors,
+    /// the result is a path to the earliest of them, which is the most
+    /// preferred one for a `TrustAnchorSet`.
+    ///
+    /// This may take several path-building searches, one more than the
+    /// number of times a path to a more preferred trust anchor is found.
+    pub fn prefer_earlier_trust_anchors(self) -> VerificationOptions<'o> {
+        VerificationOptions { prefer_earlier_trust_anchors: true, ..self }
+    }
+
+    /// When no trust anchor or intermediate certificate could have issued a
+    /// certificate in a candidate path, `fetcher` is asked for the
+    /// certificates of its issuers, and path building is retried with them
+    /// as additional intermediate certificates. This lets clients do AIA
+    /// chasing without having to re-drive path building themselves.
+    #[cfg(feature = "std")]
+    pub fn issuer_fetcher(self, fetcher: &'o IssuerFetcher)
+                          -> VerificationOptions<'o> {
+        VerificationOptions { issuer_fetcher: Some(fetcher), ..self }
+    }
+}
+
+/// Something that observes the steps of path building; see
+/// `VerificationOptions::observer`. It is implemented by `StatsCounter`, for
+/// `VerificationStats`, and, with the `std` feature, by `ReportRecorder`,
+/// for a `ValidationReport`.
+pub trait Observer {
+    #[doc(hidden)]
+    fn trace(&self) -> &Trace;
+}
+
+// Like `verify_cert::build_chain`, with the extensions of `options`.
+pub fn build_chain<'p, 'a>(inputs: &ChainInputs<'p, 'a>, cert: &Cert<'a>,
+                           options: &VerificationOptions)
+                           -> Result<VerifiedPath, Error> {
+    let trace: &Trace = match options.observer {
+        Some(observer) => observer.trace(),
+        None => &NoTrace,
+    };
+    let search = |trust_anchors: &'a [TrustAnchor<'a>]| {
+        let inputs = ChainInputs { trust_anchors: trust_anchors, ..*inputs };
+        #[cfg(feature = "std")]
+        {
+            if let Some(fetcher) = options.issuer_fetcher {
+                return issuer_fetcher::build_chain(
+                    &inputs, cert, options.revocation_filter, trace, fetcher);
+            }
+        }
+        verify_cert::build_chain(&inputs, cert, options.revocation_filter,
+                                 trace)
+    };
+
+    let trust_anchors = inputs.trust_anchors;
+    let mut best = try!(search(trust_anchors));
+    if !options.prefer_earlier_trust_anchors {
+        return Ok(best);
+    }
+
+    // If there are valid paths to more than one of the trust anchors, the
+    // result is a path to the earliest of them in `trust_anchors`. Once a path
+    // has been found, the search is repeated with only the trust anchors
+    // before the one that path terminates at, until no better path is found.
+    loop {
+        let preferred_anchors = &trust_anchors[..best.trust_anchor_index()];
+        if preferred_anchors.is_empty() {
+            return Ok(best);
+        }
+        match search(preferred_anchors) {
+            Ok(better) => { best = better; },
+            Err(..) => { return Ok(best); }
+        }
+    }
+}
@@ -0,0 +1,108 @@
+// Copyright 2016 Brian Smith.
+//
+// Permission to use, copy, modify, and/or distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHORS DISCLAIM ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHORS BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+use Error;
+use cert::Cert;
+use core::cell::Cell;
+use verification_options::Observer;
+use verify_cert::{Trace, VerifiedPath};
+
+/// Counts of the work done by a verification, e.g. for exporting as metrics.
+///
+/// Large values relative to the number of certificates provided indicate
+/// pathological inputs, e.g. many cross-signed intermediate certificates
+/// that lead to a large path-building search; see
+/// `Policy::max_candidate_issuers` and `Policy::max_signature_verifications`.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct VerificationStats {
+    /// The number of times an intermediate certificate was parsed to see
+    /// whether it is a candidate issuer. The end-entity certificate, which
+    /// was parsed before verification, isn't counted.
+    pub certs_parsed: usize,
+
+    /// The number of signatures that were verified, successfully or not.
+    pub signatures_verified: usize,
+
+    /// The number of times a trust anchor whose subject matches the issuer of
+    /// a certificate in a candidate path was tried as its issuer.
+    pub trust_anchors_tried: usize,
+
+    /// The number of times an intermediate certificate whose subject matches
+    /// the issuer of a certificate in a candidate path was tried as its
+    /// issuer.
+    pub intermediates_tried: usize,
+
+    /// The deepest position of a certificate in any candidate path: 0 for
+    /// the certificate being verified, 1 for its issuer, and so on, as for
+    /// `ReportEntry` `depth`s.
+    pub max_depth: usize,
+}
+
+/// Counts the steps of a verification for `VerificationStats`, as the
+/// `VerificationOptions::observer`. Unlike a `ReportRecorder`, this doesn't
+/// allocate.
+#[derive(Default)]
+pub struct StatsCounter {
+    stats: Cell<VerificationStats>,
+}
+
+impl StatsCounter {
+    /// A counter with all counts zero.
+    pub fn new() -> StatsCounter {
+        StatsCounter { stats: Cell::new(VerificationStats::default()) }
+    }
+
+    /// The counts so far.
+    pub fn stats(&self) -> VerificationStats { self.stats.get() }
+
+    fn update<F: FnOnce(&mut VerificationStats)>(&self, f: F) {
+        let mut stats = self.stats.get();
+        f(&mut stats);
+        self.stats.set(stats);
+    }
+}
+
+impl Observer for StatsCounter {
+    fn trace(&self) -> &Trace { self }
+}
+
+impl Trace for StatsCounter {
+    fn cert_checked(&self, depth: usize, _: &Cert, _: Result<(), Error>) {
+        self.update(|stats| {
+            if depth > stats.max_depth {
+                stats.max_depth = depth;
+            }
+        });
+    }
+
+    fn trust_anchor_tried(&self, _: usize, _: usize, _: Result<(), Error>) {
+        self.update(|stats| stats.trust_anchors_tried += 1);
+    }
+
+    fn intermediate_tried(&self, _: usize, _: usize,
+                          _: Result<VerifiedPath, Error>) {
+        self.update(|stats| stats.intermediates_tried += 1);
+    }
+
+    fn intermediate_parsed(&self, _: usize) {
+        self.update(|stats| stats.certs_parsed += 1);
+    }
+
+    fn signature_verified(&self) {
+        self.update(|stats| stats.signatures_verified += 1);
+    }
+
+    fn trust_anchor_in_intermediates(&self, _: usize, _: usize, _: usize) {}
+    fn issuer_subject_not_found(&self, _: usize, _: &Cert) {}
+}
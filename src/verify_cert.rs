@@ -12,29 +12,186 @@
 // ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
 // OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
 
+use core::cell::{Cell, RefCell};
+use core::fmt;
+use core::time::Duration;
+use revocation::RevocationFilter;
+use ring::digest;
 use untrusted;
-use {cert, der, Error, name, signed_data, SignatureAlgorithm, time,
-     TrustAnchor};
+#[cfg(feature = "log")]
+use logging;
+use {cert, certificate_policies, der, Error, name, name_constraints, Policy,
+     signed_data, SignatureAlgorithm, time, TrustAnchor, TrustAnchorSet};
 use cert::{Cert, EndEntityOrCA};
 
-pub fn build_chain<'a>(required_eku_if_present: KeyPurposeId,
+// The inputs of path building, other than the certificate that a path is
+// built for and the extensions of `VerificationOptions`.
+#[derive(Clone, Copy)]
+pub struct ChainInputs<'p, 'a: 'p> {
+    pub policy: &'p Policy,
+    pub required_eku_if_present: KeyPurposeId,
+    pub supported_sig_algs: &'p [&'p SignatureAlgorithm],
+    pub trust_anchors: &'a [TrustAnchor<'a>],
+    pub intermediate_certs: &'p [&'a [u8]],
+    pub times: VerificationTimes,
+}
+
+pub fn build_chain<'p, 'a>(inputs: &ChainInputs<'p, 'a>, cert: &Cert<'a>,
+                           revocation_filter: Option<&RevocationFilter>,
+                           trace: &Trace) -> Result<VerifiedPath, Error> {
+    let policy = inputs.policy;
+    try!(check_intermediate_certs_limits(policy, inputs.intermediate_certs));
+    #[cfg(feature = "log")]
+    let trace = &logging::LogTrace { inner: trace };
+
+    // Some servers send many copies of the same intermediate certificate.
+    // Only the first copy is tried, so that the copies don't multiply the
+    // number of paths that are searched. The copies are found once, here,
+    // instead of every time an issuer is looked for.
+    let mut is_copy = [false; MAX_INTERMEDIATE_CERTS];
+    for (i, cert_der) in inputs.intermediate_certs.iter().enumerate() {
+        is_copy[i] = inputs.intermediate_certs[..i].contains(cert_der);
+    }
+
+    let ctx = ChainContext {
+        inputs: *inputs,
+        is_copy: is_copy,
+        revocation_filter: revocation_filter,
+        candidate_issuers_remaining: Cell::new(policy.max_candidate_issuers),
+        signatures_remaining: Cell::new(policy.max_signature_verifications),
+        verified_signatures: RefCell::new([None; MAX_VERIFIED_SIGNATURES]),
+        verified_signature_count: Cell::new(0),
+        trace: trace,
+    };
+    let result = build_chain_inner(&ctx, cert, 0);
+    // Errors that are specific to the certificates are more useful, so this
+    // only replaces the error that says that no path was found.
+    let result = match result {
+        Err(Error::UnknownIssuer) if inputs.trust_anchors.is_empty() =>
+            Err(Error::NoTrustAnchors),
+        result => result,
+    };
+    #[cfg(feature = "log")]
+    logging::log_path_result(&result);
+    result
+}
+
+// Checks `cert` and each of `intermediate_certs` on its own, as far as is
+// possible without a trust anchor; see `EndEntityCert::preflight_chain`. The
+// intermediate certificates aren't assumed to be in any particular order, so
+// they are all checked as though they directly issued `cert`.
+pub fn preflight_chain(policy: &Policy,
                        supported_sig_algs: &[&SignatureAlgorithm],
-                       trust_anchors: &'a [TrustAnchor],
-                       intermediate_certs: &[untrusted::Input<'a>],
-                       cert: &Cert<'a>, time: time::Time, sub_ca_count: usize)
-                       -> Result<(), Error> {
+                       intermediate_certs: &[&[u8]], cert: &Cert,
+                       time: time::Time) -> Result<(), Error> {
+    try!(check_intermediate_certs_limits(policy, intermediate_certs));
+    try!(preflight_cert(policy, supported_sig_algs, cert, time, UsedAsCA::No));
+    for cert_der in intermediate_certs {
+        let intermediate =
+            try!(cert::parse_cert(untrusted::Input::from(cert_der),
+                                  EndEntityOrCA::CA(cert), policy));
+        try!(preflight_cert(policy, supported_sig_algs, &intermediate, time,
+                            UsedAsCA::Yes));
+    }
+    Ok(())
+}
+
+fn preflight_cert(policy: &Policy, supported_sig_algs: &[&SignatureAlgorithm],
+                  cert: &Cert, time: time::Time, used_as_ca: UsedAsCA)
+                  -> Result<(), Error> {
+    try!(check_usage_independent_properties(policy, cert, Some(time),
+                                            used_as_ca, 0));
+    signed_data::check_signature_algorithm_is_supported(
+        policy, supported_sig_algs, cert.signed_data.algorithm)
+}
+
+// Rejects oversized input before any of it is parsed; see
+// `Policy::max_cert_len`, `Policy::max_intermediate_certs`, and
+// `Policy::max_intermediate_certs_len`.
+fn check_intermediate_certs_limits(policy: &Policy,
+                                   intermediate_certs: &[&[u8]])
+                                   -> Result<(), Error> {
+    if intermediate_certs.len() > policy.max_intermediate_certs ||
+       intermediate_certs.len() > MAX_INTERMEDIATE_CERTS {
+        return Err(Error::InputTooLong);
+    }
+    let mut total_len: usize = 0;
+    for cert_der in intermediate_certs {
+        if cert_der.len() > policy.max_cert_len {
+            return Err(Error::InputTooLong);
+        }
+        total_len = total_len.saturating_add(cert_der.len());
+    }
+    if total_len > policy.max_intermediate_certs_len {
+        return Err(Error::InputTooLong);
+    }
+    Ok(())
+}
+
+// The maximum number of intermediate certificates in a path.
+const MAX_SUB_CA_COUNT: usize = 6;
+
+// The maximum number of intermediate certificates given to a verification
+// function, whatever `Policy::max_intermediate_certs` is.
+const MAX_INTERMEDIATE_CERTS: usize = 64;
+
+// The maximum number of verified signatures that a search remembers; see
+// `ChainContext::verified_signatures`. It is twice the default
+// `Policy::max_signature_verifications`.
+const MAX_VERIFIED_SIGNATURES: usize = 16;
+
+// Everything a single path search needs that doesn't change as it recurses
+// from a certificate to its potential issuers, including the counters that
+// limit the total work done by the search.
+struct ChainContext<'c, 'a: 'c> {
+    inputs: ChainInputs<'c, 'a>,
+
+    // Whether each of `inputs.intermediate_certs` is a copy of an earlier
+    // one.
+    is_copy: [bool; MAX_INTERMEDIATE_CERTS],
+
+    revocation_filter: Option<&'c RevocationFilter>,
+    candidate_issuers_remaining: Cell<usize>,
+    signatures_remaining: Cell<usize>,
+
+    // The signatures that have been verified in this search, and whose
+    // certificates aren't revoked; see `SignatureId`. Candidate paths often
+    // share their upper part, e.g. when two versions of an intermediate
+    // certificate have the same issuer, and those signatures aren't verified
+    // again for each path. Once there are `MAX_VERIFIED_SIGNATURES` of them,
+    // new ones aren't remembered.
+    verified_signatures: RefCell<[Option<SignatureId>;
+                                  MAX_VERIFIED_SIGNATURES]>,
+    verified_signature_count: Cell<usize>,
+
+    trace: &'c Trace,
+}
+
+// The signature of a certificate, as verified with the key of an issuer,
+// identified by the addresses of the certificate and of the key. Everything
+// that a search looks at stays where it is until the search is over, and the
+// copies of intermediate certificates are skipped, so the same address means
+// the same certificate or key.
+type SignatureId = (*const u8, *const u8);
+
+fn build_chain_inner<'c, 'a>(ctx: &ChainContext<'c, 'a>, cert: &Cert<'a>,
+                             sub_ca_count: usize)
+                             -> Result<VerifiedPath, Error> {
+    let inputs = &ctx.inputs;
+    let trace = ctx.trace;
     let used_as_ca = used_as_ca(&cert.ee_or_ca);
+    let depth = depth(cert);
 
-    try!(check_issuer_independent_properties(cert, time, used_as_ca,
-                                             sub_ca_count,
-                                             required_eku_if_present));
+    let result = check_issuer_independent_properties(
+        inputs.policy, cert, inputs.times.validity_time, used_as_ca,
+        sub_ca_count, inputs.required_eku_if_present);
+    trace.cert_checked(depth, cert, result);
+    try!(result);
 
     // TODO: HPKP checks.
 
     match used_as_ca {
         UsedAsCA::Yes => {
-            const MAX_SUB_CA_COUNT: usize = 6;
-
             if sub_ca_count >= MAX_SUB_CA_COUNT {
                 return Err(Error::UnknownIssuer);
             }
@@ -46,90 +203,451 @@ pub fn build_chain<'a>(required_eku_if_present: KeyPurposeId,
 
     // TODO: revocation.
 
-    match loop_while_non_fatal_error(trust_anchors,
-                                     |trust_anchor: &TrustAnchor<'a>| {
+    // Whether any trust anchor or intermediate certificate has a subject
+    // that matches the issuer of `cert`.
+    let issuer_subject_found = Cell::new(false);
+
+    let result = loop_while_non_fatal_error(
+            inputs.trust_anchors.iter().enumerate(),
+            |(trust_anchor_index, trust_anchor): (usize, &TrustAnchor<'a>)| {
         let trust_anchor_subject = untrusted::Input::from(trust_anchor.subject);
         if cert.issuer != trust_anchor_subject {
             return Err(Error::UnknownIssuer);
         }
+        issuer_subject_found.set(true);
 
         let name_constraints =
             trust_anchor.name_constraints.map(untrusted::Input::from);
 
-        try!(untrusted::read_all_optional(
+        let result = untrusted::read_all_optional(
                 name_constraints, Error::BadDER,
-                |value| name::check_name_constraints(value, &cert)));
+                |value| name::check_name_constraints(value, cert))
+            .and_then(|()| {
+                let trust_anchor_spki =
+                    untrusted::Input::from(trust_anchor.spki);
 
-        let trust_anchor_spki = untrusted::Input::from(trust_anchor.spki);
+                // TODO: try!(check_distrust(trust_anchor_subject,
+                //                           trust_anchor_spki));
 
-        // TODO: try!(check_distrust(trust_anchor_subject,
-        //                           trust_anchor_spki));
+                check_signatures(ctx, cert, trust_anchor_spki)
+            })
+            .and_then(|()| {
+                certificate_policies::check_certificate_policies(inputs.policy,
+                                                                 cert)
+            });
+        trace.trust_anchor_tried(depth, trust_anchor_index, result);
+        try!(result);
 
-        try!(check_signatures(supported_sig_algs, cert, trust_anchor_spki));
-
-        Ok(())
-    }) {
-        Ok(()) => {
-            return Ok(());
+        Ok(VerifiedPath {
+            trust_anchor_index: trust_anchor_index,
+            trust_anchor_digest: trust_anchor_digest(trust_anchor),
+            intermediate_indices: [0; MAX_SUB_CA_COUNT],
+            intermediate_count: 0,
+            valid_until: try!(path_not_after(cert)),
+            validity_time: inputs.times.validity_time,
+        })
+    });
+    let trust_anchor_error = match result {
+        Ok(path) => {
+            return Ok(path);
+        },
+        Err(Error::TooManySignatureVerifications) => {
+            // The search is over once the limit has been reached.
+            return Err(Error::TooManySignatureVerifications);
         },
-        Err(..) => {
+        Err(err) => {
             // If the error is not fatal, then keep going.
+            err
         }
-    }
+    };
 
-    loop_while_non_fatal_error(intermediate_certs, |cert_der| {
+    let result = loop_while_non_fatal_error(
+            inputs.intermediate_certs.iter().enumerate(),
+            |(intermediate_index, cert_der)| {
+        if ctx.is_copy[intermediate_index] {
+            return Err(Error::UnknownIssuer);
+        }
+
+        trace.intermediate_parsed(intermediate_index);
         let potential_issuer =
-            try!(cert::parse_cert(*cert_der, EndEntityOrCA::CA(&cert)));
+            try!(cert::parse_cert(untrusted::Input::from(cert_der),
+                                  EndEntityOrCA::CA(cert), inputs.policy));
 
         if potential_issuer.subject != cert.issuer {
             return Err(Error::UnknownIssuer)
         }
+        issuer_subject_found.set(true);
 
-        // Prevent loops; see RFC 4158 section 5.2.
-        let mut prev = cert;
-        loop {
-            if potential_issuer.spki == prev.spki &&
-               potential_issuer.subject == prev.subject {
-                return Err(Error::UnknownIssuer);
-            }
-            match &prev.ee_or_ca {
-                &EndEntityOrCA::EndEntity => { break; },
-                &EndEntityOrCA::CA(child_cert) => { prev = child_cert; }
+        // Many servers send the root certificate along with the rest of the
+        // chain. A self-issued copy of a trust anchor was already tried as a
+        // trust anchor above, so trying it again as an intermediate could
+        // only repeat the same checks, plus a check of its self-signature. A
+        // copy that is cross-signed by another CA is tried, because the path
+        // through it ends at a different trust anchor.
+        if let Some(trust_anchor_index) =
+                inputs.trust_anchors.iter().position(|trust_anchor| {
+                    potential_issuer.issuer == potential_issuer.subject &&
+                    potential_issuer.subject ==
+                        untrusted::Input::from(trust_anchor.subject) &&
+                    potential_issuer.spki ==
+                        untrusted::Input::from(trust_anchor.spki)
+                }) {
+            trace.trust_anchor_in_intermediates(depth, intermediate_index,
+                                                trust_anchor_index);
+            return Err(Error::UnknownIssuer);
+        }
+
+        if !authority_key_id_matches(cert, &potential_issuer) {
+            return Err(Error::UnknownIssuer)
+        }
+
+        let remaining = ctx.candidate_issuers_remaining.get();
+        if remaining == 0 {
+            return Err(Error::TooManyCandidateIssuers);
+        }
+        ctx.candidate_issuers_remaining.set(remaining - 1);
+
+        let result = build_chain_through(ctx, cert, &potential_issuer,
+                                         used_as_ca, sub_ca_count);
+        trace.intermediate_tried(depth, intermediate_index, result);
+        result.map(|path| path.issued_by_intermediate(intermediate_index))
+    });
+    if !issuer_subject_found.get() {
+        trace.issuer_subject_not_found(depth, cert);
+    }
+    result.map_err(|err| preferred_error(trust_anchor_error, err))
+}
+
+// https://tools.ietf.org/html/rfc5280#section-4.2.1.1
+//
+// Returns `false` if the authorityKeyIdentifier extension of `cert` rules out
+// `potential_issuer` as its issuer, so that path building doesn't need to
+// verify a signature to find out. Like OpenSSL, a candidate is ruled out if
+// its subjectKeyIdentifier, serial number, or issuer doesn't match the
+// corresponding field of the extension; fields that are absent from either
+// certificate don't rule anything out. (mozilla::pkix ignores the extension.)
+fn authority_key_id_matches(cert: &Cert, potential_issuer: &Cert) -> bool {
+    let authority_key_id = match cert.authority_key_id {
+        Some(authority_key_id) => authority_key_id,
+        None => { return true; }
+    };
+
+    if let (Some(key_id), Some(subject_key_id)) =
+            (authority_key_id.key_id, potential_issuer.subject_key_id) {
+        if key_id != subject_key_id {
+            return false;
+        }
+    }
+
+    if let Some(serial) = authority_key_id.cert_serial {
+        if serial != potential_issuer.serial {
+            return false;
+        }
+    }
+
+    // If any of the issuer's names are directoryNames, one of them must be
+    // the issuer of `potential_issuer`.
+    if let Some(cert_issuer) = authority_key_id.cert_issuer {
+        const DIRECTORY_NAME_TAG: u8 = 0x80 | der::CONSTRUCTED | 4;
+        let mut reader = untrusted::Reader::new(cert_issuer);
+        let mut has_directory_name = false;
+        while !reader.at_end() {
+            let name = match der::read_tag_and_get_value(&mut reader) {
+                Ok((DIRECTORY_NAME_TAG, name)) => name,
+                Ok(..) => { continue; },
+                Err(..) => { return true; }
+            };
+            has_directory_name = true;
+            let matches = name.read_all(Error::BadDER, |name| {
+                der::expect_tag_and_get_value(name, der::Tag::Sequence)
+            }).map(|name| name == potential_issuer.issuer);
+            if matches == Ok(true) {
+                return true;
             }
         }
+        if has_directory_name {
+            return false;
+        }
+    }
 
-        try!(untrusted::read_all_optional(
-                potential_issuer.name_constraints, Error::BadDER,
-                |value| name::check_name_constraints(value, &cert)));
+    true
+}
+
+// Continues `build_chain_inner` from `cert` with `potential_issuer`, which is
+// one of the intermediate certificates, as its issuer.
+fn build_chain_through<'c, 'a>(ctx: &ChainContext<'c, 'a>, cert: &Cert,
+                               potential_issuer: &Cert<'a>,
+                               used_as_ca: UsedAsCA, sub_ca_count: usize)
+                               -> Result<VerifiedPath, Error> {
+    // Prevent loops; see RFC 4158 section 5.2.
+    let mut prev = cert;
+    loop {
+        if potential_issuer.spki == prev.spki &&
+           potential_issuer.subject == prev.subject {
+            return Err(Error::UnknownIssuer);
+        }
+        match &prev.ee_or_ca {
+            &EndEntityOrCA::EndEntity |
+            &EndEntityOrCA::TargetCA => { break; },
+            &EndEntityOrCA::CA(child_cert) => { prev = child_cert; }
+        }
+    }
+
+    try!(untrusted::read_all_optional(
+            potential_issuer.name_constraints, Error::BadDER,
+            |value| name::check_name_constraints(value, cert)));
+
+    let next_sub_ca_count = match used_as_ca {
+        UsedAsCA::No => sub_ca_count,
+        UsedAsCA::Yes => sub_ca_count + 1
+    };
+
+    build_chain_inner(ctx, potential_issuer, next_sub_ca_count)
+}
+
+/// The times at which a certificate chain is verified.
+///
+/// Normally both times are the current time. Certificate Transparency
+/// monitors and auditors that need to know whether a certificate was valid
+/// when it was issued or logged can instead check the certificates' validity
+/// periods at that historical time, while still evaluating revocation
+/// information at the current time. Signed artifacts are verified the same
+/// way; see `VerificationTimes::signed_at`.
+#[derive(Clone, Copy, Debug)]
+pub struct VerificationTimes {
+    /// The time at which the validity period (`notBefore` and `notAfter`) of
+    /// every certificate in the chain is checked.
+    pub validity_time: time::Time,
+
+    /// The current time, at which revocation information is evaluated: the
+    /// `revocation::RevocationFilter` consulted during path building is
+    /// asked about this time, and `revocation::RevocationChecker` decides
+    /// whether revocation information is fresh at this time.
+    pub current_time: time::Time,
+}
+
+impl VerificationTimes {
+    /// Verification entirely at `time`.
+    pub fn at(time: time::Time) -> VerificationTimes {
+        VerificationTimes {
+            validity_time: time,
+            current_time: time,
+        }
+    }
+
+    /// Verification of a signature that was made at `signing_time`, e.g. of
+    /// a signed executable or package, long after it was signed.
+    ///
+    /// The validity periods of the certificates are checked at
+    /// `signing_time`, so an end-entity certificate (or intermediate
+    /// certificate) that has expired since is still accepted, as
+    /// Authenticode and JAR signing do for timestamped signatures. The caller
+    /// must only use this when `signing_time` comes from a trusted source,
+    /// usually a verified RFC 3161 countersignature timestamp (see
+    /// `Usage::TimeStamping`); a signing time asserted by the signer itself
+    /// would let a stolen key of an expired certificate sign anything. Use
+    /// `VerificationTimes::at` when there is no trusted timestamp.
+    pub fn signed_at(signing_time: time::Time, current_time: time::Time)
+                     -> VerificationTimes {
+        VerificationTimes {
+            validity_time: signing_time,
+            current_time: current_time,
+        }
+    }
+}
+
+/// The result of a successful path validation.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VerifiedPath {
+    trust_anchor_index: usize,
+    trust_anchor_digest: [u8; TRUST_ANCHOR_DIGEST_LEN],
+
+    // `intermediate_indices[..intermediate_count]` are the indices of the
+    // intermediate certificates in the path, starting with the issuer of the
+    // end-entity certificate.
+    intermediate_indices: [usize; MAX_SUB_CA_COUNT],
+    intermediate_count: usize,
+    valid_until: time::Time,
+    validity_time: time::Time,
+}
+
+impl VerifiedPath {
+    /// The index, within the `trust_anchors` slice that was passed to the
+    /// verification function, of the trust anchor that the path terminated
+    /// at. This allows an application to apply per-root policies, e.g. to
+    /// require Certificate Transparency only for publicly-trusted roots.
+    pub fn trust_anchor_index(&self) -> usize { self.trust_anchor_index }
+
+    /// The indices, within the `intermediate_certs` slice that was passed to
+    /// the verification function, of the intermediate certificates in the
+    /// path, starting with the issuer of the end-entity certificate and
+    /// ending with the certificate that the trust anchor issued.
+    ///
+    /// Together with `EndEntityCert::cert_der`, this gives the exact chain
+    /// that was verified, e.g. to store it, to fingerprint it, or to submit
+    /// it to a Certificate Transparency log:
+    /// `path.intermediate_indices().iter().map(|&i| intermediate_certs[i])`.
+    ///
+    /// When an `IssuerFetcher` is used, an index `intermediate_certs.len() +
+    /// n` is that of the `n`th distinct certificate, counting from zero, that
+    /// the fetcher returned which wasn't already in `intermediate_certs`.
+    pub fn intermediate_indices(&self) -> &[usize] {
+        &self.intermediate_indices[..self.intermediate_count]
+    }
 
-        let next_sub_ca_count = match used_as_ca {
-            UsedAsCA::No => sub_ca_count,
-            UsedAsCA::Yes => sub_ca_count + 1
-        };
+    /// The earliest `notAfter` of the certificates in the path, i.e. the last
+    /// time at which the path is valid. Trust anchors don't expire, so a
+    /// trust anchor's certificate, if any, doesn't count.
+    ///
+    /// Long-lived connections and connection pools can use this to verify
+    /// the peer again, or to replace the connection, before the path
+    /// expires.
+    pub fn valid_until(&self) -> time::Time { self.valid_until }
 
-        build_chain(required_eku_if_present, supported_sig_algs, trust_anchors,
-                    intermediate_certs, &potential_issuer, time,
-                    next_sub_ca_count)
+    /// How long the path remains valid after the time that its validity was
+    /// checked at, `VerificationTimes::validity_time`; i.e. `valid_until`
+    /// minus that time.
+    pub fn remaining_validity(&self) -> Duration {
+        self.valid_until.checked_duration_since(self.validity_time)
+                        .unwrap_or(Duration::from_secs(0))
+    }
+
+    /// Whether the trust anchor that the path terminated at is in
+    /// `trust_anchors`, with the same subject, public key, and name
+    /// constraints, e.g. after the application loaded an updated root store.
+    ///
+    /// This lets a long-running process that keeps verified paths react to a
+    /// root store update without verifying every path again: a path whose
+    /// trust anchor was removed, i.e. distrusted, or whose name constraints
+    /// changed, must be verified again, and the others remain valid. Only
+    /// the trust anchor is checked; e.g. expiry still needs to be checked
+    /// with `valid_until`.
+    pub fn still_anchored_in(&self, trust_anchors: &TrustAnchorSet) -> bool {
+        trust_anchors.trust_anchors().iter().any(|trust_anchor| {
+            trust_anchor_digest(trust_anchor) == self.trust_anchor_digest
+        })
+    }
+
+    // The path through the intermediate certificate with the index
+    // `intermediate_index`, which issued the first certificate of `self`.
+    fn issued_by_intermediate(mut self, intermediate_index: usize)
+                              -> VerifiedPath {
+        // `build_chain_inner` doesn't build longer paths.
+        assert!(self.intermediate_count < MAX_SUB_CA_COUNT);
+        for i in (0..self.intermediate_count).rev() {
+            self.intermediate_indices[i + 1] = self.intermediate_indices[i];
+        }
+        self.intermediate_indices[0] = intermediate_index;
+        self.intermediate_count += 1;
+        self
+    }
+}
+
+const TRUST_ANCHOR_DIGEST_LEN: usize = 32;
+
+// A SHA-256 digest that identifies `trust_anchor` in a `VerifiedPath`, which
+// doesn't borrow the trust anchors. Each field is prefixed with its length,
+// and the name constraints with whether there are any, so that different
+// trust anchors have different encodings.
+fn trust_anchor_digest(trust_anchor: &TrustAnchor)
+                       -> [u8; TRUST_ANCHOR_DIGEST_LEN] {
+    fn update_with_field(ctx: &mut digest::Context, field: &[u8]) {
+        let len = field.len();
+        ctx.update(&[(len >> 24) as u8, (len >> 16) as u8, (len >> 8) as u8,
+                     len as u8]);
+        ctx.update(field);
+    }
+
+    let mut ctx = digest::Context::new(&digest::SHA256);
+    update_with_field(&mut ctx, trust_anchor.subject);
+    update_with_field(&mut ctx, trust_anchor.spki);
+    match trust_anchor.name_constraints {
+        Some(name_constraints) => {
+            ctx.update(&[1]);
+            update_with_field(&mut ctx, name_constraints);
+        },
+        None => ctx.update(&[0]),
+    }
+
+    let mut result = [0u8; TRUST_ANCHOR_DIGEST_LEN];
+    for (r, d) in result.iter_mut().zip(ctx.finish().as_ref()) {
+        *r = *d;
+    }
+    result
+}
+
+// The earliest `notAfter` of `cert` and the certificates that it issued.
+fn path_not_after(cert: &Cert) -> Result<time::Time, Error> {
+    let mut not_after = try!(cert_not_after(cert));
+    let mut cert = cert;
+    while let EndEntityOrCA::CA(child_cert) = cert.ee_or_ca {
+        let child_not_after = try!(cert_not_after(child_cert));
+        if child_not_after < not_after {
+            not_after = child_not_after;
+        }
+        cert = child_cert;
+    }
+    Ok(not_after)
+}
+
+fn cert_not_after(cert: &Cert) -> Result<time::Time, Error> {
+    cert.validity.read_all(Error::BadDER, |value| {
+        let _not_before = try!(der::time_choice(value));
+        der::time_choice(value)
     })
 }
 
-fn check_signatures(supported_sig_algs: &[&SignatureAlgorithm],
-                    cert_chain: &Cert, trust_anchor_key: untrusted::Input)
-                    -> Result<(), Error> {
+fn check_signatures(ctx: &ChainContext, cert_chain: &Cert,
+                    trust_anchor_key: untrusted::Input) -> Result<(), Error> {
     let mut spki_value = trust_anchor_key;
     let mut cert = cert_chain;
     loop {
-        try!(signed_data::verify_signed_data(supported_sig_algs, spki_value,
-                                             &cert.signed_data));
-
-        // TODO: check revocation
+        let signature = (cert.der.as_slice_less_safe().as_ptr(),
+                         spki_value.as_slice_less_safe().as_ptr());
+        let count = ctx.verified_signature_count.get();
+        if !ctx.verified_signatures.borrow()[..count]
+                .contains(&Some(signature)) {
+            try!(check_signature(ctx, cert, spki_value));
+            if count < MAX_VERIFIED_SIGNATURES {
+                ctx.verified_signatures.borrow_mut()[count] = Some(signature);
+                ctx.verified_signature_count.set(count + 1);
+            }
+        }
 
         match &cert.ee_or_ca {
             &EndEntityOrCA::CA(child_cert) => {
                 spki_value = cert.spki;
                 cert = child_cert;
             },
-            &EndEntityOrCA::EndEntity => { break; }
+            &EndEntityOrCA::EndEntity |
+            &EndEntityOrCA::TargetCA => { break; }
+        }
+    }
+
+    Ok(())
+}
+
+// Verifies the signature of `cert` with `spki_value`, the issuer's key, and
+// checks whether `cert` is revoked.
+fn check_signature(ctx: &ChainContext, cert: &Cert,
+                   spki_value: untrusted::Input) -> Result<(), Error> {
+    let remaining = ctx.signatures_remaining.get();
+    if remaining == 0 {
+        return Err(Error::TooManySignatureVerifications);
+    }
+    ctx.signatures_remaining.set(remaining - 1);
+    ctx.trace.signature_verified();
+    try!(signed_data::verify_signed_data(ctx.inputs.policy,
+                                         ctx.inputs.supported_sig_algs,
+                                         spki_value, &cert.signed_data));
+
+    if let Some(revocation_filter) = ctx.revocation_filter {
+        let issuer_spki_hash =
+            der::digest_of_sequence(&digest::SHA256, spki_value);
+        if revocation_filter.is_revoked(issuer_spki_hash.as_ref(),
+                                        cert.serial.as_slice_less_safe(),
+                                        ctx.inputs.times.current_time) {
+            return Err(Error::CertRevoked);
         }
     }
 
@@ -137,60 +655,186 @@ fn check_signatures(supported_sig_algs: &[&SignatureAlgorithm],
 }
 
 fn check_issuer_independent_properties<'a>(
-        cert: &Cert<'a>, time: time::Time, used_as_ca: UsedAsCA,
-        sub_ca_count: usize, required_eku_if_present: KeyPurposeId)
-        -> Result<(), Error> {
+        policy: &Policy, cert: &Cert<'a>, time: time::Time,
+        used_as_ca: UsedAsCA, sub_ca_count: usize,
+        required_eku_if_present: KeyPurposeId) -> Result<(), Error> {
+    try!(check_usage_independent_properties(policy, cert, Some(time),
+                                            used_as_ca, sub_ca_count));
+    let ignore_eku = match used_as_ca {
+        UsedAsCA::Yes => policy.ignore_ca_eku,
+        UsedAsCA::No => false,
+    };
+    if !ignore_eku {
+        try!(untrusted::read_all_optional(
+                cert.eku, Error::BadDER,
+                |value| check_eku(value, used_as_ca,
+                                  required_eku_if_present)));
+    }
+    if let UsedAsCA::No = used_as_ca {
+        if required_eku_if_present.oid_value == EKU_TIME_STAMPING.oid_value {
+            try!(check_time_stamping_eku(cert));
+        }
+    }
+
+    Ok(())
+}
+
+// The checks of `check_usage_independent_properties` for an end-entity
+// certificate, except that its validity period isn't compared with any time;
+// see `EndEntityCert::validate_structure_with_policy`.
+pub fn check_structure(policy: &Policy, cert: &Cert) -> Result<(), Error> {
+    check_usage_independent_properties(policy, cert, None, UsedAsCA::No, 0)
+}
+
+// The issuer-independent checks other than the extended key usage checks,
+// which are all that `preflight_chain` does. Without a `time`, the validity
+// period is only checked to be well-formed.
+fn check_usage_independent_properties<'a>(
+        policy: &Policy, cert: &Cert<'a>, time: Option<time::Time>,
+        used_as_ca: UsedAsCA, sub_ca_count: usize) -> Result<(), Error> {
     // TODO: try!(check_distrust(trust_anchor_subject,
     //                           trust_anchor_spki));
-    // TODO: Check signature algorithm like mozilla::pkix.
+
+    if !signed_data::algorithm_identifiers_are_equal(
+            cert.signed_data.algorithm, cert.tbs_signature,
+            policy.allow_signature_algorithm_null_parameter_mismatch) {
+        return Err(Error::SignatureAlgorithmMismatch);
+    }
+
     // TODO: Check SPKI like mozilla::pkix.
     // TODO: check for active distrust like mozilla::pkix.
 
     // See the comment in `remember_extensions` for why we don't check the
     // KeyUsage extension.
 
-    try!(cert.validity.read_all(Error::BadDER,
-                                |value| check_validity(value, time)));
-    try!(untrusted::read_all_optional(
-            cert.basic_constraints, Error::BadDER,
-            |value| check_basic_constraints(value, used_as_ca, sub_ca_count)));
-    try!(untrusted::read_all_optional(
-            cert.eku, Error::BadDER,
-            |value| check_eku(value, used_as_ca, required_eku_if_present)));
+    if !policy.allow_nonconformant_subject_alt_name {
+        try!(check_subject_alt_name_conformance(cert));
+    }
 
-    Ok(())
+    if cert.has_duplicate_extension && !policy.allow_duplicate_extensions {
+        return Err(Error::DuplicateExtension);
+    }
+
+    if let UsedAsCA::No = used_as_ca {
+        try!(signed_data::check_end_entity_key_type(policy, cert.spki));
+    }
+
+    if cert.subject_directory_attributes_is_critical &&
+       !policy.allow_critical_subject_directory_attributes {
+        return Err(Error::UnsupportedCriticalExtension);
+    }
+
+    try!(cert.validity.read_all(Error::BadDER, |value| {
+        check_validity(value, time, policy.not_before_grace_period)
+    }));
+    if let (UsedAsCA::No, Some(max_validity)) =
+            (used_as_ca, policy.max_end_entity_validity) {
+        try!(check_validity_period_len(cert, max_validity));
+    }
+    untrusted::read_all_optional(
+        cert.basic_constraints, Error::BadDER,
+        |value| check_basic_constraints(value, used_as_ca, sub_ca_count))
+}
+
+// https://tools.ietf.org/html/rfc3161#section-2.3: "The corresponding
+// certificate MUST contain only one instance of the extended key usage field
+// extension as defined in [RFC2459] Section 4.2.1.13 with KeyPurposeID having
+// value: id-kp-timeStamping. This extension MUST be critical."
+//
+// `check_eku` has already checked that id-kp-timeStamping is present if the
+// extension is; a duplicate extension is rejected when `cert` is parsed.
+fn check_time_stamping_eku(cert: &Cert) -> Result<(), Error> {
+    let eku = match cert.eku {
+        Some(eku) if cert.eku_is_critical => eku,
+        _ => { return Err(Error::RequiredEKUNotFound); }
+    };
+    eku.read_all(Error::RequiredEKUNotFound, |input| {
+        let value = try!(der::expect_tag_and_get_value(input, der::Tag::OID));
+        if value != EKU_TIME_STAMPING.oid_value {
+            return Err(Error::RequiredEKUNotFound);
+        }
+        Ok(())
+    })
+}
+
+// https://tools.ietf.org/html/rfc5280#section-4.2.1.6: "If the subject field
+// contains an empty sequence, then the issuing CA MUST include a
+// subjectAltName extension that is marked as critical" and "If the
+// subjectAltName extension is present, the sequence MUST contain at least one
+// entry." Otherwise, a certificate with an empty subject and a non-critical
+// subjectAltName could be mistaken for one that identifies nobody by software
+// that ignores the extension.
+fn check_subject_alt_name_conformance(cert: &Cert) -> Result<(), Error> {
+    match cert.subject_alt_name {
+        Some(subject_alt_name) if subject_alt_name.len() == 0 =>
+            Err(Error::EmptySubjectAltName),
+        Some(_) if cert.subject_alt_name_is_critical => Ok(()),
+        _ if cert.subject.len() == 0 =>
+            Err(Error::EmptySubjectWithoutCriticalSubjectAltName),
+        _ => Ok(())
+    }
+}
+
+// Checks the validity period of `cert` alone, as is also done for each
+// certificate during path building.
+pub fn check_cert_validity(cert: &Cert, time: time::Time, policy: &Policy)
+                           -> Result<(), Error> {
+    cert.validity.read_all(Error::BadDER, |value| {
+        check_validity(value, Some(time), policy.not_before_grace_period)
+    })
 }
 
 // https://tools.ietf.org/html/rfc5280#section-4.1.2.5
-fn check_validity(input: &mut untrusted::Reader, time: time::Time)
-                  -> Result<(), Error> {
+fn check_validity(input: &mut untrusted::Reader, time: Option<time::Time>,
+                  not_before_grace_period: Duration) -> Result<(), Error> {
     let not_before = try!(der::time_choice(input));
     let not_after = try!(der::time_choice(input));
 
     if not_before > not_after {
         return Err(Error::InvalidCertValidity);
     }
-    if time < not_before {
-        return Err(Error::CertNotValidYet);
+    let time = match time {
+        Some(time) => time,
+        None => { return Ok(()); }
+    };
+    // If the grace period reaches back before the Unix epoch then every time
+    // is late enough.
+    match not_before.checked_sub(not_before_grace_period) {
+        Some(earliest_time) if time < earliest_time => {
+            return Err(Error::CertNotValidYet);
+        },
+        _ => ()
     }
     if time > not_after {
         return Err(Error::CertExpired);
     }
 
-    // TODO: mozilla::pkix allows the TrustDomain to check not_before and
-    // not_after, to enforce things like a maximum validity period. We should
-    // do something similar.
-
     Ok(())
 }
 
+// See `Policy::max_end_entity_validity`. `check_validity` has already checked
+// that `notBefore` isn't after `notAfter`.
+fn check_validity_period_len(cert: &Cert, max_validity: Duration)
+                             -> Result<(), Error> {
+    cert.validity.read_all(Error::BadDER, |value| {
+        let not_before = try!(der::time_choice(value));
+        let not_after = try!(der::time_choice(value));
+        match not_after.checked_duration_since(not_before) {
+            Some(validity) if validity <= max_validity => Ok(()),
+            Some(_) => Err(Error::ValidityPeriodTooLong),
+            None => Err(Error::InvalidCertValidity),
+        }
+    })
+}
+
 #[derive(Clone, Copy)]
 enum UsedAsCA { Yes, No }
 
 fn used_as_ca(ee_or_ca: &EndEntityOrCA) -> UsedAsCA {
     match ee_or_ca {
         &EndEntityOrCA::EndEntity => UsedAsCA::No,
-        &EndEntityOrCA::CA(..) => UsedAsCA::Yes
+        &EndEntityOrCA::CA(..) |
+        &EndEntityOrCA::TargetCA => UsedAsCA::Yes
     }
 }
 
@@ -228,9 +872,29 @@ fn check_basic_constraints(input: Option<&mut untrusted::Reader>,
     }
 }
 
-#[derive(Clone, Copy)]
+/// An extended key usage (EKU) purpose, e.g. id-kp-serverAuth. See
+/// `Usage::key_purpose_id`.
+///
+/// It is formatted as its dotted-decimal OID, e.g. `1.3.6.1.5.5.7.3.1`.
+#[derive(Clone, Copy, Eq, Hash, PartialEq)]
 pub struct KeyPurposeId {
-    oid_value: &'static [u8]
+    /// The encoded value of the OID, without the tag and length. It must be
+    /// a valid OID for the formatting to succeed.
+    pub oid_value: &'static [u8]
+}
+
+impl fmt::Debug for KeyPurposeId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(f.write_str("KeyPurposeId("));
+        try!(der::write_oid(f, self.oid_value));
+        f.write_str(")")
+    }
+}
+
+impl fmt::Display for KeyPurposeId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        der::write_oid(f, self.oid_value)
+    }
 }
 
 // id-pkix            OBJECT IDENTIFIER ::= { 1 3 6 1 5 5 7 }
@@ -238,14 +902,115 @@ pub struct KeyPurposeId {
 
 // id-kp-serverAuth   OBJECT IDENTIFIER ::= { id-kp 1 }
 pub static EKU_SERVER_AUTH: KeyPurposeId = KeyPurposeId {
-    oid_value: &[(40 * 1) + 3, 6, 1, 5, 5, 7, 3, 1]
+    oid_value: &oid![1, 3, 6, 1, 5, 5, 7, 3, 1]
+};
+
+// id-kp-clientAuth   OBJECT IDENTIFIER ::= { id-kp 2 }
+pub static EKU_CLIENT_AUTH: KeyPurposeId = KeyPurposeId {
+    oid_value: &oid![1, 3, 6, 1, 5, 5, 7, 3, 2]
+};
+
+// id-kp-codeSigning  OBJECT IDENTIFIER ::= { id-kp 3 }
+/// The id-kp-codeSigning extended key usage of code signing certificates, as
+/// required by `Usage::CodeSigning`. See `VerificationTimes::signed_at` for
+/// verifying signatures after the certificate has expired.
+pub static EKU_CODE_SIGNING: KeyPurposeId = KeyPurposeId {
+    oid_value: &oid![1, 3, 6, 1, 5, 5, 7, 3, 3]
+};
+
+// id-kp-emailProtection OBJECT IDENTIFIER ::= { id-kp 4 }
+/// The id-kp-emailProtection extended key usage of S/MIME certificates, as
+/// required by `Usage::EmailProtection` and
+/// `EndEntityCert::verify_is_valid_smime_cert`.
+pub static EKU_EMAIL_PROTECTION: KeyPurposeId = KeyPurposeId {
+    oid_value: &oid![1, 3, 6, 1, 5, 5, 7, 3, 4]
+};
+
+// tcg-kp-EKCertificate  OBJECT IDENTIFIER ::= { 2 23 133 8 1 }
+pub static EKU_TCG_EK_CERTIFICATE: KeyPurposeId = KeyPurposeId {
+    oid_value: &oid![2, 23, 128 + 1, 5, 8, 1]
+};
+
+// tcg-kp-AIKCertificate OBJECT IDENTIFIER ::= { 2 23 133 8 3 }
+pub static EKU_TCG_AIK_CERTIFICATE: KeyPurposeId = KeyPurposeId {
+    oid_value: &oid![2, 23, 128 + 1, 5, 8, 3]
+};
+
+// id-kp-timeStamping OBJECT IDENTIFIER ::= { id-kp 8 }
+pub static EKU_TIME_STAMPING: KeyPurposeId = KeyPurposeId {
+    oid_value: &oid![1, 3, 6, 1, 5, 5, 7, 3, 8]
 };
 
 // id-kp-OCSPSigning  OBJECT IDENTIFIER ::= { id-kp 9 }
 pub static EKU_OCSP_SIGNING: KeyPurposeId = KeyPurposeId {
-    oid_value: &[(40 * 1) + 3, 6, 1, 5, 5, 7, 3, 9]
+    oid_value: &oid![1, 3, 6, 1, 5, 5, 7, 3, 9]
+};
+
+// id-kp-ipsecIKE     OBJECT IDENTIFIER ::= { id-kp 17 }
+pub static EKU_IPSEC_IKE: KeyPurposeId = KeyPurposeId {
+    oid_value: &oid![1, 3, 6, 1, 5, 5, 7, 3, 17]
 };
 
+/// A purpose for which an end-entity certificate is verified. Each usage
+/// requires the corresponding extended key usage in every certificate of the
+/// path that has an extended key usage extension. See `TrustDomains`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Usage {
+    /// A TLS server certificate (id-kp-serverAuth).
+    TlsServer,
+
+    /// A TLS client certificate (id-kp-clientAuth).
+    TlsClient,
+
+    /// A code signing certificate (id-kp-codeSigning).
+    CodeSigning,
+
+    /// An S/MIME email certificate (id-kp-emailProtection).
+    EmailProtection,
+
+    /// A TPM endorsement key certificate (tcg-kp-EKCertificate). See
+    /// `Policy::tcg_credential_profile`.
+    TcgEndorsementKey,
+
+    /// A TPM attestation identity key certificate (tcg-kp-AIKCertificate).
+    /// See `Policy::tcg_credential_profile`.
+    TcgAttestationIdentityKey,
+
+    /// An RFC 3161 time-stamping authority (TSA) certificate
+    /// (id-kp-timeStamping), e.g. for verifying the signer of a time-stamp
+    /// token. As RFC 3161 Section 2.3 requires, the end-entity certificate
+    /// must have a critical extended key usage extension that contains only
+    /// id-kp-timeStamping; otherwise verification fails with
+    /// `Error::RequiredEKUNotFound`.
+    TimeStamping,
+
+    /// An IKE peer certificate (id-kp-ipsecIKE), e.g. for authenticating the
+    /// peer of an IPsec VPN. The obsolete id-kp-ipsecEndSystem,
+    /// id-kp-ipsecTunnel, and id-kp-ipsecUser key purposes aren't accepted
+    /// instead, as RFC 4945 Section 5.1.3.12 recommends. See
+    /// `Policy::ike_profile`.
+    IpsecIke,
+}
+
+impl Usage {
+    /// The extended key usage that the usage requires.
+    pub fn key_purpose_id(self) -> KeyPurposeId { usage_eku(self) }
+}
+
+// The extended key usage that `usage` requires.
+pub fn usage_eku(usage: Usage) -> KeyPurposeId {
+    match usage {
+        Usage::TlsServer => EKU_SERVER_AUTH,
+        Usage::TlsClient => EKU_CLIENT_AUTH,
+        Usage::CodeSigning => EKU_CODE_SIGNING,
+        Usage::EmailProtection => EKU_EMAIL_PROTECTION,
+        Usage::TcgEndorsementKey => EKU_TCG_EK_CERTIFICATE,
+        Usage::TcgAttestationIdentityKey => EKU_TCG_AIK_CERTIFICATE,
+        Usage::TimeStamping => EKU_TIME_STAMPING,
+        Usage::IpsecIke => EKU_IPSEC_IKE,
+    }
+}
+
 // id-Netscape        OBJECT IDENTIFIER ::= { 2 16 840 1 113730 }
 // id-Netscape-policy OBJECT IDENTIFIER ::= { id-Netscape 4 }
 // id-Netscape-stepUp OBJECT IDENTIFIER ::= { id-Netscape-policy 1 }
@@ -261,7 +1026,8 @@ static EKU_NETSCAPE_SERVER_STEP_UP: KeyPurposeId = KeyPurposeId {
 //   mozilla::pkix of treating the EKU extension in a CA certificate as a
 //   restriction on the allowable EKUs for certificates issued by that CA. RFC
 //   5280 doesn't prescribe any meaning to the EKU extension when a certificate
-//   is being used as a CA certificate.
+//   is being used as a CA certificate. `Policy::ignore_ca_eku` turns this
+//   off.
 //
 // * We do not recognize anyExtendedKeyUsage. NSS and mozilla::pkix do not
 //   recognize it either.
@@ -270,8 +1036,21 @@ static EKU_NETSCAPE_SERVER_STEP_UP: KeyPurposeId = KeyPurposeId {
 //   certificates (only). Comodo has issued certificates that require this
 //   behavior that don't expire until June 2020. See
 //   https://bugzilla.mozilla.org/show_bug.cgi?id=982292.
+//
+// * Like mozilla::pkix, we don't require CA certificates to allow
+//   id-kp-OCSPSigning. A delegated OCSP responder certificate is an
+//   end-entity certificate issued by the CA whose certificates' status it
+//   reports, and that CA's EKU extension (e.g. id-kp-serverAuth and
+//   id-kp-clientAuth, as in Let's Encrypt's intermediates) is about the
+//   certificates it issues for subscribers, not about its responders.
 fn check_eku(input: Option<&mut untrusted::Reader>, used_as_ca: UsedAsCA,
              required_eku_if_present: KeyPurposeId) -> Result<(), Error> {
+    if let UsedAsCA::Yes = used_as_ca {
+        if required_eku_if_present.oid_value == EKU_OCSP_SIGNING.oid_value {
+            return Ok(());
+        }
+    }
+
     match input {
         Some(input) => {
             let match_step_up = match used_as_ca {
@@ -314,18 +1093,235 @@ fn check_eku(input: Option<&mut untrusted::Reader>, used_as_ca: UsedAsCA,
     }
 }
 
-fn loop_while_non_fatal_error<V, F>(values: V, f: F) -> Result<(), Error>
-                                    where V: IntoIterator,
-                                          F: Fn(V::Item) -> Result<(), Error> {
+// Whether the CA certificate `cert` is technically constrained from issuing
+// TLS server certificates, as defined in section 7.1.5 of the CA/Browser
+// Forum Baseline Requirements. Certificate Transparency policies exempt the
+// certificates issued by such CAs.
+//
+// A CA is technically constrained if it has an EKU extension that contains
+// neither anyExtendedKeyUsage nor id-kp-serverAuth (or id-Netscape-stepUp,
+// which we treat as equivalent to id-kp-serverAuth in CA certificates; see
+// `check_eku`). If it does allow id-kp-serverAuth, then it must have name
+// constraints that permit at least one dNSName subtree, and that either
+// permit at least one iPAddress subtree or exclude all IPv4 and IPv6
+// addresses. The directoryName constraints that the Baseline Requirements
+// also require are not checked.
+pub fn is_technically_constrained(cert: &Cert) -> Result<bool, Error> {
+    let eku = match cert.eku {
+        Some(eku) => eku,
+        None => { return Ok(false); },
+    };
+
+    let mut allows_server_auth = false;
+    try!(eku.read_all(Error::BadDER, |input| {
+        loop {
+            let value =
+                try!(der::expect_tag_and_get_value(input, der::Tag::OID));
+            if value == ANY_EKU ||
+               value == EKU_SERVER_AUTH.oid_value ||
+               value == EKU_NETSCAPE_SERVER_STEP_UP.oid_value {
+                allows_server_auth = true;
+            }
+            if input.at_end() {
+                return Ok(());
+            }
+        }
+    }));
+    if !allows_server_auth {
+        return Ok(true);
+    }
+
+    let name_constraints = match cert.name_constraints {
+        Some(name_constraints) => try!(
+            name_constraints::NameConstraints::from(
+                name_constraints.as_slice_less_safe())),
+        None => { return Ok(false); },
+    };
+
+    let permits_dns_names = name_constraints.permitted_subtrees().any(|s| {
+        match s {
+            name_constraints::GeneralSubtree::DnsName(_) => true,
+            _ => false,
+        }
+    });
+    let permits_ip_addresses =
+        name_constraints.permitted_subtrees().any(|s| {
+            match s {
+                name_constraints::GeneralSubtree::IpAddress { .. } => true,
+                _ => false,
+            }
+        });
+    let excludes_all = |len: usize| {
+        name_constraints.excluded_subtrees().any(|s| {
+            match s {
+                name_constraints::GeneralSubtree::IpAddress { mask, .. } =>
+                    mask.len() == len && mask.iter().all(|b| *b == 0),
+                _ => false,
+            }
+        })
+    };
+
+    Ok(permits_dns_names &&
+       (permits_ip_addresses || (excludes_all(4) && excludes_all(16))))
+}
+
+// 2.5.29.37.0
+static ANY_EKU: &'static [u8] = &[0x55, 0x1d, 0x25, 0x00];
+
+// Observes the steps of `build_chain` as each of them completes, for
+// `ValidationReport` and `VerificationStats`. `depth` is the number of
+// certificates between the certificate being processed and the certificate
+// being verified.
+pub trait Trace {
+    // The issuer-independent checks of `cert` were done.
+    fn cert_checked(&self, depth: usize, cert: &Cert,
+                    result: Result<(), Error>);
+
+    // The trust anchor at `trust_anchor_index`, whose subject matches the
+    // issuer of the certificate at `depth`, was tried as its issuer.
+    fn trust_anchor_tried(&self, depth: usize, trust_anchor_index: usize,
+                          result: Result<(), Error>);
+
+    // The intermediate certificate at `intermediate_index`, whose subject
+    // matches the issuer of the certificate at `depth`, was tried as its
+    // issuer; `result` is the result of building the rest of the path.
+    fn intermediate_tried(&self, depth: usize, intermediate_index: usize,
+                          result: Result<VerifiedPath, Error>);
+
+    // The intermediate certificate at `intermediate_index` is about to be
+    // parsed to see whether it is a candidate issuer.
+    fn intermediate_parsed(&self, intermediate_index: usize);
+
+    // A signature in a candidate path is about to be verified.
+    fn signature_verified(&self);
+
+    // The intermediate certificate at `intermediate_index`, whose subject
+    // matches the issuer of the certificate at `depth`, was skipped because
+    // it is a self-issued copy of the trust anchor at `trust_anchor_index`.
+    fn trust_anchor_in_intermediates(&self, depth: usize,
+                                     intermediate_index: usize,
+                                     trust_anchor_index: usize);
+
+    // No trust anchor or intermediate certificate has a subject that matches
+    // the issuer of `cert`, the certificate at `depth`.
+    fn issuer_subject_not_found(&self, depth: usize, cert: &Cert);
+}
+
+pub struct NoTrace;
+
+impl Trace for NoTrace {
+    fn cert_checked(&self, _: usize, _: &Cert, _: Result<(), Error>) {}
+    fn trust_anchor_tried(&self, _: usize, _: usize, _: Result<(), Error>) {}
+    fn intermediate_tried(&self, _: usize, _: usize,
+                          _: Result<VerifiedPath, Error>) {}
+    fn intermediate_parsed(&self, _: usize) {}
+    fn signature_verified(&self) {}
+    fn trust_anchor_in_intermediates(&self, _: usize, _: usize, _: usize) {}
+    fn issuer_subject_not_found(&self, _: usize, _: &Cert) {}
+}
+
+fn depth(cert: &Cert) -> usize {
+    let mut depth = 0;
+    let mut cert = cert;
+    while let EndEntityOrCA::CA(child_cert) = cert.ee_or_ca {
+        depth += 1;
+        cert = child_cert;
+    }
+    depth
+}
+
+// Returns the first successful result of `f` for `values`, each of which is a
+// candidate issuer. An error only rules out that candidate, so the others are
+// still tried: e.g. `Error::InvalidSignatureForPublicKey` from a stale
+// cross-signed certificate that reuses the subject of the real issuer must not
+// prevent the real issuer from being found. The only fatal errors are
+// `Error::TooManyCandidateIssuers` and `Error::TooManySignatureVerifications`,
+// which end the whole search.
+//
+// If every candidate is ruled out, the result is the preferred one of their
+// errors, or `Error::UnknownIssuer` if there are none.
+fn loop_while_non_fatal_error<V, F, R>(values: V, f: F) -> Result<R, Error>
+        where V: IntoIterator, F: Fn(V::Item) -> Result<R, Error> {
+    let mut error = Error::UnknownIssuer;
     for v in values {
         match f(v) {
-            Ok(()) => {
-                return Ok(());
+            Ok(result) => {
+                return Ok(result);
             },
-            Err(..) => {
+            Err(err @ Error::TooManyCandidateIssuers) |
+            Err(err @ Error::TooManySignatureVerifications) => {
+                // The search is over once a limit has been reached.
+                return Err(err);
+            },
+            Err(err) => {
                 // If the error is not fatal, then keep going.
+                error = preferred_error(error, err);
             }
         }
     }
-    Err(Error::UnknownIssuer)
+    Err(error)
+}
+
+// Returns whichever of `a` and `b` comes first in the order documented for
+// `Error`, so that the error of a failed search doesn't depend on the order
+// in which the candidate paths were tried.
+fn preferred_error(a: Error, b: Error) -> Error {
+    if error_precedence(b) > error_precedence(a) { b } else { a }
+}
+
+// The position of `error` in the order documented for `Error`, counted from
+// the end, so that errors that come first have the highest values. No two
+// errors have the same value.
+pub fn error_precedence(error: Error) -> usize {
+    // There is intentionally no `_` arm, so that every new `Error` variant
+    // must be added here.
+    match error {
+        // The search was cut short, so the other errors are incomplete.
+        Error::TooManySignatureVerifications => 36,
+        Error::TooManyCandidateIssuers => 35,
+
+        // The path is otherwise valid.
+        Error::CertRevoked => 34,
+        Error::RevocationStatusUnknown => 33,
+        Error::CertExpired => 32,
+        Error::CertNotValidYet => 31,
+
+        Error::NameConstraintViolation => 30,
+        Error::PathLenConstraintViolated => 29,
+        Error::PolicyValidationFailed => 28,
+        Error::RequiredEKUNotFound => 27,
+        Error::CAUsedAsEndEntity => 26,
+        Error::EndEntityUsedAsCA => 25,
+        Error::EndEntityKeyTypeNotAllowed => 24,
+        Error::PublicKeyTooSmall => 23,
+        Error::ValidityPeriodTooLong => 22,
+        Error::UnsupportedCriticalExtension => 21,
+
+        Error::InvalidSignatureForPublicKey => 20,
+        Error::UnsupportedSignatureAlgorithmForPublicKey => 19,
+        Error::UnsupportedSignatureAlgorithm => 18,
+        Error::SignatureAlgorithmMismatch => 17,
+
+        Error::InvalidCertValidity => 16,
+        Error::UnsupportedCertVersion => 15,
+        Error::DuplicateExtension => 14,
+        Error::ExtensionValueInvalid => 13,
+        Error::EmptySubjectAltName => 12,
+        Error::EmptySubjectWithoutCriticalSubjectAltName => 11,
+        Error::BadDERTime => 10,
+        Error::BadDER => 9,
+        Error::DERElementTooLong => 8,
+        Error::DERNestingTooDeep => 7,
+        Error::InputTooLong => 6,
+
+        // Path building doesn't return these for a candidate path.
+        Error::CertNotValidForName => 5,
+        Error::InvalidAcmeIdentifier => 4,
+        Error::InvalidReferenceName => 3,
+        Error::MalformedDnsIdentifier => 2,
+        Error::NoTrustAnchors => 1,
+
+        // No issuer was found at all.
+        Error::UnknownIssuer => 0,
+    }
 }
@@ -77,13 +77,17 @@
     while_true,
 )]
 
-#[cfg(any(test, feature = "trust_anchor_util"))]
-#[macro_use(format)]
+#[cfg(any(test, feature = "std"))]
+#[cfg_attr(any(test, feature = "trust_anchor_util"), macro_use(format))]
 extern crate std;
 
+#[cfg(feature = "log")]
+#[macro_use]
+extern crate log;
+
 extern crate ring;
 
-#[cfg(test)]
+#[cfg(any(test, feature = "openssl_dir"))]
 extern crate rustc_serialize;
 
 extern crate untrusted;
@@ -91,18 +95,95 @@ extern crate untrusted;
 #[macro_use]
 mod der;
 
+mod acme;
+mod alloc_scope;
+
+#[cfg(feature = "std")]
+pub mod capture;
+
 mod cert;
+mod certificate_policies;
+
+#[cfg(feature = "certdata")]
+mod certdata;
+
+pub mod constant_time;
+pub mod crl;
+
+pub mod der_writer;
+
+pub mod dn;
+mod error_category;
+
+#[cfg(feature = "std")]
+mod issuer_fetcher;
+
+#[cfg(feature = "std")]
+pub mod lint;
+
+#[cfg(feature = "log")]
+mod logging;
+
 mod name;
+pub mod name_constraints;
+pub mod ocsp;
+mod policy;
+pub mod prelude;
+
+#[cfg(feature = "idna")]
+mod punycode;
+
+pub mod revocation;
+
+#[cfg(feature = "trust_anchor_util")]
+mod root_store;
+
 mod signed_data;
-mod time;
+
+#[cfg(feature = "trust_anchor_util")]
+pub mod spiffe;
+
+pub mod time;
+mod tls_alert;
+
+#[cfg(feature = "std")]
+mod validation_report;
 
 #[cfg(feature = "trust_anchor_util")]
 pub mod trust_anchor_util;
 
+mod verification_options;
+mod verification_stats;
 mod verify_cert;
 
+use core::convert::TryFrom;
+use core::fmt;
+use ring::digest;
+
+pub use alloc_scope::{AllocScope, BumpArena};
+pub use cert::{Extension, Extensions};
+pub use der::{Der, MAX_DER_ELEMENT_LEN};
+pub use error_category::ErrorCategory;
+pub use name::{DnsNameMatch, ServerName, SubjectAltName, SubjectAltNames};
+pub use policy::Policy;
+pub use time::Time;
+pub use tls_alert::TlsAlert;
+pub use verification_options::{Observer, VerificationOptions};
+pub use verification_stats::{StatsCounter, VerificationStats};
+pub use verify_cert::{EKU_CODE_SIGNING, EKU_EMAIL_PROTECTION, KeyPurposeId,
+                      Usage, VerificationTimes, VerifiedPath};
+
+#[cfg(feature = "std")]
+pub use issuer_fetcher::IssuerFetcher;
+
+#[cfg(feature = "std")]
+pub use validation_report::{ReportEntry, ReportRecorder, ValidationReport};
+
+pub use signed_data::{CompatibleSignatureAlgorithms, KeyType,
+                      SignatureAlgorithm, SignatureRequirements};
+
+#[cfg(feature = "ecdsa")]
 pub use signed_data::{
-    SignatureAlgorithm,
     ECDSA_P256_SHA1,
     ECDSA_P256_SHA256,
     ECDSA_P256_SHA384,
@@ -111,6 +192,10 @@ pub use signed_data::{
     ECDSA_P384_SHA256,
     ECDSA_P384_SHA384,
     ECDSA_P384_SHA512,
+};
+
+#[cfg(feature = "rsa")]
+pub use signed_data::{
     RSA_PKCS1_2048_8192_SHA1,
     RSA_PKCS1_2048_8192_SHA256,
     RSA_PKCS1_2048_8192_SHA384,
@@ -125,7 +210,8 @@ pub use signed_data::{
 ///
 /// * `EndEntityCert.verify_is_valid_tls_server_cert`: Verify that the server's
 ///   certificate is currently valid.
-/// * `EndEntityCert.verify_is_valid_for_dns_name`: Verify that the server's
+/// * `EndEntityCert.verify_is_valid_for_dns_name` (or
+///   `EndEntityCert.verify_is_valid_for`): Verify that the server's
 ///   certificate is valid for the host that is being connected to.
 /// * `EndEntityCert.verify_signature`: Verify that the signature of server's
 ///   `ServerKeyExchange` message is valid for the server's certificate.
@@ -139,6 +225,7 @@ pub use signed_data::{
 /// deterministic, so if these tasks are done in multiple threads, it is
 /// probably best to just call `EndEntityCert::from` multiple times (before each
 /// operation) for the same DER-encoded ASN.1 certificate bytes.
+#[derive(Clone)]
 pub struct EndEntityCert<'a> {
     inner: cert::Cert<'a>,
 }
@@ -146,15 +233,34 @@ pub struct EndEntityCert<'a> {
 impl <'a> EndEntityCert<'a> {
     /// Parse the ASN.1 DER-encoded X.509 encoding of the certificate
     /// `cert_der`.
-    pub fn from(cert_der: untrusted::Input<'a>)
-                -> Result<EndEntityCert<'a>, Error> {
+    pub fn from(cert_der: &'a [u8]) -> Result<EndEntityCert<'a>, Error> {
         Ok(EndEntityCert {
             inner:
-                try!(cert::parse_cert(cert_der,
-                                      cert::EndEntityOrCA::EndEntity))
+                try!(cert::parse_cert(untrusted::Input::from(cert_der),
+                                      cert::EndEntityOrCA::EndEntity,
+                                      &Policy::default()))
         })
     }
 
+    /// Like `from`, but `cert_der` is rejected with `Error::InputTooLong`,
+    /// without being parsed, if it is longer than `policy.max_cert_len`, and
+    /// with `Error::EndEntityKeyTypeNotAllowed` if its public key isn't one of
+    /// `policy.end_entity_key_types`. It is parsed within
+    /// `policy.max_der_nesting_depth` and `policy.max_der_element_len`.
+    pub fn from_with_policy(cert_der: &'a [u8], policy: &Policy)
+                            -> Result<EndEntityCert<'a>, Error> {
+        if cert_der.len() > policy.max_cert_len {
+            return Err(Error::InputTooLong);
+        }
+        let cert = EndEntityCert {
+            inner:
+                try!(cert::parse_cert(untrusted::Input::from(cert_der),
+                                      cert::EndEntityOrCA::EndEntity, policy))
+        };
+        try!(signed_data::check_end_entity_key_type(policy, cert.inner.spki));
+        Ok(cert)
+    }
+
     /// Verifies that the end-entity certificate is valid for use by a TLS
     /// server.
     ///
@@ -165,24 +271,270 @@ impl <'a> EndEntityCert<'a> {
     /// intermediate certificates that the server sent in the TLS handshake.
     /// `cert` is the purported end-entity certificate of the server. `time` is
     /// the time for which the validation is effective (usually the current
-    /// time).
+    /// time, e.g. `Time::try_from(std::time::SystemTime::now())`).
+    ///
+    /// On success, the returned `VerifiedPath` identifies which of the
+    /// `trust_anchors` the validated path terminated at.
     pub fn verify_is_valid_tls_server_cert(
             &self, supported_sig_algs: &[&SignatureAlgorithm],
             trust_anchors: &[TrustAnchor],
-            intermediate_certs: &[untrusted::Input], time: time::Time)
-            -> Result<(), Error> {
-        verify_cert::build_chain(verify_cert::EKU_SERVER_AUTH,
-                                 supported_sig_algs, trust_anchors,
-                                 intermediate_certs, &self.inner, time, 0)
+            intermediate_certs: &[&[u8]], time: time::Time)
+            -> Result<VerifiedPath, Error> {
+        self.verify_is_valid_tls_server_cert_with_options(
+            &Policy::default(), supported_sig_algs, trust_anchors,
+            intermediate_certs, VerificationTimes::at(time),
+            &VerificationOptions::new())
+    }
+
+    /// Like `verify_is_valid_tls_server_cert`, but using `policy` instead of
+    /// the default policy, checking the validity periods of the certificates
+    /// at `times.validity_time` instead of at the current time, and with the
+    /// extensions of path building in `options`. Checking at other times
+    /// answers questions like "was this certificate valid when it was
+    /// logged?"; see `VerificationTimes`.
+    pub fn verify_is_valid_tls_server_cert_with_options(
+            &self, policy: &Policy, supported_sig_algs: &[&SignatureAlgorithm],
+            trust_anchors: &[TrustAnchor],
+            intermediate_certs: &[&[u8]], times: VerificationTimes,
+            options: &VerificationOptions) -> Result<VerifiedPath, Error> {
+        let inputs = verify_cert::ChainInputs {
+            policy: policy,
+            required_eku_if_present: verify_cert::EKU_SERVER_AUTH,
+            supported_sig_algs: supported_sig_algs,
+            trust_anchors: trust_anchors,
+            intermediate_certs: intermediate_certs,
+            times: times,
+        };
+        verification_options::build_chain(&inputs, &self.inner, options)
+    }
+
+    /// Verifies that the end-entity certificate is valid for `usage`, using
+    /// the trust anchors that `trust_domains` has for that usage. If it has
+    /// none, no path can be found and the error is `Error::NoTrustAnchors`.
+    ///
+    /// The other parameters are as for
+    /// `verify_is_valid_tls_server_cert_with_options`; the returned
+    /// `VerifiedPath::trust_anchor_index` is an index into the slice returned
+    /// by `trust_domains.trust_anchors(usage)`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn verify_for_usage(
+            &self, policy: &Policy, usage: Usage,
+            supported_sig_algs: &[&SignatureAlgorithm],
+            trust_domains: &TrustDomains,
+            intermediate_certs: &[&[u8]], times: VerificationTimes,
+            options: &VerificationOptions) -> Result<VerifiedPath, Error> {
+        let inputs = verify_cert::ChainInputs {
+            policy: policy,
+            required_eku_if_present: verify_cert::usage_eku(usage),
+            supported_sig_algs: supported_sig_algs,
+            trust_anchors: trust_domains.trust_anchors(usage),
+            intermediate_certs: intermediate_certs,
+            times: times,
+        };
+        verification_options::build_chain(&inputs, &self.inner, options)
+    }
+
+    /// Verifies that the end-entity certificate is valid for `usage` with
+    /// `trust_anchors`, and then that `signature` is a valid signature of
+    /// `msg` by the certificate's key using `signature_alg`. This is the usual
+    /// way to verify a detached signature over external data, e.g. a firmware
+    /// image signed by a certificate for `Usage::CodeSigning`. For a
+    /// signature with a trusted timestamp, pass
+    /// `VerificationTimes::signed_at` so that it remains valid after the
+    /// certificate expires.
+    ///
+    /// The signature is only verified once the certificate is known to be
+    /// valid, so the result is the certificate's error if both are invalid.
+    /// The other parameters are as for
+    /// `verify_is_valid_tls_server_cert_with_options` and `verify_signature`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn verify_signed_message(
+            &self, policy: &Policy, usage: Usage,
+            supported_sig_algs: &[&SignatureAlgorithm],
+            trust_anchors: &[TrustAnchor], intermediate_certs: &[&[u8]],
+            times: VerificationTimes, options: &VerificationOptions,
+            signature_alg: &SignatureAlgorithm, msg: &[u8], signature: &[u8])
+            -> Result<VerifiedPath, Error> {
+        let inputs = verify_cert::ChainInputs {
+            policy: policy,
+            required_eku_if_present: verify_cert::usage_eku(usage),
+            supported_sig_algs: supported_sig_algs,
+            trust_anchors: trust_anchors,
+            intermediate_certs: intermediate_certs,
+            times: times,
+        };
+        let path = try!(verification_options::build_chain(&inputs, &self.inner,
+                                                          options));
+        try!(self.verify_signature(signature_alg, msg, signature));
+        Ok(path)
+    }
+
+    /// Verifies that the end-entity certificate is a valid S/MIME
+    /// certificate for the email address `email`, e.g. the signer of a
+    /// signed message or the recipient of an encrypted one.
+    ///
+    /// The path is built as for `verify_for_usage` with
+    /// `Usage::EmailProtection`, so an extended key usage extension in the
+    /// certificate must contain `EKU_EMAIL_PROTECTION`, and then `email` is
+    /// matched as `ServerName::Email` against the certificate's rfc822Name
+    /// subjectAltName entries. The legacy emailAddress attribute of the
+    /// subject isn't used, as RFC 8550 Section 3 recommends. The name is only
+    /// matched once the path is known to be valid, so the result is the
+    /// path's error if both are invalid. The other parameters are as for
+    /// `verify_is_valid_tls_server_cert_with_options`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn verify_is_valid_smime_cert(
+            &self, policy: &Policy, supported_sig_algs: &[&SignatureAlgorithm],
+            trust_anchors: &[TrustAnchor], intermediate_certs: &[&[u8]],
+            times: VerificationTimes, options: &VerificationOptions,
+            email: &[u8]) -> Result<VerifiedPath, Error> {
+        let inputs = verify_cert::ChainInputs {
+            policy: policy,
+            required_eku_if_present: EKU_EMAIL_PROTECTION,
+            supported_sig_algs: supported_sig_algs,
+            trust_anchors: trust_anchors,
+            intermediate_certs: intermediate_certs,
+            times: times,
+        };
+        let path = try!(verification_options::build_chain(&inputs, &self.inner,
+                                                          options));
+        try!(self.verify_is_valid_for_with_policy(policy,
+                                                  &ServerName::Email(email)));
+        Ok(path)
     }
 
     /// Verifies that the certificate is valid for the given DNS host name.
     ///
     /// `dns_name` is assumed to a normalized ASCII (punycode if non-ASCII) DNS
     /// name.
-    pub fn verify_is_valid_for_dns_name(&self, dns_name: untrusted::Input)
+    ///
+    /// The subjectAltName extension is located once, by
+    /// `EndEntityCert::from`, so checking many names against the same
+    /// `EndEntityCert` only walks the subjectAltName entries each time.
+    pub fn verify_is_valid_for_dns_name(&self, dns_name: &[u8])
                                         -> Result<(), Error> {
-        name::verify_cert_dns_name(&self, dns_name)
+        self.verify_is_valid_for_dns_name_with_policy(&Policy::default(),
+                                                      dns_name)
+    }
+
+    /// Like `verify_is_valid_for_dns_name`, but using the name-matching rules
+    /// of `policy` instead of the default ones.
+    pub fn verify_is_valid_for_dns_name_with_policy(
+            &self, policy: &Policy, dns_name: &[u8]) -> Result<(), Error> {
+        name::verify_cert_dns_name(&self.inner, policy,
+                                   untrusted::Input::from(dns_name))
+    }
+
+    /// Like `verify_is_valid_for_dns_name`, but on success also reports how
+    /// `dns_name` matched: which subjectAltName entry matched it and whether
+    /// that entry is a wildcard. This allows an application to apply extra
+    /// policy, e.g. to refuse wildcard matches for payment endpoints, and to
+    /// log precisely what matched.
+    pub fn match_dns_name(&self, dns_name: &[u8])
+                          -> Result<DnsNameMatch<'a>, Error> {
+        self.match_dns_name_with_policy(&Policy::default(), dns_name)
+    }
+
+    /// Like `match_dns_name`, but using the name-matching rules of `policy`
+    /// instead of the default ones.
+    pub fn match_dns_name_with_policy(&self, policy: &Policy, dns_name: &[u8])
+                                      -> Result<DnsNameMatch<'a>, Error> {
+        name::match_cert_dns_name(&self.inner, policy,
+                                  untrusted::Input::from(dns_name))
+    }
+
+    /// Verifies that the certificate is valid for the given server name,
+    /// which may be a DNS name, an IP address, or a URI; see `ServerName`.
+    ///
+    /// This allows a TLS implementation to store a single kind of reference
+    /// identity in its configuration regardless of how the server is
+    /// identified.
+    pub fn verify_is_valid_for(&self, server_name: &ServerName)
+                               -> Result<(), Error> {
+        self.verify_is_valid_for_with_policy(&Policy::default(), server_name)
+    }
+
+    /// Like `verify_is_valid_for`, but using the name-matching rules of
+    /// `policy` instead of the default ones.
+    pub fn verify_is_valid_for_with_policy(&self, policy: &Policy,
+                                           server_name: &ServerName)
+                                           -> Result<(), Error> {
+        name::verify_cert_server_name(&self.inner, policy, server_name)
+    }
+
+    /// Verifies that the certificate is a valid ACME tls-alpn-01 challenge
+    /// certificate for `identifier`, which must be a DNS name or an IP
+    /// address, as RFC 8737 Section 3 and RFC 8738 Section 6 require.
+    ///
+    /// The certificate must have a critical id-pe-acmeIdentifier extension
+    /// containing `key_authorization_digest`, the SHA-256 digest of the key
+    /// authorization of the challenge, or else this fails with
+    /// `Error::InvalidAcmeIdentifier`. Its subjectAltName extension must
+    /// contain exactly one name, which must be `identifier`, compared
+    /// exactly except for the case of ASCII letters; wildcards aren't
+    /// matched. The certificate is self-signed by the ACME client, so, as in
+    /// RFC 8737, neither its signature nor its validity period is checked.
+    pub fn verify_is_valid_tls_alpn_01_cert(
+            &self, identifier: &ServerName, key_authorization_digest: &[u8])
+            -> Result<(), Error> {
+        acme::verify_tls_alpn_01_cert(&self.inner, identifier,
+                                      key_authorization_digest)
+    }
+
+    /// The certificate's first Microsoft user principal name (UPN)
+    /// subjectAltName entry (otherName type 1.3.6.1.4.1.311.20.2.3), e.g.
+    /// `user@example.com`, as the UTF-8 contents of its `UTF8String`, or
+    /// `None` if it has none. Client certificates used to log on to Active
+    /// Directory accounts, e.g. on smart cards, identify the account this
+    /// way.
+    pub fn user_principal_name(&self) -> Result<Option<Der<'a>>, Error> {
+        name::cert_user_principal_name(&self.inner)
+            .map(|upn| upn.map(der::from_input))
+    }
+
+    /// The names in the certificate's subjectAltName extension, including the
+    /// kinds of names that name verification skips, e.g. registeredIDs, so
+    /// that diagnostics can show them. There are none if it doesn't have the
+    /// extension.
+    ///
+    /// This fails only if one of the names is malformed, in which case the
+    /// certificate isn't valid for any name either.
+    pub fn subject_alt_names(&self) -> Result<SubjectAltNames<'a>, Error> {
+        name::subject_alt_names(self.inner.subject_alt_name)
+    }
+
+    /// Verifies that the certificate has a user principal name (UPN)
+    /// subjectAltName entry equal to `upn`, ignoring the case of ASCII
+    /// characters as Active Directory does. See `user_principal_name`.
+    pub fn verify_is_valid_for_user_principal_name(&self, upn: &[u8])
+                                                   -> Result<(), Error> {
+        name::verify_cert_user_principal_name(&self.inner,
+                                              untrusted::Input::from(upn))
+    }
+
+    /// Verifies that the certificate is valid for at least one of the given
+    /// DNS host names, returning one that it is valid for.
+    ///
+    /// The certificate's names are scanned only once regardless of how many
+    /// candidate names are given, so this is cheaper than calling
+    /// `verify_is_valid_for_dns_name` for each candidate. This is useful, for
+    /// example, for a server that accepts any of a set of client identities,
+    /// or for a client that follows redirects across aliases of a host.
+    ///
+    /// The name returned is the first of `dns_names` that matches the first
+    /// of the certificate's names that any of them matches, so it isn't
+    /// necessarily the first of `dns_names` that the certificate is valid
+    /// for.
+    ///
+    /// Each of `dns_names` is assumed to be a normalized ASCII (punycode if
+    /// non-ASCII) DNS name. If any of them is malformed then
+    /// `Error::InvalidReferenceName` is returned.
+    pub fn verify_is_valid_for_at_least_one_dns_name<'b, Names>(
+            &self, dns_names: Names) -> Result<&'b [u8], Error>
+            where Names: Iterator<Item=&'b [u8]> + Clone {
+        name::verify_cert_dns_names(&self.inner, &Policy::default(),
+                                    dns_names.map(untrusted::Input::from))
+            .map(|dns_name| dns_name.as_slice_less_safe())
     }
 
     /// Verifies the signature `signature` of message `msg` using the
@@ -206,15 +558,444 @@ impl <'a> EndEntityCert<'a> {
     /// one-to-one correspondence between TLS 1.3's `SignatureScheme` and
     /// `SignatureAlgorithm`.
     pub fn verify_signature(&self, signature_alg: &SignatureAlgorithm,
-                            msg: untrusted::Input,
-                            signature: untrusted::Input) -> Result<(), Error> {
-        signed_data::verify_signature(signature_alg, self.inner.spki, msg,
-                                      signature)
+                            msg: &[u8], signature: &[u8])
+                            -> Result<(), Error> {
+        signed_data::verify_signature(signature_alg, self.inner.spki,
+                                      untrusted::Input::from(msg),
+                                      untrusted::Input::from(signature))
+    }
+
+    /// Like `verify_signature`, but also requires `signature_alg` and the
+    /// certificate's public key to be at least as strong as `requirements`.
+    ///
+    /// This is meant for signatures made online by the peer, e.g. TLS 1.2's
+    /// `ServerKeyExchange` or TLS 1.3's `CertificateVerify`, so that they are
+    /// held to a stricter standard than the certificate chain even when the
+    /// caller accepts `signature_alg` by looking it up in the same list of
+    /// supported algorithms that it verifies the chain with.
+    pub fn verify_signature_with_requirements(
+            &self, requirements: &SignatureRequirements,
+            signature_alg: &SignatureAlgorithm, msg: &[u8], signature: &[u8])
+            -> Result<(), Error> {
+        signed_data::verify_signature_with_requirements(
+            requirements, signature_alg, self.inner.spki,
+            untrusted::Input::from(msg), untrusted::Input::from(signature))
+    }
+
+    /// The value of the certificate's `issuer` field, without the outer
+    /// `SEQUENCE` tag and length, in the same form as `TrustAnchor::subject`.
+    pub fn issuer(&self) -> Der<'a> { der::from_input(self.inner.issuer) }
+
+    /// The certificate's `issuer` field, parsed so that its attributes can
+    /// be inspected.
+    pub fn issuer_name(&self) -> Result<dn::DistinguishedName<'a>, Error> {
+        dn::DistinguishedName::from(self.inner.issuer.as_slice_less_safe())
+    }
+
+    /// The certificate's `subject` field, parsed so that its attributes can
+    /// be inspected.
+    pub fn subject_name(&self) -> Result<dn::DistinguishedName<'a>, Error> {
+        dn::DistinguishedName::from(self.inner.subject.as_slice_less_safe())
     }
+
+    /// The certificate's serial number as a big-endian unsigned integer,
+    /// without any leading zero byte.
+    ///
+    /// `ocsp::cert_id` can be used to compute the OCSP `CertID` for the
+    /// certificate from this and the issuer's certificate.
+    pub fn serial_number(&self) -> Der<'a> {
+        der::from_input(self.inner.serial)
+    }
+
+    /// The ASN.1 DER-encoded certificate that this was parsed from. Together
+    /// with `VerifiedPath::intermediate_indices`, this gives the exact chain
+    /// that was verified.
+    pub fn cert_der(&self) -> &'a [u8] {
+        self.inner.der.as_slice_less_safe()
+    }
+
+    /// The certificate's extensions, including the ones that webpki doesn't
+    /// understand, so that callers can implement their own policies on top
+    /// of webpki's.
+    ///
+    /// webpki ignores non-critical extensions that it doesn't understand and
+    /// rejects certificates with critical extensions that it doesn't
+    /// understand, so every unrecognized extension yielded here is
+    /// non-critical.
+    pub fn extensions(&self) -> Extensions<'a> {
+        cert::extensions(&self.inner)
+    }
+
+    /// Verifies that `time` is within the certificate's validity period,
+    /// without verifying anything else about it, using the same rules as
+    /// path building does.
+    ///
+    /// This answers questions like "does this certificate expire within the
+    /// next 30 days?" with webpki's own time parsing: verify the validity at
+    /// that time in the future, e.g.
+    /// `now.checked_add(Duration::from_secs(30 * 24 * 60 * 60))`, and check
+    /// for `Error::CertExpired`.
+    pub fn verify_validity(&self, time: time::Time) -> Result<(), Error> {
+        self.verify_validity_with_policy(&Policy::default(), time)
+    }
+
+    /// Like `verify_validity`, but using the
+    /// `Policy::not_before_grace_period` of `policy` instead of the default
+    /// one.
+    pub fn verify_validity_with_policy(&self, policy: &Policy,
+                                       time: time::Time) -> Result<(), Error> {
+        verify_cert::check_cert_validity(&self.inner, time, policy)
+    }
+
+    /// Checks everything about the end-entity certificate that path building
+    /// checks for it on its own, except whether it is valid at any particular
+    /// time, e.g. for an inventory system that indexes certificates
+    /// regardless of expiry and evaluates their validity per query with
+    /// `verify_validity`.
+    ///
+    /// The certificate was already parsed by `EndEntityCert::from`. This also
+    /// checks that its validity period is well-formed, that it isn't a CA
+    /// certificate, and the consistency of its signature algorithm
+    /// identifiers and of its subjectAltName extension. Its extended key
+    /// usage isn't checked, since it depends on the usage being verified, and
+    /// neither is its signature.
+    pub fn validate_structure(&self) -> Result<(), Error> {
+        self.validate_structure_with_policy(&Policy::default())
+    }
+
+    /// Like `validate_structure`, but using `policy` instead of the default
+    /// policy, so that e.g. `Policy::end_entity_key_types` and
+    /// `Policy::max_end_entity_validity` are also checked.
+    pub fn validate_structure_with_policy(&self, policy: &Policy)
+                                          -> Result<(), Error> {
+        verify_cert::check_structure(policy, &self.inner)
+    }
+
+    /// Checks the structure of the end-entity certificate and of
+    /// `intermediate_certs` without a trust anchor, e.g. for a certificate
+    /// installer or linter that runs before a root store is available.
+    ///
+    /// Every certificate is parsed, and the checks that path building does
+    /// for each certificate on its own are done at `time`: its validity
+    /// period, its basicConstraints (the end-entity certificate must not be a
+    /// CA and the intermediate certificates must be), the consistency of its
+    /// signature algorithm identifiers, and whether its signature algorithm
+    /// is one of `supported_sig_algs`. No signatures are verified, the
+    /// intermediate certificates can be in any order, and extended key usage
+    /// isn't checked, so success doesn't mean that the certificate will
+    /// verify once trust anchors are available; any error is one it would
+    /// fail with, though, unless a different path avoids the failing
+    /// intermediate certificate.
+    pub fn preflight_chain(&self, policy: &Policy,
+                           supported_sig_algs: &[&SignatureAlgorithm],
+                           intermediate_certs: &[&[u8]], time: time::Time)
+                           -> Result<(), Error> {
+        verify_cert::preflight_chain(policy, supported_sig_algs,
+                                     intermediate_certs, &self.inner, time)
+    }
+
+    /// The key identifier in the certificate's subjectKeyIdentifier
+    /// extension, if it has one. Together with `authority_key_id`, this
+    /// allows tooling to link certificates to their issuers by key.
+    pub fn subject_key_id(&self) -> Option<Der<'a>> {
+        self.inner.subject_key_id.map(der::from_input)
+    }
+
+    /// The `keyIdentifier` of the certificate's authorityKeyIdentifier
+    /// extension, if it has one, which should be the `subject_key_id` of its
+    /// issuer. Path building uses it to rule out candidate issuers.
+    pub fn authority_key_id(&self) -> Option<Der<'a>> {
+        self.inner.authority_key_id.and_then(|akid| akid.key_id)
+                                   .map(der::from_input)
+    }
+
+    /// The `authorityCertSerialNumber` of the certificate's
+    /// authorityKeyIdentifier extension, if it has one, which should be the
+    /// `serial_number` of its issuer's certificate, in the same form as
+    /// `serial_number`.
+    pub fn authority_cert_serial_number(&self) -> Option<Der<'a>> {
+        self.inner.authority_key_id.and_then(|akid| akid.cert_serial)
+                                   .map(der::from_input)
+    }
+
+    /// Returns `true` if the certificate's `subjectPublicKeyInfo` is `spki`,
+    /// which is in the same form as `TrustAnchor::spki` and the result of
+    /// `parse_spki_from_cert_der`: the value of the `SEQUENCE` without its
+    /// tag and length.
+    ///
+    /// The comparison is done in constant time; see `constant_time`.
+    pub fn spki_matches(&self, spki: &[u8]) -> bool {
+        constant_time::slices_are_equal(self.inner.spki.as_slice_less_safe(),
+                                        spki)
+    }
+
+    /// Returns `true` if the SHA-256 digest of the certificate's DER-encoded
+    /// `subjectPublicKeyInfo` is one of `pins`. This is the form of public
+    /// key pin used by HTTP Public Key Pinning (RFC 7469).
+    ///
+    /// The comparison is done in constant time; see `constant_time`.
+    pub fn spki_sha256_is_one_of(&self, pins: &[[u8; 32]]) -> bool {
+        let digest = der::digest_of_sequence(&digest::SHA256, self.inner.spki);
+        constant_time::slice_is_one_of(digest.as_ref(), pins)
+    }
+
+    /// The algorithms of `supported_sig_algs`, in the same order, that can
+    /// verify signatures made with the certificate's key; see
+    /// `SignatureAlgorithm::is_compatible_with_spki`.
+    ///
+    /// A TLS server can use this to choose the algorithm for its
+    /// CertificateVerify message from the ones that the client offered, and
+    /// a client can use it to find the ones worth offering for a key it
+    /// already knows, e.g. a pinned one.
+    pub fn compatible_signature_algorithms<'s>(
+            &self, supported_sig_algs: &'s [&'s SignatureAlgorithm])
+            -> CompatibleSignatureAlgorithms<'a, 's> {
+        signed_data::compatible_signature_algorithms(self.inner.spki,
+                                                     supported_sig_algs)
+    }
+
+    /// The certificate's `signatureAlgorithm` and `tbsCertificate.signature`
+    /// `AlgorithmIdentifier`s.
+    ///
+    /// When verification fails with `Error::SignatureAlgorithmMismatch`, this
+    /// can be used to find out how they differ. (The same information is
+    /// available for an intermediate certificate by parsing it as an
+    /// `EndEntityCert`.)
+    pub fn signature_algorithm_ids(&self) -> SignatureAlgorithmIds<'a> {
+        SignatureAlgorithmIds {
+            signature_algorithm:
+                der::from_input(self.inner.signed_data.algorithm),
+            tbs_signature: der::from_input(self.inner.tbs_signature),
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a [u8]> for EndEntityCert<'a> {
+    type Error = Error;
+
+    /// Like `EndEntityCert::from`.
+    fn try_from(cert_der: &'a [u8]) -> Result<EndEntityCert<'a>, Error> {
+        EndEntityCert::from(cert_der)
+    }
+}
+
+// The certificate's bytes are intentionally not included, so that logging a
+// certificate doesn't dump it; only its subject is, in its RFC 4514 form.
+impl<'a> fmt::Debug for EndEntityCert<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.subject_name() {
+            Ok(subject) =>
+                write!(f, "EndEntityCert {{ subject: \"{}\", .. }}", subject),
+            Err(_) => f.write_str("EndEntityCert { .. }"),
+        }
+    }
+}
+
+/// A CA certificate, e.g. a sub-CA certificate that is to be installed.
+///
+/// This allows a CA certificate to be validated on its own, without an
+/// end-entity certificate issued by it.
+pub struct CaCert<'a> {
+    inner: cert::Cert<'a>,
+}
+
+impl <'a> CaCert<'a> {
+    /// Parse the ASN.1 DER-encoded X.509 encoding of the certificate
+    /// `cert_der`.
+    pub fn from(cert_der: &'a [u8]) -> Result<CaCert<'a>, Error> {
+        CaCert::from_with_policy(cert_der, &Policy::default())
+    }
+
+    /// Like `from`, but `cert_der` is rejected with `Error::InputTooLong`,
+    /// without being parsed, if it is longer than `policy.max_cert_len`, and
+    /// it is parsed within `policy.max_der_nesting_depth` and
+    /// `policy.max_der_element_len`.
+    pub fn from_with_policy(cert_der: &'a [u8], policy: &Policy)
+                            -> Result<CaCert<'a>, Error> {
+        if cert_der.len() > policy.max_cert_len {
+            return Err(Error::InputTooLong);
+        }
+        Ok(CaCert {
+            inner:
+                try!(cert::parse_cert(untrusted::Input::from(cert_der),
+                                      cert::EndEntityOrCA::TargetCA, policy))
+        })
+    }
+
+    /// Verifies that the certificate is a valid CA certificate for issuing
+    /// TLS server certificates.
+    ///
+    /// The certificate must chain to one of `trust_anchors`, possibly through
+    /// some of `intermediate_certs`, and it must have the cA basic constraint.
+    /// The pathLenConstraints of it and of its issuers are enforced as though
+    /// it issues end-entity certificates directly. The parameters are
+    /// otherwise the same as for
+    /// `EndEntityCert::verify_is_valid_tls_server_cert`.
+    pub fn verify_is_valid_ca_cert(
+            &self, supported_sig_algs: &[&SignatureAlgorithm],
+            trust_anchors: &[TrustAnchor],
+            intermediate_certs: &[&[u8]], time: time::Time)
+            -> Result<VerifiedPath, Error> {
+        self.verify_is_valid_ca_cert_with_policy(
+            &Policy::default(), supported_sig_algs, trust_anchors,
+            intermediate_certs, time)
+    }
+
+    /// Like `verify_is_valid_ca_cert`, but using `policy` instead of the
+    /// default policy.
+    pub fn verify_is_valid_ca_cert_with_policy(
+            &self, policy: &Policy, supported_sig_algs: &[&SignatureAlgorithm],
+            trust_anchors: &[TrustAnchor],
+            intermediate_certs: &[&[u8]], time: time::Time)
+            -> Result<VerifiedPath, Error> {
+        let inputs = verify_cert::ChainInputs {
+            policy: policy,
+            required_eku_if_present: verify_cert::EKU_SERVER_AUTH,
+            supported_sig_algs: supported_sig_algs,
+            trust_anchors: trust_anchors,
+            intermediate_certs: intermediate_certs,
+            times: VerificationTimes::at(time),
+        };
+        verify_cert::build_chain(&inputs, &self.inner, None,
+                                 &verify_cert::NoTrace)
+    }
+
+    /// Verifies that `time` is within the certificate's validity period,
+    /// without verifying anything else about it. See
+    /// `EndEntityCert::verify_validity`.
+    pub fn verify_validity(&self, time: time::Time) -> Result<(), Error> {
+        self.verify_validity_with_policy(&Policy::default(), time)
+    }
+
+    /// Like `verify_validity`, but using the
+    /// `Policy::not_before_grace_period` of `policy` instead of the default
+    /// one.
+    pub fn verify_validity_with_policy(&self, policy: &Policy,
+                                       time: time::Time) -> Result<(), Error> {
+        verify_cert::check_cert_validity(&self.inner, time, policy)
+    }
+
+    /// Verifies that the certificate's own names, i.e. its subject and its
+    /// subjectAltNames, include the given name, using the same rules as
+    /// `EndEntityCert::verify_is_valid_for`.
+    ///
+    /// This is intended for auditing constrained delegation, e.g. checking
+    /// that a sub-CA identifies the organization it was delegated to. See
+    /// also `name_constraints::NameConstraints::check_cert`.
+    pub fn verify_is_valid_for(&self, server_name: &ServerName)
+                               -> Result<(), Error> {
+        self.verify_is_valid_for_with_policy(&Policy::default(), server_name)
+    }
+
+    /// Like `verify_is_valid_for`, but using the name-matching rules of
+    /// `policy` instead of the default ones.
+    pub fn verify_is_valid_for_with_policy(&self, policy: &Policy,
+                                           server_name: &ServerName)
+                                           -> Result<(), Error> {
+        name::verify_cert_server_name(&self.inner, policy, server_name)
+    }
+
+    /// Whether the certificate is "technically constrained" from issuing TLS
+    /// server certificates, as defined in section 7.1.5 of the CA/Browser
+    /// Forum Baseline Requirements, so that Certificate Transparency
+    /// policies can exempt the certificates it issues.
+    ///
+    /// That is the case if its extendedKeyUsage extension contains neither
+    /// anyExtendedKeyUsage nor id-kp-serverAuth, or if its name constraints
+    /// permit some dNSName subtrees and either permit some iPAddress subtrees
+    /// or exclude all IPv4 and IPv6 addresses. A certificate without an
+    /// extendedKeyUsage extension is never technically constrained. The
+    /// directoryName constraints that the Baseline Requirements also require
+    /// are NOT checked.
+    ///
+    /// This fails only if the extendedKeyUsage or nameConstraints extension
+    /// is malformed.
+    pub fn is_technically_constrained(&self) -> Result<bool, Error> {
+        verify_cert::is_technically_constrained(&self.inner)
+    }
+
+    /// The value of the certificate's `subject` field, without the outer
+    /// `SEQUENCE` tag and length, in the same form as `TrustAnchor::subject`.
+    pub fn subject(&self) -> Der<'a> { der::from_input(self.inner.subject) }
+}
+
+/// The two `AlgorithmIdentifier`s of a certificate that identify the
+/// algorithm of its signature. RFC 5280 requires them to be the same.
+///
+/// Each value is the contents of the `AlgorithmIdentifier` `SEQUENCE`, without
+/// the outer tag and length.
+#[derive(Clone, Copy)]
+pub struct SignatureAlgorithmIds<'a> {
+    /// The value of the `signatureAlgorithm` field of the `Certificate`.
+    pub signature_algorithm: Der<'a>,
+
+    /// The value of the `signature` field of the `TBSCertificate`.
+    pub tbs_signature: Der<'a>,
+}
+
+
+/// Extracts the value of the `subjectPublicKeyInfo` field of the ASN.1
+/// DER-encoded X.509 certificate `cert_der`, without the outer `SEQUENCE` tag
+/// and length, in the same form as `TrustAnchor::spki`.
+///
+/// Only as much of the certificate is parsed as is needed to find the
+/// `subjectPublicKeyInfo`; in particular, the certificate is NOT validated in
+/// any way and its extensions are not parsed. This is intended for things
+/// like trust-on-first-use databases and key continuity checks that only
+/// need to compare public keys. Use `EndEntityCert` for anything else.
+pub fn parse_spki_from_cert_der(cert_der: &[u8]) -> Result<&[u8], Error> {
+    cert::parse_spki(untrusted::Input::from(cert_der))
+        .map(|spki| spki.as_slice_less_safe())
+}
+
+/// Returns the length of the certificate whose DER encoding starts with
+/// `prefix`, or `None` if `prefix` is too short to tell, for reading a
+/// certificate that arrives in fragments, e.g. across fixed-size record
+/// buffers, into a buffer of exactly the right size for
+/// `EndEntityCert::from`.
+///
+/// Only the outer tag and length are looked at, so `prefix` never needs to be
+/// longer than `CERT_DER_LEN_PREFIX_LEN` bytes. A certificate that is too
+/// long to be parsed at all is rejected with `Error::InputTooLong`, and one
+/// whose tag or length is malformed with `Error::BadDER`. Compare the length
+/// with `Policy::max_cert_len` before buffering that much of a peer's data.
+pub fn cert_der_len(prefix: &[u8]) -> Result<Option<usize>, Error> {
+    der::element_len(prefix, der::Tag::Sequence)
 }
 
+/// The maximum number of bytes of a certificate that `cert_der_len` needs.
+pub const CERT_DER_LEN_PREFIX_LEN: usize = 4;
 
 /// An error that occurs during certificate validation or name validation.
+///
+/// See `ErrorCategory` for a classification of the errors, and `TlsAlert`
+/// for the TLS alert to send for each of them.
+///
+/// When path building tries several candidate paths and all of them fail,
+/// possibly for different reasons, the error returned is the first of their
+/// errors in the following order, regardless of the order in which the
+/// trust anchors and intermediate certificates were given:
+///
+/// 1. `TooManySignatureVerifications`, `TooManyCandidateIssuers`: the search
+///    was cut short.
+/// 2. `CertRevoked`, `RevocationStatusUnknown`.
+/// 3. `CertExpired`, `CertNotValidYet`.
+/// 4. `NameConstraintViolation`, `PathLenConstraintViolated`,
+///    `PolicyValidationFailed`, `RequiredEKUNotFound`, `CAUsedAsEndEntity`,
+///    `EndEntityUsedAsCA`, `EndEntityKeyTypeNotAllowed`, `PublicKeyTooSmall`,
+///    `ValidityPeriodTooLong`, `UnsupportedCriticalExtension`.
+/// 5. `InvalidSignatureForPublicKey`,
+///    `UnsupportedSignatureAlgorithmForPublicKey`,
+///    `UnsupportedSignatureAlgorithm`, `SignatureAlgorithmMismatch`.
+/// 6. The errors for malformed certificates, from `InvalidCertValidity`,
+///    `UnsupportedCertVersion`, `DuplicateExtension`,
+///    `ExtensionValueInvalid`, `EmptySubjectAltName`, and
+///    `EmptySubjectWithoutCriticalSubjectAltName` to `BadDERTime`, `BadDER`,
+///    `DERElementTooLong`, `DERNestingTooDeep`, and `InputTooLong`.
+/// 7. `UnknownIssuer`, when no candidate path was found at all.
+///
+/// Use a `ReportRecorder` as the `VerificationOptions::observer` and
+/// `ValidationReport::rejections` to get all of the errors.
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Error {
     /// The encoding of some ASN.1 DER-encoded item is invalid.
@@ -237,12 +1018,55 @@ pub enum Error {
     /// for is earlier than the certificate's notBefore time.
     CertNotValidYet,
 
+    /// The certificate has been revoked. See `revocation::RevocationChecker`
+    /// and `revocation::RevocationFilter`.
+    CertRevoked,
+
+    /// The value of a DER element is longer than
+    /// `Policy::max_der_element_len`, or too long for `der_writer` to encode.
+    DERElementTooLong,
+
+    /// DER elements are nested more deeply than
+    /// `Policy::max_der_nesting_depth`.
+    DERNestingTooDeep,
+
+    /// A certificate contains more than one instance of an extension. See
+    /// `Policy::allow_duplicate_extensions`.
+    DuplicateExtension,
+
+    /// A certificate has a subjectAltName extension that contains no names.
+    /// See `Policy::allow_nonconformant_subject_alt_name`.
+    EmptySubjectAltName,
+
+    /// A certificate has an empty subject but no subjectAltName extension,
+    /// or a subjectAltName extension that isn't marked critical. See
+    /// `Policy::allow_nonconformant_subject_alt_name`.
+    EmptySubjectWithoutCriticalSubjectAltName,
+
+    /// An end-entity certificate has a public key of a type that the policy
+    /// doesn't allow. See `Policy::end_entity_key_types`.
+    EndEntityKeyTypeNotAllowed,
+
     /// An end-entity certificate is being used as a CA certificate.
     EndEntityUsedAsCA,
 
     /// An X.509 extension is invalid.
     ExtensionValueInvalid,
 
+    /// A certificate, or the intermediate certificates together, are longer
+    /// than the policy allows, or there are too many intermediate
+    /// certificates. See `Policy::max_cert_len`,
+    /// `Policy::max_intermediate_certs`, and
+    /// `Policy::max_intermediate_certs_len`. Also returned when the output
+    /// of `der_writer` doesn't fit in a `der_writer::SliceOutput`, and when
+    /// a `BumpArena` is full.
+    InputTooLong,
+
+    /// An ACME tls-alpn-01 challenge certificate doesn't have a critical
+    /// id-pe-acmeIdentifier extension with the expected key authorization
+    /// digest. See `EndEntityCert::verify_is_valid_tls_alpn_01_cert`.
+    InvalidAcmeIdentifier,
+
     /// The certificate validity period (notBefore, notAfter) is invalid; e.g.
     /// the notAfter time is earlier than the notBefore time.
     InvalidCertValidity,
@@ -255,20 +1079,61 @@ pub enum Error {
     /// The signature is invalid for the given public key.
     InvalidSignatureForPublicKey,
 
+    /// A DNS name presented in the certificate is syntactically invalid; e.g.
+    /// it contains an embedded NUL. This is distinct from
+    /// `CertNotValidForName`, which means the certificate's names are well
+    /// formed but none of them match.
+    MalformedDnsIdentifier,
+
     /// The certificate violates one or more name constraints.
     NameConstraintViolation,
 
+    /// No valid path was found, and there were no trust anchors to verify
+    /// the certificate with, e.g. because the application didn't load any
+    /// roots. This is returned instead of `UnknownIssuer` in that case;
+    /// errors in the certificates themselves are reported as usual.
+    NoTrustAnchors,
+
     /// The certificate violates one or more path length constraints.
     PathLenConstraintViolated,
 
+    /// The path doesn't have a valid certificate policy, but one is required
+    /// by `Policy::require_explicit_policy` or by the policyConstraints
+    /// extension of one of the certificates. Without the `std` feature, also
+    /// returned when the policies of the path are too numerous to be
+    /// processed without allocating.
+    PolicyValidationFailed,
+
+    /// The signer's public key is smaller than the `SignatureRequirements`
+    /// allow. See `EndEntityCert::verify_signature_with_requirements` and
+    /// `Policy::signature_requirements`.
+    PublicKeyTooSmall,
+
+    /// There is no fresh revocation information for the certificate, but
+    /// the `revocation::RevocationPolicy` requires some. Also returned for
+    /// an OCSP response whose `responseStatus` isn't `successful`; see
+    /// `ocsp::Response::from`.
+    RevocationStatusUnknown,
+
     /// The algorithm in the TBSCertificate "signature" field of a certificate
-    /// does not match the algorithm in the signature of the certificate.
+    /// does not match the algorithm in the signature of the certificate. See
+    /// `EndEntityCert::signature_algorithm_ids` and
+    /// `Policy::allow_signature_algorithm_null_parameter_mismatch`.
     SignatureAlgorithmMismatch,
 
     /// The certificate is not valid for the Extended Key Usage for which it is
-    /// being validated.
+    /// being validated. See also `Usage::TimeStamping`.
     RequiredEKUNotFound,
 
+    /// Path building gave up after trying `Policy::max_candidate_issuers`
+    /// intermediate certificates as issuers without finding a valid path.
+    TooManyCandidateIssuers,
+
+    /// A path-building search gave up after verifying
+    /// `Policy::max_signature_verifications` signatures without finding a
+    /// valid path.
+    TooManySignatureVerifications,
+
     /// A valid issuer for the certificate could not be found.
     UnknownIssuer,
 
@@ -285,6 +1150,10 @@ pub enum Error {
     /// The signature algorithm for a signature is not in the set of supported
     /// signature algorithms given.
     UnsupportedSignatureAlgorithm,
+
+    /// The end-entity certificate's validity period is longer than
+    /// `Policy::max_end_entity_validity` allows.
+    ValidityPeriodTooLong,
 }
 
 /// A trust anchor (a.k.a. root CA).
@@ -308,3 +1177,1332 @@ pub struct TrustAnchor<'a> {
     /// constraints to apply to the trust anchor, if any.
     pub name_constraints: Option<&'a [u8]>
 }
+
+/// An ordered set of trust anchors, from most to least preferred.
+///
+/// When a certificate can be verified to more than one of the trust anchors,
+/// e.g. because its issuer is cross-signed, verification with the trust
+/// anchors of a `TrustAnchorSet` and
+/// `VerificationOptions::prefer_earlier_trust_anchors` deterministically
+/// results in a path to the most preferred of them. For example, an operator
+/// can prefer a modern self-signed root and fall back to an older root that
+/// cross-signed it. Otherwise, which of the paths is found depends on the
+/// order of the intermediate certificates.
+#[derive(Clone, Copy, Debug)]
+pub struct TrustAnchorSet<'a> {
+    trust_anchors: &'a [TrustAnchor<'a>],
+}
+
+impl<'a> TrustAnchorSet<'a> {
+    /// A set of `trust_anchors` in which each trust anchor is preferred to
+    /// all of the ones after it.
+    pub fn new(trust_anchors: &'a [TrustAnchor<'a>]) -> TrustAnchorSet<'a> {
+        TrustAnchorSet { trust_anchors: trust_anchors }
+    }
+
+    /// The trust anchors, from most to least preferred. A
+    /// `VerifiedPath::trust_anchor_index` of a path verified with the set is
+    /// an index into this slice.
+    pub fn trust_anchors(&self) -> &'a [TrustAnchor<'a>] {
+        self.trust_anchors
+    }
+}
+
+/// Separate sets of trust anchors for different usages, like the
+/// per-purpose trust settings of operating system root stores, in which a
+/// root may be trusted to issue TLS server certificates but not email
+/// certificates, or vice versa.
+///
+/// `EndEntityCert::verify_for_usage` picks the set for the usage being
+/// verified, so that a root that is trusted only for one usage can't be
+/// used to verify certificates for another one, even if the certificates'
+/// extended key usage extensions allow it.
+#[derive(Clone, Copy, Debug)]
+pub struct TrustDomains<'a> {
+    domains: &'a [(Usage, &'a [TrustAnchor<'a>])],
+}
+
+impl<'a> TrustDomains<'a> {
+    /// Trust domains in which the trust anchors for each usage are those
+    /// paired with it in `domains`. If a usage appears more than once, the
+    /// first set is used; if it doesn't appear, no trust anchors are trusted
+    /// for it.
+    pub fn new(domains: &'a [(Usage, &'a [TrustAnchor<'a>])])
+               -> TrustDomains<'a> {
+        TrustDomains { domains: domains }
+    }
+
+    /// The trust anchors for `usage`, which may be empty.
+    pub fn trust_anchors(&self, usage: Usage) -> &'a [TrustAnchor<'a>] {
+        self.domains.iter()
+                    .find(|&&(u, _)| u == usage)
+                    .map(|&(_, trust_anchors)| trust_anchors)
+                    .unwrap_or(&[])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The result of verifying `ee` as a TLS server certificate, with the
+    // `ValidationReport` of the verification.
+    #[cfg(all(feature = "trust_anchor_util", feature = "ecdsa"))]
+    fn verify_with_report(ee: &EndEntityCert, policy: &Policy,
+                          supported_sig_algs: &[&SignatureAlgorithm],
+                          trust_anchors: &[TrustAnchor],
+                          intermediate_certs: &[&[u8]],
+                          times: VerificationTimes) -> ValidationReport {
+        let recorder = ReportRecorder::new();
+        let result = ee.verify_is_valid_tls_server_cert_with_options(
+            policy, supported_sig_algs, trust_anchors, intermediate_certs,
+            times, &VerificationOptions::new().observer(&recorder));
+        recorder.into_report(result)
+    }
+
+    // Likewise, with the `VerificationStats` of the verification.
+    #[cfg(all(feature = "trust_anchor_util", feature = "ecdsa"))]
+    fn verify_with_stats(ee: &EndEntityCert, policy: &Policy,
+                         supported_sig_algs: &[&SignatureAlgorithm],
+                         trust_anchors: &[TrustAnchor],
+                         intermediate_certs: &[&[u8]],
+                         times: VerificationTimes)
+                         -> (Result<VerifiedPath, Error>, VerificationStats) {
+        let counter = StatsCounter::new();
+        let result = ee.verify_is_valid_tls_server_cert_with_options(
+            policy, supported_sig_algs, trust_anchors, intermediate_certs,
+            times, &VerificationOptions::new().observer(&counter));
+        (result, counter.stats())
+    }
+
+    // Parsed certificates, trust anchors, and verification settings are meant
+    // to be shared between the worker threads of a server without locks or
+    // re-parsing, so they must all be `Send` and `Sync`. This doesn't compile
+    // if any of them isn't.
+    #[test]
+    fn test_send_and_sync() {
+        fn assert_send_and_sync<T: Send + Sync>() {}
+
+        assert_send_and_sync::<EndEntityCert>();
+        assert_send_and_sync::<CaCert>();
+        assert_send_and_sync::<Extensions>();
+        assert_send_and_sync::<Der>();
+        assert_send_and_sync::<TrustAnchor>();
+        assert_send_and_sync::<TrustAnchorSet>();
+        assert_send_and_sync::<TrustDomains>();
+        assert_send_and_sync::<name_constraints::NameConstraints>();
+        assert_send_and_sync::<ServerName>();
+        assert_send_and_sync::<Policy>();
+        assert_send_and_sync::<SignatureAlgorithm>();
+        assert_send_and_sync::<&[&SignatureAlgorithm]>();
+        assert_send_and_sync::<VerificationTimes>();
+        assert_send_and_sync::<VerifiedPath>();
+        assert_send_and_sync::<VerificationStats>();
+        assert_send_and_sync::<ocsp::CertId>();
+        assert_send_and_sync::<ocsp::ResponderId>();
+        assert_send_and_sync::<ocsp::Response>();
+        assert_send_and_sync::<revocation::RevocationChecker>();
+        assert_send_and_sync::<revocation::RevocationInfo>();
+        #[cfg(feature = "trust_anchor_util")]
+        {
+            assert_send_and_sync::<spiffe::SpiffeId>();
+            assert_send_and_sync::<spiffe::TrustBundles>();
+        }
+        assert_send_and_sync::<Time>();
+        assert_send_and_sync::<Error>();
+        assert_send_and_sync::<TlsAlert>();
+        assert_send_and_sync::<ErrorCategory>();
+    }
+
+    #[test]
+    fn test_key_purpose_id_format_and_eq() {
+        let server_auth = Usage::TlsServer.key_purpose_id();
+        assert_eq!("1.3.6.1.5.5.7.3.1", format!("{}", server_auth));
+        assert_eq!("KeyPurposeId(1.3.6.1.5.5.7.3.1)",
+                   format!("{:?}", server_auth));
+        assert_eq!("2.23.133.8.1",
+                   format!("{}",
+                           Usage::TcgEndorsementKey.key_purpose_id()));
+        assert_eq!(server_auth, Usage::TlsServer.key_purpose_id());
+        assert!(server_auth != Usage::TlsClient.key_purpose_id());
+    }
+
+    // ecdsa-wildcard-ee has the dNSNames www.example.net, mail.example.net,
+    // example.org, example.com, and *.example.com, in that order.
+    #[test]
+    fn test_verify_is_valid_for_at_least_one_dns_name() {
+        let ee = EndEntityCert::from(
+            include_bytes!("../tests/data/ecdsa-wildcard-ee.der")).unwrap();
+        let verify = |dns_names: &[&'static [u8]]| {
+            ee.verify_is_valid_for_at_least_one_dns_name(
+                dns_names.iter().cloned())
+        };
+
+        assert_eq!(Ok(&b"example.org"[..]),
+                   verify(&[b"example.org", b"invalid.example"]));
+        // mail.example.net is before example.org in the certificate.
+        assert_eq!(Ok(&b"mail.example.net"[..]),
+                   verify(&[b"example.org", b"mail.example.net"]));
+        // a.example.com only matches *.example.com, the last name.
+        assert_eq!(Ok(&b"example.com"[..]),
+                   verify(&[b"a.example.com", b"example.com"]));
+        assert_eq!(Ok(&b"a.example.com"[..]),
+                   verify(&[b"a.example.com", b"example.net"]));
+        assert_eq!(Err(Error::CertNotValidForName),
+                   verify(&[b"example.net", b"a.b.example.com"]));
+        assert_eq!(Err(Error::InvalidReferenceName),
+                   verify(&[b"example.org", b"example..org"]));
+    }
+
+    #[cfg(feature = "ecdsa")]
+    #[test]
+    fn test_input_too_long() {
+        let ee: &[u8] = include_bytes!("../tests/data/ecdsa-ee.der");
+        let intermediate: &[u8] =
+            include_bytes!("../tests/data/ecdsa-intermediate.der");
+        let time = Time::from_ymdhms_utc(2027, 1, 1, 0, 0, 0).unwrap();
+
+        let policy = Policy { max_cert_len: ee.len(), ..Policy::default() };
+        assert!(EndEntityCert::from_with_policy(ee, &policy).is_ok());
+        let policy = Policy { max_cert_len: ee.len() - 1, ..policy };
+        assert_eq!(Some(Error::InputTooLong),
+                   EndEntityCert::from_with_policy(ee, &policy).err());
+
+        let cert = EndEntityCert::from(ee).unwrap();
+        let policy = Policy {
+            max_intermediate_certs_len: 2 * intermediate.len() - 1,
+            ..Policy::default()
+        };
+        let times = VerificationTimes::at(time);
+        let options = &VerificationOptions::new();
+        assert_eq!(Err(Error::NoTrustAnchors),
+                   cert.verify_is_valid_tls_server_cert_with_options(
+                       &policy, &[&ECDSA_P256_SHA256], &[], &[intermediate],
+                       times, options));
+        assert_eq!(Err(Error::InputTooLong),
+                   cert.verify_is_valid_tls_server_cert_with_options(
+                       &policy, &[&ECDSA_P256_SHA256], &[],
+                       &[intermediate, intermediate], times, options));
+        let policy = Policy { max_intermediate_certs: 1, ..Policy::default() };
+        assert_eq!(Err(Error::InputTooLong),
+                   cert.verify_is_valid_tls_server_cert_with_options(
+                       &policy, &[&ECDSA_P256_SHA256], &[],
+                       &[intermediate, intermediate], times, options));
+        let policy = Policy { max_intermediate_certs: 100, ..policy };
+        assert_eq!(Err(Error::InputTooLong),
+                   cert.verify_is_valid_tls_server_cert_with_options(
+                       &policy, &[&ECDSA_P256_SHA256], &[],
+                       &[intermediate; 65], times, options));
+    }
+
+    #[test]
+    fn test_der_limits() {
+        // Like every certificate, the certificate nests six levels deep. Its
+        // outer `SEQUENCE` has a two-byte length.
+        let ee: &[u8] = include_bytes!("../tests/data/ecdsa-ee.der");
+        assert_eq!(0x82, ee[1]);
+        let value_len = ee.len() - 4;
+
+        let policy = Policy {
+            max_der_nesting_depth: 6,
+            max_der_element_len: value_len,
+            ..Policy::default()
+        };
+        assert!(EndEntityCert::from_with_policy(ee, &policy).is_ok());
+        assert!(CaCert::from_with_policy(ee, &policy).is_ok());
+
+        let too_shallow = Policy { max_der_nesting_depth: 5, ..policy };
+        assert_eq!(Some(Error::DERNestingTooDeep),
+                   EndEntityCert::from_with_policy(ee, &too_shallow).err());
+        assert_eq!(Some(Error::DERNestingTooDeep),
+                   CaCert::from_with_policy(ee, &too_shallow).err());
+
+        let too_short = Policy { max_der_element_len: value_len - 1, ..policy };
+        assert_eq!(Some(Error::DERElementTooLong),
+                   EndEntityCert::from_with_policy(ee, &too_short).err());
+        assert_eq!(Some(Error::DERElementTooLong),
+                   CaCert::from_with_policy(ee, &too_short).err());
+    }
+
+    #[cfg(feature = "ecdsa")]
+    #[test]
+    fn test_end_entity_key_types() {
+        let ee: &[u8] = include_bytes!("../tests/data/ecdsa-ee.der");
+        let time = Time::from_ymdhms_utc(2027, 1, 1, 0, 0, 0).unwrap();
+
+        let ecdsa_only = Policy {
+            end_entity_key_types: Some(&[KeyType::EcdsaP384,
+                                         KeyType::EcdsaP256]),
+            ..Policy::default()
+        };
+        assert!(EndEntityCert::from_with_policy(ee, &ecdsa_only).is_ok());
+
+        let rsa_only = Policy {
+            end_entity_key_types: Some(&[KeyType::Rsa]),
+            ..Policy::default()
+        };
+        assert_eq!(Some(Error::EndEntityKeyTypeNotAllowed),
+                   EndEntityCert::from_with_policy(ee, &rsa_only).err());
+        let cert = EndEntityCert::from(ee).unwrap();
+        assert_eq!(Err(Error::EndEntityKeyTypeNotAllowed),
+                   cert.verify_is_valid_tls_server_cert_with_options(
+                       &rsa_only, &[&ECDSA_P256_SHA256], &[], &[],
+                       VerificationTimes::at(time),
+                       &VerificationOptions::new()));
+    }
+
+    #[cfg(all(feature = "trust_anchor_util", feature = "ecdsa",
+              feature = "rsa"))]
+    #[test]
+    fn test_profiles() {
+        use core::time::Duration;
+
+        let ecdsa_anchors = &[trust_anchor_util::cert_der_as_trust_anchor(
+            include_bytes!("../tests/data/ecdsa-root.der")).unwrap()];
+        let ecdsa_intermediate: &[u8] =
+            include_bytes!("../tests/data/ecdsa-intermediate.der");
+        let ecdsa_ee = EndEntityCert::from(
+            include_bytes!("../tests/data/ecdsa-ee.der")).unwrap();
+        let rsa_anchors = &[trust_anchor_util::cert_der_as_trust_anchor(
+            include_bytes!("../tests/data/rsa-root.der")).unwrap()];
+        let rsa_intermediate: &[u8] =
+            include_bytes!("../tests/data/rsa-intermediate.der");
+        let rsa_ee = EndEntityCert::from(
+            include_bytes!("../tests/data/rsa-ee.der")).unwrap();
+        let sig_algs = &[&ECDSA_P256_SHA256, &RSA_PKCS1_2048_8192_SHA256];
+        let times = VerificationTimes::at(
+            Time::from_ymdhms_utc(2027, 1, 1, 0, 0, 0).unwrap());
+        let options = &VerificationOptions::new();
+
+        let verify_ecdsa = |policy: &Policy| {
+            ecdsa_ee.verify_is_valid_tls_server_cert_with_options(
+                policy, sig_algs, ecdsa_anchors, &[ecdsa_intermediate], times,
+                options).map(|_| ())
+        };
+        let verify_rsa = |policy: &Policy| {
+            rsa_ee.verify_is_valid_tls_server_cert_with_options(
+                policy, sig_algs, rsa_anchors, &[rsa_intermediate], times,
+                options).map(|_| ())
+        };
+
+        // The end-entity certificates are valid for ten years.
+        assert_eq!(Err(Error::ValidityPeriodTooLong),
+                   verify_ecdsa(&Policy::modern_profile()));
+        assert_eq!(Err(Error::ValidityPeriodTooLong),
+                   verify_rsa(&Policy::intermediate_profile()));
+        assert_eq!(Ok(()), verify_ecdsa(&Policy::legacy_profile()));
+        assert_eq!(Ok(()), verify_rsa(&Policy::legacy_profile()));
+
+        let ten_years = Some(Duration::from_secs(3653 * 24 * 60 * 60));
+        let modern = Policy {
+            max_end_entity_validity: ten_years,
+            ..Policy::modern_profile()
+        };
+        let intermediate = Policy {
+            max_end_entity_validity: ten_years,
+            ..Policy::intermediate_profile()
+        };
+        assert_eq!(Ok(()), verify_ecdsa(&modern));
+        assert_eq!(Err(Error::EndEntityKeyTypeNotAllowed),
+                   verify_rsa(&modern));
+        assert_eq!(Ok(()), verify_rsa(&intermediate));
+
+        // The signature requirements apply to every certificate in the path,
+        // regardless of the supported signature algorithms.
+        let requirements = SignatureRequirements::default();
+        let rsa_3072 = Policy {
+            signature_requirements: Some(SignatureRequirements {
+                min_rsa_modulus_bits: 3072,
+                ..requirements
+            }),
+            ..intermediate
+        };
+        assert_eq!(Err(Error::PublicKeyTooSmall), verify_rsa(&rsa_3072));
+        assert_eq!(Ok(()), verify_ecdsa(&rsa_3072));
+        let sha384 = Policy {
+            signature_requirements: Some(SignatureRequirements {
+                min_digest_bits: 384,
+                ..requirements
+            }),
+            ..intermediate
+        };
+        assert_eq!(Err(Error::UnsupportedSignatureAlgorithm),
+                   verify_ecdsa(&sha384));
+    }
+
+    #[cfg(all(feature = "trust_anchor_util", feature = "ecdsa"))]
+    #[test]
+    fn test_signed_at() {
+        // The end-entity certificate is valid for one day, from 2026-10-16
+        // 00:00:00 UTC.
+        let anchors = &[trust_anchor_util::cert_der_as_trust_anchor(
+            include_bytes!("../tests/data/codesign-root.der")).unwrap()];
+        let domains = [(Usage::CodeSigning, &anchors[..]),
+                       (Usage::TlsServer, &anchors[..])];
+        let trust_domains = TrustDomains::new(&domains);
+        let ee = EndEntityCert::from(
+            include_bytes!("../tests/data/codesign-ee.der")).unwrap();
+        let policy = Policy::default();
+        let sig_algs = &[&ECDSA_P256_SHA256];
+        let now = Time::from_ymdhms_utc(2027, 1, 1, 0, 0, 0).unwrap();
+        let verify = |usage, times| {
+            ee.verify_for_usage(&policy, usage, sig_algs, &trust_domains, &[],
+                                times, &VerificationOptions::new())
+              .map(|_| ())
+        };
+
+        assert_eq!(Err(Error::CertExpired),
+                   verify(Usage::CodeSigning, VerificationTimes::at(now)));
+        let signed =
+            Time::from_ymdhms_utc(2026, 10, 16, 12, 0, 0).unwrap();
+        assert_eq!(Ok(()),
+                   verify(Usage::CodeSigning,
+                          VerificationTimes::signed_at(signed, now)));
+        let signed_late =
+            Time::from_ymdhms_utc(2026, 10, 18, 0, 0, 0).unwrap();
+        assert_eq!(Err(Error::CertExpired),
+                   verify(Usage::CodeSigning,
+                          VerificationTimes::signed_at(signed_late, now)));
+
+        // The extended key usage is still checked.
+        assert_eq!(Err(Error::RequiredEKUNotFound),
+                   verify(Usage::TlsServer,
+                          VerificationTimes::signed_at(signed, now)));
+        assert_eq!(EKU_CODE_SIGNING, Usage::CodeSigning.key_purpose_id());
+    }
+
+    #[cfg(all(feature = "trust_anchor_util", feature = "ecdsa"))]
+    #[test]
+    fn test_smime() {
+        let anchors = &[trust_anchor_util::cert_der_as_trust_anchor(
+            include_bytes!("../tests/data/smime-root.der")).unwrap()];
+        let ee = EndEntityCert::from(
+            include_bytes!("../tests/data/smime-ee.der")).unwrap();
+        let policy = Policy::default();
+        let sig_algs = &[&ECDSA_P256_SHA256];
+        let times = VerificationTimes::at(
+            Time::from_ymdhms_utc(2027, 1, 1, 0, 0, 0).unwrap());
+        let options = &VerificationOptions::new();
+
+        assert!(ee.verify_is_valid_smime_cert(&policy, sig_algs, anchors, &[],
+                                              times, options,
+                                              b"alice@example.com")
+                  .is_ok());
+        // The domain is compared case-insensitively, but not the local part.
+        assert!(ee.verify_is_valid_smime_cert(&policy, sig_algs, anchors, &[],
+                                              times, options,
+                                              b"alice@EXAMPLE.com")
+                  .is_ok());
+        assert_eq!(Some(Error::CertNotValidForName),
+                   ee.verify_is_valid_smime_cert(
+                       &policy, sig_algs, anchors, &[], times, options,
+                       b"Alice@example.com").err());
+        assert_eq!(Some(Error::CertNotValidForName),
+                   ee.verify_is_valid_smime_cert(
+                       &policy, sig_algs, anchors, &[], times, options,
+                       b"bob@example.com").err());
+
+        // The certificate isn't valid for TLS, and a TLS server certificate
+        // isn't valid for S/MIME.
+        assert_eq!(Some(Error::RequiredEKUNotFound),
+                   ee.verify_is_valid_tls_server_cert(
+                       sig_algs, anchors, &[], times.validity_time).err());
+        let tls_anchors = &[trust_anchor_util::cert_der_as_trust_anchor(
+            include_bytes!("../tests/data/ecdsa-root.der")).unwrap()];
+        let tls_ee = EndEntityCert::from(
+            include_bytes!("../tests/data/ecdsa-ee.der")).unwrap();
+        assert_eq!(Some(Error::RequiredEKUNotFound),
+                   tls_ee.verify_is_valid_smime_cert(
+                       &policy, sig_algs, tls_anchors,
+                       &[include_bytes!(
+                           "../tests/data/ecdsa-intermediate.der")],
+                       times, options, b"alice@example.com").err());
+
+        assert_eq!(EKU_EMAIL_PROTECTION,
+                   Usage::EmailProtection.key_purpose_id());
+    }
+
+    #[test]
+    fn test_cert_der_len() {
+        use std::vec::Vec;
+
+        let cert: &[u8] = include_bytes!("../tests/data/rsa-ee.der");
+
+        // Receive the certificate in fragments of three bytes.
+        let mut received = Vec::new();
+        let mut len = None;
+        for fragment in cert.chunks(3) {
+            received.extend_from_slice(fragment);
+            if len.is_none() {
+                len = cert_der_len(&received).unwrap();
+                assert!(len.is_some() ||
+                        received.len() < CERT_DER_LEN_PREFIX_LEN);
+            }
+        }
+        assert_eq!(Some(cert.len()), len);
+        assert!(EndEntityCert::from(&received[..cert.len()]).is_ok());
+
+        assert_eq!(Err(Error::BadDER), cert_der_len(&cert[1..]));
+    }
+
+    #[test]
+    fn test_validate_structure() {
+        use core::time::Duration;
+
+        // The certificate is valid from 2026 to 2036, but that doesn't
+        // matter.
+        let ee = EndEntityCert::from(
+            include_bytes!("../tests/data/ecdsa-ee.der")).unwrap();
+        assert_eq!(Ok(()), ee.validate_structure());
+        assert_eq!(Err(Error::CertExpired),
+                   ee.verify_validity(
+                       Time::from_ymdhms_utc(2100, 1, 1, 0, 0, 0).unwrap()));
+
+        let policy = Policy {
+            max_end_entity_validity:
+                Some(Duration::from_secs(398 * 24 * 60 * 60)),
+            ..Policy::default()
+        };
+        assert_eq!(Err(Error::ValidityPeriodTooLong),
+                   ee.validate_structure_with_policy(&policy));
+
+        let ca = EndEntityCert::from(
+            include_bytes!("../tests/data/ecdsa-intermediate.der")).unwrap();
+        assert_eq!(Err(Error::CAUsedAsEndEntity), ca.validate_structure());
+    }
+
+    #[cfg(feature = "ecdsa")]
+    #[test]
+    fn test_preflight_chain() {
+        let ee = EndEntityCert::from(
+            include_bytes!("../tests/data/ecdsa-ee.der")).unwrap();
+        let intermediate: &[u8] =
+            include_bytes!("../tests/data/ecdsa-intermediate.der");
+        let policy = Policy::default();
+        let valid = Time::from_ymdhms_utc(2027, 1, 1, 0, 0, 0).unwrap();
+        let expired = Time::from_ymdhms_utc(2100, 1, 1, 0, 0, 0).unwrap();
+
+        assert_eq!(Ok(()),
+                   ee.preflight_chain(&policy, &[&ECDSA_P256_SHA256],
+                                      &[intermediate], valid));
+        assert_eq!(Err(Error::CertExpired),
+                   ee.preflight_chain(&policy, &[&ECDSA_P256_SHA256],
+                                      &[intermediate], expired));
+        assert_eq!(Err(Error::UnsupportedSignatureAlgorithm),
+                   ee.preflight_chain(&policy, &[&ECDSA_P384_SHA384],
+                                      &[intermediate], valid));
+        assert_eq!(Err(Error::BadDER),
+                   ee.preflight_chain(&policy, &[&ECDSA_P256_SHA256],
+                                      &[&intermediate[1..]], valid));
+        assert_eq!(Err(Error::EndEntityUsedAsCA),
+                   ee.preflight_chain(
+                       &policy, &[&ECDSA_P256_SHA256],
+                       &[include_bytes!("../tests/data/ecdsa-ee.der")],
+                       valid));
+    }
+
+    #[cfg(all(feature = "trust_anchor_util", feature = "ecdsa"))]
+    #[test]
+    fn test_valid_until() {
+        // The intermediate certificate expires a day before the end-entity
+        // certificate, and the root much later.
+        let root: &[u8] = include_bytes!("../tests/data/cross-old-root.der");
+        let intermediate: &[u8] = include_bytes!(
+            "../tests/data/cross-intermediate-by-old-root.der");
+        let ee = EndEntityCert::from(
+            include_bytes!("../tests/data/cross-ee.der")).unwrap();
+        let anchors =
+            &[trust_anchor_util::cert_der_as_trust_anchor(root).unwrap()];
+        let time = Time::from_ymdhms_utc(2027, 1, 1, 0, 0, 0).unwrap();
+        let not_after = Time::from_ymdhms_utc(2036, 10, 12, 0, 0, 0).unwrap();
+
+        let path = ee.verify_is_valid_tls_server_cert(
+            &[&ECDSA_P256_SHA256], anchors, &[intermediate], time).unwrap();
+        assert_eq!(not_after, path.valid_until());
+        assert_eq!(not_after.checked_duration_since(time),
+                   Some(path.remaining_validity()));
+
+        let path = ee.verify_is_valid_tls_server_cert(
+            &[&ECDSA_P256_SHA256], anchors, &[intermediate], not_after)
+            .unwrap();
+        assert_eq!(0, path.remaining_validity().as_secs());
+    }
+
+    #[cfg(all(feature = "trust_anchor_util", feature = "ecdsa"))]
+    #[test]
+    fn test_revocation_filter_time() {
+        // Revokes every certificate from the given time on.
+        struct RevokedFrom(Time);
+
+        impl revocation::RevocationFilter for RevokedFrom {
+            fn is_revoked(&self, _: &[u8], _: &[u8], time: Time) -> bool {
+                time >= self.0
+            }
+        }
+
+        let root: &[u8] = include_bytes!("../tests/data/cross-old-root.der");
+        let intermediate: &[u8] = include_bytes!(
+            "../tests/data/cross-intermediate-by-old-root.der");
+        let ee = EndEntityCert::from(
+            include_bytes!("../tests/data/cross-ee.der")).unwrap();
+        let anchors =
+            &[trust_anchor_util::cert_der_as_trust_anchor(root).unwrap()];
+        let before = Time::from_ymdhms_utc(2027, 1, 1, 0, 0, 0).unwrap();
+        let after = Time::from_ymdhms_utc(2029, 1, 1, 0, 0, 0).unwrap();
+        let filter =
+            RevokedFrom(Time::from_ymdhms_utc(2028, 1, 1, 0, 0, 0).unwrap());
+        let verify = |times| {
+            ee.verify_is_valid_tls_server_cert_with_options(
+                &Policy::default(), &[&ECDSA_P256_SHA256], anchors,
+                &[intermediate], times,
+                &VerificationOptions::new().revocation_filter(&filter))
+              .map(|_| ())
+        };
+
+        assert_eq!(Ok(()), verify(VerificationTimes::at(before)));
+        assert_eq!(Err(Error::CertRevoked),
+                   verify(VerificationTimes::at(after)));
+
+        // The filter is asked about the current time, not the time at which
+        // the validity periods are checked.
+        assert_eq!(Err(Error::CertRevoked),
+                   verify(VerificationTimes::signed_at(before, after)));
+        assert_eq!(Ok(()), verify(VerificationTimes::signed_at(after, before)));
+    }
+
+    // The intermediate certificate is cross-signed by both roots.
+    #[cfg(all(feature = "trust_anchor_util", feature = "ecdsa"))]
+    #[test]
+    fn test_prefer_earlier_trust_anchors() {
+        let root: &[u8] = include_bytes!("../tests/data/ecdsa-root.der");
+        let old_root: &[u8] =
+            include_bytes!("../tests/data/cross-old-root.der");
+        let by_root: &[u8] = include_bytes!(
+            "../tests/data/cross-intermediate-by-ecdsa-root.der");
+        let by_old_root: &[u8] = include_bytes!(
+            "../tests/data/cross-intermediate-by-old-root.der");
+        let ee = EndEntityCert::from(
+            include_bytes!("../tests/data/cross-ee.der")).unwrap();
+        let anchors = &[
+            trust_anchor_util::cert_der_as_trust_anchor(root).unwrap(),
+            trust_anchor_util::cert_der_as_trust_anchor(old_root).unwrap(),
+        ];
+        let set = TrustAnchorSet::new(anchors);
+        let times = VerificationTimes::at(
+            Time::from_ymdhms_utc(2027, 1, 1, 0, 0, 0).unwrap());
+        let verify = |options: &VerificationOptions| {
+            ee.verify_is_valid_tls_server_cert_with_options(
+                &Policy::default(), &[&ECDSA_P256_SHA256],
+                set.trust_anchors(), &[by_old_root, by_root], times, options)
+              .map(|path| path.trust_anchor_index())
+        };
+
+        assert_eq!(Ok(1), verify(&VerificationOptions::new()));
+        let counter = StatsCounter::new();
+        assert_eq!(Ok(0),
+                   verify(&VerificationOptions::new()
+                               .prefer_earlier_trust_anchors()
+                               .observer(&counter)));
+        // Both searches are observed.
+        assert_eq!(4, counter.stats().signatures_verified);
+    }
+
+    #[cfg(all(feature = "trust_anchor_util", feature = "ecdsa"))]
+    #[test]
+    fn test_ignore_ca_eku() {
+        let root: &[u8] =
+            include_bytes!("../tests/data/client-eku-root.der");
+        let intermediate: &[u8] =
+            include_bytes!("../tests/data/client-eku-intermediate.der");
+        let ee = EndEntityCert::from(
+            include_bytes!("../tests/data/client-eku-ee.der")).unwrap();
+        let anchors =
+            &[trust_anchor_util::cert_der_as_trust_anchor(root).unwrap()];
+        let time = Time::from_ymdhms_utc(2027, 1, 1, 0, 0, 0).unwrap();
+        let times = VerificationTimes::at(time);
+        let sig_algs = &[&ECDSA_P256_SHA256];
+
+        // The intermediate only allows clientAuth.
+        let report = verify_with_report(&ee, &Policy::default(), sig_algs,
+                                        anchors, &[intermediate], times);
+        assert_eq!(Err(Error::RequiredEKUNotFound), report.result);
+        assert!(report.entries.iter().any(|entry| match *entry {
+            ReportEntry::CertChecked {
+                depth: 1, result: Err(Error::RequiredEKUNotFound), ..
+            } => true,
+            _ => false,
+        }));
+
+        let ignore_ca_eku = Policy { ignore_ca_eku: true, ..Policy::default() };
+        let options = &VerificationOptions::new();
+        assert!(ee.verify_is_valid_tls_server_cert_with_options(
+                    &ignore_ca_eku, sig_algs, anchors, &[intermediate], times,
+                    options)
+                  .is_ok());
+
+        // The end-entity certificate only allows serverAuth.
+        assert_eq!(Err(Error::RequiredEKUNotFound),
+                   ee.verify_for_usage(
+                       &ignore_ca_eku, Usage::TlsClient, sig_algs,
+                       &TrustDomains::new(&[(Usage::TlsClient, anchors)]),
+                       &[intermediate], times, options));
+    }
+
+    #[cfg(all(feature = "trust_anchor_util", feature = "ecdsa"))]
+    #[test]
+    fn test_report_recorder_in_scope() {
+        let root: &[u8] =
+            include_bytes!("../tests/data/client-eku-root.der");
+        let intermediate: &[u8] =
+            include_bytes!("../tests/data/client-eku-intermediate.der");
+        let ee = EndEntityCert::from(
+            include_bytes!("../tests/data/client-eku-ee.der")).unwrap();
+        let anchors =
+            &[trust_anchor_util::cert_der_as_trust_anchor(root).unwrap()];
+        let time = Time::from_ymdhms_utc(2027, 1, 1, 0, 0, 0).unwrap();
+        let times = VerificationTimes::at(time);
+        let sig_algs = &[&ECDSA_P256_SHA256];
+        let report = verify_with_report(&ee, &Policy::default(), sig_algs,
+                                        anchors, &[intermediate], times);
+
+        let placeholder = || ReportEntry::IssuerNotFound {
+            depth: 0,
+            issuer: std::vec::Vec::new(),
+        };
+        let mut storage = [placeholder(), placeholder(), placeholder(),
+                           placeholder(), placeholder(), placeholder()];
+        for capacity in 0..(storage.len() + 1) {
+            let recorder = ReportRecorder::in_scope(
+                BumpArena::new(&mut storage[..capacity]));
+            assert_eq!(report.result,
+                       ee.verify_is_valid_tls_server_cert_with_options(
+                           &Policy::default(), sig_algs, anchors,
+                           &[intermediate], times,
+                           &VerificationOptions::new().observer(&recorder)));
+            let recorded = capacity.min(report.entries.len());
+            assert_eq!(&report.entries[..recorded], &recorder.entries()[..]);
+            assert_eq!(capacity < report.entries.len(),
+                       recorder.is_truncated());
+        }
+    }
+
+    #[test]
+    fn test_tls_alpn_01_cert() {
+        let cert = EndEntityCert::from(
+            include_bytes!("../tests/data/acme-tls-alpn-01.der")).unwrap();
+        let digest = digest::digest(&digest::SHA256,
+                                    b"webpki test key authorization");
+        let digest = digest.as_ref();
+
+        assert_eq!(Ok(()),
+                   cert.verify_is_valid_tls_alpn_01_cert(
+                       &ServerName::DnsName(b"www.example.com"), digest));
+        assert_eq!(Ok(()),
+                   cert.verify_is_valid_tls_alpn_01_cert(
+                       &ServerName::DnsName(b"WWW.Example.COM"), digest));
+        assert_eq!(Err(Error::InvalidAcmeIdentifier),
+                   cert.verify_is_valid_tls_alpn_01_cert(
+                       &ServerName::DnsName(b"www.example.com"),
+                       &[0; 32]));
+        assert_eq!(Err(Error::CertNotValidForName),
+                   cert.verify_is_valid_tls_alpn_01_cert(
+                       &ServerName::DnsName(b"example.com"), digest));
+        assert_eq!(Err(Error::CertNotValidForName),
+                   cert.verify_is_valid_tls_alpn_01_cert(
+                       &ServerName::IpAddress(&[192, 0, 2, 1]), digest));
+        assert_eq!(Err(Error::InvalidReferenceName),
+                   cert.verify_is_valid_tls_alpn_01_cert(
+                       &ServerName::DnsName(b"*.example.com"), digest));
+
+        // An ordinary certificate has no id-pe-acmeIdentifier extension.
+        let ee = EndEntityCert::from(
+            include_bytes!("../tests/data/ecdsa-ee.der")).unwrap();
+        assert_eq!(Err(Error::InvalidAcmeIdentifier),
+                   ee.verify_is_valid_tls_alpn_01_cert(
+                       &ServerName::DnsName(b"www.example.com"), digest));
+    }
+
+    #[test]
+    fn test_is_technically_constrained() {
+        fn is_technically_constrained(cert_der: &[u8]) -> bool {
+            CaCert::from(cert_der).unwrap().is_technically_constrained()
+                .unwrap()
+        }
+
+        // No EKU extension.
+        assert!(!is_technically_constrained(
+            include_bytes!("../tests/data/ecdsa-intermediate.der")));
+
+        // Only clientAuth.
+        assert!(is_technically_constrained(
+            include_bytes!("../tests/data/client-eku-intermediate.der")));
+
+        // serverAuth, with only example.com permitted and all IP addresses
+        // excluded.
+        assert!(is_technically_constrained(
+            include_bytes!("../tests/data/constrained-intermediate.der")));
+
+        // Likewise, but without the IP address exclusions.
+        assert!(!is_technically_constrained(
+            include_bytes!(
+                "../tests/data/constrained-dns-intermediate.der")));
+    }
+
+    #[cfg(all(feature = "trust_anchor_util", feature = "ecdsa"))]
+    #[test]
+    fn test_no_issuer() {
+        let root: &[u8] = include_bytes!("../tests/data/ecdsa-root.der");
+        let intermediate: &[u8] =
+            include_bytes!("../tests/data/ecdsa-intermediate.der");
+        let ee = EndEntityCert::from(
+            include_bytes!("../tests/data/ecdsa-ee.der")).unwrap();
+        let time = Time::from_ymdhms_utc(2027, 1, 1, 0, 0, 0).unwrap();
+        let times = VerificationTimes::at(time);
+        let policy = Policy::default();
+        let sig_algs = &[&ECDSA_P256_SHA256];
+        let root_anchors =
+            &[trust_anchor_util::cert_der_as_trust_anchor(root).unwrap()];
+
+        let options = &VerificationOptions::new();
+        assert_eq!(Err(Error::NoTrustAnchors),
+                   ee.verify_is_valid_tls_server_cert_with_options(
+                       &policy, sig_algs, &[], &[intermediate], times,
+                       options));
+
+        // The certificates are still checked.
+        let expired = VerificationTimes::at(
+            Time::from_ymdhms_utc(2100, 1, 1, 0, 0, 0).unwrap());
+        assert_eq!(Err(Error::CertExpired),
+                   ee.verify_is_valid_tls_server_cert_with_options(
+                       &policy, sig_algs, &[], &[intermediate], expired,
+                       options));
+
+        // The intermediate certificate is missing.
+        let report = verify_with_report(&ee, &policy, sig_algs, root_anchors,
+                                        &[], times);
+        assert_eq!(Err(Error::UnknownIssuer), report.result);
+        assert_eq!(Some(&ReportEntry::IssuerNotFound {
+                       depth: 0,
+                       issuer: ee.inner.issuer.as_slice_less_safe().to_vec(),
+                   }),
+                   report.entries.last());
+
+        let report = verify_with_report(&ee, &policy, sig_algs, root_anchors,
+                                        &[intermediate], times);
+        assert!(report.result.is_ok());
+        assert!(!report.entries.iter().any(|entry| match *entry {
+            ReportEntry::IssuerNotFound { .. } => true,
+            _ => false,
+        }));
+    }
+
+    // A candidate issuer whose key doesn't verify the signature, here a stale
+    // intermediate with the same subject and subjectKeyIdentifier as the real
+    // one, must not stop the real issuer from being tried.
+    #[cfg(all(feature = "trust_anchor_util", feature = "ecdsa"))]
+    #[test]
+    fn test_colliding_subject_intermediate() {
+        let root: &[u8] = include_bytes!("../tests/data/ecdsa-root.der");
+        let intermediate: &[u8] =
+            include_bytes!("../tests/data/ecdsa-intermediate.der");
+        let stale_root: &[u8] =
+            include_bytes!("../tests/data/collision-root.der");
+        let stale_intermediate: &[u8] =
+            include_bytes!("../tests/data/collision-intermediate.der");
+        let ee = EndEntityCert::from(
+            include_bytes!("../tests/data/ecdsa-ee.der")).unwrap();
+        let time = Time::from_ymdhms_utc(2027, 1, 1, 0, 0, 0).unwrap();
+        let times = VerificationTimes::at(time);
+        let policy = Policy::default();
+        let sig_algs = &[&ECDSA_P256_SHA256];
+        let anchors = &[
+            trust_anchor_util::cert_der_as_trust_anchor(stale_root).unwrap(),
+            trust_anchor_util::cert_der_as_trust_anchor(root).unwrap(),
+        ];
+
+        let report = verify_with_report(
+            &ee, &policy, sig_algs, anchors,
+            &[stale_intermediate, intermediate], times);
+        assert_eq!(Some(1), report.result.map(|path| path.trust_anchor_index())
+                                         .ok());
+        assert!(report.entries.contains(&ReportEntry::TrustAnchorTried {
+            depth: 1,
+            trust_anchor_index: 0,
+            result: Err(Error::InvalidSignatureForPublicKey),
+        }));
+
+        let options = &VerificationOptions::new();
+        assert_eq!(Ok(1), ee.verify_is_valid_tls_server_cert_with_options(
+                              &policy, sig_algs, anchors,
+                              &[intermediate, stale_intermediate], times,
+                              options)
+                            .map(|path| path.trust_anchor_index()));
+        assert_eq!(Err(Error::InvalidSignatureForPublicKey),
+                   ee.verify_is_valid_tls_server_cert_with_options(
+                       &policy, sig_algs, anchors, &[stale_intermediate],
+                       times, options));
+    }
+
+    // Likewise, an expired copy of a renewed intermediate, with the same
+    // subject and key as the new one, must not stop the new one from being
+    // tried, whichever order they are sent in.
+    #[cfg(all(feature = "trust_anchor_util", feature = "ecdsa"))]
+    #[test]
+    fn test_renewed_intermediate() {
+        let root: &[u8] = include_bytes!("../tests/data/renewed-root.der");
+        let intermediate: &[u8] =
+            include_bytes!("../tests/data/renewed-intermediate.der");
+        let expired_intermediate: &[u8] = include_bytes!(
+            "../tests/data/renewed-intermediate-expired.der");
+        let ee = EndEntityCert::from(
+            include_bytes!("../tests/data/renewed-ee.der")).unwrap();
+        let time = Time::from_ymdhms_utc(2027, 1, 1, 0, 0, 0).unwrap();
+        let times = VerificationTimes::at(time);
+        let policy = Policy::default();
+        let sig_algs = &[&ECDSA_P256_SHA256];
+        let anchors =
+            &[trust_anchor_util::cert_der_as_trust_anchor(root).unwrap()];
+
+        let report = verify_with_report(
+            &ee, &policy, sig_algs, anchors,
+            &[expired_intermediate, intermediate], times);
+        assert!(report.result.is_ok());
+        assert!(report.entries.contains(&ReportEntry::IntermediateTried {
+            depth: 0,
+            intermediate_index: 0,
+            result: Err(Error::CertExpired),
+        }));
+
+        let options = &VerificationOptions::new();
+        assert!(ee.verify_is_valid_tls_server_cert_with_options(
+                    &policy, sig_algs, anchors,
+                    &[intermediate, expired_intermediate], times, options)
+                  .is_ok());
+        assert_eq!(Err(Error::CertExpired),
+                   ee.verify_is_valid_tls_server_cert_with_options(
+                       &policy, sig_algs, anchors, &[expired_intermediate],
+                       times, options));
+    }
+
+    #[cfg(all(feature = "trust_anchor_util", feature = "ecdsa"))]
+    #[test]
+    fn test_max_signature_verifications() {
+        let root: &[u8] = include_bytes!("../tests/data/ecdsa-root.der");
+        let intermediate: &[u8] =
+            include_bytes!("../tests/data/ecdsa-intermediate.der");
+        let stale_root: &[u8] =
+            include_bytes!("../tests/data/collision-root.der");
+        let stale_intermediate: &[u8] =
+            include_bytes!("../tests/data/collision-intermediate.der");
+        let ee = EndEntityCert::from(
+            include_bytes!("../tests/data/ecdsa-ee.der")).unwrap();
+        let time = Time::from_ymdhms_utc(2027, 1, 1, 0, 0, 0).unwrap();
+        let times = VerificationTimes::at(time);
+        let sig_algs = &[&ECDSA_P256_SHA256];
+        let anchors = &[
+            trust_anchor_util::cert_der_as_trust_anchor(stale_root).unwrap(),
+            trust_anchor_util::cert_der_as_trust_anchor(root).unwrap(),
+        ];
+        let intermediates = &[stale_intermediate, intermediate];
+
+        // Two signatures for the path through the stale intermediate, whose
+        // key doesn't verify the end-entity's signature, and two for the
+        // valid path.
+        let (result, stats) = verify_with_stats(
+            &ee, &Policy::default(), sig_algs, anchors, intermediates, times);
+        assert!(result.is_ok());
+        assert_eq!(4, stats.signatures_verified);
+
+        let policy = Policy {
+            max_signature_verifications: 4,
+            ..Policy::default()
+        };
+        assert!(ee.verify_is_valid_tls_server_cert_with_options(
+                    &policy, sig_algs, anchors, intermediates, times,
+                    &VerificationOptions::new())
+                  .is_ok());
+
+        let policy = Policy {
+            max_signature_verifications: 3,
+            ..Policy::default()
+        };
+        let (result, stats) = verify_with_stats(
+            &ee, &policy, sig_algs, anchors, intermediates, times);
+        assert_eq!(Err(Error::TooManySignatureVerifications), result);
+        assert_eq!(3, stats.signatures_verified);
+    }
+
+    // A signature that candidate paths share is only verified, and counted,
+    // once. The path through the old, revoked, certificate for the last of
+    // six intermediates is tried first; the valid path shares its upper five
+    // signatures, so it is found within the default limit even though it is
+    // as long as a path can be.
+    #[cfg(all(feature = "trust_anchor_util", feature = "ecdsa"))]
+    #[test]
+    fn test_max_signature_verifications_full_depth() {
+        // Revokes the certificates with the given serial number.
+        struct RevokedSerial<'a>(&'a [u8]);
+
+        impl<'a> revocation::RevocationFilter for RevokedSerial<'a> {
+            fn is_revoked(&self, _: &[u8], serial_number: &[u8], _: Time)
+                          -> bool {
+                serial_number == self.0
+            }
+        }
+
+        let root: &[u8] = include_bytes!("../tests/data/ecdsa-root.der");
+        let old_intermediate: &[u8] =
+            include_bytes!("../tests/data/deep-intermediate-6-old.der");
+        let intermediates: &[&[u8]] = &[
+            include_bytes!("../tests/data/deep-intermediate-1.der"),
+            include_bytes!("../tests/data/deep-intermediate-2.der"),
+            include_bytes!("../tests/data/deep-intermediate-3.der"),
+            include_bytes!("../tests/data/deep-intermediate-4.der"),
+            include_bytes!("../tests/data/deep-intermediate-5.der"),
+            old_intermediate,
+            include_bytes!("../tests/data/deep-intermediate-6.der"),
+        ];
+        let ee = EndEntityCert::from(
+            include_bytes!("../tests/data/deep-6-ee.der")).unwrap();
+        let anchors =
+            &[trust_anchor_util::cert_der_as_trust_anchor(root).unwrap()];
+        let time = Time::from_ymdhms_utc(2027, 1, 1, 0, 0, 0).unwrap();
+        let old_serial =
+            EndEntityCert::from(old_intermediate).unwrap().serial_number();
+        let filter = RevokedSerial(old_serial.as_slice());
+        let counter = StatsCounter::new();
+
+        let path = ee.verify_is_valid_tls_server_cert_with_options(
+            &Policy::default(), &[&ECDSA_P256_SHA256], anchors, intermediates,
+            VerificationTimes::at(time),
+            &VerificationOptions::new().revocation_filter(&filter)
+                                       .observer(&counter)).unwrap();
+        assert_eq!(&[6, 4, 3, 2, 1, 0], path.intermediate_indices());
+        // Six signatures down to the old intermediate, which is revoked, and
+        // then two for the new one and the end-entity.
+        assert_eq!(6 + 2, counter.stats().signatures_verified);
+    }
+
+    // Copies of an intermediate certificate are neither parsed nor tried.
+    #[cfg(all(feature = "trust_anchor_util", feature = "ecdsa"))]
+    #[test]
+    fn test_duplicate_intermediates() {
+        let root: &[u8] = include_bytes!("../tests/data/ecdsa-root.der");
+        let intermediate: &[u8] =
+            include_bytes!("../tests/data/ecdsa-intermediate.der");
+        let stale_intermediate: &[u8] =
+            include_bytes!("../tests/data/collision-intermediate.der");
+        let ee = EndEntityCert::from(
+            include_bytes!("../tests/data/ecdsa-ee.der")).unwrap();
+        let time = Time::from_ymdhms_utc(2027, 1, 1, 0, 0, 0).unwrap();
+        let anchors =
+            &[trust_anchor_util::cert_der_as_trust_anchor(root).unwrap()];
+        let mut intermediates = [stale_intermediate; 64];
+        intermediates[63] = intermediate;
+
+        let (result, stats) = verify_with_stats(
+            &ee, &Policy::default(), &[&ECDSA_P256_SHA256], anchors,
+            &intermediates, VerificationTimes::at(time));
+        assert!(result.is_ok());
+        // The two distinct intermediates are parsed as potential issuers of
+        // the end-entity and then of the intermediate.
+        assert_eq!(2 * 2, stats.certs_parsed);
+        assert_eq!(2, stats.intermediates_tried);
+    }
+
+    // Likewise, a trust anchor with the same subject as the real one and a
+    // different key must not stop the real one from being tried.
+    #[cfg(all(feature = "trust_anchor_util", feature = "ecdsa"))]
+    #[test]
+    fn test_colliding_subject_trust_anchor() {
+        let root: &[u8] = include_bytes!("../tests/data/ecdsa-root.der");
+        let intermediate: &[u8] =
+            include_bytes!("../tests/data/ecdsa-intermediate.der");
+        let stale_root: &[u8] =
+            include_bytes!("../tests/data/collision-root.der");
+        let ee = EndEntityCert::from(
+            include_bytes!("../tests/data/ecdsa-ee.der")).unwrap();
+        let time = Time::from_ymdhms_utc(2027, 1, 1, 0, 0, 0).unwrap();
+        let root_anchor =
+            trust_anchor_util::cert_der_as_trust_anchor(root).unwrap();
+        let anchors = &[
+            TrustAnchor {
+                spki: trust_anchor_util::cert_der_as_trust_anchor(stale_root)
+                          .unwrap().spki,
+                ..root_anchor
+            },
+            root_anchor,
+        ];
+
+        let path = ee.verify_is_valid_tls_server_cert(
+            &[&ECDSA_P256_SHA256], anchors, &[intermediate], time).unwrap();
+        assert_eq!(1, path.trust_anchor_index());
+    }
+
+    // A verified path is still anchored in an updated set of trust anchors
+    // only if the set contains the same trust anchor, wherever it is in the
+    // set.
+    #[cfg(all(feature = "trust_anchor_util", feature = "ecdsa"))]
+    #[test]
+    fn test_still_anchored_in() {
+        let root: &[u8] = include_bytes!("../tests/data/ecdsa-root.der");
+        let intermediate: &[u8] =
+            include_bytes!("../tests/data/ecdsa-intermediate.der");
+        let other_root: &[u8] =
+            include_bytes!("../tests/data/collision-root.der");
+        let ee = EndEntityCert::from(
+            include_bytes!("../tests/data/ecdsa-ee.der")).unwrap();
+        let time = Time::from_ymdhms_utc(2027, 1, 1, 0, 0, 0).unwrap();
+        let root_anchor =
+            trust_anchor_util::cert_der_as_trust_anchor(root).unwrap();
+        let other_anchor =
+            trust_anchor_util::cert_der_as_trust_anchor(other_root).unwrap();
+
+        let path = ee.verify_is_valid_tls_server_cert(
+            &[&ECDSA_P256_SHA256], &[root_anchor], &[intermediate],
+            time).unwrap();
+
+        let root_anchor =
+            trust_anchor_util::cert_der_as_trust_anchor(root).unwrap();
+        const PERMITTED_EXAMPLE_COM: &'static [u8] = &[
+            0xa0, 0x0f, 0x30, 0x0d, 0x82, 0x0b,
+            b'e', b'x', b'a', b'm', b'p', b'l', b'e', b'.', b'c', b'o', b'm',
+        ];
+        let constrained_anchor = TrustAnchor {
+            subject: root_anchor.subject,
+            spki: root_anchor.spki,
+            name_constraints: Some(PERMITTED_EXAMPLE_COM),
+        };
+        let anchors = &[other_anchor, root_anchor];
+
+        assert!(path.still_anchored_in(&TrustAnchorSet::new(anchors)));
+
+        // The root was removed.
+        assert!(!path.still_anchored_in(&TrustAnchorSet::new(&anchors[..1])));
+        assert!(!path.still_anchored_in(&TrustAnchorSet::new(&[])));
+
+        // The root was constrained.
+        assert!(!path.still_anchored_in(
+                    &TrustAnchorSet::new(&[constrained_anchor])));
+    }
+
+    #[cfg(all(feature = "trust_anchor_util", feature = "ecdsa"))]
+    #[test]
+    fn test_intermediate_indices() {
+        let root: &[u8] = include_bytes!("../tests/data/ecdsa-root.der");
+        let ee_der: &[u8] = include_bytes!("../tests/data/deep-ee.der");
+        let ee = EndEntityCert::from(ee_der).unwrap();
+        let anchors =
+            &[trust_anchor_util::cert_der_as_trust_anchor(root).unwrap()];
+        let time = Time::from_ymdhms_utc(2027, 1, 1, 0, 0, 0).unwrap();
+
+        // The root issued intermediate 1, which issued intermediate 2, and so
+        // on; intermediate 5 issued the end-entity certificate.
+        let intermediates: &[&[u8]] = &[
+            include_bytes!("../tests/data/deep-intermediate-3.der"),
+            include_bytes!("../tests/data/ecdsa-intermediate.der"),
+            include_bytes!("../tests/data/deep-intermediate-1.der"),
+            include_bytes!("../tests/data/deep-intermediate-5.der"),
+            include_bytes!("../tests/data/deep-intermediate-2.der"),
+            include_bytes!("../tests/data/deep-intermediate-4.der"),
+        ];
+        let path = ee.verify_is_valid_tls_server_cert(
+            &[&ECDSA_P256_SHA256], anchors, intermediates, time).unwrap();
+        assert_eq!(&[3, 5, 0, 4, 2], path.intermediate_indices());
+        assert_eq!(ee_der, ee.cert_der());
+
+        // The trust anchor issued the certificate directly.
+        let ca = CaCert::from(intermediates[1]).unwrap();
+        let path = ca.verify_is_valid_ca_cert(&[&ECDSA_P256_SHA256], anchors,
+                                              intermediates, time).unwrap();
+        assert!(path.intermediate_indices().is_empty());
+    }
+
+    #[cfg(all(feature = "ecdsa", feature = "rsa"))]
+    #[test]
+    fn test_compatible_signature_algorithms() {
+        use std::vec::Vec;
+
+        let all: &[&SignatureAlgorithm] = &[
+            &RSA_PKCS1_2048_8192_SHA256,
+            &ECDSA_P384_SHA384,
+            &ECDSA_P256_SHA256,
+            &RSA_PKCS1_3072_8192_SHA384,
+            &ECDSA_P256_SHA384,
+        ];
+
+        let ecdsa_p256_ee = EndEntityCert::from(
+            include_bytes!("../tests/data/ecdsa-ee.der")).unwrap();
+        let compatible = ecdsa_p256_ee.compatible_signature_algorithms(all)
+                                      .collect::<Vec<_>>();
+        assert_eq!(&[&ECDSA_P256_SHA256, &ECDSA_P256_SHA384], &compatible[..]);
+
+        let rsa_ee = EndEntityCert::from(
+            include_bytes!("../tests/data/rsa-ee.der")).unwrap();
+        let compatible = rsa_ee.compatible_signature_algorithms(all)
+                               .collect::<Vec<_>>();
+        assert_eq!(&[&RSA_PKCS1_2048_8192_SHA256, &RSA_PKCS1_3072_8192_SHA384],
+                   &compatible[..]);
+        assert_eq!(0, rsa_ee.compatible_signature_algorithms(&all[1..3])
+                            .count());
+    }
+
+    // When every candidate path fails, the error of the result doesn't
+    // depend on the order of the trust anchors.
+    #[cfg(all(feature = "trust_anchor_util", feature = "ecdsa"))]
+    #[test]
+    fn test_error_precedence() {
+        let root: &[u8] = include_bytes!("../tests/data/ecdsa-root.der");
+        let intermediate: &[u8] =
+            include_bytes!("../tests/data/ecdsa-intermediate.der");
+        let stale_root: &[u8] =
+            include_bytes!("../tests/data/collision-root.der");
+        let ee = EndEntityCert::from(
+            include_bytes!("../tests/data/ecdsa-ee.der")).unwrap();
+        let time = Time::from_ymdhms_utc(2027, 1, 1, 0, 0, 0).unwrap();
+        let times = VerificationTimes::at(time);
+        let policy = Policy::default();
+        let sig_algs = &[&ECDSA_P256_SHA256];
+
+        // Only www.example.org is permitted.
+        const PERMITTED_EXAMPLE_ORG: &'static [u8] = &[
+            0xa0, 0x13, 0x30, 0x11, 0x82, 0x0f,
+            b'w', b'w', b'w', b'.', b'e', b'x', b'a', b'm', b'p', b'l', b'e',
+            b'.', b'o', b'r', b'g',
+        ];
+        let root_anchor = trust_anchor_util::cert_der_as_trust_anchor(root)
+            .unwrap();
+        let constrained_anchor = || TrustAnchor {
+            subject: root_anchor.subject,
+            spki: root_anchor.spki,
+            name_constraints: Some(PERMITTED_EXAMPLE_ORG),
+        };
+        // The same subject as the root, but another key.
+        let stale_anchor = || TrustAnchor {
+            subject: root_anchor.subject,
+            spki: trust_anchor_util::cert_der_as_trust_anchor(stale_root)
+                .unwrap().spki,
+            name_constraints: None,
+        };
+
+        for anchors in &[[stale_anchor(), constrained_anchor()],
+                         [constrained_anchor(), stale_anchor()]] {
+            let report = verify_with_report(&ee, &policy, sig_algs, anchors,
+                                            &[intermediate], times);
+            assert_eq!(Err(Error::NameConstraintViolation), report.result);
+            assert_eq!(&[Error::NameConstraintViolation,
+                         Error::InvalidSignatureForPublicKey][..],
+                       &report.rejections()[..]);
+        }
+    }
+
+    // Servers commonly send the root certificate along with the rest of the
+    // chain. The copy of the root must not be tried again as an intermediate
+    // when the trust anchor itself is rejected, and it is flagged in the
+    // report.
+    #[cfg(all(feature = "trust_anchor_util", feature = "ecdsa"))]
+    #[test]
+    fn test_root_in_intermediate_certs() {
+        let root: &[u8] = include_bytes!("../tests/data/ecdsa-root.der");
+        let intermediate: &[u8] =
+            include_bytes!("../tests/data/ecdsa-intermediate.der");
+        let ee = EndEntityCert::from(
+            include_bytes!("../tests/data/ecdsa-ee.der")).unwrap();
+        let time = Time::from_ymdhms_utc(2027, 1, 1, 0, 0, 0).unwrap();
+        let times = VerificationTimes::at(time);
+        let policy = Policy::default();
+        let sig_algs = &[&ECDSA_P256_SHA256];
+
+        let root_anchors =
+            &[trust_anchor_util::cert_der_as_trust_anchor(root).unwrap()];
+        let intermediates = &[root, intermediate];
+
+        let (result, stats) = verify_with_stats(
+            &ee, &policy, sig_algs, root_anchors, intermediates, times);
+        assert!(result.is_ok());
+        assert_eq!(2, stats.signatures_verified);
+
+        // Only www.example.org is permitted, so the trust anchor is rejected.
+        const PERMITTED_EXAMPLE_ORG: &'static [u8] = &[
+            0xa0, 0x13, 0x30, 0x11, 0x82, 0x0f,
+            b'w', b'w', b'w', b'.', b'e', b'x', b'a', b'm', b'p', b'l', b'e',
+            b'.', b'o', b'r', b'g',
+        ];
+        let constrained_anchors = &[TrustAnchor {
+            subject: root_anchors[0].subject,
+            spki: root_anchors[0].spki,
+            name_constraints: Some(PERMITTED_EXAMPLE_ORG),
+        }];
+        let (result, stats) = verify_with_stats(
+            &ee, &policy, sig_algs, constrained_anchors, intermediates, times);
+        assert_eq!(Err(Error::NameConstraintViolation), result);
+        assert_eq!(0, stats.signatures_verified);
+        assert_eq!(1, stats.trust_anchors_tried);
+
+        let report = verify_with_report(&ee, &policy, sig_algs,
+                                        constrained_anchors, intermediates,
+                                        times);
+        assert!(report.entries.contains(
+            &ReportEntry::RedundantTrustAnchor {
+                depth: 1,
+                intermediate_index: 0,
+                trust_anchor_index: 0,
+            }));
+    }
+
+    // A cross-signed copy of a trust anchor isn't redundant: when the trust
+    // anchor itself is rejected, the path through the copy can still end at
+    // the trust anchor that cross-signed it.
+    #[cfg(all(feature = "trust_anchor_util", feature = "ecdsa"))]
+    #[test]
+    fn test_cross_signed_root_in_intermediate_certs() {
+        let root: &[u8] = include_bytes!("../tests/data/ecdsa-root.der");
+        let old_root: &[u8] =
+            include_bytes!("../tests/data/cross-old-root.der");
+        let cross_signed_root: &[u8] =
+            include_bytes!("../tests/data/cross-ecdsa-root-by-old-root.der");
+        let intermediate: &[u8] =
+            include_bytes!("../tests/data/ecdsa-intermediate.der");
+        let ee = EndEntityCert::from(
+            include_bytes!("../tests/data/ecdsa-ee.der")).unwrap();
+        let time = Time::from_ymdhms_utc(2027, 1, 1, 0, 0, 0).unwrap();
+        let times = VerificationTimes::at(time);
+        let policy = Policy::default();
+        let sig_algs = &[&ECDSA_P256_SHA256];
+
+        // Only www.example.org is permitted, so the preferred trust anchor
+        // is rejected.
+        const PERMITTED_EXAMPLE_ORG: &'static [u8] = &[
+            0xa0, 0x13, 0x30, 0x11, 0x82, 0x0f,
+            b'w', b'w', b'w', b'.', b'e', b'x', b'a', b'm', b'p', b'l', b'e',
+            b'.', b'o', b'r', b'g',
+        ];
+        let root_anchor =
+            trust_anchor_util::cert_der_as_trust_anchor(root).unwrap();
+        let anchors = &[
+            TrustAnchor {
+                name_constraints: Some(PERMITTED_EXAMPLE_ORG),
+                ..root_anchor
+            },
+            trust_anchor_util::cert_der_as_trust_anchor(old_root).unwrap(),
+        ];
+        let intermediates = &[intermediate, cross_signed_root];
+
+        let (result, stats) = verify_with_stats(
+            &ee, &policy, sig_algs, anchors, intermediates, times);
+        let path = result.unwrap();
+        assert_eq!(1, path.trust_anchor_index());
+        assert_eq!(&[0, 1], path.intermediate_indices());
+        assert_eq!(2, stats.trust_anchors_tried);
+        assert_eq!(3, stats.signatures_verified);
+
+        let report = verify_with_report(&ee, &policy, sig_algs, anchors,
+                                        intermediates, times);
+        assert!(!report.entries.contains(
+            &ReportEntry::RedundantTrustAnchor {
+                depth: 1,
+                intermediate_index: 1,
+                trust_anchor_index: 0,
+            }));
+    }
+}
@@ -0,0 +1,253 @@
+// Copyright 2016 Brian Smith.
+//
+// Permission to use, copy, modify, and/or distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHORS DISCLAIM ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHORS BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+//! Compares the verdicts of webpki with those of `openssl verify` for a
+//! corpus of certificate chains, e.g. to evaluate the behavior changes
+//! between releases.
+//!
+//! Run it with
+//! `WEBPKI_COMPAT_CORPUS=<dir> cargo test --features=compat-tests --test
+//! compat`; without `WEBPKI_COMPAT_CORPUS`, the test is skipped, so that
+//! `cargo test --all-features` works. Each subdirectory of the corpus is one
+//! case, containing:
+//!
+//! * `ee.der`: the end-entity certificate, which is verified as a TLS server
+//!   certificate.
+//! * `intermediate*.der`: the intermediate certificates, if any.
+//! * `root*.der`: the trust anchors.
+//! * `hostname` (optional): a DNS name that the certificate must be valid
+//!   for.
+//!
+//! The chains are verified at the time given by `WEBPKI_COMPAT_TIME`, in
+//! seconds since the Unix epoch, or else at the current time. `OPENSSL` is
+//! the `openssl` executable to use; by default, the one in the `PATH`.
+//!
+//! The report has a line for each case with both verdicts, starting with
+//! `DIFF` if only one of them accepts the chain. It is written to the file
+//! named by `WEBPKI_COMPAT_REPORT`, or else to standard output (use
+//! `--nocapture` to see it). Differences are expected, e.g. because webpki
+//! rejects RSA keys shorter than 2048 bits, so they don't fail the test;
+//! only a case that can't be run does.
+
+#![cfg(feature = "compat-tests")]
+
+extern crate rustc_serialize;
+extern crate webpki;
+
+use rustc_serialize::base64::{self, ToBase64};
+use std::env;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+
+static ALL_SIGALGS: &'static [&'static webpki::SignatureAlgorithm] = &[
+    &webpki::ECDSA_P256_SHA256,
+    &webpki::ECDSA_P256_SHA384,
+    &webpki::ECDSA_P384_SHA256,
+    &webpki::ECDSA_P384_SHA384,
+    &webpki::RSA_PKCS1_2048_8192_SHA1,
+    &webpki::RSA_PKCS1_2048_8192_SHA256,
+    &webpki::RSA_PKCS1_2048_8192_SHA384,
+    &webpki::RSA_PKCS1_2048_8192_SHA512,
+    &webpki::RSA_PKCS1_3072_8192_SHA384,
+];
+
+struct Case {
+    name: String,
+    ee: Vec<u8>,
+    intermediates: Vec<Vec<u8>>,
+    roots: Vec<Vec<u8>>,
+    hostname: Option<String>,
+}
+
+#[test]
+fn compare_with_openssl() {
+    let corpus = match env::var("WEBPKI_COMPAT_CORPUS") {
+        Ok(corpus) => corpus,
+        Err(..) => {
+            println!("skipped: WEBPKI_COMPAT_CORPUS doesn't name a corpus \
+                      directory");
+            return;
+        }
+    };
+    let time = match env::var("WEBPKI_COMPAT_TIME") {
+        Ok(seconds) => seconds.parse().expect("WEBPKI_COMPAT_TIME"),
+        Err(..) => {
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH).unwrap().as_secs()
+        }
+    };
+    let openssl = env::var("OPENSSL").unwrap_or("openssl".to_string());
+    let scratch_dir = env::temp_dir()
+        .join(format!("webpki-compat-{}", process::id()));
+    fs::create_dir_all(&scratch_dir).unwrap();
+
+    let mut report = String::new();
+    let mut diffs = 0;
+    let cases = sorted_paths(Path::new(&corpus));
+    for case_dir in &cases {
+        if !case_dir.is_dir() {
+            continue;
+        }
+        let case = read_case(case_dir);
+        let webpki_result = webpki_verdict(&case, time);
+        let openssl_result =
+            openssl_verdict(&case, time, &openssl, &scratch_dir);
+        let is_diff = webpki_result.is_ok() != openssl_result.is_ok();
+        if is_diff {
+            diffs += 1;
+        }
+        report.push_str(&format!(
+            "{} {}: webpki: {}; openssl: {}\n",
+            if is_diff { "DIFF" } else { "same" }, case.name,
+            webpki_result.map(|()| "ok".to_string())
+                         .unwrap_or_else(|e| format!("{:?}", e)),
+            openssl_result.map(|()| "ok".to_string())
+                          .unwrap_or_else(|e| e)));
+    }
+    report.push_str(&format!("{} differences\n", diffs));
+    let _ = fs::remove_dir_all(&scratch_dir);
+
+    match env::var("WEBPKI_COMPAT_REPORT") {
+        Ok(path) => {
+            write_file(Path::new(&path), report.as_bytes());
+        },
+        Err(..) => { print!("{}", report); }
+    }
+}
+
+// The entries of `dir`, sorted by name so that runs are reproducible.
+fn sorted_paths(dir: &Path) -> Vec<PathBuf> {
+    let mut paths = fs::read_dir(dir).unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect::<Vec<_>>();
+    paths.sort();
+    paths
+}
+
+fn read_file(path: &Path) -> io::Result<Vec<u8>> {
+    let mut contents = Vec::new();
+    let _ = try!(try!(fs::File::open(path)).read_to_end(&mut contents));
+    Ok(contents)
+}
+
+fn write_file(path: &Path, contents: &[u8]) {
+    fs::File::create(path).unwrap().write_all(contents).unwrap();
+}
+
+fn read_case(dir: &Path) -> Case {
+    let mut case = Case {
+        name: dir.file_name().unwrap().to_string_lossy().into_owned(),
+        ee: Vec::new(),
+        intermediates: Vec::new(),
+        roots: Vec::new(),
+        hostname: None,
+    };
+    for path in sorted_paths(dir) {
+        let file_name = path.file_name().unwrap().to_string_lossy()
+                            .into_owned();
+        if file_name == "hostname" {
+            let hostname =
+                String::from_utf8(read_file(&path).unwrap()).unwrap();
+            case.hostname = Some(hostname.trim().to_string());
+        } else if file_name == "ee.der" {
+            case.ee = read_file(&path).unwrap();
+        } else if file_name.starts_with("intermediate") &&
+                  file_name.ends_with(".der") {
+            case.intermediates.push(read_file(&path).unwrap());
+        } else if file_name.starts_with("root") &&
+                  file_name.ends_with(".der") {
+            case.roots.push(read_file(&path).unwrap());
+        }
+    }
+    assert!(!case.ee.is_empty(), "{}: missing ee.der", case.name);
+    assert!(!case.roots.is_empty(), "{}: missing root*.der", case.name);
+    case
+}
+
+fn webpki_verdict(case: &Case, time: u64) -> Result<(), webpki::Error> {
+    let mut anchors = Vec::new();
+    for root in &case.roots {
+        anchors.push(try!(
+            webpki::trust_anchor_util::cert_der_as_trust_anchor(root)));
+    }
+    let intermediates = case.intermediates.iter()
+                                          .map(|cert_der| &cert_der[..])
+                                          .collect::<Vec<_>>();
+    let cert = try!(webpki::EndEntityCert::from(&case.ee));
+    let _ = try!(cert.verify_is_valid_tls_server_cert(
+        ALL_SIGALGS, &anchors, &intermediates,
+        webpki::Time::from_seconds_since_unix_epoch(time)));
+    if let Some(ref hostname) = case.hostname {
+        try!(cert.verify_is_valid_for_dns_name(hostname.as_bytes()));
+    }
+    Ok(())
+}
+
+// Runs `openssl verify` on the case; the error is OpenSSL's first error
+// message.
+fn openssl_verdict(case: &Case, time: u64, openssl: &str, scratch_dir: &Path)
+                   -> Result<(), String> {
+    let ee = write_pem(scratch_dir, "ee.pem", &[&case.ee]);
+    let roots = write_pem(scratch_dir, "roots.pem", &case.roots.iter()
+                          .map(|cert_der| &cert_der[..])
+                          .collect::<Vec<_>>());
+
+    let mut command = Command::new(openssl);
+    let _ = command.arg("verify")
+                   .arg("-purpose").arg("sslserver")
+                   .arg("-attime").arg(time.to_string())
+                   .arg("-no-CApath")
+                   .arg("-CAfile").arg(&roots);
+    if !case.intermediates.is_empty() {
+        let intermediates = write_pem(scratch_dir, "intermediates.pem",
+                                      &case.intermediates.iter()
+                                          .map(|cert_der| &cert_der[..])
+                                          .collect::<Vec<_>>());
+        let _ = command.arg("-untrusted").arg(intermediates);
+    }
+    if let Some(ref hostname) = case.hostname {
+        let _ = command.arg("-verify_hostname").arg(hostname);
+    }
+    let output = command.arg(&ee).output()
+                        .expect("couldn't run openssl; see OPENSSL");
+    if output.status.success() {
+        return Ok(());
+    }
+    let mut messages = String::from_utf8_lossy(&output.stdout).into_owned();
+    messages.push_str(&String::from_utf8_lossy(&output.stderr));
+    Err(messages.lines()
+                .find(|line| line.contains("error"))
+                .unwrap_or("failed")
+                .trim()
+                .to_string())
+}
+
+fn write_pem(dir: &Path, file_name: &str, certs: &[&[u8]]) -> PathBuf {
+    let config = base64::Config {
+        line_length: Some(64),
+        newline: base64::Newline::LF,
+        ..base64::STANDARD
+    };
+    let mut pem = String::new();
+    for cert_der in certs {
+        pem.push_str("-----BEGIN CERTIFICATE-----\n");
+        pem.push_str(&cert_der.to_base64(config));
+        pem.push_str("\n-----END CERTIFICATE-----\n");
+    }
+    let path = dir.join(file_name);
+    write_file(&path, pem.as_bytes());
+    path
+}
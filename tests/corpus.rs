@@ -0,0 +1,1128 @@
+// Copyright 2016 Brian Smith.
+//
+// Permission to use, copy, modify, and/or distribute this software for any
+// purpose with or without fee is hereby granted, provided that the above
+// copyright notice and this permission notice appear in all copies.
+//
+// THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHORS DISCLAIM ALL WARRANTIES
+// WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+// MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHORS BE LIABLE FOR
+// ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+// WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+// ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+// OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+//! Generates a corpus of certificate chains covering the permutations of
+//! signature algorithm, chain depth, name constraints, end-entity EKU, and
+//! expiry, together with the verdict that webpki must reach for each, and
+//! checks webpki's verdicts against it.
+//!
+//! Run it with
+//! `WEBPKI_CORPUS=<dir> cargo test --features=corpus-tests --test corpus`;
+//! without `WEBPKI_CORPUS`, the test is skipped, so that
+//! `cargo test --all-features` works.
+//!
+//! Each case is written to a subdirectory of `<dir>` in the layout that
+//! `tests/compat.rs` reads, so the corpus can also be used to compare webpki
+//! with OpenSSL, plus a file `expected` that contains `ok` or `reject`. All
+//! the chains are to be verified at `CORPUS_TIME`, 2026-01-01T00:00:00Z, as
+//! TLS server certificates for `www.example.com`, with the signature
+//! algorithms in `SUPPORTED_SIGALGS`.
+//!
+//! The certificates are encoded with `der_writer` and signed with the fixed
+//! keys below, and the ECDSA signatures use a fixed nonce, so every run
+//! generates exactly the same corpus. It can be published as a golden corpus
+//! for other verifiers, and a failure can be reproduced from the name of the
+//! case. The keys are only for this test; since they are published here, and
+//! the nonce is reused, they must not be trusted for anything else.
+//!
+//! The test fails, listing the cases, if webpki's verdict for any case
+//! differs from the expected one.
+
+#![cfg(feature = "corpus-tests")]
+
+extern crate ring;
+extern crate rustc_serialize;
+extern crate webpki;
+
+use ring::digest;
+use rustc_serialize::hex::FromHex;
+use std::env;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use webpki::der_writer;
+use webpki::name_constraints::{self, GeneralSubtree};
+
+// 2026-01-01T00:00:00Z.
+const CORPUS_TIME: u64 = 1767225600;
+
+// The validity periods of the certificates, in seconds since the Unix epoch,
+// which contain `CORPUS_TIME` unless they are expired: 2025-01-01 to
+// 2035-01-01, and 2020-01-01 to 2021-01-01.
+const VALID: (u64, u64) = (1735689600, 2051222400);
+const EXPIRED: (u64, u64) = (1577836800, 1609459200);
+
+const HOSTNAME: &'static str = "www.example.com";
+
+// webpki accepts at most 6 intermediate certificates; the deepest chains
+// have one more.
+const MAX_INTERMEDIATES: usize = 7;
+
+static SUPPORTED_SIGALGS: &'static [&'static webpki::SignatureAlgorithm] = &[
+    &webpki::ECDSA_P256_SHA256,
+    &webpki::ECDSA_P384_SHA384,
+    &webpki::RSA_PKCS1_2048_8192_SHA256,
+];
+
+// The keys and signature algorithm of every certificate in a chain.
+struct Algorithm {
+    name: &'static str,
+
+    // The keys of the root, of the intermediates, and of the end-entity
+    // certificate, in that order.
+    keys: &'static [Key; 3],
+
+    digest: &'static Digest,
+
+    // The value of the signature `AlgorithmIdentifier`.
+    signature_algorithm_id: &'static [u8],
+
+    // Whether webpki accepts the signatures with `SUPPORTED_SIGALGS`.
+    supported: bool,
+}
+
+static ALGORITHMS: &'static [Algorithm] = &[
+    Algorithm {
+        name: "p256-sha256",
+        keys: &P256_KEYS,
+        digest: &SHA256,
+        // ecdsa-with-SHA256.
+        signature_algorithm_id:
+            &[0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02],
+        supported: true,
+    },
+    Algorithm {
+        name: "p384-sha384",
+        keys: &P384_KEYS,
+        digest: &SHA384,
+        // ecdsa-with-SHA384.
+        signature_algorithm_id:
+            &[0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x03],
+        supported: true,
+    },
+    Algorithm {
+        name: "rsa2048-sha256",
+        keys: &RSA2048_KEYS,
+        digest: &SHA256,
+        // sha256WithRSAEncryption.
+        signature_algorithm_id: &[0x06, 0x09, 0x2a, 0x86, 0x48, 0x86, 0xf7,
+                                  0x0d, 0x01, 0x01, 0x0b, 0x05, 0x00],
+        supported: true,
+    },
+    Algorithm {
+        name: "rsa2048-sha1",
+        keys: &RSA2048_KEYS,
+        digest: &SHA1,
+        // sha1WithRSAEncryption.
+        signature_algorithm_id: &[0x06, 0x09, 0x2a, 0x86, 0x48, 0x86, 0xf7,
+                                  0x0d, 0x01, 0x01, 0x05, 0x05, 0x00],
+        supported: false,
+    },
+    Algorithm {
+        name: "rsa1024-sha256",
+        keys: &RSA1024_KEYS,
+        digest: &SHA256,
+        signature_algorithm_id: &[0x06, 0x09, 0x2a, 0x86, 0x48, 0x86, 0xf7,
+                                  0x0d, 0x01, 0x01, 0x0b, 0x05, 0x00],
+        supported: false,
+    },
+];
+
+// The permitted and excluded subtrees of a nameConstraints extension.
+type Subtrees = (&'static [GeneralSubtree<'static>],
+                 &'static [GeneralSubtree<'static>]);
+
+// (name, nameConstraints extension of the root certificate, whether
+// `HOSTNAME` is within it).
+static NAME_CONSTRAINTS: &'static [(&'static str, Option<Subtrees>, bool)] = &[
+    ("unconstrained", None, true),
+    ("permit-example.com", Some((&[EXAMPLE_COM], &[])), true),
+    ("permit-example.org", Some((&[EXAMPLE_ORG], &[])), false),
+    ("exclude-example.com", Some((&[], &[EXAMPLE_COM])), false),
+];
+
+const EXAMPLE_COM: GeneralSubtree<'static> =
+    GeneralSubtree::DnsName(b"example.com");
+const EXAMPLE_ORG: GeneralSubtree<'static> =
+    GeneralSubtree::DnsName(b"example.org");
+
+// (name, extendedKeyUsage extension of the end-entity certificate, whether
+// it allows TLS server authentication).
+static EKUS: &'static [(&'static str, Option<&'static [u8]>, bool)] = &[
+    ("server-eku", Some(ID_KP_SERVER_AUTH), true),
+    ("client-eku", Some(ID_KP_CLIENT_AUTH), false),
+    ("no-eku", None, true),
+];
+
+const ID_KP_SERVER_AUTH: &'static [u8] =
+    &[0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x03, 0x01];
+const ID_KP_CLIENT_AUTH: &'static [u8] =
+    &[0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x03, 0x02];
+
+#[derive(Clone, Copy, PartialEq)]
+enum Expiry {
+    Valid,
+    EndEntityExpired,
+
+    // The intermediate certificate that issued the end-entity certificate is
+    // expired.
+    IntermediateExpired,
+}
+
+static EXPIRIES: &'static [(&'static str, Expiry)] = &[
+    ("valid", Expiry::Valid),
+    ("ee-expired", Expiry::EndEntityExpired),
+    ("intermediate-expired", Expiry::IntermediateExpired),
+];
+
+const SEQUENCE: u8 = 0x30;
+
+#[test]
+fn generate_and_check_corpus() {
+    let corpus = match env::var("WEBPKI_CORPUS") {
+        Ok(corpus) => PathBuf::from(corpus),
+        Err(..) => {
+            println!("skipped: WEBPKI_CORPUS doesn't name the directory to \
+                      write to");
+            return;
+        }
+    };
+    fs::create_dir_all(&corpus).unwrap();
+
+    let mut case_count = 0;
+    let mut wrong = Vec::new();
+    for algorithm in ALGORITHMS {
+        let chain = Chain::new(algorithm);
+        for depth in 0..(MAX_INTERMEDIATES + 1) {
+            for &(eku_name, eku, eku_allows_server) in EKUS {
+                let ee = chain.ee(depth, eku, VALID);
+                let expired_ee = chain.ee(depth, eku, EXPIRED);
+                for &(expiry_name, expiry) in EXPIRIES {
+                    if expiry == Expiry::IntermediateExpired && depth == 0 {
+                        continue;
+                    }
+                    let mut intermediates =
+                        chain.intermediates[..depth].to_vec();
+                    if expiry == Expiry::IntermediateExpired {
+                        intermediates[depth - 1] =
+                            chain.expired_intermediates[depth - 1].clone();
+                    }
+                    let ee = if expiry == Expiry::EndEntityExpired {
+                        &expired_ee
+                    } else {
+                        &ee
+                    };
+
+                    for (root, &(constraints_name, _, hostname_is_permitted))
+                            in chain.roots.iter().zip(NAME_CONSTRAINTS) {
+                        let name =
+                            format!("{}-{}-intermediates-{}-{}-{}",
+                                    algorithm.name, depth, constraints_name,
+                                    eku_name, expiry_name);
+                        let expected =
+                            algorithm.supported &&
+                            depth < MAX_INTERMEDIATES &&
+                            hostname_is_permitted && eku_allows_server &&
+                            expiry == Expiry::Valid;
+
+                        let dir = corpus.join(&name);
+                        write_case(&dir, root, &intermediates, ee, expected);
+                        if webpki_verdict(&dir).is_ok() != expected {
+                            wrong.push(name);
+                        }
+                        case_count += 1;
+                    }
+                }
+            }
+        }
+    }
+
+    assert!(wrong.is_empty(), "{} of {} cases have the wrong verdict: {:?}",
+            wrong.len(), case_count, wrong);
+}
+
+// The certificates from which the cases for an algorithm are put together.
+// The certificates don't depend on the root's name constraints, except for
+// the root itself, so each is only signed once.
+struct Chain {
+    algorithm: &'static Algorithm,
+
+    // A root for each of `NAME_CONSTRAINTS`, all with the same subject and
+    // key.
+    roots: Vec<Vec<u8>>,
+
+    // Intermediate `i + 1` was issued by intermediate `i`, or by the root
+    // for `i == 0`; the expired ones have the same subject and key as the
+    // valid ones.
+    intermediates: Vec<Vec<u8>>,
+    expired_intermediates: Vec<Vec<u8>>,
+}
+
+impl Chain {
+    fn new(algorithm: &'static Algorithm) -> Chain {
+        let roots = NAME_CONSTRAINTS.iter().map(|&(_, subtrees, _)| {
+            cert(algorithm, &issuer_name(algorithm, 0), &algorithm.keys[0],
+                 None, 1, VALID, &ca_extensions(subtrees))
+        }).collect();
+
+        let mut intermediates = Vec::new();
+        let mut expired_intermediates = Vec::new();
+        for i in 1..(MAX_INTERMEDIATES + 1) {
+            let issuer = (&issuer_name(algorithm, i - 1)[..],
+                          &algorithm.keys[if i == 1 { 0 } else { 1 }]);
+            let intermediate = |validity| {
+                cert(algorithm, &issuer_name(algorithm, i), &algorithm.keys[1],
+                     Some(issuer), 1 + i as u64, validity,
+                     &ca_extensions(None))
+            };
+            intermediates.push(intermediate(VALID));
+            expired_intermediates.push(intermediate(EXPIRED));
+        }
+
+        Chain {
+            algorithm: algorithm,
+            roots: roots,
+            intermediates: intermediates,
+            expired_intermediates: expired_intermediates,
+        }
+    }
+
+    // The end-entity certificate for `HOSTNAME` issued by intermediate
+    // `depth`, or by the root for `depth == 0`.
+    fn ee(&self, depth: usize, eku: Option<&[u8]>, validity: (u64, u64))
+          -> Vec<u8> {
+        let issuer = (&issuer_name(self.algorithm, depth)[..],
+                      &self.algorithm.keys[if depth == 0 { 0 } else { 1 }]);
+        let mut extensions = vec![
+            Extension::new(ID_CE_BASIC_CONSTRAINTS, true, |out| {
+                der_writer::write_sequence(out, |_| Ok(()))
+            }),
+            Extension::new(ID_CE_KEY_USAGE, true, |out| {
+                // digitalSignature.
+                der_writer::write_tlv(out, 0x03, &[0x07, 0x80])
+            }),
+            Extension::new(ID_CE_SUBJECT_ALT_NAME, false, |out| {
+                der_writer::write_sequence(out, |out| {
+                    der_writer::write_tlv(out, 0x82, HOSTNAME.as_bytes())
+                })
+            }),
+        ];
+        if let Some(eku) = eku {
+            extensions.push(Extension::new(ID_CE_EXT_KEY_USAGE, false, |out| {
+                der_writer::write_sequence(out, |out| {
+                    der_writer::write_oid(out, eku)
+                })
+            }));
+        }
+        cert(self.algorithm, HOSTNAME, &self.algorithm.keys[2], Some(issuer),
+             depth as u64 + 2, validity, &extensions)
+    }
+}
+
+// The subject of the root, for `i == 0`, or of intermediate `i`.
+fn issuer_name(algorithm: &Algorithm, i: usize) -> String {
+    if i == 0 {
+        format!("webpki corpus root {}", algorithm.name)
+    } else {
+        format!("webpki corpus intermediate {}", i)
+    }
+}
+
+const ID_CE_BASIC_CONSTRAINTS: &'static [u8] = &[0x55, 0x1d, 0x13];
+const ID_CE_KEY_USAGE: &'static [u8] = &[0x55, 0x1d, 0x0f];
+const ID_CE_SUBJECT_ALT_NAME: &'static [u8] = &[0x55, 0x1d, 0x11];
+const ID_CE_NAME_CONSTRAINTS: &'static [u8] = &[0x55, 0x1d, 0x1e];
+const ID_CE_EXT_KEY_USAGE: &'static [u8] = &[0x55, 0x1d, 0x25];
+
+struct Extension {
+    id: &'static [u8],
+    critical: bool,
+    value: Vec<u8>,
+}
+
+impl Extension {
+    // An extension whose value is what `f` writes.
+    fn new<F>(id: &'static [u8], critical: bool, f: F) -> Extension
+              where F: FnOnce(&mut der_writer::Output)
+                              -> Result<(), webpki::Error> {
+        let mut value = Vec::new();
+        f(&mut value).unwrap();
+        Extension { id: id, critical: critical, value: value }
+    }
+}
+
+// The extensions of a CA certificate, with name constraints if `subtrees`
+// isn't `None`.
+fn ca_extensions(subtrees: Option<Subtrees>) -> Vec<Extension> {
+    let mut extensions = vec![
+        Extension::new(ID_CE_BASIC_CONSTRAINTS, true, |out| {
+            der_writer::write_sequence(out, |out| {
+                der_writer::write_boolean(out, true)
+            })
+        }),
+        Extension::new(ID_CE_KEY_USAGE, true, |out| {
+            // keyCertSign and cRLSign.
+            der_writer::write_tlv(out, 0x03, &[0x01, 0x06])
+        }),
+    ];
+    if let Some((permitted, excluded)) = subtrees {
+        let value = name_constraints::encode(permitted, excluded).unwrap();
+        extensions.push(Extension::new(ID_CE_NAME_CONSTRAINTS, true, |out| {
+            der_writer::write_tlv(out, SEQUENCE, &value)
+        }));
+    }
+    extensions
+}
+
+// Issues a certificate for `key` with the subject `CN=<subject>`, signed by
+// `issuer` (the issuer's subject and key) or else self-signed, and returns
+// its DER encoding.
+fn cert(algorithm: &Algorithm, subject: &str, key: &Key,
+        issuer: Option<(&str, &Key)>, serial: u64, validity: (u64, u64),
+        extensions: &[Extension]) -> Vec<u8> {
+    let (issuer_subject, issuer_key) = issuer.unwrap_or((subject, key));
+
+    let mut tbs = Vec::new();
+    der_writer::write_sequence(&mut tbs, |out| {
+        try!(der_writer::write_nested(out, 0xa0, |out| {
+            der_writer::write_unsigned_integer(out, 2)
+        }));
+        try!(der_writer::write_unsigned_integer(out, serial));
+        try!(der_writer::write_tlv(out, SEQUENCE,
+                                   algorithm.signature_algorithm_id));
+        try!(write_name(out, issuer_subject));
+        try!(der_writer::write_sequence(out, |out| {
+            try!(der_writer::write_time(
+                out, webpki::Time::from_seconds_since_unix_epoch(validity.0)));
+            der_writer::write_time(
+                out, webpki::Time::from_seconds_since_unix_epoch(validity.1))
+        }));
+        try!(write_name(out, subject));
+        try!(key.write_spki(out));
+        der_writer::write_nested(out, 0xa3, |out| {
+            der_writer::write_sequence(out, |out| {
+                for extension in extensions {
+                    try!(der_writer::write_sequence(out, |out| {
+                        try!(der_writer::write_oid(out, extension.id));
+                        if extension.critical {
+                            try!(der_writer::write_boolean(out, true));
+                        }
+                        der_writer::write_octet_string(out, &extension.value)
+                    }));
+                }
+                Ok(())
+            })
+        })
+    }).unwrap();
+
+    let signature = issuer_key.sign(algorithm.digest, &tbs);
+    let mut cert = Vec::new();
+    der_writer::write_sequence(&mut cert, |out| {
+        try!(out.write_bytes(&tbs));
+        try!(der_writer::write_tlv(out, SEQUENCE,
+                                   algorithm.signature_algorithm_id));
+        der_writer::write_bit_string(out, &signature)
+    }).unwrap();
+    cert
+}
+
+// Appends the `Name` `CN=<common_name>`.
+fn write_name(out: &mut der_writer::Output, common_name: &str)
+              -> Result<(), webpki::Error> {
+    der_writer::write_sequence(out, |out| {
+        der_writer::write_nested(out, 0x31, |out| {
+            der_writer::write_sequence(out, |out| {
+                // id-at-commonName.
+                try!(der_writer::write_oid(out, &[0x55, 0x04, 0x03]));
+                // UTF8String.
+                der_writer::write_tlv(out, 0x0c, common_name.as_bytes())
+            })
+        })
+    })
+}
+
+fn write_case(dir: &Path, root: &[u8], intermediates: &[Vec<u8>], ee: &[u8],
+              expected: bool) {
+    fs::create_dir_all(dir).unwrap();
+    write_file(&dir.join("root.der"), root);
+    for (i, cert) in intermediates.iter().enumerate() {
+        write_file(&dir.join(format!("intermediate{}.der", i + 1)), cert);
+    }
+    write_file(&dir.join("ee.der"), ee);
+    write_file(&dir.join("hostname"), format!("{}\n", HOSTNAME).as_bytes());
+    write_file(&dir.join("expected"),
+               if expected { b"ok\n" } else { b"reject\n" });
+}
+
+// Verifies a case as described in the module documentation.
+fn webpki_verdict(dir: &Path) -> Result<(), webpki::Error> {
+    let root = read_file(&dir.join("root.der")).unwrap();
+    let mut intermediates = Vec::new();
+    for i in 1.. {
+        match read_file(&dir.join(format!("intermediate{}.der", i))) {
+            Ok(cert_der) => intermediates.push(cert_der),
+            Err(..) => { break; }
+        }
+    }
+    let intermediates = intermediates.iter()
+                                     .map(|cert_der| &cert_der[..])
+                                     .collect::<Vec<_>>();
+    let ee = read_file(&dir.join("ee.der")).unwrap();
+
+    let anchors =
+        [try!(webpki::trust_anchor_util::cert_der_as_trust_anchor(&root))];
+    let cert = try!(webpki::EndEntityCert::from(&ee));
+    let _ = try!(cert.verify_is_valid_tls_server_cert(
+        SUPPORTED_SIGALGS, &anchors, &intermediates,
+        webpki::Time::from_seconds_since_unix_epoch(CORPUS_TIME)));
+    cert.verify_is_valid_for_dns_name(HOSTNAME.as_bytes())
+}
+
+fn read_file(path: &Path) -> io::Result<Vec<u8>> {
+    let mut contents = Vec::new();
+    let _ = try!(try!(fs::File::open(path)).read_to_end(&mut contents));
+    Ok(contents)
+}
+
+fn write_file(path: &Path, contents: &[u8]) {
+    fs::File::create(path).unwrap().write_all(contents).unwrap();
+}
+
+// A digest algorithm and the value of its `AlgorithmIdentifier` in an RSA
+// `DigestInfo`.
+struct Digest {
+    algorithm: &'static digest::Algorithm,
+    id: &'static [u8],
+}
+
+static SHA1: Digest = Digest {
+    algorithm: &digest::SHA1,
+    id: &[0x06, 0x05, 0x2b, 0x0e, 0x03, 0x02, 0x1a, 0x05, 0x00],
+};
+
+static SHA256: Digest = Digest {
+    algorithm: &digest::SHA256,
+    id: &[0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01,
+          0x05, 0x00],
+};
+
+static SHA384: Digest = Digest {
+    algorithm: &digest::SHA384,
+    id: &[0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x02,
+          0x05, 0x00],
+};
+
+// A signing key, with its big-endian values in hex.
+enum Key {
+    // An RSA key with the public exponent 65537, and the private key in the
+    // form used with the Chinese remainder theorem, named as in PKCS#1's
+    // `RSAPrivateKey`.
+    Rsa {
+        modulus: &'static str,
+        prime1: &'static str,
+        prime2: &'static str,
+        exponent1: &'static str,
+        exponent2: &'static str,
+        coefficient: &'static str,
+    },
+
+    Ecdsa {
+        curve: &'static Curve,
+        private_key: &'static str,
+
+        // The uncompressed point.
+        public_key: &'static str,
+    },
+}
+
+impl Key {
+    // Appends the `SubjectPublicKeyInfo`.
+    fn write_spki(&self, out: &mut der_writer::Output)
+                  -> Result<(), webpki::Error> {
+        der_writer::write_sequence(out, |out| match *self {
+            Key::Rsa { modulus, .. } => {
+                // rsaEncryption.
+                try!(der_writer::write_tlv(out, SEQUENCE, &[
+                    0x06, 0x09, 0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01,
+                    0x01, 0x01, 0x05, 0x00]));
+                let mut public_key = Vec::new();
+                try!(der_writer::write_sequence(&mut public_key, |out| {
+                    try!(der_writer::write_positive_integer(
+                        out, &from_hex(modulus)));
+                    der_writer::write_unsigned_integer(out, 65537)
+                }));
+                der_writer::write_bit_string(out, &public_key)
+            },
+            Key::Ecdsa { curve, public_key, .. } => {
+                try!(der_writer::write_tlv(out, SEQUENCE,
+                                           curve.spki_algorithm_id));
+                der_writer::write_bit_string(out, &from_hex(public_key))
+            },
+        })
+    }
+
+    // Signs `msg` with PKCS#1 v1.5 padding or ECDSA, as appropriate, and
+    // returns the signature in the form of the `signatureValue` of a
+    // certificate.
+    fn sign(&self, digest: &Digest, msg: &[u8]) -> Vec<u8> {
+        let hash = digest::digest(digest.algorithm, msg);
+        match *self {
+            Key::Rsa { modulus, prime1, prime2, exponent1, exponent2,
+                       coefficient } => {
+                let modulus = Modulus::new(&from_hex(modulus));
+                let mut digest_info = Vec::new();
+                der_writer::write_sequence(&mut digest_info, |out| {
+                    try!(der_writer::write_tlv(out, SEQUENCE, digest.id));
+                    der_writer::write_octet_string(out, hash.as_ref())
+                }).unwrap();
+                let mut encoded = vec![0xff; modulus.len];
+                encoded[0] = 0x00;
+                encoded[1] = 0x01;
+                let digest_info_start = modulus.len - digest_info.len();
+                encoded[digest_info_start - 1] = 0x00;
+                encoded[digest_info_start..].copy_from_slice(&digest_info);
+
+                // m = m2 + q (qInv (m1 - m2) mod p), where m1 and m2 are the
+                // signature modulo p and q.
+                let (p, q) = (Modulus::new(&from_hex(prime1)),
+                              Modulus::new(&from_hex(prime2)));
+                let m1 = p.pow(&p.reduce(&encoded), &from_hex(exponent1));
+                let m2 = q.pow(&q.reduce(&encoded), &from_hex(exponent2));
+                let h = p.mul(&from_hex(coefficient),
+                              &p.sub(&m1, &p.reduce(&m2)));
+                modulus.add(&m2, &modulus.mul(&h, &from_hex(prime2)))
+            },
+
+            // s = k^-1 (z + r d) mod n, where r is the same for every
+            // signature since the nonce k is. Each digest is as long as the
+            // curve's order, so it isn't truncated.
+            Key::Ecdsa { curve, private_key, .. } => {
+                let order = Modulus::new(&from_hex(curve.order));
+                let r = from_hex(curve.r);
+                let z = order.reduce(hash.as_ref());
+                let s = order.mul(
+                    &order.prime_inverse(&from_hex(curve.nonce)),
+                    &order.add(&z, &order.mul(&r, &from_hex(private_key))));
+                let mut signature = Vec::new();
+                der_writer::write_sequence(&mut signature, |out| {
+                    try!(der_writer::write_positive_integer(out, &r));
+                    der_writer::write_positive_integer(out, &s)
+                }).unwrap();
+                signature
+            },
+        }
+    }
+}
+
+fn from_hex(hex: &str) -> Vec<u8> { hex.from_hex().unwrap() }
+
+// An ECDSA curve and the nonce of every signature on it.
+struct Curve {
+    // The value of the `SubjectPublicKeyInfo`'s `AlgorithmIdentifier`.
+    spki_algorithm_id: &'static [u8],
+
+    order: &'static str,
+    nonce: &'static str,
+
+    // The x coordinate of the point `nonce` * G, modulo `order`.
+    r: &'static str,
+}
+
+static P256: Curve = Curve {
+    // id-ecPublicKey, prime256v1.
+    spki_algorithm_id: &[0x06, 0x07, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01,
+                         0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01,
+                         0x07],
+    order:
+        "ffffffff00000000ffffffffffffffffbce6faada7179e84f3b9cac2fc632551",
+    nonce:
+        "66374b0e1616a197658b6434f0a237287adc6754dd99c54b6aa53624b242abad",
+    r:
+        "c91c1f201cfe85f3cc706bafa6af8217d03252105abc59c91cf989bb7b2b7161",
+};
+
+static P384: Curve = Curve {
+    // id-ecPublicKey, secp384r1.
+    spki_algorithm_id: &[0x06, 0x07, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01,
+                         0x06, 0x05, 0x2b, 0x81, 0x04, 0x00, 0x22],
+    order: concat!(
+        "ffffffffffffffffffffffffffffffffffffffffffffffffc7634d81f4372ddf",
+        "581a0db248b0a77aecec196accc52973"),
+    nonce: concat!(
+        "1034f41e5b834b690b6525892b344e17016199781ee8da650d1af5a5493382a6",
+        "9c1ecd6f45c1a6c37834deef8e4621ec"),
+    r: concat!(
+        "9119f149bbe65a9894e804dee9d1b16c77a30561470a3a6168f19f11589b2ada",
+        "b8d5014046769beb5aafc5d3135f4c73"),
+};
+
+static P256_KEYS: [Key; 3] = [
+    Key::Ecdsa {
+        curve: &P256,
+        private_key:
+            "8903b70a529bc10318f43dcf6a6e16c221f5cbc21afa95c511f1603c55df6e5f",
+        public_key: concat!(
+            "045e6fde8700439aaee80f630562eb09e64928f6a7144798aa3e015ec498dd5e",
+            "d43b9cd67a2b17772989646b1c7e29000bc60b7c512c8b4a37e16f3e348f41b1",
+            "b9"),
+    },
+    Key::Ecdsa {
+        curve: &P256,
+        private_key:
+            "692506ad261e4cc2ec6039c95ce00fc3a9c19799d4417880f8537f6c0b3c894f",
+        public_key: concat!(
+            "04db5039ce7cd753dd1a42a8d39d77e1c817c7c0c6e157fd601347498cb85019",
+            "75cf7fcd6c24240d55e5e077e7daa1e35af38d09b1ca5e130b2cf08c9a8c7621",
+            "ef"),
+    },
+    Key::Ecdsa {
+        curve: &P256,
+        private_key:
+            "5ab8448ef920ee073eacea579a612066649dbeef5fce1289b5e022c37c96b35a",
+        public_key: concat!(
+            "041f81c0ae9023be7de1bd784870a71b11e77988b954f0c15679255bb6d1aade",
+            "a725f9829245faf03aaf5e0b184047fb60b5e35751bcc278017d47b746cc724b",
+            "79"),
+    },
+];
+
+static P384_KEYS: [Key; 3] = [
+    Key::Ecdsa {
+        curve: &P384,
+        private_key: concat!(
+            "89e411e5cbb6be303a47b0f82013443c4965c0f06cd62453bea924866208f8d0",
+            "d020675f1877c1d59e59c12d702deb9b"),
+        public_key: concat!(
+            "046ba078d2834627267863040d5c4f3782482ca6d4c29dc189f454396116e3ca",
+            "ae200cbff260e959474688c566872461863c27d7931f5ee7e9182b5a2e8dc8b8",
+            "e6b7aba05736ff877ea7ce47c3784e239623eaf20dda7d22fed518e33a327a5e",
+            "eb"),
+    },
+    Key::Ecdsa {
+        curve: &P384,
+        private_key: concat!(
+            "2ea8e83492be61c3c3f352be427524fd26ad6d21ec141248f0b9cf64aa002226",
+            "d3b3c234eed2dcd157b30ab0dc5df4fd"),
+        public_key: concat!(
+            "04f81ec56b1c36d2fe81f6e8d2ba297a5402ea8582d1fd621c6ff355e5ec66d9",
+            "372eca125ecef9e14b21c965719727abbc74a2ab16d2a6aea2702cfb10d8132c",
+            "d9e97de02088ff8aff134530c60305e3a45b0b8977eb7eab04960d9f6641dda8",
+            "58"),
+    },
+    Key::Ecdsa {
+        curve: &P384,
+        private_key: concat!(
+            "a6961f9d2ce53a75f17675801855d14ef17e7937e055fa66a21d732313e5a433",
+            "dfebf3a90a3187360c8824460a03274d"),
+        public_key: concat!(
+            "04cd8c0f29c38f80de724179f18cb38f07a77e0d440c12454a2a11e8daee861f",
+            "75895c6ee673f2b6d7d0e3572ae56baa6ebbce2ce562294115ee18e17d7b3a30",
+            "31bbf8f491974e710bacf69e056a7fdb2f4d0574dbca02da87a8ca6dbc851bcf",
+            "51"),
+    },
+];
+
+static RSA2048_KEYS: [Key; 3] = [
+    Key::Rsa {
+        modulus: concat!(
+            "9ede5460ead60c811be4f44094dfa60d65137a52465a190d76204939c1fc0db6",
+            "e58807b93027c5c408d6927cc27e2bc021c29f5af4c299d7ded990290080f37a",
+            "892c687191f703136a3bce5af495cafabbfcc3c41fe0d68f09f30c543f7e00dc",
+            "d3879d7a6b0c5fa623a7f47e4c1371a45c699133cf779871241724a91bda1f28",
+            "005b96c867a828daeb6e95920b72b6fe3d3f9008c32124d26ddbefd55be9075a",
+            "20dda991ca62add511956a94de3d88fb14ec5f087c1c58be656e68dfecb223d2",
+            "991bf300062ca0e626b7ed57d78d538824860228e5992de07cbb483240a14127",
+            "ced56d4d1a1b5c569f7ced40e36d06b9991bbe3bf617c50f655cc8d53af925a5"),
+        prime1: concat!(
+            "d3a52805f88d64629c9abf1666ba9a77d89c3f520c96c38ebae1c3718eb9d22f",
+            "738c7686ab02221db4e47c428837d4c7a5337ac0c7823163901d3636aa123aba",
+            "109867a48d75a6cfbb42a13c00222983626a9dff7bad5bca78711938fd06a786",
+            "0e0646604ab7f35345eeac04c9e635f0a5550683187e6a4884dce959c02d5a9d"),
+        prime2: concat!(
+            "c029af47856ab2cece35231c076fc508913d2cbf3c8e050d0f40818923d65e64",
+            "72020b7a577f153bac95c1a6465928d05433b42eec9fa23b91374c50b7e31c64",
+            "6cc5df99cd38cc6181b13e3e3db39a16f2f3b5ffa72b67d5b90903c531aa7ba0",
+            "ef8b2300f4492e162c6b367c27af731d3b0e5147e90ea49aad136a23ed3f64a9"),
+        exponent1: concat!(
+            "9bcfe2ff425515aa6390a94d5ca3d44496fbb0c23ef25f561ccf488bb97fa3f5",
+            "bb2395bedef6db16f3ada2ad788b61e2f9044542d79e49adfad306ae87d693d5",
+            "97921bfb5109649d5a475b5c48dcdda3143959c854ccf3dec37cc00cec7a91aa",
+            "6778c726ecdb3010065407e01ea394e26d55834dd022812c9263f0875aac0921"),
+        exponent2: concat!(
+            "5fe2ed01a5434ea1ff2768b38dca69e91280121a02df6309d7387aeab1a54048",
+            "a2d2fe6337d1c40898dfc9576cf704749fef84d37d635292a2ad93391be909bb",
+            "dac30a0636ef469cc9186654ac793844874ab0e036cc63edb37d1a1285ebe9f0",
+            "ae57214a529b4991eaec3cadcb27b32b3cd2b2204ddceea9f7ff1e117b4b0cb1"),
+        coefficient: concat!(
+            "18905e9d7c9ba50180dc5f6b0c8984c1e66f4ce7954096b4b863abf73ae2e559",
+            "e373c46b923c72409d7348a6eabe29b2d497986a9698e6f83c35b324765eef13",
+            "6355ac29b8406f4dab6200e01f3c3d7b039e3d7de372ed5a6056a916b247c097",
+            "636ca8d0c4573becbbd0cf44c0f85a9c0c286667d5b88270b9e55ff1e2d5cf24"),
+    },
+    Key::Rsa {
+        modulus: concat!(
+            "db4cdeff81d5fd0c75146da30b89ea0d754df7d489c93894177c709789890b64",
+            "5af28269b6ae54843d07dc66b45016d271e446338ab854ba0d213887e4af4191",
+            "cdf5820c8ee9372fb102d18a6fdc13d2f7d4551cd0aa293a439772a01170340d",
+            "37fb3b5d35649872024b4bccd4a3ba38335cb9a8711fa60f6d93aa990d280f0a",
+            "11220a09ce02176ed221a941925778c9662dea1dd889bafb277b135a240ddbcd",
+            "1e6d8f5c357768cbdd53eb6700122146a9f9f09e157a6b18491d1678e9dd0977",
+            "7ed099d90ed72ef20896aa16afc5b98d6ae1bb81fc42d9964e85389ce23907b2",
+            "bd1ab006e0c6092fdd01e65844af5771b8e5d799d08e4796c8f4fced7564bd0d"),
+        prime1: concat!(
+            "fddab597054231deaca8ae4e3ff17c5b57ca40ecca12ccc1ad1785de57f1179d",
+            "31f43b7f6ea6ce5de70c007b4791c45c6051e5d73dcc2b6b43d507dfd6f95f3f",
+            "f8e0d9f06b3fe2cddaee696ba3e0cac9f5000c73d3b318e60af3d757103a7ff2",
+            "e9e36ef49686291f9e6206d40ae6be024fa5e18ced4bc57fac0db2c3f8e264f9"),
+        prime2: concat!(
+            "dd2764c2ab0c73d5f1582f1b800e8539f0dcd5a998fb4b38149a6c398b40acb5",
+            "4e11a1c71588c74cb9af3d27df5c24b3f51060dcd29559c401de0582fb24b87b",
+            "f0eb79653e8929e106eab7384d1e790e18c79f326568edd1cb06f84dd4744959",
+            "6edd5d58c66aef5434dfde19b5386c93d044380b4dbc5b62d7e1591f8ed961b5"),
+        exponent1: concat!(
+            "9af81133087ac886ef506a584aa0d911ca1e26546767e2a7b94b4ae5d939ccf8",
+            "a1ae3f442400a0aaa289a92199275b80292cd5eaf846d39abfe624d07923f309",
+            "d29d316e9b8a7ca2beac1c9f2183f14828d0c6c94ecb5ebfd09fdb2dc3327339",
+            "8f9ecff5708ea63c2f2c188f09b8a2f6363945e00a5cdf4c797443556f7285c1"),
+        exponent2: concat!(
+            "808a811a54e92bfad2087b78f4797c1e2d4d6e32e1644e580e41a35ddefe7c63",
+            "f538725b90bcab7a8102c5fd02ea3fbb0959b7b3220304e5d46003bc19d54215",
+            "67f6b5dc8ef8928ae4c650303d42d501c88d5e8ea03ad8dff3addd6cff6b87a6",
+            "9fb5b2a802cbecc778e415e7c3594462b5ceb0f480fe55efa1a2ad769644eb71"),
+        coefficient: concat!(
+            "9397fa83796bbc5925207b3931fd093ed7b72b70defbb99de3f654d1a6a68174",
+            "b2fb1503529c343d87fb0e37817a453b91ac5a5fddfa08476194357016c75666",
+            "cf9970309643797b5907e81a3dfaebf749187c56d57f4e9e51ea7114569d2264",
+            "2f855368d63a11ef3673373063f852d9681db3fd924b340e75a72e2c0375f048"),
+    },
+    Key::Rsa {
+        modulus: concat!(
+            "bfdaa51785f0ebe48895de5c46c8477665b27fb3560ecdd12923fc35cb63f770",
+            "5fa66bbc20cb2588ada9f06e1d27e66285be18c65c0466eaa7e474be7340f902",
+            "cac4bb82a95a849295701f5f98a826717fd1de761ef84759d603a34d9ea7e083",
+            "84c05ede4edcea93775cf4d36172751fea71e2b8667822eed36ce2cd88048de7",
+            "2a2b936573c690d225aec5e0bb90efa69d542645bb6eb25ae6bea0e7e3f667d3",
+            "98367b16745c8ee03cbdebeadc09175c38e99677d4d1946d4b15baca18e109f7",
+            "9c6fa32ef6a0da6055595b41fb8535c66394d95815ca969627d14f8a1ec89bf5",
+            "b437b66834ba7b556398f773ac8d36da5b75964569a8e844228f562198137b4f"),
+        prime1: concat!(
+            "df8ed92c11b0d655143c54705831410d24c159a90246278c6359e897782b571b",
+            "882ec84186a80286a880ef0bb40f14a7bff159f9111cc845e63f56c18aef24c6",
+            "4b1a3360deeca2091fec853ac9f01f1ebb3028a0c87d38d77f3ba61f4badf3a1",
+            "84ab9fb1755321595a1846cb1741000b370feb126a54afda093c11f39b5e24c7"),
+        prime2: concat!(
+            "dbb1fffb36ea1efc868ac5f03cd2ef82c4a9551e04f263c94d705d766c7f5434",
+            "ae5d4672632f17147ade7777a2e88ba6475e5757063f23eae281fa68ad3aff93",
+            "79586d89af7cb98e0b3363f5e595d097a6925f14fc9df908a807b19959d829e4",
+            "40e6326c087f25941327748010f29dae1f71801bdf8864f2ff0ca18dea375d39"),
+        exponent1: concat!(
+            "40aec22340131c508d844f221a425602098a6ff8b891b7cba4cb54c2cea02b99",
+            "e9b32f79d60e327ce7b03679fa650aa2e6a45c55f61f8fcab9adc5da45ea9f87",
+            "481f92920aae08cbff129e11be6fdd7134d3e04d74f4d64773a6bc8ffd839d24",
+            "3490bba9caba7cc1af91aa9a0466682ed685a28c377bbd4da6b25cb75152d1ed"),
+        exponent2: concat!(
+            "94e48e18339f7860acce6057f0e104721bd6a7593101159fd85bcefbf38c5805",
+            "5c26db17ab20bf03a24223b57133297207ec68451276b860f8221f13d253a9e2",
+            "c8daa5622b0cdcb4e7e26c5cbabc4e227941ba2eff0611414c9fea3d84267c3d",
+            "b5be4d6dea5441a39957a41d275f467e3bd13081b37d3ced72ed9c9011ac7401"),
+        coefficient: concat!(
+            "0f47bf6be33ad3d50ad36f81eeb1ab3c9a06e58c86d5e80bf881582d2f740216",
+            "f412ee008bdccc212a4151f556445e5f99d748a9d764bb3436f91ba0d5e44a41",
+            "a5711fc495b696e85977866259ff68d7d785392d8aff5b9203d8095e522f266a",
+            "edee29a1215dd3dee85f8bffb2f2e3ffefd4afae7fef395b8bdd2ef242ca3041"),
+    },
+];
+
+static RSA1024_KEYS: [Key; 3] = [
+    Key::Rsa {
+        modulus: concat!(
+            "e66e926ba0786a67c300cea088acaeaa959395500bd81e8421c787ca674c6b87",
+            "d56087445179edef9f0196ed69847e0b8a6309bd292adffebc72708a0b8f7e65",
+            "154b04c10d9d80eb86c9df9bc93c6435b09fc61be22a77da758e1d1c5fc645b9",
+            "ba54063cf24c89ec87ae7fb35804b3767037dd3c355686693c1408e561b345dd"),
+        prime1: concat!(
+            "f300a218c3fa17aadf4f8822e517362923f40ed854c23e36e0d73dcaea72252f",
+            "61b2056d6289acb336232c97147010d4715b4fa2c6fac7a9689a603c350cdc05"),
+        prime2: concat!(
+            "f2c1d049366b378e6d3753889a5d89ec4bfcbe26af54782fd6c12fb94cc9e697",
+            "afc94b940fe2cbf7dd6a054eb4b6b90816eed72fff3f0570897046d6374441f9"),
+        exponent1: concat!(
+            "a3f08b6b55dc05d79bac9655a464d27f52e83693b02b13ba065a702fd3978a8a",
+            "bac8556d048b895da66a788cbc9716639cce539da7cc4ca378149e54401e1ad9"),
+        exponent2: concat!(
+            "7c2c5d91858f946c7b3b48b46059a67a4368360fedb16484fbf1f7aa6e37fc86",
+            "ecb927bac5e179fa00bd0197ffe253856acda32efefe4a970506748590e18e69"),
+        coefficient: concat!(
+            "c33bca2ffb09ee3c20ffb5f7a265440b3d507b306efa0e41f110e5ae66d73c45",
+            "cc8571e1e06937cffc77661c99651d35ac39ed05bb922fdefff0230b804da5b0"),
+    },
+    Key::Rsa {
+        modulus: concat!(
+            "9e897b89058a83ad72b7f91c65ba8d018089494a11db604a5df277b954ee3d70",
+            "e6f11a66134413bc01d8b609737aab39f4fdc18ee399b7999d4da9f2b5d891ad",
+            "a064470cf4e5433975233a4f0f130747d6141c4d8892c5bfbbce4b9612d97697",
+            "f8b4900a455f51f7122e917f35ecdf1ce9fd296b115581c8beabb5300692bff1"),
+        prime1: concat!(
+            "d22dbe06ee71b7f164529ecf9fd6dc1d97ebb5b9cce6471a6a5d8f14f4b54be3",
+            "58eb33ae6343a9b8cd599840f7923c96efede8012e6e280c08a52b9cfe39f439"),
+        prime2: concat!(
+            "c119932481c770e5879078d7c4d731663074b212d0ea0e194e8391101bf024f9",
+            "7f94bf04714c9c9d9d4f72f968ae79794ecfd0594a854a0fab7823352a7ad979"),
+        exponent1: concat!(
+            "2d81f5f92e3bb5263120aa7e2e0fa98d46cda4855c5627e31250bba03ff3d940",
+            "9a8a22a089a1a6e7fa9e8196df83ec568a10ab01467a57757391bfc5b84df811"),
+        exponent2: concat!(
+            "0f8b9b88e8d9c180f8f4ec85925730a6e567bf9e3c2b5b8f35fee982be7d0abc",
+            "fb44624c5d42a9a916acfab89170da5ea5b59d3453077ffadaf704fe96b8e521"),
+        coefficient: concat!(
+            "6a191291d684bf88e1e78088ef17eb9ff84b07ba0c694eb7e9f6af6fc6bc152e",
+            "d925023d6e7b63bdea5d5e31a814d91106144116205680e913c84db3748a628a"),
+    },
+    Key::Rsa {
+        modulus: concat!(
+            "a05b2424d9c6d6b9db43ac5d0ad8278cde2ad85a5556ff74dcd70cb92732b71e",
+            "b2712400f90b94500ec9fbf490e077ddeff73935331a0ff819cf0f3032fefc23",
+            "5d18ba7e6781a973d03ede64d9bed7e8269413397a9678e9abcfbdf8caf4c77e",
+            "f00657a48505d0f74dce88516763d183815aa23c646fc2aa1ec3bdfb821cc4e9"),
+        prime1: concat!(
+            "cbabc4c2fecbc16bddbcfc393d901e3596d237618990b8a2e88853c29a2e77ee",
+            "acf1f06e1f329e4f5850c16880509c17ee5e0787a848dbd5754f78091550fcb3"),
+        prime2: concat!(
+            "c98e614204adbee41c961f71b19ea220fa108b83dd2d6b25c1a88d0ffb5552d3",
+            "351c99312c6dbb61ef1239fdf80aa29ca1518c73c822eb62c3dd6a671180fdf3"),
+        exponent1: concat!(
+            "a21093682a818deebc29e675d1d3fd05dd76771508f6fcc4bccdc600c2d9191b",
+            "f5c82aee25ab0ca7cc79adfceea5b61fa0d077a36db8541d5dd4978a7ac8cfdb"),
+        exponent2: concat!(
+            "0cd4af84505fef22fe5ee02990b114891ce228bf8038fff52b5adb8004f3ed8d",
+            "dc77b198dbc730828f818d87895ec3f34ca1b05b3e865a8db302495fbe5183d9"),
+        coefficient: concat!(
+            "aa964e4b3bb72f5f184ff76fe51795d517ed4d1f69e4c1e5e275aa41c5637a9d",
+            "ae79f249a1f251888d2a5f4335e02f9a5d5bf392ba56b4ae1cf20c8e8fb23ecd"),
+    },
+];
+
+// Arithmetic modulo an odd number `n` whose most significant limb has its
+// top bit set, for signing. The operands are big-endian and at most as long
+// as `n`, and the results are as long as `n`. It uses Montgomery
+// multiplication with 32-bit limbs, and it is neither fast nor constant-time,
+// which doesn't matter for these keys.
+struct Modulus {
+    // `n`, least significant limb first.
+    n: Vec<u32>,
+
+    // The length of `n` in bytes.
+    len: usize,
+
+    // -n^-1 mod 2^32.
+    n0: u32,
+
+    // R^2 mod n, where R = 2^(32 * n.len()).
+    rr: Vec<u32>,
+}
+
+impl Modulus {
+    fn new(n: &[u8]) -> Modulus {
+        let mut modulus = Modulus {
+            n: Vec::new(),
+            len: n.len(),
+            n0: 0,
+            rr: Vec::new(),
+        };
+        modulus.n = modulus.limbs(n);
+
+        // Newton's method doubles the number of correct low bits of the
+        // inverse in each step, starting from one since `n` is odd.
+        let mut inverse = 1u32;
+        for _ in 0..5 {
+            inverse = inverse.wrapping_mul(
+                2u32.wrapping_sub(modulus.n[0].wrapping_mul(inverse)));
+        }
+        modulus.n0 = inverse.wrapping_neg();
+
+        // 2^(64 * n.len()) mod n, by doubling one that many times.
+        let mut rr = vec![0; modulus.n.len()];
+        rr[0] = 1;
+        for _ in 0..(64 * modulus.n.len()) {
+            let mut carry = 0;
+            for limb in rr.iter_mut() {
+                let doubled = ((*limb as u64) << 1) | carry;
+                *limb = doubled as u32;
+                carry = doubled >> 32;
+            }
+            rr.push(carry as u32);
+            modulus.subtract_if_not_less(&mut rr);
+        }
+        modulus.rr = rr;
+        modulus
+    }
+
+    // a b mod n.
+    fn mul(&self, a: &[u8], b: &[u8]) -> Vec<u8> {
+        let ab = self.montgomery_mul(&self.limbs(a), &self.limbs(b));
+        self.bytes(&self.montgomery_mul(&ab, &self.rr))
+    }
+
+    // a + b mod n.
+    fn add(&self, a: &[u8], b: &[u8]) -> Vec<u8> {
+        let (a, b) = (self.limbs(a), self.limbs(b));
+        let mut sum = Vec::with_capacity(a.len() + 1);
+        let mut carry = 0;
+        for (a, b) in a.iter().zip(&b) {
+            let limb_sum = *a as u64 + *b as u64 + carry;
+            sum.push(limb_sum as u32);
+            carry = limb_sum >> 32;
+        }
+        sum.push(carry as u32);
+        self.subtract_if_not_less(&mut sum);
+        self.bytes(&sum)
+    }
+
+    // a - b mod n, for a and b less than n.
+    fn sub(&self, a: &[u8], b: &[u8]) -> Vec<u8> {
+        let (a, b) = (self.limbs(a), self.limbs(b));
+        let mut difference = Vec::with_capacity(a.len());
+        let mut borrow = 0;
+        for (a, b) in a.iter().zip(&b) {
+            let x = (*a as u64).wrapping_sub(*b as u64).wrapping_sub(borrow);
+            difference.push(x as u32);
+            borrow = x >> 63;
+        }
+        if borrow != 0 {
+            let mut carry = 0;
+            for (limb, n) in difference.iter_mut().zip(&self.n) {
+                let x = *limb as u64 + *n as u64 + carry;
+                *limb = x as u32;
+                carry = x >> 32;
+            }
+        }
+        self.bytes(&difference)
+    }
+
+    // a mod n, for an `a` of any length: a is split into chunks as long as
+    // n, each less than 2n, and r = r R + chunk mod n for each in turn.
+    fn reduce(&self, a: &[u8]) -> Vec<u8> {
+        let chunk_len = 4 * self.n.len();
+        let mut padded = vec![0; (chunk_len - a.len() % chunk_len) % chunk_len];
+        padded.extend_from_slice(a);
+        let mut r = vec![0; self.n.len()];
+        for chunk in padded.chunks(chunk_len) {
+            let mut chunk = self.limbs(chunk);
+            chunk.push(0);
+            self.subtract_if_not_less(&mut chunk);
+            r = self.limbs(&self.add(
+                &self.bytes(&self.montgomery_mul(&r, &self.rr)),
+                &self.bytes(&chunk)));
+        }
+        self.bytes(&r)
+    }
+
+    // a^exponent mod n, where `exponent` is big-endian and of any length.
+    fn pow(&self, a: &[u8], exponent: &[u8]) -> Vec<u8> {
+        let mut one = vec![0; self.n.len()];
+        one[0] = 1;
+        let a = self.montgomery_mul(&self.limbs(a), &self.rr);
+        let mut result = self.montgomery_mul(&one, &self.rr);
+        for byte in exponent {
+            for bit in (0..8).rev() {
+                result = self.montgomery_mul(&result, &result);
+                if (byte >> bit) & 1 == 1 {
+                    result = self.montgomery_mul(&result, &a);
+                }
+            }
+        }
+        self.bytes(&self.montgomery_mul(&result, &one))
+    }
+
+    // a^-1 mod n, which is a^(n - 2) if n is prime.
+    fn prime_inverse(&self, a: &[u8]) -> Vec<u8> {
+        let mut n_minus_2 = self.bytes(&self.n);
+        let mut borrow = 2;
+        for byte in n_minus_2.iter_mut().rev() {
+            let (difference, overflowed) = byte.overflowing_sub(borrow);
+            *byte = difference;
+            borrow = overflowed as u8;
+        }
+        self.pow(a, &n_minus_2)
+    }
+
+    // a b R^-1 mod n, for a and b less than n.
+    fn montgomery_mul(&self, a: &[u32], b: &[u32]) -> Vec<u32> {
+        let s = self.n.len();
+        let mut t = vec![0u32; s + 2];
+        for &b_i in &b[..s] {
+            let mut carry = 0;
+            for j in 0..s {
+                let x = t[j] as u64 + a[j] as u64 * b_i as u64 + carry;
+                t[j] = x as u32;
+                carry = x >> 32;
+            }
+            let x = t[s] as u64 + carry;
+            t[s] = x as u32;
+            t[s + 1] = (x >> 32) as u32;
+
+            // Adding m n makes t divisible by 2^32.
+            let m = t[0].wrapping_mul(self.n0) as u64;
+            let mut carry = (t[0] as u64 + m * self.n[0] as u64) >> 32;
+            for j in 1..s {
+                let x = t[j] as u64 + m * self.n[j] as u64 + carry;
+                t[j - 1] = x as u32;
+                carry = x >> 32;
+            }
+            let x = t[s] as u64 + carry;
+            t[s - 1] = x as u32;
+            t[s] = t[s + 1] + (x >> 32) as u32;
+        }
+        t.truncate(s + 1);
+        self.subtract_if_not_less(&mut t);
+        t
+    }
+
+    // Subtracts n from `a`, which has one more limb than n and is less than
+    // 2n, if it isn't less than n, and removes the extra limb.
+    fn subtract_if_not_less(&self, a: &mut Vec<u32>) {
+        let s = self.n.len();
+        let not_less = a[s] != 0 ||
+                       match (0..s).rev().find(|&i| a[i] != self.n[i]) {
+                           Some(i) => a[i] > self.n[i],
+                           None => true,
+                       };
+        if not_less {
+            let mut borrow = 0;
+            for (a_i, &n_i) in a.iter_mut().zip(&self.n) {
+                let x = (*a_i as u64).wrapping_sub(n_i as u64)
+                                     .wrapping_sub(borrow);
+                *a_i = x as u32;
+                borrow = x >> 63;
+            }
+        }
+        a.truncate(s);
+    }
+
+    // The limbs of the big-endian `value`, which is at most as long as n.
+    fn limbs(&self, value: &[u8]) -> Vec<u32> {
+        let mut limbs = vec![0; (self.len + 3) / 4];
+        for (i, byte) in value.iter().rev().enumerate() {
+            limbs[i / 4] |= (*byte as u32) << (8 * (i % 4));
+        }
+        limbs
+    }
+
+    // The big-endian encoding of `limbs`, as long as n.
+    fn bytes(&self, limbs: &[u32]) -> Vec<u8> {
+        (0..self.len).rev()
+                     .map(|i| (limbs[i / 4] >> (8 * (i % 4))) as u8)
+                     .collect()
+    }
+}
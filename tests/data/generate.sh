@@ -0,0 +1,285 @@
+#!/bin/sh
+#
+# Copyright 2016 Brian Smith.
+#
+# Permission to use, copy, modify, and/or distribute this software for any
+# purpose with or without fee is hereby granted, provided that the above
+# copyright notice and this permission notice appear in all copies.
+#
+# THE SOFTWARE IS PROVIDED "AS IS" AND THE AUTHORS DISCLAIM ALL WARRANTIES
+# WITH REGARD TO THIS SOFTWARE INCLUDING ALL IMPLIED WARRANTIES OF
+# MERCHANTABILITY AND FITNESS. IN NO EVENT SHALL THE AUTHORS BE LIABLE FOR
+# ANY SPECIAL, DIRECT, INDIRECT, OR CONSEQUENTIAL DAMAGES OR ANY DAMAGES
+# WHATSOEVER RESULTING FROM LOSS OF USE, DATA OR PROFITS, WHETHER IN AN
+# ACTION OF CONTRACT, NEGLIGENCE OR OTHER TORTIOUS ACTION, ARISING OUT OF
+# OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
+
+# Generates the certificates used by the tests and the benchmarks. The
+# certificates are checked in, and so are their keys, in `keys/`, which are
+# used for nothing else.
+#
+# Only the certificates that don't exist yet are generated, along with any
+# keys that don't exist yet, so adding a certificate doesn't change the
+# others. To regenerate a certificate, delete it first. The validity periods
+# are fixed and the serial numbers are derived from the names, so a
+# regenerated certificate only differs from the old one in its signature.
+#
+# `-not_before` and `-not_after` need OpenSSL 3.4 or later.
+
+set -eu
+
+cd "$(dirname "$0")"
+
+tmp=$(mktemp -d)
+trap 'rm -rf "$tmp"' EXIT
+
+mkdir -p keys
+
+NOT_BEFORE=20261016000000Z
+ONE_DAY=20261017000000Z
+
+# key <name> <key type>
+key() {
+  [ -e "keys/$1.pem" ] && return
+  case $2 in
+    ec) openssl genpkey -algorithm EC -pkeyopt ec_paramgen_curve:P-256 \
+          -out "keys/$1.pem" 2>/dev/null ;;
+    rsa) openssl genpkey -algorithm RSA -pkeyopt rsa_keygen_bits:2048 \
+           -out "keys/$1.pem" 2>/dev/null ;;
+  esac
+}
+
+# serial <name>
+serial() {
+  printf '0x1%s' "$(printf '%s' "$1" | openssl dgst -sha256 -hex \
+    | sed 's/.* //' | cut -c 1-31)"
+}
+
+# cert <name> <issuer name or "self">[:<issuer key name>] <key name>
+#      <subject CN> <extensions> [<not after>] [<digest>]
+#
+# The issuer's key name defaults to the issuer's name. <not after> defaults
+# to 7300 days after $NOT_BEFORE for self-signed certificates and to 3650
+# days after it for the others.
+cert() {
+  name=$1
+  issuer=${2%%:*}
+  issuer_key=${2#*:}
+  key=$3
+  cn=$4
+  extensions=$5
+  digest=${7:-sha256}
+
+  [ -e "$name.der" ] && return
+
+  printf '[ext]\n%s\n' "$extensions" > "$tmp/$name.cnf"
+
+  if [ "$issuer" = self ]; then
+    openssl req -new -x509 -key "keys/$key.pem" -subj "/CN=$cn" \
+      -set_serial "$(serial "$name")" -not_before $NOT_BEFORE \
+      -not_after "${6:-20461011000000Z}" -"$digest" \
+      -config "$tmp/$name.cnf" -extensions ext -outform DER -out "$name.der"
+  else
+    openssl req -new -key "keys/$key.pem" -subj "/CN=$cn" \
+      -out "$tmp/$name.csr"
+    openssl x509 -req -in "$tmp/$name.csr" -inform PEM \
+      -CA "$issuer.der" -CAform DER -CAkey "keys/$issuer_key.pem" \
+      -set_serial "$(serial "$name")" -not_before $NOT_BEFORE \
+      -not_after "${6:-20361013000000Z}" -"$digest" \
+      -extfile "$tmp/$name.cnf" -extensions ext \
+      -outform DER -out "$name.der" 2>/dev/null
+  fi
+}
+
+CA='basicConstraints=critical,CA:TRUE
+keyUsage=critical,keyCertSign,cRLSign'
+
+EE='basicConstraints=critical,CA:FALSE
+keyUsage=critical,digitalSignature,keyEncipherment
+extendedKeyUsage=serverAuth'
+
+# ECDSA P-256: root -> intermediate -> end-entity, plus a wildcard end-entity
+# issued directly by the root.
+key ecdsa-root ec
+cert ecdsa-root self ecdsa-root "webpki benchmark ECDSA root" "$CA"
+key ecdsa-intermediate ec
+cert ecdsa-intermediate ecdsa-root ecdsa-intermediate \
+  "webpki benchmark ECDSA intermediate" "$CA"
+key ecdsa-ee ec
+cert ecdsa-ee ecdsa-intermediate ecdsa-ee "www.example.com" \
+  "$EE
+subjectAltName=DNS:www.example.com"
+key ecdsa-wildcard-ee ec
+cert ecdsa-wildcard-ee ecdsa-root ecdsa-wildcard-ee "*.example.com" \
+  "$EE
+subjectAltName=DNS:www.example.net,DNS:mail.example.net,DNS:example.org,DNS:example.com,DNS:*.example.com"
+
+# RSA-2048: root -> intermediate -> end-entity.
+key rsa-root rsa
+cert rsa-root self rsa-root "webpki benchmark RSA root" "$CA"
+key rsa-intermediate rsa
+cert rsa-intermediate rsa-root rsa-intermediate \
+  "webpki benchmark RSA intermediate" "$CA"
+key rsa-ee rsa
+cert rsa-ee rsa-intermediate rsa-ee "www.example.com" \
+  "$EE
+subjectAltName=DNS:www.example.com"
+
+# Cross-signing: the intermediate is issued both by an old root, which the
+# benchmarks don't trust, and by the trusted ECDSA root. The end-entity's
+# issuer has the same name and key as both versions of the intermediate. The
+# version issued by the old root expires a day before the end-entity.
+key cross-old-root ec
+cert cross-old-root self cross-old-root "webpki benchmark old root" "$CA"
+key cross-intermediate ec
+cert cross-intermediate-by-old-root cross-old-root cross-intermediate \
+  "webpki benchmark cross-signed intermediate" "$CA" 20361012000000Z
+cert cross-intermediate-by-ecdsa-root ecdsa-root cross-intermediate \
+  "webpki benchmark cross-signed intermediate" "$CA"
+cert cross-ee cross-intermediate-by-old-root:cross-intermediate ecdsa-ee \
+  "www.example.com" "$EE
+subjectAltName=DNS:www.example.com"
+
+# A cross-signed root: the ECDSA root's name and key, issued by the old root.
+cert cross-ecdsa-root-by-old-root cross-old-root ecdsa-root \
+  "webpki benchmark ECDSA root" "$CA"
+
+# A deep private PKI: the ECDSA root -> 5 intermediates -> end-entity.
+issuer=ecdsa-root
+for i in 1 2 3 4 5; do
+  key deep-intermediate-$i ec
+  cert deep-intermediate-$i $issuer deep-intermediate-$i \
+    "webpki benchmark deep intermediate $i" "$CA"
+  issuer=deep-intermediate-$i
+done
+cert deep-ee $issuer ecdsa-ee "www.example.com" \
+  "$EE
+subjectAltName=DNS:www.example.com"
+
+# The same PKI with a sixth intermediate, the most that a path can have, and
+# an old certificate for it with the same name and key.
+key deep-intermediate-6 ec
+cert deep-intermediate-6-old deep-intermediate-5 deep-intermediate-6 \
+  "webpki benchmark deep intermediate 6" "$CA"
+cert deep-intermediate-6 deep-intermediate-5 deep-intermediate-6 \
+  "webpki benchmark deep intermediate 6" "$CA"
+cert deep-6-ee deep-intermediate-6 ecdsa-ee "www.example.com" \
+  "$EE
+subjectAltName=DNS:www.example.com"
+
+# A private PKI whose intermediate's EKU allows only clientAuth, but which
+# issues serverAuth end-entity certificates; see `Policy::ignore_ca_eku`.
+key client-eku-root ec
+cert client-eku-root self client-eku-root "webpki test clientAuth EKU root" \
+  "$CA"
+key client-eku-intermediate ec
+cert client-eku-intermediate client-eku-root client-eku-intermediate \
+  "webpki test clientAuth EKU intermediate" "$CA
+extendedKeyUsage=clientAuth"
+key client-eku-ee ec
+cert client-eku-ee client-eku-intermediate client-eku-ee "www.example.com" \
+  "$EE
+subjectAltName=DNS:www.example.com"
+
+# Colliding subjects: a stale intermediate with the same subject and
+# subjectKeyIdentifier as the ECDSA intermediate, but a different key, issued
+# by another root. Path building tries it first when it is sent first, and the
+# end-entity's signature then doesn't verify with its key.
+key collision-root ec
+cert collision-root self collision-root "webpki test colliding-subject root" \
+  "$CA"
+key collision-intermediate ec
+cert collision-intermediate collision-root collision-intermediate \
+  "webpki benchmark ECDSA intermediate" "$CA
+subjectKeyIdentifier=$(openssl x509 -inform DER -in ecdsa-intermediate.der \
+  -noout -ext subjectKeyIdentifier | tail -n 1 | tr -d ' ')"
+
+# A renewed intermediate: the old certificate for it, with the same subject
+# and key, has expired (its validity period is one day), but servers may still
+# send it along with the new one, e.g. after a CA rollover.
+key renewed-root ec
+cert renewed-root self renewed-root "webpki test renewed-intermediate root" \
+  "$CA"
+key renewed-intermediate ec
+cert renewed-intermediate-expired renewed-root renewed-intermediate \
+  "webpki test renewed intermediate" "$CA" $ONE_DAY
+cert renewed-intermediate renewed-root renewed-intermediate \
+  "webpki test renewed intermediate" "$CA"
+key renewed-ee ec
+cert renewed-ee renewed-intermediate renewed-ee "www.example.com" \
+  "$EE
+subjectAltName=DNS:www.example.com"
+
+# Technically-constrained intermediates, in the sense of the CA/Browser Forum
+# Baseline Requirements: both allow serverAuth and permit only example.com,
+# but only the first also excludes all IP addresses.
+key constrained-root ec
+cert constrained-root self constrained-root \
+  "webpki test technically-constrained root" "$CA"
+key constrained-intermediate ec
+cert constrained-intermediate constrained-root constrained-intermediate \
+  "webpki test technically-constrained intermediate" "$CA
+extendedKeyUsage=serverAuth
+nameConstraints=critical,permitted;DNS:example.com,\
+ excluded;IP:0.0.0.0/0.0.0.0,excluded;IP:::/::"
+cert constrained-dns-intermediate constrained-root constrained-intermediate \
+  "webpki test DNS-constrained intermediate" "$CA
+extendedKeyUsage=serverAuth
+nameConstraints=critical,permitted;DNS:example.com"
+
+# An ACME tls-alpn-01 challenge certificate for www.example.com. The key
+# authorization digest is the SHA-256 digest of "webpki test key
+# authorization".
+key acme-tls-alpn-01 ec
+cert acme-tls-alpn-01 self acme-tls-alpn-01 "www.example.com" \
+  "subjectAltName=DNS:www.example.com
+1.3.6.1.5.5.7.1.31=critical,DER:0420$(printf 'webpki test key authorization' \
+  | openssl dgst -sha256 -hex | sed 's/.* //')"
+
+# A CA certificate with the problems that `lint::lint_cert_der` reports,
+# other than a long validity period: two unsupported critical extensions, no
+# subjectKeyIdentifier, and a SHA-1 signature.
+key lint-ca ec
+cert lint-ca self lint-ca "webpki test lint CA" "$CA
+subjectKeyIdentifier=none
+1.2.3.4=critical,DER:0500
+1.2.3.5=critical,DER:0500" 20461011000000Z sha1
+
+# SPIFFE X.509-SVIDs in two federated trust domains, example.org and
+# example.net, each with its own root. The rogue SVID claims an example.org
+# identity but is issued by the example.net root.
+SVID='basicConstraints=critical,CA:FALSE
+keyUsage=critical,digitalSignature
+extendedKeyUsage=serverAuth,clientAuth'
+key spiffe-org-root ec
+cert spiffe-org-root self spiffe-org-root \
+  "webpki test SPIFFE example.org root" "$CA"
+key spiffe-net-root ec
+cert spiffe-net-root self spiffe-net-root \
+  "webpki test SPIFFE example.net root" "$CA"
+key spiffe-ee ec
+cert spiffe-ee spiffe-org-root spiffe-ee "webpki test SVID" "$SVID
+subjectAltName=URI:spiffe://example.org/ns/default/sa/web"
+cert spiffe-rogue-ee spiffe-net-root spiffe-ee "webpki test SVID" "$SVID
+subjectAltName=URI:spiffe://example.org/ns/default/sa/web"
+
+# An S/MIME signer certificate for alice@example.com, issued directly by its
+# own root.
+key smime-root ec
+cert smime-root self smime-root "webpki test SMIME root" "$CA"
+key smime-ee ec
+cert smime-ee smime-root smime-ee "webpki test SMIME signer" \
+  "basicConstraints=critical,CA:FALSE
+keyUsage=critical,digitalSignature
+extendedKeyUsage=emailProtection
+subjectAltName=email:alice@example.com"
+
+# A code signing certificate that is only valid for a day, for verifying
+# signatures made while it was valid.
+key codesign-root ec
+cert codesign-root self codesign-root "webpki test code signing root" "$CA"
+key codesign-ee ec
+cert codesign-ee codesign-root codesign-ee "webpki test code signer" \
+  "basicConstraints=critical,CA:FALSE
+keyUsage=critical,digitalSignature
+extendedKeyUsage=codeSigning" $ONE_DAY